@@ -74,6 +74,8 @@ pub use self::{
     transform::TransformChannel,
     util::{get_animation_set, SamplerPrimitive},
 };
+#[cfg(feature = "ui")]
+pub use self::{ui_fill::UiFillChannel, ui_transform::UiTransformChannel};
 
 mod bundle;
 mod material;
@@ -83,5 +85,7 @@ mod sprite;
 mod systems;
 mod transform;
 #[cfg(feature = "ui")]
+mod ui_fill;
+#[cfg(feature = "ui")]
 mod ui_transform;
 mod util;
@@ -0,0 +1,61 @@
+use amethyst_assets::{register_asset_type, AssetProcessorSystem, TypeUuid};
+use amethyst_core::{ecs::CommandBuffer, math::zero};
+use amethyst_ui::UiFill;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    resources::{AnimationSampling, BlendMethod},
+    util::SamplerPrimitive,
+    Animation,
+};
+
+/// Channels that can be animated on `UiFill`
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum UiFillChannel {
+    /// The filled proportion of the image
+    Amount,
+}
+
+impl TypeUuid for Animation<UiFill> {
+    const UUID: type_uuid::Bytes =
+        *Uuid::from_u128(151_824_093_607_264_870_341_586_728_830_154_202_913).as_bytes();
+}
+register_asset_type!(Animation<UiFill> => Animation<UiFill>; AssetProcessorSystem<Animation<UiFill>>);
+
+impl AnimationSampling for UiFill {
+    type Primitive = SamplerPrimitive<f32>;
+    type Channel = UiFillChannel;
+
+    fn apply_sample(
+        &mut self,
+        channel: &Self::Channel,
+        data: &SamplerPrimitive<f32>,
+        _buffer: &mut CommandBuffer,
+    ) {
+        use self::UiFillChannel::Amount;
+        use crate::util::SamplerPrimitive::Scalar;
+
+        match (channel, *data) {
+            (&Amount, Scalar(d)) => self.amount = d,
+            _ => panic!("Attempt to apply invalid sample to UiFill"),
+        }
+    }
+
+    fn current_sample(&self, channel: &Self::Channel) -> SamplerPrimitive<f32> {
+        use self::UiFillChannel::Amount;
+        match channel {
+            Amount => SamplerPrimitive::Scalar(self.amount),
+        }
+    }
+    fn default_primitive(channel: &Self::Channel) -> Self::Primitive {
+        use self::UiFillChannel::Amount;
+        match channel {
+            Amount => SamplerPrimitive::Scalar(zero()),
+        }
+    }
+
+    fn blend_method(&self, _: &Self::Channel) -> Option<BlendMethod> {
+        Some(BlendMethod::Linear)
+    }
+}
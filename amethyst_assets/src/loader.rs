@@ -21,6 +21,7 @@ use distill_loader::{
 };
 pub use distill_loader::{storage::LoadStatus, AssetUuid};
 use log::debug;
+use parking_lot::Mutex;
 use serde::de::Deserialize;
 
 use crate::{
@@ -147,6 +148,9 @@ pub struct DefaultLoader {
     ref_receiver: Receiver<RefOp>,
     handle_allocator: Arc<AtomicHandleAllocator>,
     pub(crate) indirection_table: IndirectionTable,
+    /// The paths of the assets loaded by path, with the number of live references to them, by
+    /// their indirect handles. Entries are removed when their last reference is released.
+    paths: Mutex<HashMap<LoadHandle, (String, usize)>>,
 }
 
 impl Default for DefaultLoader {
@@ -185,8 +189,26 @@ impl DefaultLoader {
             ref_sender: tx,
             ref_receiver: rx,
             handle_allocator,
+            paths: Mutex::default(),
         }
     }
+
+    /// Returns the path an asset was loaded by, or `None` if it was only loaded by UUID or all
+    /// the handles loading it by path were dropped.
+    ///
+    /// # Parameters
+    ///
+    /// * `handle`: Load handle of the asset, as given to its processor.
+    pub fn path(&self, handle: LoadHandle) -> Option<String> {
+        let paths = self.paths.lock();
+        if let Some((path, _)) = paths.get(&handle) {
+            return Some(path.clone());
+        }
+        paths
+            .iter()
+            .find(|(indirect, _)| self.indirection_table.resolve(**indirect) == Some(handle))
+            .map(|(_, (path, _))| path.clone())
+    }
 }
 
 impl Loader for DefaultLoader {
@@ -197,14 +219,18 @@ impl Loader for DefaultLoader {
         Handle::new(self.ref_sender.clone(), self.loader.add_ref(id))
     }
     fn load<A: TypeUuid>(&self, path: &str) -> Handle<A> {
-        Handle::new(
-            self.ref_sender.clone(),
-            self.loader
-                .add_ref_indirect(IndirectIdentifier::PathWithType(
-                    path.to_string(),
-                    AssetTypeId(A::UUID),
-                )),
-        )
+        let handle = self
+            .loader
+            .add_ref_indirect(IndirectIdentifier::PathWithType(
+                path.to_string(),
+                AssetTypeId(A::UUID),
+            ));
+        self.paths
+            .lock()
+            .entry(handle)
+            .or_insert_with(|| (path.to_string(), 0))
+            .1 += 1;
+        Handle::new(self.ref_sender.clone(), handle)
     }
     fn get_load(&self, id: AssetUuid) -> Option<WeakHandle> {
         self.loader.get_load(id).map(WeakHandle::new)
@@ -251,9 +277,19 @@ impl Loader for DefaultLoader {
                 Err(TryRecvError::Disconnected) => panic!("RefOp receiver disconnected"),
                 Ok(RefOp::Decrease(handle)) => {
                     self.loader.remove_ref(handle);
+                    let mut paths = self.paths.lock();
+                    if let Some((_, refs)) = paths.get_mut(&handle) {
+                        *refs -= 1;
+                        if *refs == 0 {
+                            paths.remove(&handle);
+                        }
+                    }
                 }
                 Ok(RefOp::Increase(handle)) => {
                     self.loader.add_ref_handle(handle);
+                    if let Some((_, refs)) = self.paths.lock().get_mut(&handle) {
+                        *refs += 1;
+                    }
                 }
                 Ok(RefOp::IncreaseUuid(uuid)) => {
                     self.loader.add_ref(uuid);
@@ -17,10 +17,10 @@
 
 use amethyst_assets::{
     register_asset_type, register_importer, Asset, AssetProcessorSystem, AssetStorage, Format,
-    LoadHandle, ProcessableAsset, ProcessingState,
+    Handle, LoadHandle, ProcessableAsset, ProcessingState,
 };
 use amethyst_error::Error;
-pub use fluent::{concurrent::FluentBundle, FluentResource};
+pub use fluent::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
 use serde::{Deserialize, Serialize};
 use type_uuid::TypeUuid;
 use unic_langid::langid;
@@ -67,6 +67,16 @@ impl ProcessableAsset for Locale {
     }
 }
 
+/// Resource holding the `Locale` currently in use.
+///
+/// Changing it switches the language of everything resolved against it, such as localized ui
+/// texts.
+#[derive(Debug, Default, Clone)]
+pub struct ActiveLocale {
+    /// The locale in use, if any.
+    pub locale: Option<Handle<Locale>>,
+}
+
 /// Loads the strings from localisation files.
 #[derive(Clone, Debug, Default, TypeUuid, Serialize, Deserialize)]
 #[uuid = "fe7720ec-ecb5-4f59-8a09-656805eb4eff"]
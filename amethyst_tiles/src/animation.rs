@@ -0,0 +1,246 @@
+//! Animations of the sprites of tiles.
+
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use amethyst_core::math::Point3;
+use serde::{Deserialize, Serialize};
+
+/// A frame of a `TileAnimation`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TileAnimationFrame {
+    /// The sprite number of the frame in the sprite sheet of the map.
+    pub sprite: usize,
+    /// How long the frame is shown, in seconds.
+    pub duration: f32,
+}
+
+/// The looping animation of the tiles whose `Tile::sprite` is a given sprite.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TileAnimation {
+    /// The frames of the animation.
+    pub frames: Vec<TileAnimationFrame>,
+    /// Whether each tile starts the animation at a different time, picked from its coordinates,
+    /// rather than all the tiles showing the same frame.
+    #[serde(default)]
+    pub desynchronize: bool,
+}
+
+impl TileAnimation {
+    /// Creates an animation showing each sprite for the same duration, in seconds.
+    #[must_use]
+    pub fn uniform(sprites: &[usize], duration: f32) -> Self {
+        Self {
+            frames: sprites
+                .iter()
+                .map(|&sprite| TileAnimationFrame { sprite, duration })
+                .collect(),
+            desynchronize: false,
+        }
+    }
+
+    /// Desynchronizes the tiles of this animation.
+    #[must_use]
+    pub fn desynchronized(mut self) -> Self {
+        self.desynchronize = true;
+        self
+    }
+
+    /// The duration of a loop of the animation, in seconds.
+    #[must_use]
+    pub fn duration(&self) -> f32 {
+        self.frames
+            .iter()
+            .map(|frame| frame.duration.max(0.0))
+            .sum()
+    }
+
+    /// Returns the sprite shown at `time`, in seconds, by the tile at `coord`, or `None` if the
+    /// animation has no frames.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn sprite(&self, time: f64, coord: &Point3<u32>) -> Option<usize> {
+        let duration = f64::from(self.duration());
+        if duration <= 0.0 {
+            return self.frames.first().map(|frame| frame.sprite);
+        }
+        let offset = if self.desynchronize {
+            f64::from(position_fraction(coord)) * duration
+        } else {
+            0.0
+        };
+        let mut remaining = (time + offset).rem_euclid(duration) as f32;
+        for frame in &self.frames {
+            let frame_duration = frame.duration.max(0.0);
+            if remaining < frame_duration {
+                return Some(frame.sprite);
+            }
+            remaining -= frame_duration;
+        }
+        self.frames.last().map(|frame| frame.sprite)
+    }
+}
+
+/// The last version given to `TileAnimations`.
+static VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a version which no `TileAnimations` had before.
+fn next_version() -> u64 {
+    VERSION.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// The animations of the tiles of the `TileMap` or `ChunkedTileMap` of its entity, by the sprite
+/// number returned by `Tile::sprite`, which `DrawTiles2D` replaces with the current frame.
+///
+/// Every change of the animations gives them a new version, which no other `TileAnimations` had,
+/// so that `DrawTiles2D` only compares versions to find out whether the animations changed. The
+/// tiles of a `TileMap` are kept between frames, only the frames of the animated tiles being
+/// updated when they end.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TileAnimations {
+    animations: HashMap<usize, TileAnimation>,
+    #[serde(skip, default = "next_version")]
+    version: u64,
+}
+
+impl PartialEq for TileAnimations {
+    fn eq(&self, other: &Self) -> bool {
+        self.animations == other.animations
+    }
+}
+
+impl TileAnimations {
+    /// Adds the animation of the tiles whose sprite is `sprite`.
+    #[must_use]
+    pub fn with(mut self, sprite: usize, animation: TileAnimation) -> Self {
+        self.insert(sprite, animation);
+        self
+    }
+
+    /// Sets the animation of the tiles whose sprite is `sprite`, returning the animation it
+    /// replaces.
+    pub fn insert(&mut self, sprite: usize, animation: TileAnimation) -> Option<TileAnimation> {
+        self.version = next_version();
+        self.animations.insert(sprite, animation)
+    }
+
+    /// Removes the animation of the tiles whose sprite is `sprite`.
+    pub fn remove(&mut self, sprite: usize) -> Option<TileAnimation> {
+        self.version = next_version();
+        self.animations.remove(&sprite)
+    }
+
+    /// Returns the animation of the tiles whose sprite is `sprite`.
+    #[must_use]
+    pub fn get(&self, sprite: usize) -> Option<&TileAnimation> {
+        self.animations.get(&sprite)
+    }
+
+    /// Returns a mutable reference to the animation of the tiles whose sprite is `sprite`.
+    pub fn get_mut(&mut self, sprite: usize) -> Option<&mut TileAnimation> {
+        self.version = next_version();
+        self.animations.get_mut(&sprite)
+    }
+
+    /// The number of animated sprites.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.animations.len()
+    }
+
+    /// Returns whether no sprite is animated.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.animations.is_empty()
+    }
+
+    /// The version of these animations, which changes whenever they do.
+    #[must_use]
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns the sprite drawn at `time`, in seconds, for a tile at `coord` whose sprite is
+    /// `sprite`.
+    #[must_use]
+    pub fn sprite(&self, sprite: usize, time: f64, coord: &Point3<u32>) -> usize {
+        self.animations
+            .get(&sprite)
+            .and_then(|animation| animation.sprite(time, coord))
+            .unwrap_or(sprite)
+    }
+}
+
+/// Returns a fraction in [0, 1) spread over the coordinates of tiles.
+#[allow(clippy::cast_precision_loss)]
+fn position_fraction(coord: &Point3<u32>) -> f32 {
+    let mut hash = coord
+        .x
+        .wrapping_mul(0x9E37_79B1)
+        .wrapping_add(coord.y.wrapping_mul(0x85EB_CA77))
+        .wrapping_add(coord.z.wrapping_mul(0xC2B2_AE3D));
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2C1B_3C6D);
+    hash ^= hash >> 12;
+    (hash & 0xFFFF) as f32 / 65536.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loops_frames() {
+        let animation = TileAnimation {
+            frames: vec![
+                TileAnimationFrame {
+                    sprite: 4,
+                    duration: 0.5,
+                },
+                TileAnimationFrame {
+                    sprite: 7,
+                    duration: 0.25,
+                },
+            ],
+            desynchronize: false,
+        };
+        let coord = Point3::new(3, 1, 0);
+        assert_eq!(animation.sprite(0.0, &coord), Some(4));
+        assert_eq!(animation.sprite(0.6, &coord), Some(7));
+        assert_eq!(animation.sprite(1.0, &coord), Some(4));
+        assert_eq!(animation.sprite(-0.1, &coord), Some(7));
+        assert_eq!(TileAnimation::default().sprite(1.0, &coord), None);
+    }
+
+    #[test]
+    fn desynchronizes_tiles() {
+        let animation = TileAnimation::uniform(&[0, 1, 2, 3, 4, 5, 6, 7], 0.1).desynchronized();
+        let first = (0..16)
+            .map(|x| animation.sprite(0.0, &Point3::new(x, 0, 0)).unwrap())
+            .collect::<Vec<_>>();
+        assert!(first.iter().any(|sprite| *sprite != first[0]));
+
+        let animations = TileAnimations::default().with(9, animation);
+        assert_eq!(animations.sprite(2, 0.3, &Point3::new(0, 0, 0)), 2);
+    }
+
+    #[test]
+    fn versions_changes() {
+        let mut animations = TileAnimations::default().with(1, TileAnimation::default());
+        let other = TileAnimations::default().with(1, TileAnimation::default());
+        assert_ne!(animations.version(), other.version());
+        assert_eq!(animations.clone().version(), animations.version());
+
+        let version = animations.version();
+        animations.get_mut(1).unwrap().desynchronize = true;
+        assert_ne!(animations.version(), version);
+
+        let serialized = ron::ser::to_string(&animations).unwrap();
+        let deserialized: TileAnimations = ron::de::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, animations);
+        assert_ne!(deserialized.version(), animations.version());
+    }
+}
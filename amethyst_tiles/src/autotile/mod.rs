@@ -0,0 +1,256 @@
+//! Rule-based autotiling, choosing the sprites of tiles from the terrain of their neighbors.
+//!
+//! An `AutotileRuleset` asset, loaded from a RON `.autotile` file, lists the sprites of each
+//! terrain for every arrangement of neighbors of a blob or Wang tileset. A `TileMap` of
+//! `AutotileTile`s with an `Autotile` component has the sprites of its tiles set by the
+//! `AutotileSystem`, which recomputes the neighborhood of the tiles in the changes of the map since
+//! it was last autotiled.
+//!
+//! Neighbors are taken along the x and y axes of tile coordinates, so autotiling suits orthogonal
+//! and isometric diamond maps. Neighbors outside of the map have the terrain of the tile.
+
+use std::collections::HashMap;
+
+use amethyst_assets::{register_asset_type, Asset, AssetProcessorSystem, Format, Handle};
+use amethyst_core::{
+    ecs::{Resources, World},
+    math::Point3,
+};
+use amethyst_error::{format_err, Error, ResultExt};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use crate::Tile;
+
+mod system;
+
+pub use self::system::AutotileSystem;
+
+/// The neighbor above a tile in a neighbor mask.
+pub const NORTH: u8 = 1;
+/// The neighbor above and to the right of a tile in a neighbor mask.
+pub const NORTH_EAST: u8 = 1 << 1;
+/// The neighbor to the right of a tile in a neighbor mask.
+pub const EAST: u8 = 1 << 2;
+/// The neighbor below and to the right of a tile in a neighbor mask.
+pub const SOUTH_EAST: u8 = 1 << 3;
+/// The neighbor below a tile in a neighbor mask.
+pub const SOUTH: u8 = 1 << 4;
+/// The neighbor below and to the left of a tile in a neighbor mask.
+pub const SOUTH_WEST: u8 = 1 << 5;
+/// The neighbor to the left of a tile in a neighbor mask.
+pub const WEST: u8 = 1 << 6;
+/// The neighbor above and to the left of a tile in a neighbor mask.
+pub const NORTH_WEST: u8 = 1 << 7;
+
+/// The 47 neighbor masks of a blob tileset, in the order of the sprites of its terrains. Corner
+/// neighbors are only part of these masks when both of the edge neighbors next to them are.
+pub const BLOB_MASKS: [u8; 47] = [
+    0, 1, 4, 5, 7, 16, 17, 20, 21, 23, 28, 29, 31, 64, 65, 68, 69, 71, 80, 81, 84, 85, 87, 92, 93,
+    95, 112, 113, 116, 117, 119, 124, 125, 127, 193, 197, 199, 209, 213, 215, 221, 223, 241, 245,
+    247, 253, 255,
+];
+
+/// The kind of tileset of an `AutotileRuleset`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AutotileKind {
+    /// 47 sprites per terrain, matching the 8 neighbors of tiles, in the order of `BLOB_MASKS`.
+    Blob47,
+    /// 16 sprites per terrain, matching the 4 edge neighbors of tiles, indexed by the sum of 1
+    /// for the north neighbor, 2 for the east one, 4 for the south one and 8 for the west one.
+    Wang16,
+}
+
+/// The sprites of the terrains of an autotiled map, for every arrangement of neighbors.
+///
+/// ```ron
+/// (
+///     kind: Wang16,
+///     terrains: {
+///         1: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+///     },
+/// )
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "815e37c7-bd62-4ca4-8e69-130e88e0efa0"]
+pub struct AutotileRuleset {
+    /// The kind of tileset.
+    pub kind: AutotileKind,
+    /// The sprite numbers of each terrain, in the order of the neighbor masks of the `kind`.
+    pub terrains: HashMap<u32, Vec<usize>>,
+}
+
+register_asset_type!(AutotileRuleset => AutotileRuleset; AssetProcessorSystem<AutotileRuleset>);
+
+impl Asset for AutotileRuleset {
+    fn name() -> &'static str {
+        "tiles::AutotileRuleset"
+    }
+    type Data = Self;
+}
+
+impl AutotileRuleset {
+    /// Returns the sprite of a tile of `terrain` whose neighbors of the same terrain are those of
+    /// `mask`, made of `NORTH`, `NORTH_EAST` and the other neighbor constants. Returns `None` if
+    /// the terrain or its sprite is missing.
+    #[must_use]
+    pub fn sprite(&self, terrain: u32, mask: u8) -> Option<usize> {
+        let sprites = self.terrains.get(&terrain)?;
+        let index = match self.kind {
+            AutotileKind::Blob47 => BLOB_MASKS.binary_search(&blob_mask(mask)).ok()?,
+            AutotileKind::Wang16 => usize::from(
+                u8::from(mask & NORTH != 0)
+                    | u8::from(mask & EAST != 0) << 1
+                    | u8::from(mask & SOUTH != 0) << 2
+                    | u8::from(mask & WEST != 0) << 3,
+            ),
+        };
+        sprites.get(index).copied()
+    }
+}
+
+/// Removes the corner neighbors of a mask which are not next to two edge neighbors.
+fn blob_mask(mask: u8) -> u8 {
+    let mut blob = mask & (NORTH | EAST | SOUTH | WEST);
+    for &(corner, first, second) in &[
+        (NORTH_EAST, NORTH, EAST),
+        (SOUTH_EAST, SOUTH, EAST),
+        (SOUTH_WEST, SOUTH, WEST),
+        (NORTH_WEST, NORTH, WEST),
+    ] {
+        if mask & (corner | first | second) == corner | first | second {
+            blob |= corner;
+        }
+    }
+    blob
+}
+
+/// Loads `AutotileRuleset`s from RON `.autotile` files.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[uuid = "1c8b7b43-5a8e-4b6e-a1c4-0f7d5d6a8e2b"]
+pub struct AutotileFormat;
+
+amethyst_assets::register_importer!(".autotile", AutotileFormat);
+impl Format<AutotileRuleset> for AutotileFormat {
+    fn name(&self) -> &'static str {
+        "AutotileRuleset"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<AutotileRuleset, Error> {
+        let mut deserializer = ron::de::Deserializer::from_bytes(&bytes)
+            .with_context(|_| format_err!("Failed deserializing autotile ruleset"))?;
+        let ruleset = AutotileRuleset::deserialize(&mut deserializer)
+            .with_context(|_| format_err!("Failed parsing autotile ruleset"))?;
+        deserializer
+            .end()
+            .with_context(|_| format_err!("Failed parsing autotile ruleset"))?;
+        Ok(ruleset)
+    }
+}
+
+/// A `Tile` whose sprite is set by the `AutotileSystem` from its terrain.
+pub trait AutotileTile: Tile {
+    /// The terrain of the tile, or `None` to leave its sprite as it is.
+    fn terrain(&self) -> Option<u32>;
+
+    /// Sets the sprite chosen for the terrain of the tile, which its `Tile::sprite` should return.
+    fn set_autotile_sprite(&mut self, sprite: usize);
+}
+
+/// A tile of an autotiled map, with a terrain and the sprite chosen for it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutotiledTile {
+    /// The terrain of the tile.
+    pub terrain: Option<u32>,
+    /// The sprite of the tile.
+    pub sprite: Option<usize>,
+}
+
+impl Tile for AutotiledTile {
+    fn sprite(&self, _: Point3<u32>, _: &World, _: &Resources) -> Option<usize> {
+        self.sprite
+    }
+}
+
+impl AutotileTile for AutotiledTile {
+    fn terrain(&self) -> Option<u32> {
+        self.terrain
+    }
+
+    fn set_autotile_sprite(&mut self, sprite: usize) {
+        self.sprite = Some(sprite);
+    }
+}
+
+/// Autotiles the `TileMap` of its entity with a ruleset, with the `AutotileSystem`.
+#[derive(Clone, Debug)]
+pub struct Autotile {
+    /// The ruleset choosing the sprites of the tiles.
+    pub rules: Handle<AutotileRuleset>,
+    pub(crate) state: AutotileState,
+}
+
+/// The state of an autotiled map when it was last autotiled.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AutotileState {
+    /// The version of the map.
+    pub(crate) version: u64,
+    /// The terrains of the tiles, row by row for each z-level.
+    pub(crate) terrains: Vec<Option<u32>>,
+}
+
+impl Autotile {
+    /// Creates an `Autotile` with a ruleset. The whole map is autotiled once the ruleset is
+    /// loaded.
+    #[must_use]
+    pub fn new(rules: Handle<AutotileRuleset>) -> Self {
+        Self {
+            rules,
+            state: AutotileState::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ruleset(kind: AutotileKind, count: usize) -> AutotileRuleset {
+        let mut terrains = HashMap::new();
+        terrains.insert(2, (100..100 + count).collect());
+        AutotileRuleset { kind, terrains }
+    }
+
+    #[test]
+    fn reduces_blob_corners() {
+        assert_eq!(blob_mask(NORTH_EAST), 0);
+        assert_eq!(blob_mask(NORTH | NORTH_EAST), NORTH);
+        assert_eq!(
+            blob_mask(NORTH | NORTH_EAST | EAST | SOUTH_WEST),
+            NORTH | NORTH_EAST | EAST
+        );
+        assert!((0..=255).all(|mask| BLOB_MASKS.contains(&blob_mask(mask))));
+    }
+
+    #[test]
+    fn chooses_sprites_by_mask() {
+        let blob = ruleset(AutotileKind::Blob47, 47);
+        assert_eq!(blob.sprite(2, 0), Some(100));
+        assert_eq!(blob.sprite(2, 255), Some(146));
+        assert_eq!(blob.sprite(2, NORTH | NORTH_EAST), Some(101));
+        assert_eq!(blob.sprite(3, 0), None);
+
+        let wang = ruleset(AutotileKind::Wang16, 16);
+        assert_eq!(wang.sprite(2, NORTH | SOUTH_EAST | WEST), Some(109));
+        assert_eq!(wang.sprite(2, 255), Some(115));
+    }
+
+    #[test]
+    fn parses_rulesets() {
+        let ruleset = AutotileFormat
+            .import_simple(b"(kind: Wang16, terrains: { 1: [4, 5] })".to_vec())
+            .unwrap();
+        assert_eq!(ruleset.kind, AutotileKind::Wang16);
+        assert_eq!(ruleset.terrains[&1], vec![4, 5]);
+    }
+}
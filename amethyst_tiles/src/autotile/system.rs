@@ -0,0 +1,202 @@
+//! Autotiling of `TileMap`s.
+
+use std::marker::PhantomData;
+
+use amethyst_assets::AssetStorage;
+use amethyst_core::{
+    ecs::{IntoQuery, ParallelRunnable, System, SystemBuilder},
+    math::{Point3, Vector3},
+};
+use derivative::Derivative;
+use fnv::FnvHashSet;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{
+    Autotile, AutotileRuleset, AutotileState, AutotileTile, EAST, NORTH, NORTH_EAST, NORTH_WEST,
+    SOUTH, SOUTH_EAST, SOUTH_WEST, WEST,
+};
+use crate::{CoordinateEncoder, Map, MapStorage, MortonEncoder2D, Region, TileMap};
+
+/// The offsets of the neighbors of a tile, with their bit in a neighbor mask.
+const NEIGHBORS: [(i64, i64, u8); 8] = [
+    (0, -1, NORTH),
+    (1, -1, NORTH_EAST),
+    (1, 0, EAST),
+    (1, 1, SOUTH_EAST),
+    (0, 1, SOUTH),
+    (-1, 1, SOUTH_WEST),
+    (-1, 0, WEST),
+    (-1, -1, NORTH_WEST),
+];
+
+/// Sets the sprites of the tiles of each `TileMap<T, E>` with an `Autotile` from their terrain,
+/// when the version of the map changes.
+///
+/// Only the tiles in the changes of the map since it was last autotiled, and their neighbors, get
+/// a new sprite. Sprites are set without changing the version of the map.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Default(bound = ""))]
+pub struct AutotileSystem<T: AutotileTile, E: CoordinateEncoder = MortonEncoder2D> {
+    _marker: PhantomData<(T, E)>,
+}
+
+impl<T: AutotileTile, E: CoordinateEncoder> System for AutotileSystem<T, E> {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("AutotileSystem")
+                .read_resource::<AssetStorage<AutotileRuleset>>()
+                .with_query(<(&mut TileMap<T, E>, &mut Autotile)>::query())
+                .build(move |_, world, rules_storage, query| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("autotile_system");
+
+                    for (map, autotile) in query.iter_mut(world) {
+                        if map.version() == autotile.state.version {
+                            continue;
+                        }
+                        if let Some(rules) = rules_storage.get(&autotile.rules) {
+                            autotile_map(map, rules, &mut autotile.state);
+                        }
+                    }
+                }),
+        )
+    }
+}
+
+/// Returns the index of a tile in the terrains of an `Autotile`.
+fn terrain_index(dimensions: &Vector3<u32>, coord: &Point3<u32>) -> usize {
+    (coord.x + dimensions.x * (coord.y + dimensions.y * coord.z)) as usize
+}
+
+/// Returns the neighbor of a tile at an offset, if it is in the map.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn neighbor(dimensions: &Vector3<u32>, coord: &Point3<u32>, x: i64, y: i64) -> Option<Point3<u32>> {
+    let x = i64::from(coord.x) + x;
+    let y = i64::from(coord.y) + y;
+    if x < 0 || y < 0 || x >= i64::from(dimensions.x) || y >= i64::from(dimensions.y) {
+        None
+    } else {
+        Some(Point3::new(x as u32, y as u32, coord.z))
+    }
+}
+
+/// Sets the sprites of the tiles of a map in its changes since it was last autotiled, and of their
+/// neighbors, or of all its tiles the first time and after changes were drained.
+pub(crate) fn autotile_map<T: AutotileTile, E: CoordinateEncoder>(
+    map: &mut TileMap<T, E>,
+    rules: &AutotileRuleset,
+    state: &mut AutotileState,
+) {
+    let dimensions = *map.dimensions();
+    let region = Region::new(Point3::new(0, 0, 0), Point3::from(dimensions));
+    let len = dimensions.x as usize * dimensions.y as usize * dimensions.z as usize;
+
+    let changed = match map.changes().since(state.version) {
+        Some(changed) if state.terrains.len() == len => changed
+            .filter(|coord| region.contains(coord))
+            .copied()
+            .collect::<Vec<_>>(),
+        _ => {
+            state.terrains = region
+                .iter()
+                .take(len)
+                .map(|coord| map.get(&coord).and_then(AutotileTile::terrain))
+                .collect();
+            region.iter().take(len).collect()
+        }
+    };
+
+    let mut dirty = FnvHashSet::default();
+    for coord in changed {
+        state.terrains[terrain_index(&dimensions, &coord)] =
+            map.get(&coord).and_then(AutotileTile::terrain);
+        dirty.insert(coord);
+        dirty.extend(
+            NEIGHBORS
+                .iter()
+                .filter_map(|&(x, y, _)| neighbor(&dimensions, &coord, x, y)),
+        );
+    }
+
+    for coord in dirty {
+        let terrain = match state.terrains[terrain_index(&dimensions, &coord)] {
+            Some(terrain) => terrain,
+            None => continue,
+        };
+        // Neighbors outside of the map match every terrain
+        let mask = NEIGHBORS.iter().fold(0, |mask, &(x, y, bit)| {
+            match neighbor(&dimensions, &coord, x, y) {
+                Some(neighbor)
+                    if state.terrains[terrain_index(&dimensions, &neighbor)] != Some(terrain) =>
+                {
+                    mask
+                }
+                _ => mask | bit,
+            }
+        });
+        if let Some(sprite) = rules.sprite(terrain, mask) {
+            if let Some(tile) = map.get_mut_nochange(&coord) {
+                tile.set_autotile_sprite(sprite);
+            }
+        }
+    }
+
+    state.version = map.version();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{autotile::AutotileKind, AutotiledTile, FlatEncoder};
+
+    fn rules() -> AutotileRuleset {
+        let mut terrains = HashMap::new();
+        terrains.insert(1, (0..16).collect());
+        AutotileRuleset {
+            kind: AutotileKind::Wang16,
+            terrains,
+        }
+    }
+
+    fn sprite(map: &TileMap<AutotiledTile, FlatEncoder>, x: u32, y: u32) -> Option<usize> {
+        map.get(&Point3::new(x, y, 0)).unwrap().sprite
+    }
+
+    #[test]
+    fn autotiles_changed_neighborhoods() {
+        let mut state = AutotileState::default();
+        let mut map = TileMap::<AutotiledTile, FlatEncoder>::new(
+            Vector3::new(4, 3, 1),
+            Vector3::new(16, 16, 1),
+            None,
+        );
+        for x in 0..4 {
+            map.get_mut(&Point3::new(x, 1, 0)).unwrap().terrain = Some(1);
+        }
+
+        autotile_map(&mut map, &rules(), &mut state);
+        assert_eq!(state.version, map.version());
+        // The edges of the map match, the empty rows above and below do not
+        assert_eq!(sprite(&map, 0, 1), Some(10));
+        assert_eq!(sprite(&map, 3, 1), Some(10));
+        assert_eq!(sprite(&map, 0, 0), None);
+
+        map.get_mut_nochange(&Point3::new(0, 1, 0)).unwrap().sprite = Some(99);
+        map.get_mut(&Point3::new(3, 0, 0)).unwrap().terrain = Some(1);
+        autotile_map(&mut map, &rules(), &mut state);
+        assert_eq!(sprite(&map, 3, 0), Some(7));
+        assert_eq!(sprite(&map, 3, 1), Some(11));
+        // Tiles outside of the changed neighborhood keep their sprite
+        assert_eq!(sprite(&map, 0, 1), Some(99));
+
+        // Changes which were drained are missed, so every tile is autotiled again
+        map.get_mut(&Point3::new(3, 0, 0)).unwrap().terrain = None;
+        map.drain_changes();
+        autotile_map(&mut map, &rules(), &mut state);
+        assert_eq!(sprite(&map, 3, 1), Some(10));
+        assert_eq!(sprite(&map, 0, 1), Some(10));
+    }
+}
@@ -0,0 +1,716 @@
+//! Unbounded tile maps, made of lazily allocated chunks.
+
+use std::{collections::HashMap, convert::TryFrom};
+
+use amethyst_assets::Handle;
+use amethyst_core::{
+    math::{Matrix4, Point3, Vector3},
+    transform::Transform,
+};
+use amethyst_rendy::SpriteSheet;
+
+use crate::{
+    error::ChunkDimensionsError,
+    iters::Region,
+    map::{Map, MapStorage, Tile, TileMap},
+    CoordinateEncoder, TileChanges, TileOutOfBoundsError,
+};
+
+/// The offset of signed tile coordinates in the `Point3<u32>` coordinates used by `Map` and
+/// `MapStorage`.
+const COORDINATE_OFFSET: i64 = 1 << 31;
+
+/// A tile map without bounds, addressed with signed coordinates, made of chunks of fixed
+/// dimensions which are allocated when a tile of them is first written.
+///
+/// Each chunk is a `TileMap` of the chunk dimensions, encoded with the `CoordinateEncoder` of the
+/// map. Chunks can be unloaded to save them, and inserted back when streaming them in, and only the
+/// resident chunks are rendered by `DrawTiles2D`.
+///
+/// The tile at the signed coordinates (0, 0, 0) is centered on the origin of the map, and tile
+/// coordinates grow to the right and downwards, like in a `TileMap`.
+///
+/// `Map` and `MapStorage` address tiles with `Point3<u32>` coordinates, so in their methods the
+/// signed coordinates are offset by 2^31, as converted by `to_unsigned` and `to_signed`. Their
+/// `dimensions` span every unsigned coordinate below `u32::MAX`, so that `astar`, `line_of_sight`
+/// and `TileMapDiff` reach every tile below `i32::MAX`, and their raw indices are only valid until
+/// a chunk is unloaded. Dijkstra maps, flow fields, `field_of_view` and `TileCollision` allocate
+/// for the whole dimensions of a map, so they return a `MapTooLargeError` for chunked maps.
+///
+/// The changes of the map record the tiles mutably borrowed in the coordinates of `Map`, every
+/// tile of a chunk being recorded when the whole chunk is mutably borrowed, inserted or unloaded.
+#[derive(Clone, Debug)]
+pub struct ChunkedTileMap<T: Tile, E: CoordinateEncoder = crate::MortonEncoder2D> {
+    pub(crate) origin: Point3<f32>,
+    pub(crate) tile_dimensions: Vector3<u32>,
+    pub(crate) chunk_dimensions: Vector3<u32>,
+    /// The dimensions of the map in the coordinates of `Map`.
+    pub(crate) dimensions: Vector3<u32>,
+    pub(crate) transform: Matrix4<f32>,
+
+    pub(crate) version: u64,
+
+    pub(crate) changes: TileChanges,
+
+    pub(crate) sprite_sheet: Option<Handle<SpriteSheet>>,
+
+    /// The resident chunks with their chunk coordinates.
+    pub(crate) chunks: Vec<(Point3<i32>, TileMap<T, E>)>,
+    /// The indices of the resident chunks in `chunks`, by chunk coordinates.
+    pub(crate) slots: HashMap<Point3<i32>, usize>,
+
+    pub(crate) encoder: E,
+}
+
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+impl<T: Tile, E: CoordinateEncoder> ChunkedTileMap<T, E> {
+    /// Create a new instance of `ChunkedTileMap`, with chunks of `chunk_dimensions` tiles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a chunk dimension is 0 or greater than `i32::MAX`.
+    #[must_use]
+    pub fn new(
+        chunk_dimensions: Vector3<u32>,
+        tile_dimensions: Vector3<u32>,
+        sprite_sheet: Option<Handle<SpriteSheet>>,
+    ) -> Self {
+        assert!(
+            chunk_dimensions
+                .iter()
+                .all(|dimension| *dimension > 0 && i32::try_from(*dimension).is_ok()),
+            "Invalid chunk dimensions {:?}",
+            chunk_dimensions
+        );
+        let transform = Matrix4::new_nonuniform_scaling(&Vector3::new(
+            tile_dimensions.x as f32,
+            tile_dimensions.y as f32,
+            tile_dimensions.z as f32,
+        ));
+
+        Self {
+            origin: Point3::new(0.0, 0.0, 0.0),
+            tile_dimensions,
+            chunk_dimensions,
+            dimensions: Vector3::new(u32::MAX, u32::MAX, u32::MAX),
+            transform,
+            version: 1,
+            changes: TileChanges::default(),
+            sprite_sheet,
+            chunks: Vec::new(),
+            slots: HashMap::new(),
+            encoder: E::from_dimensions(chunk_dimensions),
+        }
+    }
+
+    /// Versioning for change cache management
+    #[must_use]
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The tiles of this map which were mutably borrowed since its changes were last drained, in
+    /// the coordinates of `Map`.
+    #[must_use]
+    pub fn changes(&self) -> &TileChanges {
+        &self.changes
+    }
+
+    /// Takes the tiles of this map which were mutably borrowed since its changes were last
+    /// drained, like `TileMap::drain_changes`.
+    pub fn drain_changes(&mut self) -> TileChanges {
+        std::mem::replace(&mut self.changes, TileChanges::drained_in(self.version))
+    }
+
+    /// The dimensions of the chunks of this map.
+    #[must_use]
+    pub fn chunk_dimensions(&self) -> &Vector3<u32> {
+        &self.chunk_dimensions
+    }
+
+    /// Converts signed tile coordinates to the coordinates used by `Map` and `MapStorage`.
+    #[must_use]
+    pub fn to_unsigned(coord: &Point3<i32>) -> Point3<u32> {
+        coord.map(|value| (i64::from(value) + COORDINATE_OFFSET) as u32)
+    }
+
+    /// Converts the coordinates used by `Map` and `MapStorage` to signed tile coordinates.
+    #[must_use]
+    pub fn to_signed(coord: &Point3<u32>) -> Point3<i32> {
+        coord.map(|value| (i64::from(value) - COORDINATE_OFFSET) as i32)
+    }
+
+    /// Returns the coordinates of the chunk of a tile, and the coordinates of the tile in it.
+    #[must_use]
+    pub fn split(&self, coord: &Point3<i32>) -> (Point3<i32>, Point3<u32>) {
+        let dimensions = self.chunk_dimensions.map(|dimension| dimension as i32);
+        let chunk = Point3::new(
+            coord.x.div_euclid(dimensions.x),
+            coord.y.div_euclid(dimensions.y),
+            coord.z.div_euclid(dimensions.z),
+        );
+        let local = Point3::new(
+            coord.x.rem_euclid(dimensions.x) as u32,
+            coord.y.rem_euclid(dimensions.y) as u32,
+            coord.z.rem_euclid(dimensions.z) as u32,
+        );
+        (chunk, local)
+    }
+
+    /// Returns the signed coordinates of the first tile of a chunk.
+    #[must_use]
+    pub fn chunk_origin(&self, chunk: &Point3<i32>) -> Point3<i32> {
+        Point3::new(
+            chunk.x.saturating_mul(self.chunk_dimensions.x as i32),
+            chunk.y.saturating_mul(self.chunk_dimensions.y as i32),
+            chunk.z.saturating_mul(self.chunk_dimensions.z as i32),
+        )
+    }
+
+    /// Returns the tile at the provided signed coordinates, if its chunk is resident.
+    #[must_use]
+    pub fn tile(&self, coord: &Point3<i32>) -> Option<&T> {
+        let (chunk, local) = self.split(coord);
+        self.chunk(&chunk)?.get(&local)
+    }
+
+    /// Returns a mutable reference to the tile at the provided signed coordinates, allocating
+    /// its chunk if it is not resident.
+    pub fn tile_mut(&mut self, coord: &Point3<i32>) -> Option<&mut T> {
+        let (chunk, local) = self.split(coord);
+        self.version += 1;
+        self.changes.insert(Self::to_unsigned(coord), self.version);
+        let slot = self.slot(&chunk);
+        self.chunks[slot].1.get_mut_nochange(&local)
+    }
+
+    /// Returns whether a chunk is resident.
+    #[must_use]
+    pub fn is_resident(&self, chunk: &Point3<i32>) -> bool {
+        self.slots.contains_key(chunk)
+    }
+
+    /// Returns a resident chunk.
+    #[must_use]
+    pub fn chunk(&self, chunk: &Point3<i32>) -> Option<&TileMap<T, E>> {
+        Some(&self.chunks[*self.slots.get(chunk)?].1)
+    }
+
+    /// Returns a mutable reference to a resident chunk.
+    pub fn chunk_mut(&mut self, chunk: &Point3<i32>) -> Option<&mut TileMap<T, E>> {
+        let slot = *self.slots.get(chunk)?;
+        self.version += 1;
+        self.record_chunk(chunk);
+        Some(&mut self.chunks[slot].1)
+    }
+
+    /// Iterates over the resident chunks with their chunk coordinates.
+    pub fn resident_chunks(&self) -> impl Iterator<Item = (&Point3<i32>, &TileMap<T, E>)> {
+        self.chunks.iter().map(|(chunk, tiles)| (chunk, tiles))
+    }
+
+    /// Returns a mutable reference to a resident chunk, allocating it with default tiles if it is
+    /// not resident.
+    pub fn load_chunk(&mut self, chunk: &Point3<i32>) -> &mut TileMap<T, E> {
+        let slot = self.slot(chunk);
+        self.version += 1;
+        self.record_chunk(chunk);
+        &mut self.chunks[slot].1
+    }
+
+    /// Streams a chunk in, replacing the resident chunk at the same coordinates which is
+    /// returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ChunkDimensionsError` if the dimensions of `tiles` are not the chunk dimensions of
+    /// this map.
+    pub fn insert_chunk(
+        &mut self,
+        chunk: Point3<i32>,
+        tiles: TileMap<T, E>,
+    ) -> Result<Option<TileMap<T, E>>, ChunkDimensionsError> {
+        if *tiles.dimensions() != self.chunk_dimensions {
+            return Err(ChunkDimensionsError {
+                dimensions: *tiles.dimensions(),
+                chunk_dimensions: self.chunk_dimensions,
+            });
+        }
+        let previous = self.unload_chunk(&chunk);
+        self.version += 1;
+        self.record_chunk(&chunk);
+        self.push_chunk(chunk, tiles);
+        Ok(previous)
+    }
+
+    /// Streams a chunk out, returning it if it was resident.
+    pub fn unload_chunk(&mut self, chunk: &Point3<i32>) -> Option<TileMap<T, E>> {
+        let slot = self.slots.remove(chunk)?;
+        self.version += 1;
+        self.record_chunk(chunk);
+        let (_, tiles) = self.chunks.swap_remove(slot);
+        if let Some((moved, _)) = self.chunks.get(slot) {
+            self.slots.insert(*moved, slot);
+        }
+        Some(tiles)
+    }
+
+    /// Streams out the resident chunks for which `predicate` returns `true`, returning them with
+    /// their chunk coordinates.
+    pub fn unload_chunks<F>(&mut self, mut predicate: F) -> Vec<(Point3<i32>, TileMap<T, E>)>
+    where
+        F: FnMut(&Point3<i32>) -> bool,
+    {
+        let unloaded = self
+            .slots
+            .keys()
+            .filter(|chunk| predicate(chunk))
+            .copied()
+            .collect::<Vec<_>>();
+        unloaded
+            .into_iter()
+            .filter_map(|chunk| Some((chunk, self.unload_chunk(&chunk)?)))
+            .collect()
+    }
+
+    /// Converts signed tile coordinates to world coordinates, like `Map::to_world`.
+    #[must_use]
+    pub fn tile_to_world(
+        &self,
+        coord: &Point3<i32>,
+        map_transform: Option<&Transform>,
+    ) -> Vector3<f32> {
+        let coord = Point3::new(coord.x as f32, -i64::from(coord.y) as f32, coord.z as f32);
+        let point = self.transform.transform_point(&coord);
+        map_transform.map_or(point.coords, |map_transform| {
+            map_transform.global_matrix().transform_point(&point).coords
+        })
+    }
+
+    /// Converts world coordinates to the signed coordinates of the tile containing them, like
+    /// `Map::to_tile`. Every coordinate is inside the map.
+    #[must_use]
+    pub fn world_to_tile(
+        &self,
+        coord: &Vector3<f32>,
+        map_transform: Option<&Transform>,
+    ) -> Point3<i32> {
+        let point = Point3::from(*coord);
+        let point = map_transform.map_or(point, |map_transform| {
+            map_transform.global_view_matrix().transform_point(&point)
+        });
+        let inverse = self
+            .transform
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity)
+            .transform_point(&point);
+        Point3::new(
+            inverse.x.round() as i32,
+            (-inverse.y).round() as i32,
+            inverse.z.floor() as i32,
+        )
+    }
+
+    /// Returns the transform from the coordinates of the tiles in a chunk to the coordinates of the
+    /// map, as used by `DrawTiles2D`.
+    #[must_use]
+    pub fn chunk_transform(&self, chunk: &Point3<i32>) -> Matrix4<f32> {
+        let origin = self.chunk_origin(chunk);
+        self.transform
+            * Matrix4::new_translation(&Vector3::new(
+                origin.x as f32,
+                -i64::from(origin.y) as f32,
+                origin.z as f32,
+            ))
+    }
+
+    /// Returns the region of the tiles of a chunk within `bounds`, in the coordinates of the
+    /// chunk, or `None` if no tile of the chunk is within them. `bounds` are signed tile
+    /// coordinates from the minimum inclusive to the maximum exclusive, and `None` bounds contain
+    /// every tile.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn chunk_region(
+        &self,
+        chunk: &Point3<i32>,
+        bounds: Option<(Point3<i32>, Point3<i32>)>,
+    ) -> Option<Region> {
+        let dimensions = self.chunk_dimensions;
+        let (min, max) = match bounds {
+            Some(bounds) => bounds,
+            None => return Some(Region::new(Point3::new(0, 0, 0), Point3::from(dimensions))),
+        };
+        let origin = self.chunk_origin(chunk);
+        let local = |value: i32, origin: i32, dimension: u32| {
+            (i64::from(value) - i64::from(origin))
+                .max(0)
+                .min(i64::from(dimension)) as u32
+        };
+        let min = Point3::new(
+            local(min.x, origin.x, dimensions.x),
+            local(min.y, origin.y, dimensions.y),
+            local(min.z, origin.z, dimensions.z),
+        );
+        let max = Point3::new(
+            local(max.x, origin.x, dimensions.x),
+            local(max.y, origin.y, dimensions.y),
+            local(max.z, origin.z, dimensions.z),
+        );
+        if min.x < max.x && min.y < max.y && min.z < max.z {
+            Some(Region::new(min, max))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the slot of a chunk, allocating it with default tiles if it is not resident.
+    fn slot(&mut self, chunk: &Point3<i32>) -> usize {
+        match self.slots.get(chunk) {
+            Some(slot) => *slot,
+            None => {
+                let tiles = TileMap::new(self.chunk_dimensions, self.tile_dimensions, None);
+                self.push_chunk(*chunk, tiles)
+            }
+        }
+    }
+
+    /// Records a change of every tile of a chunk.
+    #[allow(clippy::cast_possible_wrap)]
+    fn record_chunk(&mut self, chunk: &Point3<i32>) {
+        let origin = self.chunk_origin(chunk);
+        let all = Region::new(Point3::new(0, 0, 0), Point3::from(self.chunk_dimensions));
+        for local in all.iter() {
+            let coord = Point3::new(
+                origin.x.wrapping_add(local.x as i32),
+                origin.y.wrapping_add(local.y as i32),
+                origin.z.wrapping_add(local.z as i32),
+            );
+            self.changes.insert(Self::to_unsigned(&coord), self.version);
+        }
+    }
+
+    fn push_chunk(&mut self, chunk: Point3<i32>, tiles: TileMap<T, E>) -> usize {
+        self.chunks.push((chunk, tiles));
+        self.slots.insert(chunk, self.chunks.len() - 1);
+        self.chunks.len() - 1
+    }
+
+    /// Returns the slot of a raw index and the raw index in its chunk.
+    fn split_raw(&self, coord: u32) -> (usize, u32) {
+        let size = E::allocation_size(self.chunk_dimensions) as u32;
+        ((coord / size) as usize, coord % size)
+    }
+}
+
+impl<T: Tile, E: CoordinateEncoder> Map for ChunkedTileMap<T, E> {
+    #[inline]
+    fn tile_dimensions(&self) -> &Vector3<u32> {
+        &self.tile_dimensions
+    }
+
+    #[inline]
+    fn dimensions(&self) -> &Vector3<u32> {
+        &self.dimensions
+    }
+
+    #[inline]
+    fn origin(&self) -> &Point3<f32> {
+        &self.origin
+    }
+
+    #[inline]
+    fn set_sprite_sheet(&mut self, sprite_sheet: Option<Handle<SpriteSheet>>) {
+        self.sprite_sheet = sprite_sheet;
+    }
+
+    #[inline]
+    fn to_world(&self, coord: &Point3<u32>, map_transform: Option<&Transform>) -> Vector3<f32> {
+        self.tile_to_world(&Self::to_signed(coord), map_transform)
+    }
+
+    #[inline]
+    fn to_tile(
+        &self,
+        coord: &Vector3<f32>,
+        map_transform: Option<&Transform>,
+    ) -> Result<Point3<u32>, TileOutOfBoundsError> {
+        Ok(Self::to_unsigned(&self.world_to_tile(coord, map_transform)))
+    }
+
+    #[inline]
+    fn transform(&self) -> &Matrix4<f32> {
+        &self.transform
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn encode(&self, coord: &Point3<u32>) -> Option<u32> {
+        let (chunk, local) = self.split(&Self::to_signed(coord));
+        let slot = u32::try_from(*self.slots.get(&chunk)?).ok()?;
+        let size = E::allocation_size(self.chunk_dimensions) as u32;
+        let local = self.encoder.encode(local.x, local.y, local.z)?;
+        slot.checked_mul(size)?.checked_add(local)
+    }
+
+    #[inline]
+    fn encode_raw(&self, coord: &(u32, u32, u32)) -> Option<u32> {
+        self.encode(&Point3::new(coord.0, coord.1, coord.2))
+    }
+
+    #[allow(clippy::cast_possible_wrap)]
+    fn decode(&self, morton: u32) -> Option<Point3<u32>> {
+        let (slot, local) = self.split_raw(morton);
+        let (chunk, _) = self.chunks.get(slot)?;
+        let local = self.encoder.decode(local)?;
+        let origin = self.chunk_origin(chunk);
+        Some(Self::to_unsigned(&Point3::new(
+            origin.x + local.0 as i32,
+            origin.y + local.1 as i32,
+            origin.z + local.2 as i32,
+        )))
+    }
+
+    #[inline]
+    fn decode_raw(&self, morton: u32) -> Option<(u32, u32, u32)> {
+        self.decode(morton).map(|coord| (coord.x, coord.y, coord.z))
+    }
+}
+
+impl<T: Tile, E: CoordinateEncoder> MapStorage<T> for ChunkedTileMap<T, E> {
+    #[inline]
+    fn get(&self, coord: &Point3<u32>) -> Option<&T> {
+        self.tile(&Self::to_signed(coord))
+    }
+
+    #[inline]
+    fn get_mut(&mut self, coord: &Point3<u32>) -> Option<&mut T> {
+        self.tile_mut(&Self::to_signed(coord))
+    }
+
+    #[inline]
+    fn get_mut_nochange(&mut self, coord: &Point3<u32>) -> Option<&mut T> {
+        let (chunk, local) = self.split(&Self::to_signed(coord));
+        let slot = *self.slots.get(&chunk)?;
+        self.chunks[slot].1.get_mut_nochange(&local)
+    }
+
+    #[inline]
+    fn get_raw(&self, coord: u32) -> Option<&T> {
+        let (slot, local) = self.split_raw(coord);
+        self.chunks.get(slot)?.1.get_raw(local)
+    }
+
+    #[inline]
+    fn get_raw_mut(&mut self, coord: u32) -> Option<&mut T> {
+        self.version += 1;
+        if let Some(point) = self.decode(coord) {
+            self.changes.insert(point, self.version);
+        }
+        self.get_raw_mut_nochange(coord)
+    }
+
+    #[inline]
+    fn get_raw_mut_nochange(&mut self, coord: u32) -> Option<&mut T> {
+        let (slot, local) = self.split_raw(coord);
+        self.chunks.get_mut(slot)?.1.get_raw_mut_nochange(local)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        collision::TileCollision,
+        fov::{field_of_view, line_of_sight, FovAlgorithm, Visibility},
+        pathfinding::{astar, Connectivity, DijkstraMap},
+        FlatEncoder,
+    };
+
+    #[derive(Clone, Debug, Default, PartialEq)]
+    struct TestTile(u32);
+    impl Tile for TestTile {}
+
+    fn map() -> ChunkedTileMap<TestTile, FlatEncoder> {
+        ChunkedTileMap::new(Vector3::new(4, 4, 1), Vector3::new(16, 16, 1), None)
+    }
+
+    #[test]
+    fn clips_bounds_to_chunks() {
+        let map = map();
+        let bounds = Some((Point3::new(-2, 1, i32::MIN), Point3::new(3, 9, i32::MAX)));
+        assert_eq!(
+            map.chunk_region(&Point3::new(-1, 0, 0), bounds),
+            Some(Region::new(Point3::new(2, 1, 0), Point3::new(4, 4, 1)))
+        );
+        assert_eq!(
+            map.chunk_region(&Point3::new(0, 2, 0), bounds),
+            Some(Region::new(Point3::new(0, 0, 0), Point3::new(3, 1, 1)))
+        );
+        assert_eq!(map.chunk_region(&Point3::new(1, 0, 0), bounds), None);
+        assert_eq!(
+            map.chunk_region(&Point3::new(5, 5, 0), None),
+            Some(Region::new(Point3::new(0, 0, 0), Point3::new(4, 4, 1)))
+        );
+    }
+
+    #[test]
+    fn splits_signed_coordinates() {
+        let map = map();
+        assert_eq!(
+            map.split(&Point3::new(5, -1, 0)),
+            (Point3::new(1, -1, 0), Point3::new(1, 3, 0))
+        );
+        assert_eq!(
+            map.split(&Point3::new(-4, 3, -1)),
+            (Point3::new(-1, 0, -1), Point3::new(0, 3, 0))
+        );
+        let coord = Point3::new(-7, 12, 0);
+        assert_eq!(
+            ChunkedTileMap::<TestTile, FlatEncoder>::to_signed(&ChunkedTileMap::<
+                TestTile,
+                FlatEncoder,
+            >::to_unsigned(&coord)),
+            coord
+        );
+    }
+
+    #[test]
+    fn allocates_chunks_lazily() {
+        let mut map = map();
+        assert!(map.tile(&Point3::new(-3, 9, 0)).is_none());
+        *map.tile_mut(&Point3::new(-3, 9, 0)).unwrap() = TestTile(7);
+        assert_eq!(map.tile(&Point3::new(-3, 9, 0)), Some(&TestTile(7)));
+        assert_eq!(map.tile(&Point3::new(-4, 8, 0)), Some(&TestTile(0)));
+        assert!(map.is_resident(&Point3::new(-1, 2, 0)));
+        assert_eq!(map.resident_chunks().count(), 1);
+    }
+
+    #[test]
+    fn streams_chunks() {
+        let mut map = map();
+        *map.tile_mut(&Point3::new(1, 1, 0)).unwrap() = TestTile(1);
+        *map.tile_mut(&Point3::new(10, 1, 0)).unwrap() = TestTile(2);
+
+        let unloaded = map.unload_chunks(|chunk| chunk.x > 0);
+        assert_eq!(unloaded.len(), 1);
+        assert!(map.tile(&Point3::new(10, 1, 0)).is_none());
+
+        let (chunk, tiles) = unloaded.into_iter().next().unwrap();
+        assert!(map.insert_chunk(chunk, tiles).unwrap().is_none());
+        assert_eq!(map.tile(&Point3::new(10, 1, 0)), Some(&TestTile(2)));
+        assert_eq!(map.chunks.len(), 2);
+
+        let wrong = TileMap::new(Vector3::new(2, 2, 1), Vector3::new(16, 16, 1), None);
+        assert!(map.insert_chunk(Point3::new(5, 5, 0), wrong).is_err());
+    }
+
+    #[test]
+    fn records_changes() {
+        let mut map = map();
+        *map.tile_mut(&Point3::new(-1, 2, 0)).unwrap() = TestTile(1);
+        let changes = map.drain_changes();
+        assert_eq!(changes.len(), 1);
+        assert!(
+            changes.contains(&ChunkedTileMap::<TestTile, FlatEncoder>::to_unsigned(
+                &Point3::new(-1, 2, 0)
+            ))
+        );
+
+        let (chunk, tiles) = map.unload_chunks(|_| true).pop().unwrap();
+        assert_eq!(map.drain_changes().len(), 16);
+        map.insert_chunk(chunk, tiles).unwrap();
+        let changes = map.drain_changes();
+        assert_eq!(changes.len(), 16);
+        assert!(
+            changes.contains(&ChunkedTileMap::<TestTile, FlatEncoder>::to_unsigned(
+                &Point3::new(-4, 3, 0)
+            ))
+        );
+    }
+
+    #[test]
+    fn encodes_through_resident_chunks() {
+        let mut map = map();
+        let coord = ChunkedTileMap::<TestTile, FlatEncoder>::to_unsigned(&Point3::new(-2, 5, 0));
+        assert!(map.encode(&coord).is_none());
+        *map.get_mut(&coord).unwrap() = TestTile(3);
+        let index = map.encode(&coord).unwrap();
+        assert_eq!(map.decode(index), Some(coord));
+        assert_eq!(map.get_raw(index), Some(&TestTile(3)));
+    }
+
+    #[test]
+    fn finds_paths_across_chunks() {
+        let mut map = map();
+        for x in -6..6 {
+            for y in -6..6 {
+                *map.tile_mut(&Point3::new(x, y, 0)).unwrap() =
+                    TestTile(u32::from(x == 0 && y < 5));
+            }
+        }
+        let start = ChunkedTileMap::<TestTile, FlatEncoder>::to_unsigned(&Point3::new(-3, 0, 0));
+        let goal = ChunkedTileMap::<TestTile, FlatEncoder>::to_unsigned(&Point3::new(3, 0, 0));
+        let path = astar(
+            &map,
+            Connectivity::Four,
+            &start,
+            &goal,
+            |_, tile: &TestTile| {
+                if tile.0 == 0 {
+                    Some(1.0)
+                } else {
+                    None
+                }
+            },
+        )
+        .unwrap();
+        assert_eq!(path.tiles.first(), Some(&start));
+        assert_eq!(path.tiles.last(), Some(&goal));
+        assert!(path.tiles.iter().any(|tile| {
+            ChunkedTileMap::<TestTile, FlatEncoder>::to_signed(tile) == Point3::new(0, 5, 0)
+        }));
+        assert!(!line_of_sight(
+            &map,
+            &start,
+            &goal,
+            |_, tile: &TestTile| tile.0 != 0
+        ));
+    }
+
+    #[test]
+    fn rejects_whole_map_computations() {
+        let mut map = map();
+        *map.tile_mut(&Point3::new(0, 0, 0)).unwrap() = TestTile(1);
+        let origin = ChunkedTileMap::<TestTile, FlatEncoder>::to_unsigned(&Point3::new(0, 0, 0));
+        assert!(DijkstraMap::<FlatEncoder>::new(
+            &map,
+            Connectivity::Four,
+            Some(origin),
+            |_, _: &TestTile| Some(1.0)
+        )
+        .is_err());
+        assert!(field_of_view(
+            &map,
+            FovAlgorithm::Shadowcasting,
+            &origin,
+            4,
+            |_, tile: &TestTile| tile.0 != 0,
+            &mut Visibility::default()
+        )
+        .is_err());
+        assert!(TileCollision::new(&map, origin.z, |_, tile: &TestTile| tile.0 != 0).is_err());
+    }
+
+    #[test]
+    fn converts_world_coordinates() {
+        let map = map();
+        let coord = Point3::new(-3, 2, 0);
+        let world = map.tile_to_world(&coord, None);
+        assert_eq!(world, Vector3::new(-48., -32., 0.));
+        assert_eq!(
+            map.world_to_tile(&(world + Vector3::new(7., 7., 0.)), None),
+            coord
+        );
+        let far = map.tile_to_world(&Point3::new(0, i32::MIN, 0), None);
+        assert!(far.y > 0.0);
+    }
+}
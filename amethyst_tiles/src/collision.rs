@@ -0,0 +1,726 @@
+//! Collision shapes of the solid tiles of a z-level of a map, for physics integrations.
+//!
+//! A `TileCollision` merges the solid tiles of a z-level into few rectangles, and traces the
+//! outlines of solid areas as closed polygons whose edges can be used as chain shapes. Shapes are
+//! made of tile corners, the corner (x, y) being the top left corner of the tile (x, y), and are
+//! converted to world space with `Map::to_world` by a `CollisionSpace`.
+//!
+//! When tiles change, `TileCollision::update` only merges again the tiles of the rectangles
+//! touching the changed region, and traces again the outlines touching it, returning the shapes
+//! removed and added. `TileCollision::update_changes` finds the changed region in the changes of a
+//! `TileMap`.
+//!
+//! Shapes follow the grid of the tiles, so they suit orthogonal and isometric diamond maps.
+
+use amethyst_core::{
+    math::{Point2, Point3, Vector3},
+    transform::Transform,
+};
+use fnv::FnvHashSet;
+
+use crate::{CoordinateEncoder, Map, MapStorage, MapTooLargeError, Region, Tile, TileMap};
+
+/// The outline of a solid area, or of a hole in one, along the edges of its tiles.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Outline {
+    /// The tile corners where the outline turns. The solid tiles are on the right of the edges
+    /// from a corner to the next one, so the outlines of solid areas go clockwise and those of
+    /// holes go counterclockwise, with y growing downwards.
+    pub corners: Vec<Point2<u32>>,
+}
+
+impl Outline {
+    /// Returns the edges of the outline, from each corner to the next one and from the last
+    /// corner back to the first one.
+    pub fn edges(&self) -> impl Iterator<Item = (Point2<u32>, Point2<u32>)> + '_ {
+        self.corners
+            .iter()
+            .zip(self.corners.iter().cycle().skip(1))
+            .map(|(from, to)| (*from, *to))
+    }
+}
+
+/// The rectangles and outlines of a `TileCollision` removed and added by an update.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CollisionChanges {
+    /// The rectangles which are no longer part of the collision shapes.
+    pub removed: Vec<Region>,
+    /// The new rectangles.
+    pub added: Vec<Region>,
+    /// The outlines which are no longer part of the collision shapes.
+    pub removed_outlines: Vec<Outline>,
+    /// The new outlines.
+    pub added_outlines: Vec<Outline>,
+}
+
+/// The collision shapes of the solid tiles of a z-level of a map.
+#[derive(Clone, Debug, Default)]
+pub struct TileCollision {
+    z: u32,
+    width: u32,
+    height: u32,
+    solid: Vec<bool>,
+    rectangles: Vec<Region>,
+    outlines: Vec<Outline>,
+    /// The version of the map when the shapes were last updated by `update_changes`.
+    version: u64,
+}
+
+impl TileCollision {
+    /// Computes the collision shapes of the z-level `z` of a map, where `solid` returns whether a
+    /// tile collides.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MapTooLargeError` if a z-level of the map has more than
+    /// `MapTooLargeError::MAX_TILES` tiles, such as a `ChunkedTileMap`.
+    pub fn new<T, M, F>(map: &M, z: u32, solid: F) -> Result<Self, MapTooLargeError>
+    where
+        T: Tile,
+        M: Map + MapStorage<T>,
+        F: FnMut(&Point3<u32>, &T) -> bool,
+    {
+        let mut collision = Self {
+            z,
+            ..Self::default()
+        };
+        collision.rebuild(map, solid)?;
+        Ok(collision)
+    }
+
+    /// The z-level of the map.
+    #[must_use]
+    pub fn z(&self) -> u32 {
+        self.z
+    }
+
+    /// The solid tiles merged into rectangles spanning a single z-level. The rectangles do not
+    /// overlap.
+    #[must_use]
+    pub fn rectangles(&self) -> &[Region] {
+        &self.rectangles
+    }
+
+    /// Returns whether a tile of the z-level is solid.
+    #[must_use]
+    pub fn is_solid(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height && self.solid[self.index(x, y)]
+    }
+
+    /// The outlines of the solid areas of the z-level and of their holes. Tiles touching only by a
+    /// corner are in separate outlines.
+    #[must_use]
+    pub fn outlines(&self) -> &[Outline] {
+        &self.outlines
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        x as usize + self.width as usize * y as usize
+    }
+
+    /// Computes the collision shapes of the whole z-level.
+    fn rebuild<T, M, F>(&mut self, map: &M, mut solid: F) -> Result<(), MapTooLargeError>
+    where
+        T: Tile,
+        M: Map + MapStorage<T>,
+        F: FnMut(&Point3<u32>, &T) -> bool,
+    {
+        let dimensions = *map.dimensions();
+        MapTooLargeError::check(Vector3::new(dimensions.x, dimensions.y, 1))?;
+        self.width = dimensions.x;
+        self.height = dimensions.y;
+        self.solid = vec![false; self.width as usize * self.height as usize];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = self.index(x, y);
+                self.solid[index] = tile_is_solid(map, &Point3::new(x, y, self.z), &mut solid);
+            }
+        }
+
+        self.rectangles.clear();
+        let mut cover = self.solid.clone();
+        merge_rectangles(
+            &mut cover,
+            Point2::new(0, 0),
+            self.width,
+            self.height,
+            self.z,
+            &mut self.rectangles,
+        );
+
+        self.outlines.clear();
+        let all = Region::new(
+            Point3::new(0, 0, self.z),
+            Point3::new(self.width, self.height, self.z + 1),
+        );
+        self.trace_outlines(&all, |_| true);
+        Ok(())
+    }
+
+    /// Updates the collision shapes after the tiles of the `dirty` region of a map changed. Only
+    /// the tiles of the rectangles touching the region are merged again, and only the outlines
+    /// touching it are traced again, unless the dimensions of the map changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MapTooLargeError` if the dimensions of the map changed and a z-level has more
+    /// than `MapTooLargeError::MAX_TILES` tiles.
+    pub fn update<T, M, F>(
+        &mut self,
+        map: &M,
+        dirty: &Region,
+        mut solid: F,
+    ) -> Result<CollisionChanges, MapTooLargeError>
+    where
+        T: Tile,
+        M: Map + MapStorage<T>,
+        F: FnMut(&Point3<u32>, &T) -> bool,
+    {
+        let dimensions = map.dimensions();
+        if dimensions.x != self.width || dimensions.y != self.height {
+            return self.replace(map, solid);
+        }
+
+        let min = Point2::new(dirty.min.x.min(self.width), dirty.min.y.min(self.height));
+        let max = Point2::new(dirty.max.x.min(self.width), dirty.max.y.min(self.height));
+        if dirty.min.z > self.z || dirty.max.z <= self.z || min.x >= max.x || min.y >= max.y {
+            return Ok(CollisionChanges::default());
+        }
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let index = self.index(x, y);
+                self.solid[index] = tile_is_solid(map, &Point3::new(x, y, self.z), &mut solid);
+            }
+        }
+
+        // The tiles of the rectangles touching the changed tiles are merged again with them
+        let changed = Region::new(
+            Point3::new(min.x, min.y, self.z),
+            Point3::new(max.x, max.y, self.z + 1),
+        );
+        let (removed, kept): (Vec<_>, Vec<_>) = self
+            .rectangles
+            .drain(..)
+            .partition(|rectangle| rectangle.intersects(&changed));
+        self.rectangles = kept;
+
+        let mut bounds_min = min;
+        let mut bounds_max = max;
+        for rectangle in &removed {
+            bounds_min = Point2::new(
+                bounds_min.x.min(rectangle.min.x),
+                bounds_min.y.min(rectangle.min.y),
+            );
+            bounds_max = Point2::new(
+                bounds_max.x.max(rectangle.max.x),
+                bounds_max.y.max(rectangle.max.y),
+            );
+        }
+        let width = bounds_max.x - bounds_min.x;
+        let height = bounds_max.y - bounds_min.y;
+        let mut cover = vec![false; width as usize * height as usize];
+        for region in removed.iter().chain(Some(&changed)) {
+            for y in region.min.y..region.max.y {
+                for x in region.min.x..region.max.x {
+                    let local =
+                        (x - bounds_min.x) as usize + width as usize * (y - bounds_min.y) as usize;
+                    cover[local] = self.solid[self.index(x, y)];
+                }
+            }
+        }
+
+        let first_added = self.rectangles.len();
+        merge_rectangles(
+            &mut cover,
+            bounds_min,
+            width,
+            height,
+            self.z,
+            &mut self.rectangles,
+        );
+
+        // The outlines with an edge touching a corner of the changed tiles are traced again, from
+        // the edges of the tiles around them touching these corners
+        let (removed_outlines, kept): (Vec<_>, Vec<_>) = self
+            .outlines
+            .drain(..)
+            .partition(|outline| outline.edges().any(|edge| segment_touches(&edge, min, max)));
+        self.outlines = kept;
+        let first_added_outline = self.outlines.len();
+        let around = Region::new(
+            Point3::new(min.x.saturating_sub(1), min.y.saturating_sub(1), self.z),
+            Point3::new(max.x.saturating_add(1), max.y.saturating_add(1), self.z + 1),
+        );
+        self.trace_outlines(&around, |edge| segment_touches(edge, min, max));
+
+        Ok(CollisionChanges {
+            removed,
+            added: self.rectangles[first_added..].to_vec(),
+            removed_outlines,
+            added_outlines: self.outlines[first_added_outline..].to_vec(),
+        })
+    }
+
+    /// Updates the collision shapes after the tiles in the changes of a `TileMap` changed since
+    /// the last call, like `update` with the region bounding the changed tiles of the z-level.
+    /// Every shape is computed again if the changes made since were drained.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MapTooLargeError` if every shape is computed again and a z-level of the map has
+    /// more than `MapTooLargeError::MAX_TILES` tiles.
+    pub fn update_changes<T, E, F>(
+        &mut self,
+        map: &TileMap<T, E>,
+        solid: F,
+    ) -> Result<CollisionChanges, MapTooLargeError>
+    where
+        T: Tile,
+        E: CoordinateEncoder,
+        F: FnMut(&Point3<u32>, &T) -> bool,
+    {
+        let z = self.z;
+        let dirty = map.changes().since(self.version).map(|changed| {
+            changed
+                .filter(|coord| coord.z == z)
+                .fold(None, |region: Option<Region>, coord| {
+                    let max = Point3::new(
+                        coord.x.saturating_add(1),
+                        coord.y.saturating_add(1),
+                        z.saturating_add(1),
+                    );
+                    Some(region.map_or(Region::new(*coord, max), |region| {
+                        Region::new(
+                            Point3::new(region.min.x.min(coord.x), region.min.y.min(coord.y), z),
+                            Point3::new(region.max.x.max(max.x), region.max.y.max(max.y), max.z),
+                        )
+                    }))
+                })
+        });
+        self.version = map.version();
+        match dirty {
+            Some(Some(dirty)) => self.update(map, &dirty, solid),
+            Some(None) => Ok(CollisionChanges::default()),
+            None => self.replace(map, solid),
+        }
+    }
+
+    /// Computes every shape again, returning all the shapes as removed and added.
+    fn replace<T, M, F>(&mut self, map: &M, solid: F) -> Result<CollisionChanges, MapTooLargeError>
+    where
+        T: Tile,
+        M: Map + MapStorage<T>,
+        F: FnMut(&Point3<u32>, &T) -> bool,
+    {
+        let removed = std::mem::take(&mut self.rectangles);
+        let removed_outlines = std::mem::take(&mut self.outlines);
+        self.rebuild(map, solid)?;
+        Ok(CollisionChanges {
+            removed,
+            added: self.rectangles.clone(),
+            removed_outlines,
+            added_outlines: self.outlines.clone(),
+        })
+    }
+
+    /// Traces the outlines starting with an edge of the solid tiles of `tiles` for which `start`
+    /// returns `true`, in the order of the tiles.
+    fn trace_outlines<S>(&mut self, tiles: &Region, mut start: S)
+    where
+        S: FnMut(&(Point2<u32>, Point2<u32>)) -> bool,
+    {
+        let mut used = FnvHashSet::default();
+        for y in tiles.min.y..tiles.max.y.min(self.height) {
+            for x in tiles.min.x..tiles.max.x.min(self.width) {
+                for edge in self.tile_edges(x, y) {
+                    if used.contains(&edge) || !start(&edge) {
+                        continue;
+                    }
+                    let corners = self.trace(edge, &mut used);
+                    self.outlines.push(Outline {
+                        corners: turning_corners(&corners),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Follows the edges of an outline from its `first` edge, returning the corners they start
+    /// from.
+    fn trace(
+        &self,
+        first: (Point2<u32>, Point2<u32>),
+        used: &mut FnvHashSet<(Point2<u32>, Point2<u32>)>,
+    ) -> Vec<Point2<u32>> {
+        let mut corners = Vec::new();
+        let mut edge = first;
+        loop {
+            used.insert(edge);
+            let (from, to) = edge;
+            corners.push(from);
+            if to == first.0 {
+                break;
+            }
+            // Turning towards the solid tile first keeps tiles touching by a corner apart
+            let direction = edge_direction(&edge);
+            let next = self
+                .edges_from(to)
+                .into_iter()
+                .filter(|candidate| !used.contains(candidate))
+                .max_by_key(|candidate| {
+                    let turn = edge_direction(candidate);
+                    direction.0 * turn.1 - direction.1 * turn.0
+                });
+            match next {
+                Some(next) => edge = next,
+                None => break,
+            }
+        }
+        corners
+    }
+
+    /// Returns whether the tile at an offset from the tile (x, y) is solid.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn is_solid_at(&self, x: u32, y: u32, offset_x: i64, offset_y: i64) -> bool {
+        let x = i64::from(x) + offset_x;
+        let y = i64::from(y) + offset_y;
+        x >= 0 && y >= 0 && self.is_solid(x as u32, y as u32)
+    }
+
+    /// Returns the edges between the tile (x, y), if it is solid, and the empty tiles around it,
+    /// with the solid tile on their right, clockwise from the top edge.
+    fn tile_edges(&self, x: u32, y: u32) -> Vec<(Point2<u32>, Point2<u32>)> {
+        let mut edges = Vec::new();
+        if !self.is_solid(x, y) {
+            return edges;
+        }
+        if !self.is_solid_at(x, y, 0, -1) {
+            edges.push((Point2::new(x, y), Point2::new(x + 1, y)));
+        }
+        if !self.is_solid_at(x, y, 1, 0) {
+            edges.push((Point2::new(x + 1, y), Point2::new(x + 1, y + 1)));
+        }
+        if !self.is_solid_at(x, y, 0, 1) {
+            edges.push((Point2::new(x + 1, y + 1), Point2::new(x, y + 1)));
+        }
+        if !self.is_solid_at(x, y, -1, 0) {
+            edges.push((Point2::new(x, y + 1), Point2::new(x, y)));
+        }
+        edges
+    }
+
+    /// Returns the edges of the outlines starting at a tile corner, which are edges of the tiles
+    /// around it.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn edges_from(&self, corner: Point2<u32>) -> Vec<(Point2<u32>, Point2<u32>)> {
+        let (x, y) = (i64::from(corner.x), i64::from(corner.y));
+        [(x, y), (x - 1, y), (x - 1, y - 1), (x, y - 1)]
+            .iter()
+            .filter(|(x, y)| *x >= 0 && *y >= 0)
+            .flat_map(|(x, y)| self.tile_edges(*x as u32, *y as u32))
+            .filter(|(from, _)| *from == corner)
+            .collect()
+    }
+}
+
+/// Returns whether an edge of an outline, along the x or y axis, has a point within the tile
+/// corners from `min` to `max`, both included.
+fn segment_touches(
+    (from, to): &(Point2<u32>, Point2<u32>),
+    min: Point2<u32>,
+    max: Point2<u32>,
+) -> bool {
+    from.x.min(to.x) <= max.x
+        && from.x.max(to.x) >= min.x
+        && from.y.min(to.y) <= max.y
+        && from.y.max(to.y) >= min.y
+}
+
+/// Returns whether a tile is in the map and solid.
+fn tile_is_solid<T, M, F>(map: &M, coord: &Point3<u32>, solid: &mut F) -> bool
+where
+    T: Tile,
+    M: Map + MapStorage<T>,
+    F: FnMut(&Point3<u32>, &T) -> bool,
+{
+    let dimensions = map.dimensions();
+    if coord.x >= dimensions.x || coord.y >= dimensions.y || coord.z >= dimensions.z {
+        return false;
+    }
+    map.get(coord).map_or(false, |tile| solid(coord, tile))
+}
+
+/// Merges the tiles to `cover` of an area starting at `min` into rectangles, clearing them. Each
+/// rectangle spans the run of tiles from the first tile left to cover, extended downwards while
+/// the rows below are covered along the whole run.
+fn merge_rectangles(
+    cover: &mut [bool],
+    min: Point2<u32>,
+    width: u32,
+    height: u32,
+    z: u32,
+    rectangles: &mut Vec<Region>,
+) {
+    let index = |x: u32, y: u32| x as usize + width as usize * y as usize;
+    for y in 0..height {
+        for x in 0..width {
+            if !cover[index(x, y)] {
+                continue;
+            }
+            let mut end_x = x + 1;
+            while end_x < width && cover[index(end_x, y)] {
+                end_x += 1;
+            }
+            let mut end_y = y + 1;
+            while end_y < height && (x..end_x).all(|run_x| cover[index(run_x, end_y)]) {
+                end_y += 1;
+            }
+            for row in y..end_y {
+                for column in x..end_x {
+                    cover[index(column, row)] = false;
+                }
+            }
+            rectangles.push(Region::new(
+                Point3::new(min.x + x, min.y + y, z),
+                Point3::new(min.x + end_x, min.y + end_y, z + 1),
+            ));
+        }
+    }
+}
+
+/// Returns the direction of an edge between two tile corners.
+fn edge_direction((from, to): &(Point2<u32>, Point2<u32>)) -> (i64, i64) {
+    (
+        i64::from(to.x) - i64::from(from.x),
+        i64::from(to.y) - i64::from(from.y),
+    )
+}
+
+/// Returns the corners of a closed outline where its direction changes.
+fn turning_corners(corners: &[Point2<u32>]) -> Vec<Point2<u32>> {
+    let count = corners.len();
+    (0..count)
+        .filter(|&index| {
+            let previous = corners[(index + count - 1) % count];
+            let next = corners[(index + 1) % count];
+            edge_direction(&(previous, corners[index])) != edge_direction(&(corners[index], next))
+        })
+        .map(|index| corners[index])
+        .collect()
+}
+
+/// Converts the tile corners of a z-level of a map to world space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CollisionSpace {
+    origin: Vector3<f32>,
+    column_step: Vector3<f32>,
+    row_step: Vector3<f32>,
+}
+
+impl CollisionSpace {
+    /// Creates the `CollisionSpace` of the z-level `z` of a map, with the transform of its
+    /// entity if it has one.
+    pub fn new<M: Map>(map: &M, z: u32, map_transform: Option<&Transform>) -> Self {
+        let origin = map.to_world(&Point3::new(0, 0, z), map_transform);
+        Self {
+            origin,
+            column_step: map.to_world(&Point3::new(1, 0, z), map_transform) - origin,
+            row_step: map.to_world(&Point3::new(0, 1, z), map_transform) - origin,
+        }
+    }
+
+    /// Returns the world position of a tile corner.
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn corner(&self, corner: &Point2<u32>) -> Vector3<f32> {
+        self.origin
+            + self.column_step * (corner.x as f32 - 0.5)
+            + self.row_step * (corner.y as f32 - 0.5)
+    }
+
+    /// Returns the world positions of the corners of a rectangle, clockwise from its minimum
+    /// corner with y growing downwards.
+    #[must_use]
+    pub fn rectangle(&self, rectangle: &Region) -> [Vector3<f32>; 4] {
+        [
+            self.corner(&Point2::new(rectangle.min.x, rectangle.min.y)),
+            self.corner(&Point2::new(rectangle.max.x, rectangle.min.y)),
+            self.corner(&Point2::new(rectangle.max.x, rectangle.max.y)),
+            self.corner(&Point2::new(rectangle.min.x, rectangle.max.y)),
+        ]
+    }
+
+    /// Returns the world positions of the corners of an outline.
+    #[must_use]
+    pub fn outline(&self, outline: &Outline) -> Vec<Vector3<f32>> {
+        outline
+            .corners
+            .iter()
+            .map(|corner| self.corner(corner))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FlatEncoder, TileMap};
+
+    #[derive(Clone, Copy, Debug, Default)]
+    struct Cell {
+        wall: bool,
+    }
+
+    impl Tile for Cell {}
+
+    fn solid(_: &Point3<u32>, cell: &Cell) -> bool {
+        cell.wall
+    }
+
+    /// A map of 4 by 3 tiles with walls in the shape of an L.
+    fn l_map() -> TileMap<Cell, FlatEncoder> {
+        let mut map = TileMap::new(Vector3::new(4, 3, 1), Vector3::new(16, 16, 1), None);
+        for &(x, y) in &[
+            (0, 0),
+            (1, 0),
+            (0, 1),
+            (1, 1),
+            (0, 2),
+            (1, 2),
+            (2, 2),
+            (3, 2),
+        ] {
+            map.get_mut(&Point3::new(x, y, 0)).unwrap().wall = true;
+        }
+        map
+    }
+
+    fn rectangle(min: (u32, u32), max: (u32, u32)) -> Region {
+        Region::new(Point3::new(min.0, min.1, 0), Point3::new(max.0, max.1, 1))
+    }
+
+    #[test]
+    fn merges_tiles_into_rectangles() {
+        let collision = TileCollision::new(&l_map(), 0, solid).unwrap();
+        assert_eq!(
+            collision.rectangles(),
+            &[rectangle((0, 0), (2, 3)), rectangle((2, 2), (4, 3))]
+        );
+    }
+
+    #[test]
+    fn updates_touched_rectangles() {
+        let mut map = l_map();
+        let mut collision = TileCollision::new(&map, 0, solid).unwrap();
+
+        map.get_mut(&Point3::new(3, 2, 0)).unwrap().wall = false;
+        let dirty = rectangle((3, 2), (4, 3));
+        let changes = collision.update(&map, &dirty, solid).unwrap();
+        assert_eq!(changes.removed, vec![rectangle((2, 2), (4, 3))]);
+        assert_eq!(changes.added, vec![rectangle((2, 2), (3, 3))]);
+        assert_eq!(collision.rectangles().len(), 2);
+
+        // Changes of other z-levels leave the shapes as they are
+        let other_level = Region::new(Point3::new(0, 0, 1), Point3::new(4, 3, 2));
+        assert_eq!(
+            collision.update(&map, &other_level, solid).unwrap(),
+            CollisionChanges::default()
+        );
+    }
+
+    #[test]
+    fn traces_touched_outlines_again() {
+        let mut map = l_map();
+        map.get_mut(&Point3::new(3, 0, 0)).unwrap().wall = true;
+        let mut collision = TileCollision::new(&map, 0, solid).unwrap();
+        assert_eq!(collision.outlines().len(), 2);
+        map.drain_changes();
+        collision.update_changes(&map, solid).unwrap();
+
+        // Filling the gap joins the outlines, the outline of the tile left alone being removed
+        map.get_mut(&Point3::new(3, 1, 0)).unwrap().wall = true;
+        let changes = collision.update_changes(&map, solid).unwrap();
+        assert_eq!(changes.removed_outlines.len(), 2);
+        assert_eq!(changes.added_outlines.len(), 1);
+        // The outline is traced from another corner than when tracing the whole map
+        let sorted = |outline: &Outline| {
+            let mut corners = outline.corners.clone();
+            corners.sort_by_key(|corner| (corner.y, corner.x));
+            corners
+        };
+        assert_eq!(
+            sorted(&collision.outlines()[0]),
+            sorted(&TileCollision::new(&map, 0, solid).unwrap().outlines()[0])
+        );
+
+        // Tiles away from an outline leave it as it is
+        let mut wide =
+            TileMap::<Cell, FlatEncoder>::new(Vector3::new(8, 3, 1), Vector3::new(16, 16, 1), None);
+        wide.get_mut(&Point3::new(0, 0, 0)).unwrap().wall = true;
+        let mut collision = TileCollision::new(&wide, 0, solid).unwrap();
+        collision.update_changes(&wide, solid).unwrap();
+        wide.get_mut(&Point3::new(6, 1, 0)).unwrap().wall = true;
+        let changes = collision.update_changes(&wide, solid).unwrap();
+        assert!(changes.removed_outlines.is_empty());
+        assert_eq!(changes.added_outlines.len(), 1);
+        assert_eq!(collision.outlines().len(), 2);
+
+        // Drained changes compute every shape again
+        wide.get_mut(&Point3::new(6, 1, 0)).unwrap().wall = false;
+        wide.drain_changes();
+        let changes = collision.update_changes(&wide, solid).unwrap();
+        assert_eq!(changes.removed_outlines.len(), 2);
+        assert_eq!(collision.outlines().len(), 1);
+    }
+
+    #[test]
+    fn traces_outlines_and_holes() {
+        let collision = TileCollision::new(&l_map(), 0, solid).unwrap();
+        let outlines = collision.outlines();
+        assert_eq!(outlines.len(), 1);
+        assert_eq!(
+            outlines[0].corners,
+            vec![
+                Point2::new(0, 0),
+                Point2::new(2, 0),
+                Point2::new(2, 2),
+                Point2::new(4, 2),
+                Point2::new(4, 3),
+                Point2::new(0, 3),
+            ]
+        );
+        assert_eq!(outlines[0].edges().count(), 6);
+
+        let mut ring =
+            TileMap::<Cell, FlatEncoder>::new(Vector3::new(3, 3, 1), Vector3::new(16, 16, 1), None);
+        for x in 0..3 {
+            for y in 0..3 {
+                ring.get_mut(&Point3::new(x, y, 0)).unwrap().wall = (x, y) != (1, 1);
+            }
+        }
+        let ring_collision = TileCollision::new(&ring, 0, solid).unwrap();
+        let outlines = ring_collision.outlines();
+        assert_eq!(outlines.len(), 2);
+        assert_eq!(
+            outlines[1].corners,
+            vec![
+                Point2::new(2, 1),
+                Point2::new(1, 1),
+                Point2::new(1, 2),
+                Point2::new(2, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn converts_corners_to_world() {
+        let map = l_map();
+        let space = CollisionSpace::new(&map, 0, None);
+        assert_eq!(
+            space.corner(&Point2::new(0, 0)),
+            Vector3::new(-32.0, 24.0, 0.0)
+        );
+        let corners = space.rectangle(&rectangle((0, 0), (4, 3)));
+        assert_eq!(corners[2], Vector3::new(32.0, -24.0, 0.0));
+    }
+}
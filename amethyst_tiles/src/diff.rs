@@ -0,0 +1,382 @@
+//! Tracking of the tiles changed in a `TileMap`, and diffs of tiles which can be applied to
+//! another map.
+
+use amethyst_core::math::Point3;
+use fnv::FnvHashMap;
+
+use crate::{Map, MapStorage, Region, Tile, TileOutOfBoundsError};
+
+/// The coordinates of the tiles of a `TileMap` or `ChunkedTileMap` which were mutably borrowed
+/// since its changes were last drained, with the `Region` bounding them.
+///
+/// Each tile is recorded with the version of the map it was last changed in, so that the systems
+/// updating something from the tiles of a map, such as autotiling or collision shapes, find the
+/// tiles changed since the version they last saw with `since`, without draining the changes.
+///
+/// Tiles borrowed with `get_mut_nochange` or `get_raw_mut_nochange` are not recorded.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TileChanges {
+    coords: FnvHashMap<Point3<u32>, u64>,
+    region: Option<Region>,
+    /// The version of the map when its changes were last drained.
+    drained: u64,
+}
+
+impl TileChanges {
+    /// Creates the empty changes of a map whose changes were drained in `version`.
+    #[must_use]
+    pub fn drained_in(version: u64) -> Self {
+        Self {
+            drained: version,
+            ..Self::default()
+        }
+    }
+
+    /// Records a change of the tile at `coord` in `version` of its map.
+    pub fn insert(&mut self, coord: Point3<u32>, version: u64) {
+        if self.coords.insert(coord, version).is_some() {
+            return;
+        }
+
+        // Coordinates are below the dimensions of maps, so they are at most `u32::MAX - 1`
+        let max = Point3::new(
+            coord.x.saturating_add(1),
+            coord.y.saturating_add(1),
+            coord.z.saturating_add(1),
+        );
+        self.region = Some(match self.region {
+            Some(region) => Region::new(
+                Point3::new(
+                    region.min.x.min(coord.x),
+                    region.min.y.min(coord.y),
+                    region.min.z.min(coord.z),
+                ),
+                Point3::new(
+                    region.max.x.max(max.x),
+                    region.max.y.max(max.y),
+                    region.max.z.max(max.z),
+                ),
+            ),
+            None => Region::new(coord, max),
+        });
+    }
+
+    /// Returns whether no tile changed.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.coords.is_empty()
+    }
+
+    /// The number of tiles which changed.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.coords.len()
+    }
+
+    /// Returns whether the tile at `coord` changed.
+    #[must_use]
+    pub fn contains(&self, coord: &Point3<u32>) -> bool {
+        self.coords.contains_key(coord)
+    }
+
+    /// The smallest `Region` containing all the tiles which changed.
+    #[must_use]
+    pub fn region(&self) -> Option<Region> {
+        self.region
+    }
+
+    /// Iterates over the coordinates of the tiles which changed, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &Point3<u32>> {
+        self.coords.keys()
+    }
+
+    /// Iterates over the coordinates of the tiles which changed after `version` of the map, in no
+    /// particular order. Returns `None` if changes made after `version` were drained, in which
+    /// case any tile may have changed.
+    #[must_use]
+    pub fn since(&self, version: u64) -> Option<impl Iterator<Item = &Point3<u32>>> {
+        if version < self.drained {
+            return None;
+        }
+        Some(
+            self.coords
+                .iter()
+                .filter(move |(_, changed)| **changed > version)
+                .map(|(coord, _)| coord),
+        )
+    }
+}
+
+/// A tile written by a `TileMapDiff`.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TileEdit<T> {
+    /// The coordinates of the tile.
+    pub coord: Point3<u32>,
+    /// The tile written at `coord`.
+    pub tile: T,
+}
+
+/// A list of tiles to write to a map, such as an entry of the undo stack of an editor, or the
+/// changes of a map sent to the clients of a server.
+///
+/// Applying a diff returns the diff which reverts it, so that undoing an edit is applying the
+/// diff returned when it was done.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TileMapDiff<T> {
+    /// The tiles to write, in order.
+    pub edits: Vec<TileEdit<T>>,
+}
+
+impl<T> Default for TileMapDiff<T> {
+    fn default() -> Self {
+        Self { edits: Vec::new() }
+    }
+}
+
+impl<T: Tile> TileMapDiff<T> {
+    /// Creates a diff writing the current tiles of `map` which are in `changes`, so that applying
+    /// it to a copy of the map before the changes brings the copy up to date.
+    ///
+    /// The edits are sorted by their coordinates, on z, y and then x.
+    #[must_use]
+    pub fn from_changes<M>(map: &M, changes: &TileChanges) -> Self
+    where
+        M: Map + MapStorage<T>,
+    {
+        let mut coords = changes
+            .iter()
+            .filter(|coord| in_bounds(map, coord))
+            .copied()
+            .collect::<Vec<_>>();
+        coords.sort_by_key(|coord| (coord.z, coord.y, coord.x));
+
+        Self {
+            edits: coords
+                .into_iter()
+                .filter_map(|coord| {
+                    map.get(&coord).map(|tile| TileEdit {
+                        coord,
+                        tile: tile.clone(),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Creates a diff writing the tiles of `to` in `region` which differ from the tiles of
+    /// `from`, so that applying it to `from` makes that region of the maps equal.
+    #[must_use]
+    pub fn between<M>(from: &M, to: &M, region: &Region) -> Self
+    where
+        T: PartialEq,
+        M: Map + MapStorage<T>,
+    {
+        let mut edits = Vec::new();
+        for z in region.min.z..region.max.z {
+            for y in region.min.y..region.max.y {
+                for x in region.min.x..region.max.x {
+                    let coord = Point3::new(x, y, z);
+                    if !in_bounds(from, &coord) || !in_bounds(to, &coord) {
+                        continue;
+                    }
+                    if let (Some(before), Some(after)) = (from.get(&coord), to.get(&coord)) {
+                        if before != after {
+                            edits.push(TileEdit {
+                                coord,
+                                tile: after.clone(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        Self { edits }
+    }
+
+    /// Adds the writing of `tile` at `coord` to this diff.
+    pub fn push(&mut self, coord: Point3<u32>, tile: T) {
+        self.edits.push(TileEdit { coord, tile });
+    }
+
+    /// Returns whether this diff writes no tile.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// The number of tiles this diff writes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.edits.len()
+    }
+
+    /// Writes the tiles of this diff to `map`, in order, and returns the diff writing back the
+    /// tiles they replaced.
+    ///
+    /// The written tiles are recorded in the changes of the map.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TileOutOfBoundsError` without writing any tile if an edit is outside the
+    /// dimensions of the map.
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn apply<M>(&self, map: &mut M) -> Result<Self, TileOutOfBoundsError>
+    where
+        M: Map + MapStorage<T>,
+    {
+        if let Some(edit) = self.edits.iter().find(|edit| !in_bounds(map, &edit.coord)) {
+            return Err(TileOutOfBoundsError {
+                point_dimensions: edit.coord.map(|value| value as i32),
+                max_dimensions: *map.dimensions(),
+            });
+        }
+
+        let mut reverted = Vec::with_capacity(self.edits.len());
+        for edit in &self.edits {
+            if let Some(tile) = map.get_mut(&edit.coord) {
+                reverted.push(TileEdit {
+                    coord: edit.coord,
+                    tile: std::mem::replace(tile, edit.tile.clone()),
+                });
+            }
+        }
+        reverted.reverse();
+
+        Ok(Self { edits: reverted })
+    }
+}
+
+/// Returns whether `coord` is within the dimensions of `map`.
+fn in_bounds<M: Map>(map: &M, coord: &Point3<u32>) -> bool {
+    let dimensions = map.dimensions();
+    coord.x < dimensions.x && coord.y < dimensions.y && coord.z < dimensions.z
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::Vector3;
+
+    use super::*;
+    use crate::{ChunkedTileMap, FlatEncoder, TileMap};
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Cell {
+        wall: bool,
+    }
+    impl Tile for Cell {}
+
+    fn map() -> TileMap<Cell, FlatEncoder> {
+        TileMap::new(Vector3::new(4, 3, 1), Vector3::new(8, 8, 1), None)
+    }
+
+    #[test]
+    fn records_changed_tiles() {
+        let mut map = map();
+        assert!(map.changes().is_empty());
+
+        map.get_mut(&Point3::new(1, 2, 0)).unwrap().wall = true;
+        map.get_mut(&Point3::new(3, 0, 0)).unwrap().wall = true;
+        map.get_mut(&Point3::new(1, 2, 0)).unwrap().wall = false;
+        map.get_mut_nochange(&Point3::new(0, 0, 0)).unwrap().wall = true;
+
+        let changes = map.drain_changes();
+        assert_eq!(changes.len(), 2);
+        assert!(changes.contains(&Point3::new(1, 2, 0)));
+        assert!(!changes.contains(&Point3::new(0, 0, 0)));
+        assert_eq!(
+            changes.region(),
+            Some(Region::new(Point3::new(1, 0, 0), Point3::new(4, 3, 1)))
+        );
+        assert!(map.changes().is_empty());
+    }
+
+    #[test]
+    fn finds_tiles_changed_since_a_version() {
+        let mut map = map();
+        map.get_mut(&Point3::new(1, 2, 0)).unwrap().wall = true;
+        let version = map.version();
+        map.get_mut(&Point3::new(3, 0, 0)).unwrap().wall = true;
+
+        let changed = map.changes().since(version).unwrap().collect::<Vec<_>>();
+        assert_eq!(changed, vec![&Point3::new(3, 0, 0)]);
+        assert_eq!(map.changes().since(0).unwrap().count(), 2);
+
+        map.drain_changes();
+        assert!(map.changes().since(version).is_none());
+        assert_eq!(map.changes().since(map.version()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn bounds_changes_at_the_last_coordinate() {
+        let mut changes = TileChanges::default();
+        changes.insert(Point3::new(u32::MAX - 1, 0, 0), 1);
+        changes.insert(Point3::new(u32::MAX, 2, 0), 1);
+        assert_eq!(
+            changes.region(),
+            Some(Region::new(
+                Point3::new(u32::MAX - 1, 0, 0),
+                Point3::new(u32::MAX, 3, 1)
+            ))
+        );
+    }
+
+    #[test]
+    fn applies_and_reverts_diffs() {
+        let mut source = map();
+        let mut target = map();
+
+        source.get_mut(&Point3::new(2, 1, 0)).unwrap().wall = true;
+        source.get_mut(&Point3::new(0, 2, 0)).unwrap().wall = true;
+        let changes = source.drain_changes();
+        let diff = TileMapDiff::from_changes(&source, &changes);
+        assert_eq!(diff.edits[0].coord, Point3::new(2, 1, 0));
+        assert_eq!(
+            diff,
+            TileMapDiff::between(
+                &target,
+                &source,
+                &Region::new(Point3::new(0, 0, 0), Point3::new(4, 3, 1))
+            )
+        );
+
+        let serialized = ron::ser::to_string(&diff).unwrap();
+        let diff: TileMapDiff<Cell> = ron::de::from_str(&serialized).unwrap();
+
+        let undo = diff.apply(&mut target).unwrap();
+        assert_eq!(target, source);
+        assert_eq!(target.changes().len(), 2);
+
+        undo.apply(&mut target).unwrap();
+        assert!(target.data.iter().all(|cell| !cell.wall));
+
+        let mut outside = TileMapDiff::default();
+        outside.push(Point3::new(4, 0, 0), Cell { wall: true });
+        assert!(outside.apply(&mut target).is_err());
+    }
+
+    #[test]
+    fn diffs_chunked_maps() {
+        type Chunked = ChunkedTileMap<Cell, FlatEncoder>;
+        let chunked = || Chunked::new(Vector3::new(4, 4, 1), Vector3::new(8, 8, 1), None);
+        let mut source = chunked();
+        let mut target = chunked();
+
+        let coords = [Point3::new(-3, 5, 0), Point3::new(6, -1, 0)];
+        for coord in &coords {
+            source.tile_mut(coord).unwrap().wall = true;
+        }
+        let changes = source.drain_changes();
+        assert_eq!(changes.len(), 2);
+        let diff = TileMapDiff::from_changes(&source, &changes);
+        assert_eq!(diff.len(), 2);
+
+        let undo = diff.apply(&mut target).unwrap();
+        assert!(coords.iter().all(|coord| target.tile(coord).unwrap().wall));
+        assert!(target
+            .changes()
+            .contains(&Chunked::to_unsigned(&Point3::new(6, -1, 0))));
+
+        undo.apply(&mut target).unwrap();
+        assert!(coords.iter().all(|coord| !target.tile(coord).unwrap().wall));
+    }
+}
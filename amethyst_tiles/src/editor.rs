@@ -0,0 +1,121 @@
+//! Helpers shared by the loaders of the files of level editors.
+
+use std::io::Read;
+
+use amethyst_assets::{
+    distill_importer::{self, ImportOp, ImportedAsset, ImporterValue},
+    prefab::{legion_prefab, Prefab},
+    AssetUuid,
+};
+use amethyst_core::ecs::{CommandBuffer, Entity, World};
+use amethyst_error::Error;
+use amethyst_rendy::sprite::{SpriteList, SpritePosition, Sprites};
+
+/// Imports the prefab of the world created from the bytes of a source file, keeping the UUID of
+/// the prefab in `id` between imports.
+pub(crate) fn import_prefab(
+    op: &mut ImportOp,
+    source: &mut dyn Read,
+    id: &mut Option<AssetUuid>,
+    create_world: impl FnOnce(&[u8]) -> Result<World, Error>,
+) -> distill_importer::Result<ImporterValue> {
+    let id = *id.get_or_insert_with(|| op.new_asset_uuid());
+
+    let mut bytes = Vec::new();
+    source.read_to_end(&mut bytes)?;
+    let world =
+        create_world(&bytes).map_err(|err| distill_importer::Error::Boxed(err.into_error()))?;
+
+    let prefab = Prefab::new(legion_prefab::Prefab::new(world));
+    Ok(ImporterValue {
+        assets: vec![ImportedAsset {
+            id,
+            search_tags: Vec::new(),
+            build_deps: Vec::new(),
+            load_deps: Vec::new(),
+            asset_data: Box::new(prefab),
+            build_pipeline: None,
+        }],
+    })
+}
+
+/// The grid of the tiles of a tileset image, in pixels.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct TileGrid {
+    /// The width of the image.
+    pub(crate) texture_width: u32,
+    /// The height of the image.
+    pub(crate) texture_height: u32,
+    /// The width of a tile.
+    pub(crate) tile_width: u32,
+    /// The height of a tile.
+    pub(crate) tile_height: u32,
+    /// The space around the tiles.
+    pub(crate) margin: u32,
+    /// The space between two tiles.
+    pub(crate) spacing: u32,
+    /// The number of tile columns.
+    pub(crate) columns: u32,
+    /// The number of tiles.
+    pub(crate) count: u32,
+}
+
+/// Returns the sprites of the tiles of a grid, row by row, followed by their copies flipped
+/// horizontally, vertically and both.
+pub(crate) fn flipped_sprites(grid: &TileGrid) -> Sprites {
+    let columns = grid.columns.max(1);
+    let mut sprites = Vec::with_capacity(grid.count as usize * 4);
+    for &(flip_horizontal, flip_vertical) in
+        &[(false, false), (true, false), (false, true), (true, true)]
+    {
+        sprites.extend((0..grid.count).map(|tile| SpritePosition {
+            x: grid.margin + (tile % columns) * (grid.tile_width + grid.spacing),
+            y: grid.margin + (tile / columns) * (grid.tile_height + grid.spacing),
+            width: grid.tile_width,
+            height: grid.tile_height,
+            offsets: None,
+            flip_horizontal,
+            flip_vertical,
+        }));
+    }
+    Sprites::List(SpriteList {
+        texture_width: grid.texture_width,
+        texture_height: grid.texture_height,
+        sprites,
+    })
+}
+
+/// Removes spawned entities.
+pub(crate) fn despawn(commands: &mut CommandBuffer, entities: Option<Vec<Entity>>) {
+    for entity in entities.into_iter().flatten() {
+        commands.remove(entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creates_sprites_of_tiles_and_flips() {
+        let grid = TileGrid {
+            tile_width: 16,
+            tile_height: 8,
+            margin: 2,
+            spacing: 1,
+            columns: 4,
+            count: 8,
+            ..TileGrid::default()
+        };
+        let sprites = match flipped_sprites(&grid) {
+            Sprites::List(list) => list.sprites,
+            _ => unreachable!(),
+        };
+        assert_eq!(sprites.len(), 32);
+        assert_eq!((sprites[5].x, sprites[5].y), (19, 11));
+        assert_eq!((sprites[5].width, sprites[5].height), (16, 8));
+        assert!(sprites[13].flip_horizontal && !sprites[13].flip_vertical);
+        assert!(sprites[21].flip_vertical && !sprites[21].flip_horizontal);
+        assert!(sprites[29].flip_horizontal && sprites[29].flip_vertical);
+    }
+}
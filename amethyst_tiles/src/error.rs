@@ -16,3 +16,52 @@ pub struct TileOutOfBoundsError {
     /// Map dimensions.
     pub max_dimensions: Vector3<u32>,
 }
+
+/// Chunk does not have the chunk dimensions of its map.
+#[derive(Debug, Error)]
+#[error(
+    display = "Chunk dimensions '{:?}' do not match the chunk dimensions of the map: '{:?}'",
+    dimensions,
+    chunk_dimensions
+)]
+pub struct ChunkDimensionsError {
+    /// Dimensions of the chunk.
+    pub dimensions: Vector3<u32>,
+    /// Chunk dimensions of the map.
+    pub chunk_dimensions: Vector3<u32>,
+}
+
+/// Map has more tiles than can be computed over at once, such as a `ChunkedTileMap`.
+#[derive(Debug, Error)]
+#[error(
+    display = "Map dimensions '{:?}' have more than {} tiles to compute over",
+    dimensions,
+    max_tiles
+)]
+pub struct MapTooLargeError {
+    /// Dimensions of the map.
+    pub dimensions: Vector3<u32>,
+    /// The most tiles which can be computed over.
+    pub max_tiles: u64,
+}
+
+impl MapTooLargeError {
+    /// The most tiles which whole-map computations allocate for, as many as the tiles indexed by a
+    /// `CoordinateEncoder`.
+    pub const MAX_TILES: u64 = (1 << 32) - 1;
+
+    /// Checks that a map of the given dimensions has at most `MAX_TILES` tiles.
+    pub(crate) fn check(dimensions: Vector3<u32>) -> Result<(), Self> {
+        let tiles = u64::from(dimensions.x)
+            .saturating_mul(u64::from(dimensions.y))
+            .saturating_mul(u64::from(dimensions.z));
+        if tiles > Self::MAX_TILES {
+            Err(Self {
+                dimensions,
+                max_tiles: Self::MAX_TILES,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,661 @@
+//! Field of view and line of sight over the tiles of maps.
+//!
+//! The field of view of a viewer is computed on its z-level into a `Visibility`, from an opacity
+//! predicate returning whether a tile blocks the sight. A `Visibility` also remembers the tiles
+//! which were seen before, so that `Tile::tint` can fade the explored tiles which are out of
+//! sight. `DrawTiles2D` keeps the tints of the tiles of a map between frames, so maps tinted by a
+//! `Visibility` need `DynamicTiles` to be tinted again every frame.
+
+use amethyst_core::math::{Point3, Vector3};
+use amethyst_rendy::palette::Srgba;
+
+use crate::{Map, MapStorage, MapTooLargeError, Tile};
+
+/// The multipliers from the coordinates of a permissive quadrant to the x and y axes of a map.
+const QUADRANTS: [(i64, i64); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+/// The multipliers from the coordinates of a shadowcasting octant to the x and y axes of a map.
+const OCTANTS: [(i64, i64, i64, i64); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+/// How the tiles in the field of view of a viewer are found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FovAlgorithm {
+    /// Recursive shadowcasting, seeing the transparent tiles whose center is in sight, and the
+    /// opaque tiles of which any part is in sight.
+    Shadowcasting,
+    /// Precise permissive field of view, seeing the tiles reached by a line from any point of the
+    /// origin tile to any point of them which crosses no opaque tile. It sees more of the tiles
+    /// around the corners of walls, and a tile seeing another is always seen by it.
+    Permissive,
+}
+
+impl Default for FovAlgorithm {
+    fn default() -> Self {
+        FovAlgorithm::Shadowcasting
+    }
+}
+
+/// The tiles of a map which a viewer sees, and those which it saw before.
+///
+/// A `Visibility` is usually a component of the viewer entity, which tiles look up in their
+/// `Tile::tint` to fade the tiles out of sight with `Visibility::tint`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Visibility {
+    dimensions: Vector3<u32>,
+    visible: Vec<u64>,
+    explored: Vec<u64>,
+}
+
+impl Visibility {
+    /// Creates a `Visibility` of a map of the given dimensions, where no tile was seen.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MapTooLargeError` if the map has more than `MapTooLargeError::MAX_TILES` tiles,
+    /// such as a `ChunkedTileMap`.
+    pub fn new(dimensions: Vector3<u32>) -> Result<Self, MapTooLargeError> {
+        MapTooLargeError::check(dimensions)?;
+        let words =
+            (dimensions.x as usize * dimensions.y as usize * dimensions.z as usize + 63) / 64;
+        Ok(Self {
+            dimensions,
+            visible: vec![0; words],
+            explored: vec![0; words],
+        })
+    }
+
+    /// The dimensions of the map.
+    #[must_use]
+    pub fn dimensions(&self) -> &Vector3<u32> {
+        &self.dimensions
+    }
+
+    /// Returns the index of the bit of a tile, if it is in the map.
+    fn bit(&self, coord: &Point3<u32>) -> Option<usize> {
+        if coord.x < self.dimensions.x && coord.y < self.dimensions.y && coord.z < self.dimensions.z
+        {
+            Some(
+                coord.x as usize
+                    + self.dimensions.x as usize
+                        * (coord.y as usize + self.dimensions.y as usize * coord.z as usize),
+            )
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether a tile is in sight.
+    #[must_use]
+    pub fn is_visible(&self, coord: &Point3<u32>) -> bool {
+        self.bit(coord)
+            .map_or(false, |bit| self.visible[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Returns whether a tile was ever in sight.
+    #[must_use]
+    pub fn is_explored(&self, coord: &Point3<u32>) -> bool {
+        self.bit(coord).map_or(false, |bit| {
+            self.explored[bit / 64] & (1 << (bit % 64)) != 0
+        })
+    }
+
+    /// Puts a tile in sight, marking it as explored.
+    pub fn set_visible(&mut self, coord: &Point3<u32>) {
+        if let Some(bit) = self.bit(coord) {
+            self.visible[bit / 64] |= 1 << (bit % 64);
+            self.explored[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Puts every tile out of sight, keeping the tiles explored.
+    pub fn clear_visible(&mut self) {
+        self.visible.iter_mut().for_each(|word| *word = 0);
+    }
+
+    /// Forgets every tile seen.
+    pub fn clear(&mut self) {
+        self.clear_visible();
+        self.explored.iter_mut().for_each(|word| *word = 0);
+    }
+
+    /// Returns the tint of a tile: white in sight, `explored` if it was explored but is out of
+    /// sight, and transparent otherwise.
+    #[must_use]
+    pub fn tint(&self, coord: &Point3<u32>, explored: Srgba) -> Srgba {
+        if self.is_visible(coord) {
+            Srgba::new(1.0, 1.0, 1.0, 1.0)
+        } else if self.is_explored(coord) {
+            explored
+        } else {
+            Srgba::new(0.0, 0.0, 0.0, 0.0)
+        }
+    }
+}
+
+/// Computes the tiles seen from `origin` up to `radius` tiles away on its z-level, where `opaque`
+/// returns whether a tile blocks the sight. The tiles seen before stay explored in `visibility`,
+/// unless the dimensions of the map changed.
+///
+/// # Errors
+///
+/// Returns a `MapTooLargeError` if the map has more than `MapTooLargeError::MAX_TILES` tiles,
+/// such as a `ChunkedTileMap`.
+pub fn field_of_view<T, M, F>(
+    map: &M,
+    algorithm: FovAlgorithm,
+    origin: &Point3<u32>,
+    radius: u32,
+    opaque: F,
+    visibility: &mut Visibility,
+) -> Result<(), MapTooLargeError>
+where
+    T: Tile,
+    M: Map + MapStorage<T>,
+    F: FnMut(&Point3<u32>, &T) -> bool,
+{
+    if visibility.dimensions != *map.dimensions() {
+        *visibility = Visibility::new(*map.dimensions())?;
+    }
+    visibility.clear_visible();
+    if visibility.bit(origin).is_none() {
+        return Ok(());
+    }
+    visibility.set_visible(origin);
+
+    // No tile of the map is farther than the sum of its dimensions
+    let dimensions = map.dimensions();
+    let mut viewer = Viewer {
+        map,
+        origin,
+        radius: i64::from(radius).min(i64::from(dimensions.x) + i64::from(dimensions.y)),
+        opaque,
+        visibility,
+        _marker: std::marker::PhantomData,
+    };
+    match algorithm {
+        FovAlgorithm::Shadowcasting => {
+            for octant in &OCTANTS {
+                viewer.cast(octant, 1, 1.0, 0.0);
+            }
+        }
+        FovAlgorithm::Permissive => {
+            for quadrant in &QUADRANTS {
+                viewer.permissive(*quadrant);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The state of a field of view being computed.
+struct Viewer<'a, T, M, F> {
+    map: &'a M,
+    origin: &'a Point3<u32>,
+    radius: i64,
+    opaque: F,
+    visibility: &'a mut Visibility,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<'a, T, M, F> Viewer<'a, T, M, F>
+where
+    T: Tile,
+    M: Map + MapStorage<T>,
+    F: FnMut(&Point3<u32>, &T) -> bool,
+{
+    /// Returns the tile at an offset from the origin, and whether it blocks the sight. Tiles out
+    /// of the map block the sight.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn tile(&mut self, x: i64, y: i64) -> (Option<Point3<u32>>, bool) {
+        let x = i64::from(self.origin.x) + x;
+        let y = i64::from(self.origin.y) + y;
+        let dimensions = self.map.dimensions();
+        if x < 0 || y < 0 || x >= i64::from(dimensions.x) || y >= i64::from(dimensions.y) {
+            return (None, true);
+        }
+        let coord = Point3::new(x as u32, y as u32, self.origin.z);
+        let opaque = match self.map.get(&coord) {
+            Some(tile) => (self.opaque)(&coord, tile),
+            None => true,
+        };
+        (Some(coord), opaque)
+    }
+
+    /// Puts a tile at an offset from the origin in sight, if it is in the map and in the radius.
+    fn see(&mut self, coord: Option<Point3<u32>>, x: i64, y: i64) {
+        if let Some(coord) = coord {
+            if x * x + y * y <= self.radius * self.radius {
+                self.visibility.set_visible(&coord);
+            }
+        }
+    }
+
+    /// Lights the rows of an octant from `row`, between the `start` and `end` slopes.
+    #[allow(clippy::cast_precision_loss)]
+    fn cast(&mut self, octant: &(i64, i64, i64, i64), row: i64, mut start: f32, end: f32) {
+        if start < end {
+            return;
+        }
+        let mut new_start = 0.0;
+        for distance in row..=self.radius {
+            let dy = -distance;
+            let mut blocked = false;
+            for dx in -distance..=0 {
+                let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+                if start < right_slope {
+                    continue;
+                } else if end > left_slope {
+                    break;
+                }
+
+                let (coord, opaque) =
+                    self.tile(dx * octant.0 + dy * octant.1, dx * octant.2 + dy * octant.3);
+                let center_slope = dx as f32 / dy as f32;
+                if opaque || (center_slope <= start && center_slope >= end) {
+                    self.see(coord, dx, dy);
+                }
+
+                if blocked {
+                    if opaque {
+                        new_start = right_slope;
+                    } else {
+                        blocked = false;
+                        start = new_start;
+                    }
+                } else if opaque && distance < self.radius {
+                    blocked = true;
+                    self.cast(octant, distance + 1, start, left_slope);
+                    new_start = right_slope;
+                }
+            }
+            if blocked {
+                break;
+            }
+        }
+    }
+
+    /// Lights a quadrant with precise permissive field of view. The tiles of the quadrant are
+    /// visited along the diagonals moving away from the origin, and each view is the area between
+    /// a shallow and a steep line, from a corner of the origin tile past the corners of the
+    /// opaque tiles bumping into it. The quadrant coordinates of the corners of a tile are those
+    /// of the tile and one more, the origin tile spanning from (0, 0) to (1, 1).
+    fn permissive(&mut self, quadrant: (i64, i64)) {
+        let mut views = vec![View {
+            shallow: SightLine::new((0, 1), (self.radius, 0)),
+            steep: SightLine::new((1, 0), (0, self.radius)),
+            shallow_bump: None,
+            steep_bump: None,
+        }];
+        let mut bumps = Vec::new();
+        for diagonal in 1..=2 * self.radius {
+            let mut view = 0;
+            for y in (diagonal - self.radius).max(0)..=diagonal.min(self.radius) {
+                if view >= views.len() {
+                    break;
+                }
+                let x = diagonal - y;
+                view = self.visit(quadrant, x, y, view, &mut views, &mut bumps);
+            }
+            if views.is_empty() {
+                break;
+            }
+        }
+    }
+
+    /// Lights a tile of a permissive quadrant if it is in a view, from the first view which may
+    /// have it, narrowing the view if the tile is opaque. Returns the first view which may have
+    /// the next tile of the diagonal.
+    fn visit(
+        &mut self,
+        quadrant: (i64, i64),
+        x: i64,
+        y: i64,
+        mut view: usize,
+        views: &mut Vec<View>,
+        bumps: &mut Vec<Bump>,
+    ) -> usize {
+        let top_left = (x, y + 1);
+        let bottom_right = (x + 1, y);
+        while view < views.len() && views[view].steep.is_below_or_collinear(bottom_right) {
+            view += 1;
+        }
+        if view == views.len() || views[view].shallow.is_above_or_collinear(top_left) {
+            return view;
+        }
+
+        let (coord, opaque) = self.tile(x * quadrant.0, y * quadrant.1);
+        self.see(coord, x, y);
+        if !opaque {
+            return view;
+        }
+
+        let shallow_blocked = views[view].shallow.is_above(bottom_right);
+        let steep_blocked = views[view].steep.is_below(top_left);
+        if shallow_blocked && steep_blocked {
+            // The tile covers the whole view
+            views.remove(view);
+        } else if shallow_blocked {
+            views[view].bump_shallow(top_left, bumps);
+            check_view(views, view);
+        } else if steep_blocked {
+            views[view].bump_steep(bottom_right, bumps);
+            check_view(views, view);
+        } else {
+            // The tile splits the view in a shallower and a steeper view
+            let shallower = views[view];
+            views.insert(view, shallower);
+            views[view].bump_steep(bottom_right, bumps);
+            let steeper = if check_view(views, view) {
+                view + 1
+            } else {
+                view
+            };
+            views[steeper].bump_shallow(top_left, bumps);
+            check_view(views, steeper);
+        }
+        view
+    }
+}
+
+/// A line between two tile corners of a permissive quadrant.
+#[derive(Clone, Copy, Debug)]
+struct SightLine {
+    start: (i64, i64),
+    end: (i64, i64),
+}
+
+impl SightLine {
+    fn new(start: (i64, i64), end: (i64, i64)) -> Self {
+        Self { start, end }
+    }
+
+    /// Returns a positive number if a point is below the line, a negative number if it is above
+    /// it, and zero if it is on it.
+    fn relative_slope(&self, point: (i64, i64)) -> i64 {
+        (self.end.1 - self.start.1) * (self.end.0 - point.0)
+            - (self.end.0 - self.start.0) * (self.end.1 - point.1)
+    }
+
+    fn is_below(&self, point: (i64, i64)) -> bool {
+        self.relative_slope(point) > 0
+    }
+
+    fn is_below_or_collinear(&self, point: (i64, i64)) -> bool {
+        self.relative_slope(point) >= 0
+    }
+
+    fn is_above(&self, point: (i64, i64)) -> bool {
+        self.relative_slope(point) < 0
+    }
+
+    fn is_above_or_collinear(&self, point: (i64, i64)) -> bool {
+        self.relative_slope(point) <= 0
+    }
+
+    fn is_collinear(&self, point: (i64, i64)) -> bool {
+        self.relative_slope(point) == 0
+    }
+}
+
+/// A corner of an opaque tile which a line of a permissive view was moved to, with the index of
+/// the previous bump of the line.
+#[derive(Clone, Copy, Debug)]
+struct Bump {
+    point: (i64, i64),
+    parent: Option<usize>,
+}
+
+/// The area of a permissive quadrant between two lines, in which tiles are in sight.
+#[derive(Clone, Copy, Debug)]
+struct View {
+    shallow: SightLine,
+    steep: SightLine,
+    /// The index of the last bump of the shallow line.
+    shallow_bump: Option<usize>,
+    /// The index of the last bump of the steep line.
+    steep_bump: Option<usize>,
+}
+
+impl View {
+    /// Moves the end of the shallow line to a corner, moving its start to the steep bumps left
+    /// below it.
+    fn bump_shallow(&mut self, point: (i64, i64), bumps: &mut Vec<Bump>) {
+        self.shallow.end = point;
+        bumps.push(Bump {
+            point,
+            parent: self.shallow_bump,
+        });
+        self.shallow_bump = Some(bumps.len() - 1);
+        let mut bump = self.steep_bump;
+        while let Some(index) = bump {
+            if self.shallow.is_above(bumps[index].point) {
+                self.shallow.start = bumps[index].point;
+            }
+            bump = bumps[index].parent;
+        }
+    }
+
+    /// Moves the end of the steep line to a corner, moving its start to the shallow bumps left
+    /// above it.
+    fn bump_steep(&mut self, point: (i64, i64), bumps: &mut Vec<Bump>) {
+        self.steep.end = point;
+        bumps.push(Bump {
+            point,
+            parent: self.steep_bump,
+        });
+        self.steep_bump = Some(bumps.len() - 1);
+        let mut bump = self.shallow_bump;
+        while let Some(index) = bump {
+            if self.steep.is_below(bumps[index].point) {
+                self.steep.start = bumps[index].point;
+            }
+            bump = bumps[index].parent;
+        }
+    }
+}
+
+/// Removes a view whose lines became the same line through a corner of the origin tile, leaving
+/// nothing in sight. Returns whether the view was kept.
+fn check_view(views: &mut Vec<View>, view: usize) -> bool {
+    let View { shallow, steep, .. } = views[view];
+    if shallow.is_collinear(steep.start)
+        && shallow.is_collinear(steep.end)
+        && (shallow.is_collinear((0, 1)) || shallow.is_collinear((1, 0)))
+    {
+        views.remove(view);
+        false
+    } else {
+        true
+    }
+}
+
+/// Returns the tiles of the line between two tiles with Bresenham's algorithm, from `from` to
+/// `to` both included, on the z-level of `from`.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn line(from: &Point3<u32>, to: &Point3<u32>) -> impl Iterator<Item = Point3<u32>> {
+    let (mut x, mut y, z) = (i64::from(from.x), i64::from(from.y), from.z);
+    let (end_x, end_y) = (i64::from(to.x), i64::from(to.y));
+    let dx = (end_x - x).abs();
+    let dy = -(end_y - y).abs();
+    let step_x = if x < end_x { 1 } else { -1 };
+    let step_y = if y < end_y { 1 } else { -1 };
+    let mut error = dx + dy;
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let tile = Point3::new(x as u32, y as u32, z);
+        if x == end_x && y == end_y {
+            done = true;
+        } else {
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+        Some(tile)
+    })
+}
+
+/// Returns whether `to` can be seen from `from`: both tiles are on the same z-level of the map
+/// and no tile of the `line` between them is opaque.
+pub fn line_of_sight<T, M, F>(map: &M, from: &Point3<u32>, to: &Point3<u32>, mut opaque: F) -> bool
+where
+    T: Tile,
+    M: Map + MapStorage<T>,
+    F: FnMut(&Point3<u32>, &T) -> bool,
+{
+    let dimensions = map.dimensions();
+    if from.z != to.z || to.x >= dimensions.x || to.y >= dimensions.y || to.z >= dimensions.z {
+        return false;
+    }
+    line(from, to)
+        .skip(1)
+        .take_while(|tile| tile != to)
+        .all(|tile| map.get(&tile).map_or(false, |t| !opaque(&tile, t)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FlatEncoder, TileMap};
+
+    #[derive(Clone, Copy, Debug, Default)]
+    struct Cell {
+        wall: bool,
+    }
+
+    impl Tile for Cell {}
+
+    fn opaque(_: &Point3<u32>, cell: &Cell) -> bool {
+        cell.wall
+    }
+
+    /// A map of 16 by 16 tiles with two walls east of (5, 5).
+    fn walled_map() -> TileMap<Cell, FlatEncoder> {
+        let mut map = TileMap::new(Vector3::new(16, 16, 1), Vector3::new(16, 16, 1), None);
+        map.get_mut(&Point3::new(6, 5, 0)).unwrap().wall = true;
+        map.get_mut(&Point3::new(6, 6, 0)).unwrap().wall = true;
+        map
+    }
+
+    #[test]
+    fn casts_shadows_behind_walls() {
+        let map = walled_map();
+        let origin = Point3::new(5, 5, 0);
+        let mut visibility = Visibility::default();
+        field_of_view(
+            &map,
+            FovAlgorithm::Shadowcasting,
+            &origin,
+            8,
+            opaque,
+            &mut visibility,
+        )
+        .unwrap();
+        assert!(visibility.is_visible(&origin));
+        assert!(visibility.is_visible(&Point3::new(6, 5, 0)));
+        assert!(visibility.is_visible(&Point3::new(5, 13, 0)));
+        assert!(!visibility.is_visible(&Point3::new(8, 5, 0)));
+        assert!(!visibility.is_visible(&Point3::new(7, 10, 0)));
+        // Out of the radius
+        assert!(!visibility.is_visible(&Point3::new(12, 12, 0)));
+
+        // The tiles seen from elsewhere stay explored
+        field_of_view(
+            &map,
+            FovAlgorithm::Shadowcasting,
+            &Point3::new(0, 0, 0),
+            2,
+            opaque,
+            &mut visibility,
+        )
+        .unwrap();
+        assert!(!visibility.is_visible(&origin));
+        assert!(visibility.is_explored(&origin));
+        let faded = Srgba::new(0.5, 0.5, 0.5, 1.0);
+        assert_eq!(visibility.tint(&origin, faded), faded);
+        assert_eq!(
+            visibility.tint(&Point3::new(15, 15, 0), faded),
+            Srgba::new(0.0, 0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn permissive_sees_past_corners() {
+        let map = walled_map();
+        let origin = Point3::new(5, 5, 0);
+        let mut shadowcasting = Visibility::default();
+        field_of_view(
+            &map,
+            FovAlgorithm::Shadowcasting,
+            &origin,
+            8,
+            opaque,
+            &mut shadowcasting,
+        )
+        .unwrap();
+        let mut permissive = Visibility::default();
+        field_of_view(
+            &map,
+            FovAlgorithm::Permissive,
+            &origin,
+            8,
+            opaque,
+            &mut permissive,
+        )
+        .unwrap();
+
+        // A line from the top right of the origin tile passes over the corner of the wall
+        let past_corner = Point3::new(8, 4, 0);
+        assert!(!shadowcasting.is_visible(&past_corner));
+        assert!(permissive.is_visible(&past_corner));
+        assert!(!permissive.is_visible(&Point3::new(8, 5, 0)));
+        assert!(permissive.is_visible(&Point3::new(6, 5, 0)));
+
+        // The tiles seen see the origin back
+        let mut back = Visibility::default();
+        field_of_view(
+            &map,
+            FovAlgorithm::Permissive,
+            &past_corner,
+            8,
+            opaque,
+            &mut back,
+        )
+        .unwrap();
+        assert!(back.is_visible(&origin));
+    }
+
+    #[test]
+    fn traces_lines_of_sight() {
+        let tiles = line(&Point3::new(0, 0, 0), &Point3::new(4, 2, 0)).collect::<Vec<_>>();
+        assert_eq!(tiles.len(), 5);
+        assert_eq!(tiles[0], Point3::new(0, 0, 0));
+        assert_eq!(tiles[4], Point3::new(4, 2, 0));
+        let reversed = line(&Point3::new(4, 2, 0), &Point3::new(0, 0, 0)).count();
+        assert_eq!(reversed, 5);
+
+        let map = walled_map();
+        let origin = Point3::new(5, 5, 0);
+        assert!(line_of_sight(&map, &origin, &Point3::new(6, 5, 0), opaque));
+        assert!(!line_of_sight(&map, &origin, &Point3::new(9, 5, 0), opaque));
+        assert!(line_of_sight(&map, &origin, &Point3::new(5, 12, 0), opaque));
+    }
+}
@@ -0,0 +1,295 @@
+//! Importers of `LDtk` projects and level files.
+
+use std::{collections::HashMap, io::Read};
+
+use amethyst_assets::{
+    distill_importer::{self, ImportOp, Importer, ImporterValue},
+    erased_serde,
+    prefab::{ComponentRegistration, ComponentRegistry, ComponentRegistryBuilder},
+    AssetUuid, Format,
+};
+use amethyst_core::{
+    ecs::{Entity, World},
+    transform::Transform,
+};
+use amethyst_error::{format_err, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use type_uuid::TypeUuid;
+
+use super::{
+    parse::{parse_ldtk, parse_ldtkl, ParsedLevel},
+    LdtkEntityInstance, LdtkLevelData, LdtkLevelFile,
+};
+use crate::editor::import_prefab;
+
+/// Options of the `LdtkImporter`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[serde(default)]
+#[uuid = "05fd99af-e788-4975-b117-d2b79b19428e"]
+pub struct LdtkOptions {
+    /// The directory of the project, relative to the asset directory, which the paths of its
+    /// tilesets are joined to.
+    pub directory: String,
+}
+
+/// A simple state for the `LdtkImporter` to retain the same UUID between imports.
+#[derive(Default, Debug, Deserialize, Serialize, TypeUuid)]
+#[uuid = "0c283656-0a33-43fc-b89c-66f5745d8a04"]
+pub struct LdtkImporterState {
+    /// The UUID of the project prefab.
+    pub id: Option<AssetUuid>,
+}
+
+/// The importer of `.ldtk` projects, producing a prefab.
+///
+/// The prefab has an entity with an `LdtkLevelData`, or an `LdtkExternalLevel` for the levels
+/// saved in separate files, and a `Transform` per level, and an entity with a `Transform`, an
+/// `LdtkEntity` and the registered components named by its identifier and fields per entity
+/// instance.
+#[derive(Default, TypeUuid, Debug)]
+#[uuid = "92b5a0fe-fe9d-4ea0-90c4-4fc8cb2cd3d0"]
+pub struct LdtkImporter;
+
+amethyst_assets::inventory::submit! {
+    amethyst_assets::SourceFileImporter {
+        extension: "ldtk",
+        instantiator: || Box::new(LdtkImporter::default()),
+    }
+}
+
+impl Importer for LdtkImporter {
+    fn version_static() -> u32 {
+        1
+    }
+
+    fn version(&self) -> u32 {
+        Self::version_static()
+    }
+
+    type Options = LdtkOptions;
+    type State = LdtkImporterState;
+
+    fn import(
+        &self,
+        op: &mut ImportOp,
+        source: &mut dyn Read,
+        options: &Self::Options,
+        state: &mut Self::State,
+    ) -> distill_importer::Result<ImporterValue> {
+        log::info!("Importing LDtk project with options {:?}", options);
+        import_prefab(op, source, &mut state.id, |bytes| {
+            project_world(bytes, &options.directory)
+        })
+    }
+}
+
+/// Loads the `.ldtkl` files of the levels of `LDtk` projects saving their levels in separate
+/// files.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[uuid = "bb5262c7-7586-449d-b1a4-06ca917de71c"]
+pub struct LdtkLevelFormat;
+
+amethyst_assets::register_importer!(".ldtkl", LdtkLevelFormat);
+impl Format<LdtkLevelFile> for LdtkLevelFormat {
+    fn name(&self) -> &'static str {
+        "LdtkLevel"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<LdtkLevelFile, Error> {
+        let text =
+            std::str::from_utf8(&bytes).map_err(|_| format_err!("LDtk level is not UTF-8"))?;
+        parse_ldtkl(text)
+    }
+}
+
+/// Returns the name of a type without its path and generic parameters.
+fn short_type_name(type_name: &str) -> &str {
+    let name = type_name.split('<').next().unwrap_or(type_name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// The registered prefab components, by the names of their types without their paths.
+pub(crate) struct ComponentNames<'a>(HashMap<&'a str, Vec<&'a ComponentRegistration>>);
+
+impl<'a> ComponentNames<'a> {
+    /// Names the components of a registry.
+    pub(crate) fn new(registry: &'a ComponentRegistry) -> Self {
+        let mut names: HashMap<&str, Vec<_>> = HashMap::new();
+        for registration in registry.components().values() {
+            names
+                .entry(short_type_name(registration.type_name()))
+                .or_default()
+                .push(registration);
+        }
+        Self(names)
+    }
+
+    /// Returns the component named `name`, or an error if several components have that name.
+    fn get(&self, name: &str) -> Result<Option<&'a ComponentRegistration>, Error> {
+        match self.0.get(name).map(Vec::as_slice) {
+            None | Some([]) => Ok(None),
+            Some([registration]) => Ok(Some(*registration)),
+            Some(registrations) => {
+                let mut types = registrations
+                    .iter()
+                    .map(|registration| registration.type_name())
+                    .collect::<Vec<_>>();
+                types.sort_unstable();
+                Err(format_err!(
+                    "LDtk identifier `{}` names several components: {}",
+                    name,
+                    types.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+/// Adds the registered components named by the identifier and the fields of an entity instance
+/// to its entity.
+pub(crate) fn add_components(
+    world: &mut World,
+    components: &ComponentNames<'_>,
+    entity: Entity,
+    instance: &LdtkEntityInstance,
+) -> Result<(), Error> {
+    if let Some(registration) = components.get(&instance.entity.identifier)? {
+        registration.add_default_to_entity(world, entity);
+    }
+    for (name, value) in &instance.values {
+        if let Some(registration) = components.get(name)? {
+            let value: Value = serde_json::from_str(value)
+                .map_err(|e| format_err!("Invalid value of LDtk field `{}`: {}", name, e))?;
+            let mut deserializer = <dyn erased_serde::Deserializer<'_>>::erase(value);
+            registration.deserialize_single(&mut deserializer, world, entity);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the transform of an entity instance, at its position in the world.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn instance_transform(instance: &LdtkEntityInstance) -> Transform {
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(
+        instance.position[0] as f32,
+        -instance.position[1] as f32,
+        0.,
+    );
+    transform
+}
+
+/// Creates the world of the prefab of a project.
+fn project_world(bytes: &[u8], directory: &str) -> Result<World, Error> {
+    let text = std::str::from_utf8(bytes).map_err(|_| format_err!("LDtk project is not UTF-8"))?;
+    let levels = parse_ldtk(text, directory)?;
+
+    let registry = ComponentRegistryBuilder::default()
+        .auto_register_components()
+        .build();
+    let components = ComponentNames::new(&registry);
+
+    let mut world = World::default();
+    for level in levels {
+        match level {
+            ParsedLevel::Loaded(file) => {
+                for instance in &file.entities {
+                    let entity =
+                        world.push((instance_transform(instance), instance.entity.clone()));
+                    add_components(&mut world, &components, entity, instance)?;
+                }
+                let transform = level_transform(&file.data);
+                world.push((file.data, transform));
+            }
+            ParsedLevel::External(external, data) => {
+                world.push((external, level_transform(&data)));
+            }
+        }
+    }
+    Ok(world)
+}
+
+/// Returns the transform of a level, at its center in the world since its `TileMap`s are
+/// centered.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn level_transform(data: &LdtkLevelData) -> Transform {
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(
+        data.world_position[0] as f32 + data.width as f32 * 0.5,
+        -(data.world_position[1] as f32 + data.height as f32 * 0.5),
+        0.,
+    );
+    transform
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::Vector3;
+
+    use super::*;
+
+    mod first {
+        use amethyst_assets::prefab::{
+            legion_prefab, register_component_type, serde_diff, SerdeDiff,
+        };
+        use serde::{Deserialize, Serialize};
+        use type_uuid::TypeUuid;
+
+        #[derive(Clone, Debug, Default, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+        #[uuid = "2bc9e78b-758c-469f-96ee-0491409d94a9"]
+        pub struct Marker {
+            pub value: u32,
+        }
+
+        register_component_type!(Marker);
+    }
+
+    mod second {
+        use amethyst_assets::prefab::{
+            legion_prefab, register_component_type, serde_diff, SerdeDiff,
+        };
+        use serde::{Deserialize, Serialize};
+        use type_uuid::TypeUuid;
+
+        #[derive(Clone, Debug, Default, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+        #[uuid = "67815ad1-a678-42f1-8e53-f5ed66fa7e82"]
+        pub struct Marker {
+            pub value: u32,
+        }
+
+        register_component_type!(Marker);
+    }
+
+    #[test]
+    fn shortens_type_names() {
+        assert_eq!(short_type_name("game::components::Health"), "Health");
+        assert_eq!(short_type_name("game::Pool<game::Coin>"), "Pool");
+        assert_eq!(short_type_name("Gold"), "Gold");
+    }
+
+    #[test]
+    fn rejects_ambiguous_component_names() {
+        let registry = ComponentRegistryBuilder::default()
+            .auto_register_components()
+            .build();
+        let components = ComponentNames::new(&registry);
+        assert!(components.get("Transform").unwrap().is_some());
+        assert!(components.get("Unregistered").unwrap().is_none());
+        assert!(components.get("Marker").is_err());
+    }
+
+    #[test]
+    fn centers_levels_in_world() {
+        let data = LdtkLevelData {
+            world_position: [256, -128],
+            width: 32,
+            height: 16,
+            ..LdtkLevelData::default()
+        };
+        assert_eq!(
+            *level_transform(&data).translation(),
+            Vector3::new(272., 120., 0.)
+        );
+    }
+}
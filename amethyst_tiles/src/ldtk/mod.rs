@@ -0,0 +1,271 @@
+//! Loading of projects made with the [LDtk](https://ldtk.io/) level editor.
+//!
+//! `.ldtk` projects are imported as prefabs. Each level becomes an entity with an
+//! `LdtkLevelData` and a `Transform` at its position in the world of the project, from which
+//! the `LdtkLevelSystem` spawns a `TileMap<LdtkTile>` child per `IntGrid`, Tiles and
+//! `AutoLayer` layer.
+//!
+//! Entity instances are spawned as entities with a `Transform` and an `LdtkEntity` holding their
+//! fields. A field whose identifier is the name of a component registered with
+//! `register_component_type!` is also added to the entity as that component, deserialized from
+//! the value of the field, and an entity whose identifier is the name of a registered component
+//! gets the default value of that component. Components are named by their type without its
+//! path, and a name shared by several registered components is an error.
+//!
+//! Levels saved in separate `.ldtkl` files become entities with an `LdtkExternalLevel` instead,
+//! whose file the `LdtkLevelSystem` loads as an `LdtkLevelFile` to add the `LdtkLevelData` of the
+//! level and spawn its entity instances.
+//!
+//! As for [Tiled maps](crate::tiled), the paths of tilesets and level files are joined to the
+//! `directory` option of the importer.
+
+use std::collections::HashMap;
+
+use amethyst_assets::{
+    prefab::{legion_prefab, register_component_type, serde_diff, SerdeDiff},
+    register_asset_type, Asset, AssetProcessorSystem,
+};
+use amethyst_core::{
+    ecs::{Resources, World},
+    math::Point3,
+};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use crate::Tile;
+
+mod importer;
+mod parse;
+mod system;
+
+pub use self::{
+    importer::{LdtkImporter, LdtkImporterState, LdtkLevelFormat, LdtkOptions},
+    system::LdtkLevelSystem,
+};
+
+/// The value of a field of a level or an entity.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LdtkFieldValue {
+    /// An unset value.
+    Null,
+    /// A `Bool` value.
+    Bool(bool),
+    /// An `Int` value.
+    Int(i64),
+    /// A `Float` value.
+    Float(f64),
+    /// A `String`, `Multilines`, `Color`, `Enum` or `FilePath` value.
+    String(String),
+    /// An array value.
+    List(Vec<LdtkFieldValue>),
+    /// A `Point`, `EntityRef` or `Tile` value, by the names of its members.
+    Object(HashMap<String, LdtkFieldValue>),
+}
+
+/// Fields, by identifier.
+pub type LdtkFields = HashMap<String, LdtkFieldValue>;
+
+/// A tileset used by a level.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LdtkTileset {
+    /// The identifier of the tileset.
+    pub identifier: String,
+    /// The path of the image of the tileset, relative to the asset directory.
+    pub path: String,
+    /// The width of the image in pixels.
+    pub width: u32,
+    /// The height of the image in pixels.
+    pub height: u32,
+    /// The size of a tile in pixels.
+    pub tile_size: u32,
+    /// The space between two tiles of the image, in pixels.
+    pub spacing: u32,
+    /// The space around the tiles of the image, in pixels.
+    pub padding: u32,
+    /// The number of tile columns of the image.
+    pub columns: u32,
+    /// The number of tile rows of the image.
+    pub rows: u32,
+}
+
+/// The kind of a layer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LdtkLayerKind {
+    /// A grid of integer values, which may be drawn with auto-layer rules.
+    IntGrid,
+    /// A layer of tiles drawn by hand.
+    Tiles,
+    /// A layer of tiles drawn with auto-layer rules from another layer.
+    AutoLayer,
+    /// A layer of entity instances.
+    Entities,
+}
+
+impl Default for LdtkLayerKind {
+    fn default() -> Self {
+        LdtkLayerKind::Tiles
+    }
+}
+
+/// A tile of a layer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LdtkLayerTile {
+    /// The cell of the tile, from the top left of the layer.
+    pub cell: [u32; 2],
+    /// The id of the tile in its tileset.
+    pub tile_id: u32,
+    /// Whether the tile is flipped horizontally.
+    pub flip_x: bool,
+    /// Whether the tile is flipped vertically.
+    pub flip_y: bool,
+}
+
+/// A layer of a level, other than an entity layer.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LdtkLayer {
+    /// The identifier of the layer.
+    pub identifier: String,
+    /// The kind of the layer.
+    pub kind: LdtkLayerKind,
+    /// Whether the layer is visible.
+    pub visible: bool,
+    /// The width of the layer in cells.
+    pub width: u32,
+    /// The height of the layer in cells.
+    pub height: u32,
+    /// The size of a cell in pixels.
+    pub grid_size: u32,
+    /// The offset of the layer from the top left of the level, in pixels.
+    pub offset: [i32; 2],
+    /// The uid of the tileset drawing the layer.
+    pub tileset: Option<u32>,
+    /// The `IntGrid` values, row by row from the top left. A value of 0 is an empty cell.
+    pub int_grid: Vec<i32>,
+    /// The tiles of the layer, in drawing order. Tiles stacked in a cell are drawn on top of the
+    /// ones before them.
+    pub tiles: Vec<LdtkLayerTile>,
+}
+
+/// The layers and tilesets of a level of an `LDtk` project, on the level entity of its prefab.
+///
+/// The `LdtkLevelSystem` spawns the `TileMap`s of the level from it, and spawns them again when it
+/// changes, such as when the project is hot-reloaded.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "0ea14425-9d4b-4252-8ff6-38d1ee019d65"]
+pub struct LdtkLevelData {
+    /// The identifier of the level.
+    pub identifier: String,
+    /// The unique id of the level in the project.
+    pub uid: u32,
+    /// The position of the top left of the level in the world, in pixels.
+    pub world_position: [i32; 2],
+    /// The width of the level in pixels.
+    pub width: u32,
+    /// The height of the level in pixels.
+    pub height: u32,
+    /// The layers of the level, from the top one.
+    #[serde_diff(opaque)]
+    pub layers: Vec<LdtkLayer>,
+    /// The tilesets used by the layers, by uid.
+    #[serde_diff(opaque)]
+    pub tilesets: HashMap<u32, LdtkTileset>,
+    /// The fields of the level.
+    #[serde_diff(opaque)]
+    pub fields: LdtkFields,
+}
+
+register_component_type!(LdtkLevelData);
+
+/// An entity instance of a level of an `LDtk` project.
+///
+/// Its entity has a `Transform` at the position of the instance in the world, which is its pivot.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "e3b35b65-bc2b-41b3-8c27-fb0e06465e0b"]
+pub struct LdtkEntity {
+    /// The identifier of the entity definition.
+    pub identifier: String,
+    /// The unique id of the instance in the project.
+    pub iid: String,
+    /// The identifier of the level of the instance.
+    pub level: String,
+    /// The identifier of the layer of the instance.
+    pub layer: String,
+    /// The cell of the instance, from the top left of its layer.
+    pub cell: [i32; 2],
+    /// The width of the instance in pixels.
+    pub width: u32,
+    /// The height of the instance in pixels.
+    pub height: u32,
+    /// The fields of the instance.
+    #[serde_diff(opaque)]
+    pub fields: LdtkFields,
+}
+
+register_component_type!(LdtkEntity);
+
+/// An entity instance of a level, with its position and the values of its fields.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LdtkEntityInstance {
+    /// The entity instance.
+    pub entity: LdtkEntity,
+    /// The position of the instance in the world, in pixels with y pointing down.
+    pub position: [i32; 2],
+    /// The JSON values of the fields, by identifier, to deserialize registered components from.
+    pub values: Vec<(String, String)>,
+}
+
+/// A level of an `LDtk` project, loaded from a `.ldtkl` file when the project saves its levels in
+/// separate files.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "493c0c52-372b-4891-bf70-ba3757c62edc"]
+pub struct LdtkLevelFile {
+    /// The level. Its tilesets are defined by the project, so they are empty.
+    pub data: LdtkLevelData,
+    /// The entity instances of the level.
+    pub entities: Vec<LdtkEntityInstance>,
+}
+
+register_asset_type!(LdtkLevelFile => LdtkLevelFile; AssetProcessorSystem<LdtkLevelFile>);
+
+impl Asset for LdtkLevelFile {
+    fn name() -> &'static str {
+        "tiles::LdtkLevelFile"
+    }
+    type Data = Self;
+}
+
+/// A level of an `LDtk` project saved in a separate file, on the level entity of the prefab of
+/// the project.
+///
+/// The `LdtkLevelSystem` loads the file, adds its `LdtkLevelData` to the entity and spawns its
+/// entity instances, and does it again when the file or the `LdtkExternalLevel` changes.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "45bbe9a6-0717-4698-9b8f-a85a302a7683"]
+pub struct LdtkExternalLevel {
+    /// The path of the `.ldtkl` file of the level, relative to the asset directory.
+    pub path: String,
+    /// The tilesets of the project, by uid.
+    #[serde_diff(opaque)]
+    pub tilesets: HashMap<u32, LdtkTileset>,
+}
+
+register_component_type!(LdtkExternalLevel);
+
+/// A tile of a `TileMap` spawned for a layer of an `LDtk` level.
+///
+/// The sprite sheet of a tileset has its tiles, followed by their copies flipped horizontally,
+/// vertically and both, so that flipped tiles are drawn flipped. The `TileMap` has a z-level per
+/// tile stacked in a cell, the first one being at the bottom.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LdtkTile {
+    /// The index of the sprite of the tile in the sprite sheet of its tileset, including flips.
+    pub sprite: Option<usize>,
+    /// The `IntGrid` value of the cell, or 0. It is only set on the bottom z-level.
+    pub value: i32,
+}
+
+impl Tile for LdtkTile {
+    fn sprite(&self, _: Point3<u32>, _: &World, _: &Resources) -> Option<usize> {
+        self.sprite
+    }
+}
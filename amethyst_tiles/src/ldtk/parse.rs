@@ -0,0 +1,499 @@
+//! Parsing of the JSON format of `LDtk` projects.
+
+use std::collections::HashMap;
+
+use amethyst_error::{format_err, Error};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{
+    LdtkEntity, LdtkEntityInstance, LdtkExternalLevel, LdtkFieldValue, LdtkFields, LdtkLayer,
+    LdtkLayerKind, LdtkLayerTile, LdtkLevelData, LdtkLevelFile, LdtkTileset,
+};
+use crate::tiled::join_path;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Project {
+    #[serde(default)]
+    external_levels: bool,
+    defs: Defs,
+    levels: Vec<Level>,
+}
+
+#[derive(Deserialize)]
+struct Defs {
+    #[serde(default)]
+    tilesets: Vec<TilesetDef>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TilesetDef {
+    uid: u32,
+    identifier: String,
+    rel_path: Option<String>,
+    px_wid: u32,
+    px_hei: u32,
+    tile_grid_size: u32,
+    #[serde(default)]
+    spacing: u32,
+    #[serde(default)]
+    padding: u32,
+    #[serde(rename = "__cWid")]
+    c_wid: u32,
+    #[serde(rename = "__cHei")]
+    c_hei: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Level {
+    identifier: String,
+    uid: u32,
+    world_x: i32,
+    world_y: i32,
+    px_wid: u32,
+    px_hei: u32,
+    #[serde(default)]
+    field_instances: Vec<FieldInstance>,
+    layer_instances: Option<Vec<LayerInstance>>,
+    external_rel_path: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FieldInstance {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__value")]
+    value: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LayerInstance {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__type")]
+    kind: String,
+    #[serde(rename = "__cWid")]
+    c_wid: u32,
+    #[serde(rename = "__cHei")]
+    c_hei: u32,
+    #[serde(rename = "__gridSize")]
+    grid_size: u32,
+    #[serde(rename = "__tilesetDefUid")]
+    tileset_def_uid: Option<u32>,
+    #[serde(rename = "__pxTotalOffsetX", default)]
+    px_total_offset_x: i32,
+    #[serde(rename = "__pxTotalOffsetY", default)]
+    px_total_offset_y: i32,
+    #[serde(default = "default_visible")]
+    visible: bool,
+    #[serde(default)]
+    int_grid_csv: Vec<i32>,
+    #[serde(default)]
+    grid_tiles: Vec<TileInstance>,
+    #[serde(default)]
+    auto_layer_tiles: Vec<TileInstance>,
+    #[serde(default)]
+    entity_instances: Vec<EntityInstance>,
+}
+
+#[derive(Deserialize)]
+struct TileInstance {
+    px: [u32; 2],
+    t: u32,
+    #[serde(default)]
+    f: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EntityInstance {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__grid")]
+    grid: [i32; 2],
+    px: [i32; 2],
+    width: u32,
+    height: u32,
+    #[serde(default)]
+    iid: String,
+    #[serde(default)]
+    field_instances: Vec<FieldInstance>,
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+/// A parsed level of a project.
+#[derive(Debug)]
+pub(crate) enum ParsedLevel {
+    /// A level saved in the project, with its entity instances.
+    Loaded(LdtkLevelFile),
+    /// A level saved in a separate file, with its position in the world.
+    External(LdtkExternalLevel, LdtkLevelData),
+}
+
+/// Converts a JSON field value.
+fn field_value(value: &Value) -> LdtkFieldValue {
+    match value {
+        Value::Null => LdtkFieldValue::Null,
+        Value::Bool(value) => LdtkFieldValue::Bool(*value),
+        Value::Number(number) => number.as_i64().map_or_else(
+            || LdtkFieldValue::Float(number.as_f64().unwrap_or_default()),
+            LdtkFieldValue::Int,
+        ),
+        Value::String(value) => LdtkFieldValue::String(value.clone()),
+        Value::Array(values) => LdtkFieldValue::List(values.iter().map(field_value).collect()),
+        Value::Object(members) => LdtkFieldValue::Object(
+            members
+                .iter()
+                .map(|(name, value)| (name.clone(), field_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn fields(instances: &[FieldInstance]) -> LdtkFields {
+    instances
+        .iter()
+        .map(|field| (field.identifier.clone(), field_value(&field.value)))
+        .collect()
+}
+
+fn layer_kind(name: &str) -> Result<LdtkLayerKind, Error> {
+    match name {
+        "IntGrid" => Ok(LdtkLayerKind::IntGrid),
+        "Tiles" => Ok(LdtkLayerKind::Tiles),
+        "AutoLayer" => Ok(LdtkLayerKind::AutoLayer),
+        "Entities" => Ok(LdtkLayerKind::Entities),
+        _ => Err(format_err!("Unknown LDtk layer type `{}`", name)),
+    }
+}
+
+fn layer_tiles(layer: &LayerInstance) -> Vec<LdtkLayerTile> {
+    let grid_size = layer.grid_size.max(1);
+    layer
+        .grid_tiles
+        .iter()
+        .chain(&layer.auto_layer_tiles)
+        .map(|tile| LdtkLayerTile {
+            cell: [tile.px[0] / grid_size, tile.px[1] / grid_size],
+            tile_id: tile.t,
+            flip_x: tile.f & 1 != 0,
+            flip_y: tile.f & 2 != 0,
+        })
+        .collect()
+}
+
+fn tileset(def: &TilesetDef, path: &str, directory: &str) -> LdtkTileset {
+    LdtkTileset {
+        identifier: def.identifier.clone(),
+        path: join_path(directory, path),
+        width: def.px_wid,
+        height: def.px_hei,
+        tile_size: def.tile_grid_size,
+        spacing: def.spacing,
+        padding: def.padding,
+        columns: def.c_wid,
+        rows: def.c_hei,
+    }
+}
+
+/// Returns the tilesets of `tilesets` drawing `layers`.
+pub(crate) fn layer_tilesets(
+    layers: &[LdtkLayer],
+    tilesets: &HashMap<u32, LdtkTileset>,
+) -> HashMap<u32, LdtkTileset> {
+    layers
+        .iter()
+        .filter_map(|layer| layer.tileset)
+        .filter_map(|uid| tilesets.get(&uid).map(|tileset| (uid, tileset.clone())))
+        .collect()
+}
+
+fn level_data(level: &Level) -> LdtkLevelData {
+    LdtkLevelData {
+        identifier: level.identifier.clone(),
+        uid: level.uid,
+        world_position: [level.world_x, level.world_y],
+        width: level.px_wid,
+        height: level.px_hei,
+        fields: fields(&level.field_instances),
+        ..LdtkLevelData::default()
+    }
+}
+
+fn level(level: Level, tilesets: &HashMap<u32, LdtkTileset>) -> Result<LdtkLevelFile, Error> {
+    let mut parsed = LdtkLevelFile {
+        data: level_data(&level),
+        entities: Vec::new(),
+    };
+    let layers = level.layer_instances.ok_or_else(|| {
+        format_err!(
+            "LDtk level `{}` has no layer instances",
+            parsed.data.identifier
+        )
+    })?;
+
+    for layer in layers {
+        let kind = layer_kind(&layer.kind)?;
+        if kind == LdtkLayerKind::Entities {
+            for instance in layer.entity_instances {
+                parsed.entities.push(LdtkEntityInstance {
+                    entity: LdtkEntity {
+                        identifier: instance.identifier,
+                        iid: instance.iid,
+                        level: parsed.data.identifier.clone(),
+                        layer: layer.identifier.clone(),
+                        cell: instance.grid,
+                        width: instance.width,
+                        height: instance.height,
+                        fields: fields(&instance.field_instances),
+                    },
+                    position: [
+                        level.world_x + layer.px_total_offset_x + instance.px[0],
+                        level.world_y + layer.px_total_offset_y + instance.px[1],
+                    ],
+                    values: instance
+                        .field_instances
+                        .into_iter()
+                        .map(|field| (field.identifier, field.value.to_string()))
+                        .collect(),
+                });
+            }
+            continue;
+        }
+
+        if !layer.int_grid_csv.is_empty()
+            && layer.int_grid_csv.len() != (layer.c_wid * layer.c_hei) as usize
+        {
+            return Err(format_err!(
+                "LDtk layer `{}` has {} IntGrid values instead of {}",
+                layer.identifier,
+                layer.int_grid_csv.len(),
+                layer.c_wid * layer.c_hei
+            ));
+        }
+        parsed.data.layers.push(LdtkLayer {
+            tiles: layer_tiles(&layer),
+            identifier: layer.identifier,
+            kind,
+            visible: layer.visible,
+            width: layer.c_wid,
+            height: layer.c_hei,
+            grid_size: layer.grid_size,
+            offset: [layer.px_total_offset_x, layer.px_total_offset_y],
+            tileset: layer.tileset_def_uid,
+            int_grid: layer.int_grid_csv,
+        });
+    }
+    parsed.data.tilesets = layer_tilesets(&parsed.data.layers, tilesets);
+    Ok(parsed)
+}
+
+/// Parses the levels of an `LDtk` project, joining the paths of its tilesets and level files to
+/// `directory`.
+pub(crate) fn parse_ldtk(text: &str, directory: &str) -> Result<Vec<ParsedLevel>, Error> {
+    let project: Project =
+        serde_json::from_str(text).map_err(|e| format_err!("Invalid LDtk project: {}", e))?;
+
+    let tilesets = project
+        .defs
+        .tilesets
+        .iter()
+        .filter_map(|def| {
+            def.rel_path
+                .as_ref()
+                .map(|path| (def.uid, tileset(def, path, directory)))
+        })
+        .collect::<HashMap<_, _>>();
+    project
+        .levels
+        .into_iter()
+        .map(|data| match &data.external_rel_path {
+            Some(path) if project.external_levels => {
+                let external = LdtkExternalLevel {
+                    path: join_path(directory, path),
+                    tilesets: tilesets.clone(),
+                };
+                Ok(ParsedLevel::External(external, level_data(&data)))
+            }
+            _ => level(data, &tilesets).map(ParsedLevel::Loaded),
+        })
+        .collect()
+}
+
+/// Parses a level saved in a separate `.ldtkl` file, whose tilesets are defined by its project.
+pub(crate) fn parse_ldtkl(text: &str) -> Result<LdtkLevelFile, Error> {
+    let data: Level =
+        serde_json::from_str(text).map_err(|e| format_err!("Invalid LDtk level: {}", e))?;
+    level(data, &HashMap::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LDTK: &str = r#"{
+        "jsonVersion": "1.3.0",
+        "externalLevels": false,
+        "defs": {
+            "tilesets": [
+                {
+                    "uid": 1, "identifier": "Cavern", "relPath": "../tilesets/cavern.png",
+                    "pxWid": 64, "pxHei": 32, "tileGridSize": 16, "spacing": 0, "padding": 0,
+                    "__cWid": 4, "__cHei": 2
+                },
+                {
+                    "uid": 2, "identifier": "Icons", "relPath": null,
+                    "pxWid": 0, "pxHei": 0, "tileGridSize": 16, "__cWid": 0, "__cHei": 0
+                }
+            ]
+        },
+        "levels": [
+            {
+                "identifier": "Entrance", "uid": 0, "worldX": 256, "worldY": -128,
+                "pxWid": 32, "pxHei": 16,
+                "fieldInstances": [
+                    { "__identifier": "music", "__type": "String", "__value": "cave.ogg" }
+                ],
+                "layerInstances": [
+                    {
+                        "__identifier": "Things", "__type": "Entities", "__cWid": 2, "__cHei": 1,
+                        "__gridSize": 16, "__tilesetDefUid": null,
+                        "__pxTotalOffsetX": 0, "__pxTotalOffsetY": 0, "visible": true,
+                        "entityInstances": [
+                            {
+                                "__identifier": "Chest", "__grid": [1, 0], "px": [24, 16],
+                                "width": 16, "height": 16, "iid": "a1b2",
+                                "fieldInstances": [
+                                    { "__identifier": "Gold", "__type": "Int", "__value": 30 },
+                                    {
+                                        "__identifier": "target", "__type": "Point",
+                                        "__value": { "cx": 1, "cy": 0 }
+                                    }
+                                ]
+                            }
+                        ]
+                    },
+                    {
+                        "__identifier": "Walls", "__type": "IntGrid", "__cWid": 2, "__cHei": 1,
+                        "__gridSize": 16, "__tilesetDefUid": 1,
+                        "__pxTotalOffsetX": 0, "__pxTotalOffsetY": 0, "visible": true,
+                        "intGridCsv": [1, 0],
+                        "autoLayerTiles": [ { "px": [0, 0], "src": [16, 0], "f": 1, "t": 1 } ]
+                    },
+                    {
+                        "__identifier": "Floor", "__type": "Tiles", "__cWid": 2, "__cHei": 1,
+                        "__gridSize": 16, "__tilesetDefUid": 1,
+                        "__pxTotalOffsetX": 4, "__pxTotalOffsetY": -2, "visible": false,
+                        "gridTiles": [ { "px": [16, 0], "src": [48, 16], "f": 2, "t": 7 } ]
+                    }
+                ]
+            }
+        ]
+    }"#;
+
+    fn loaded(level: &ParsedLevel) -> &LdtkLevelFile {
+        match level {
+            ParsedLevel::Loaded(file) => file,
+            ParsedLevel::External(..) => panic!("Level is not loaded"),
+        }
+    }
+
+    /// Returns the tilesets of `LDTK` in JSON.
+    fn tilesets_json() -> String {
+        let project: Value = serde_json::from_str(LDTK).unwrap();
+        format!(r#""tilesets": {}"#, project["defs"]["tilesets"])
+    }
+
+    #[test]
+    fn parses_levels() {
+        let levels = parse_ldtk(LDTK, "maps").unwrap();
+        assert_eq!(levels.len(), 1);
+        let level = &loaded(&levels[0]).data;
+        assert_eq!(level.identifier, "Entrance");
+        assert_eq!(level.world_position, [256, -128]);
+        assert_eq!(
+            level.fields["music"],
+            LdtkFieldValue::String("cave.ogg".into())
+        );
+        assert_eq!(level.tilesets.len(), 1);
+        assert_eq!(level.tilesets[&1].path, "tilesets/cavern.png");
+
+        assert_eq!(level.layers.len(), 2);
+        assert_eq!(level.layers[0].kind, LdtkLayerKind::IntGrid);
+        assert_eq!(level.layers[0].int_grid, vec![1, 0]);
+        assert_eq!(
+            level.layers[0].tiles,
+            vec![LdtkLayerTile {
+                cell: [0, 0],
+                tile_id: 1,
+                flip_x: true,
+                flip_y: false,
+            }]
+        );
+        assert!(!level.layers[1].visible);
+        assert_eq!(level.layers[1].offset, [4, -2]);
+        assert_eq!(level.layers[1].tiles[0].cell, [1, 0]);
+        assert!(level.layers[1].tiles[0].flip_y);
+    }
+
+    #[test]
+    fn parses_entities() {
+        let levels = parse_ldtk(LDTK, "").unwrap();
+        let entity = &loaded(&levels[0]).entities[0];
+        assert_eq!(entity.entity.identifier, "Chest");
+        assert_eq!(entity.entity.level, "Entrance");
+        assert_eq!(entity.entity.layer, "Things");
+        assert_eq!(entity.position, [280, -112]);
+        assert_eq!(entity.entity.fields["Gold"], LdtkFieldValue::Int(30));
+        match &entity.entity.fields["target"] {
+            LdtkFieldValue::Object(point) => assert_eq!(point["cy"], LdtkFieldValue::Int(0)),
+            value => panic!("Unexpected point value {:?}", value),
+        }
+        assert_eq!(entity.values[0].0, "Gold");
+    }
+
+    #[test]
+    fn parses_external_levels() {
+        let project = r#"{
+            "externalLevels": true,
+            "defs": { "tilesets": [] },
+            "levels": [
+                {
+                    "identifier": "Entrance", "uid": 0, "worldX": 256, "worldY": -128,
+                    "pxWid": 32, "pxHei": 16, "layerInstances": null,
+                    "externalRelPath": "cave/Entrance.ldtkl"
+                }
+            ]
+        }"#;
+        let project = project.replace(r#""tilesets": []"#, &tilesets_json());
+        let levels = parse_ldtk(&project, "maps").unwrap();
+        match &levels[0] {
+            ParsedLevel::External(external, data) => {
+                assert_eq!(external.path, "maps/cave/Entrance.ldtkl");
+                assert_eq!(external.tilesets[&1].path, "tilesets/cavern.png");
+                assert_eq!((data.world_position, data.width), ([256, -128], 32));
+            }
+            level => panic!("Unexpected level {:?}", level),
+        }
+
+        let level: Value = serde_json::from_str(LDTK).unwrap();
+        let file = parse_ldtkl(&level["levels"][0].to_string()).unwrap();
+        assert_eq!(file.data.identifier, "Entrance");
+        assert_eq!(file.data.layers.len(), 2);
+        assert!(file.data.tilesets.is_empty());
+        assert_eq!(file.entities[0].position, [280, -112]);
+    }
+
+    #[test]
+    fn rejects_unknown_layers() {
+        let unknown = LDTK.replace(r#""__type": "Tiles""#, r#""__type": "Stamps""#);
+        assert!(parse_ldtk(&unknown, "").is_err());
+    }
+}
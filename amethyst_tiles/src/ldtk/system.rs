@@ -0,0 +1,440 @@
+//! Spawning of the `TileMap`s of `LDtk` levels.
+
+use std::collections::HashMap;
+
+use amethyst_assets::{
+    prefab::ComponentRegistry, AssetStorage, DefaultLoader, Handle, Loader, ProcessingQueue,
+};
+use amethyst_core::{
+    ecs::{
+        maybe_changed, CommandBuffer, Entity, IntoQuery, ParallelRunnable, System, SystemBuilder,
+    },
+    math::{Point3, Vector3},
+    transform::{Parent, Transform},
+};
+use amethyst_rendy::{sprite::Sprites, SpriteSheet};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{
+    importer::{add_components, instance_transform, ComponentNames},
+    parse::layer_tilesets,
+    LdtkExternalLevel, LdtkLayer, LdtkLevelData, LdtkLevelFile, LdtkTile, LdtkTileset,
+};
+use crate::{
+    editor::{despawn, flipped_sprites, TileGrid},
+    MapStorage, TileMap,
+};
+
+/// Spawns a `TileMap<LdtkTile>` child of each entity with an `LdtkLevelData` per layer of the
+/// level, and spawns them again when the `LdtkLevelData` changes.
+///
+/// The `TileMap`s of hidden layers hold the `IntGrid` values of their layer but draw no tiles.
+///
+/// It also loads the file of each entity with an `LdtkExternalLevel`, then adds its
+/// `LdtkLevelData` to the entity and spawns its entity instances with the registered components
+/// of the `ComponentRegistry`. It does it again when the file is reloaded.
+#[derive(Debug, Default)]
+pub struct LdtkLevelSystem {
+    /// The spawned `TileMap` entities, by level entity.
+    spawned: HashMap<Entity, Vec<Entity>>,
+    /// The level files being loaded, by level entity.
+    loading: HashMap<Entity, Handle<LdtkLevelFile>>,
+    /// The loaded level files, by level entity.
+    loaded: HashMap<Entity, LoadedLevel>,
+}
+
+/// A level loaded from its file.
+#[derive(Debug)]
+struct LoadedLevel {
+    /// The file of the level.
+    file: Handle<LdtkLevelFile>,
+    /// The version of the file the level was loaded from.
+    version: u32,
+    /// The spawned entity instances.
+    instances: Vec<Entity>,
+}
+
+impl System for LdtkLevelSystem {
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("LdtkLevelSystem")
+                .read_resource::<DefaultLoader>()
+                .read_resource::<AssetStorage<LdtkLevelFile>>()
+                .read_resource::<ProcessingQueue<Sprites>>()
+                .read_resource::<ProcessingQueue<SpriteSheet>>()
+                .with_query(
+                    <(Entity, &LdtkLevelData)>::query().filter(maybe_changed::<LdtkLevelData>()),
+                )
+                .with_query(<(Entity, &LdtkLevelData)>::query())
+                .with_query(
+                    <(Entity, &LdtkExternalLevel)>::query()
+                        .filter(maybe_changed::<LdtkExternalLevel>()),
+                )
+                .with_query(<(Entity, &LdtkExternalLevel)>::query())
+                .build(
+                    move |commands,
+                          world,
+                          (loader, file_storage, sprites_queue, sheet_queue),
+                          (changed_levels, levels, changed_externals, externals)| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("ldtk_level_system");
+
+                        for (entity, external) in changed_externals.iter(world) {
+                            let level = self.loaded.remove(entity);
+                            despawn(commands, level.map(|level| level.instances));
+                            self.loading.insert(*entity, loader.load(&external.path));
+                        }
+
+                        // Forget the external levels which were deleted or lost their
+                        // `LdtkExternalLevel`, and load the reloaded files again.
+                        self.loading
+                            .retain(|entity, _| externals.get(world, *entity).is_ok());
+                        let unloaded = self
+                            .loaded
+                            .iter()
+                            .filter(|(entity, level)| {
+                                externals.get(world, **entity).is_err()
+                                    || file_storage.get_version(&level.file) != Some(level.version)
+                            })
+                            .map(|(entity, _)| *entity)
+                            .collect::<Vec<_>>();
+                        for entity in unloaded {
+                            if let Some(level) = self.loaded.remove(&entity) {
+                                despawn(commands, Some(level.instances));
+                                if externals.get(world, entity).is_ok() {
+                                    self.loading.insert(entity, level.file);
+                                }
+                            }
+                        }
+
+                        let loaded = self
+                            .loading
+                            .iter()
+                            .filter(|(_, file)| file_storage.get(file).is_some())
+                            .map(|(entity, _)| *entity)
+                            .collect::<Vec<_>>();
+                        for entity in loaded {
+                            let file = match self.loading.remove(&entity) {
+                                Some(file) => file,
+                                None => continue,
+                            };
+                            if let (Ok((_, external)), Some((level, version))) = (
+                                externals.get(world, entity),
+                                file_storage.get_asset_with_version(&file),
+                            ) {
+                                let instances = spawn_level(commands, entity, external, level);
+                                self.loaded.insert(
+                                    entity,
+                                    LoadedLevel {
+                                        file,
+                                        version,
+                                        instances,
+                                    },
+                                );
+                            }
+                        }
+
+                        for (entity, data) in changed_levels.iter(world) {
+                            despawn(commands, self.spawned.remove(entity));
+                            let children = spawn_tile_maps(
+                                commands,
+                                loader,
+                                sprites_queue,
+                                sheet_queue,
+                                *entity,
+                                data,
+                            );
+                            self.spawned.insert(*entity, children);
+                        }
+
+                        // Forget the levels which were deleted or lost their `LdtkLevelData`.
+                        let removed = self
+                            .spawned
+                            .keys()
+                            .filter(|entity| levels.get(world, **entity).is_err())
+                            .copied()
+                            .collect::<Vec<_>>();
+                        for entity in removed {
+                            despawn(commands, self.spawned.remove(&entity));
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+/// Adds the `LdtkLevelData` of a level loaded from its file to its entity, and spawns its entity
+/// instances.
+fn spawn_level(
+    commands: &mut CommandBuffer,
+    entity: Entity,
+    external: &LdtkExternalLevel,
+    level: &LdtkLevelFile,
+) -> Vec<Entity> {
+    let mut data = level.data.clone();
+    data.tilesets = layer_tilesets(&data.layers, &external.tilesets);
+    commands.add_component(entity, data);
+
+    let instances = level
+        .entities
+        .iter()
+        .map(|instance| {
+            let entity = commands.push((instance_transform(instance), instance.entity.clone()));
+            (entity, instance.clone())
+        })
+        .collect::<Vec<_>>();
+    let entities = instances.iter().map(|(entity, _)| *entity).collect();
+    commands.exec_mut(move |world, resources| {
+        let registry = match resources.get::<ComponentRegistry>() {
+            Some(registry) => registry,
+            None => {
+                log::warn!("No `ComponentRegistry` to add the components of LDtk entities");
+                return;
+            }
+        };
+        let components = ComponentNames::new(&registry);
+        for (entity, instance) in &instances {
+            if let Err(err) = add_components(world, &components, *entity, instance) {
+                log::error!(
+                    "Failed to add the components of LDtk entity `{}`: {}",
+                    instance.entity.identifier,
+                    err
+                );
+            }
+        }
+    });
+    entities
+}
+
+/// Spawns the `TileMap` children of a level entity, one per layer.
+fn spawn_tile_maps(
+    commands: &mut CommandBuffer,
+    loader: &DefaultLoader,
+    sprites_queue: &ProcessingQueue<Sprites>,
+    sheet_queue: &ProcessingQueue<SpriteSheet>,
+    entity: Entity,
+    data: &LdtkLevelData,
+) -> Vec<Entity> {
+    let mut sprite_sheets: HashMap<u32, Handle<SpriteSheet>> = HashMap::new();
+    let mut children = Vec::with_capacity(data.layers.len());
+    for (index, layer) in data.layers.iter().enumerate() {
+        let tileset = layer
+            .tileset
+            .and_then(|uid| data.tilesets.get(&uid).map(|tileset| (uid, tileset)));
+        let mut map = layer_map(layer, tileset.map(|(_, tileset)| tileset));
+        map.sprite_sheet = tileset.map(|(uid, tileset)| {
+            sprite_sheets
+                .entry(uid)
+                .or_insert_with(|| {
+                    let sprite_sheet = SpriteSheet {
+                        texture: loader.load(&tileset.path),
+                        sprites: loader.load_from_data(
+                            flipped_sprites(&tileset_grid(tileset)),
+                            (),
+                            sprites_queue,
+                        ),
+                    };
+                    loader.load_from_data(sprite_sheet, (), sheet_queue)
+                })
+                .clone()
+        });
+        children.push(commands.push((map, layer_transform(data, index), Parent(entity))));
+    }
+    children
+}
+
+/// Returns the grid of the tiles of a tileset, whose flipped sprites are in the order expected
+/// by `LdtkTile`.
+fn tileset_grid(tileset: &LdtkTileset) -> TileGrid {
+    TileGrid {
+        texture_width: tileset.width,
+        texture_height: tileset.height,
+        tile_width: tileset.tile_size,
+        tile_height: tileset.tile_size,
+        margin: tileset.padding,
+        spacing: tileset.spacing,
+        columns: tileset.columns,
+        count: tileset.columns * tileset.rows,
+    }
+}
+
+/// Returns the `TileMap` of a layer, with the `IntGrid` values of the layer and, if it is visible,
+/// the tiles drawn with `tileset`. Tiles stacked in a cell are put at increasing z-levels.
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn layer_map(layer: &LdtkLayer, tileset: Option<&LdtkTileset>) -> TileMap<LdtkTile> {
+    let (levels, depth) = tile_levels(layer);
+    let mut map = TileMap::new(
+        Vector3::new(layer.width, layer.height, depth),
+        Vector3::new(layer.grid_size, layer.grid_size, 1),
+        None,
+    );
+    for (index, value) in layer.int_grid.iter().enumerate() {
+        let index = index as u32;
+        let coordinates = Point3::new(index % layer.width, index / layer.width, 0);
+        if let Some(tile) = map.get_mut(&coordinates) {
+            tile.value = *value;
+        }
+    }
+
+    let count = match tileset {
+        Some(tileset) if layer.visible => tileset.columns * tileset.rows,
+        _ => return map,
+    };
+    for (tile, z) in layer.tiles.iter().zip(levels) {
+        let z = match z {
+            Some(z) if tile.tile_id < count => z,
+            _ => continue,
+        };
+        let flips = u32::from(tile.flip_x) + 2 * u32::from(tile.flip_y);
+        if let Some(target) = map.get_mut(&Point3::new(tile.cell[0], tile.cell[1], z)) {
+            target.sprite = Some((tile.tile_id + flips * count) as usize);
+        }
+    }
+    map
+}
+
+/// Returns the z-level of each tile of a layer, which is the number of tiles before it in its
+/// cell, or `None` for the tiles outside of the layer, and the number of z-levels of the layer.
+fn tile_levels(layer: &LdtkLayer) -> (Vec<Option<u32>>, u32) {
+    let mut stacks: HashMap<[u32; 2], u32> = HashMap::new();
+    let levels = layer
+        .tiles
+        .iter()
+        .map(|tile| {
+            if tile.cell[0] >= layer.width || tile.cell[1] >= layer.height {
+                return None;
+            }
+            let stack = stacks.entry(tile.cell).or_insert(0);
+            *stack += 1;
+            Some(*stack - 1)
+        })
+        .collect();
+    (levels, stacks.values().copied().max().unwrap_or(1))
+}
+
+/// Returns the transform of the `TileMap` of the layer at `index`, relative to its level. The top
+/// layer is the nearest to the camera, above the z-levels of the layers below it.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn layer_transform(data: &LdtkLevelData, index: usize) -> Transform {
+    let below: u32 = data.layers[index + 1..]
+        .iter()
+        .map(|layer| tile_levels(layer).1)
+        .sum();
+    let layer = &data.layers[index];
+    let width = (layer.width * layer.grid_size) as f32;
+    let height = (layer.height * layer.grid_size) as f32;
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(
+        layer.offset[0] as f32 + (width - data.width as f32) * 0.5,
+        -(layer.offset[1] as f32 + (height - data.height as f32) * 0.5),
+        (below + 1) as f32,
+    );
+    transform
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ldtk::LdtkLayerTile, Map};
+
+    fn tileset() -> LdtkTileset {
+        LdtkTileset {
+            tile_size: 16,
+            spacing: 1,
+            padding: 2,
+            columns: 4,
+            rows: 2,
+            ..LdtkTileset::default()
+        }
+    }
+
+    fn layer() -> LdtkLayer {
+        LdtkLayer {
+            visible: true,
+            width: 2,
+            height: 1,
+            grid_size: 16,
+            int_grid: vec![3, 0],
+            tiles: vec![LdtkLayerTile {
+                cell: [1, 0],
+                tile_id: 5,
+                flip_x: false,
+                flip_y: true,
+            }],
+            ..LdtkLayer::default()
+        }
+    }
+
+    #[test]
+    fn grids_tiles_of_tileset() {
+        let grid = tileset_grid(&tileset());
+        assert_eq!((grid.tile_width, grid.tile_height), (16, 16));
+        assert_eq!(
+            (grid.margin, grid.spacing, grid.columns, grid.count),
+            (2, 1, 4, 8)
+        );
+    }
+
+    #[test]
+    fn stacks_tiles_of_a_cell() {
+        let mut stacked = layer();
+        stacked.tiles.push(LdtkLayerTile {
+            cell: [1, 0],
+            tile_id: 2,
+            flip_x: false,
+            flip_y: false,
+        });
+        let map = layer_map(&stacked, Some(&tileset()));
+        assert_eq!(map.dimensions().z, 2);
+        assert_eq!(map.get(&Point3::new(1, 0, 0)).unwrap().sprite, Some(21));
+        assert_eq!(map.get(&Point3::new(1, 0, 1)).unwrap().sprite, Some(2));
+        assert_eq!(map.get(&Point3::new(0, 0, 1)).unwrap().sprite, None);
+        assert_eq!(map.get(&Point3::new(0, 0, 1)).unwrap().value, 0);
+
+        let data = LdtkLevelData {
+            layers: vec![layer(), stacked, layer()],
+            ..LdtkLevelData::default()
+        };
+        let depths = (0..3)
+            .map(|index| layer_transform(&data, index).translation().z)
+            .collect::<Vec<_>>();
+        assert_eq!(depths, vec![4., 2., 1.]);
+    }
+
+    #[test]
+    fn fills_values_and_tiles() {
+        let map = layer_map(&layer(), Some(&tileset()));
+        let first = map.get(&Point3::new(0, 0, 0)).unwrap();
+        assert_eq!((first.value, first.sprite), (3, None));
+        assert_eq!(map.get(&Point3::new(1, 0, 0)).unwrap().sprite, Some(21));
+
+        let hidden = LdtkLayer {
+            visible: false,
+            ..layer()
+        };
+        let map = layer_map(&hidden, Some(&tileset()));
+        assert_eq!(map.get(&Point3::new(1, 0, 0)).unwrap().sprite, None);
+        assert_eq!(map.get(&Point3::new(0, 0, 0)).unwrap().value, 3);
+    }
+
+    #[test]
+    fn offsets_layers_from_level_center() {
+        let data = LdtkLevelData {
+            width: 16,
+            height: 16,
+            layers: vec![
+                LdtkLayer {
+                    offset: [4, 2],
+                    ..layer()
+                },
+                layer(),
+            ],
+            ..LdtkLevelData::default()
+        };
+        let transform = layer_transform(&data, 0);
+        assert_eq!(transform.translation().x, 12.);
+        assert_eq!(transform.translation().y, -2.);
+        assert!(transform.translation().z > layer_transform(&data, 1).translation().z);
+    }
+}
@@ -7,23 +7,39 @@
 #![deny(clippy::all, clippy::pedantic, missing_docs)]
 #![allow(dead_code, clippy::module_name_repetitions)]
 
+mod animation;
+mod chunked;
+mod diff;
+mod editor;
 mod map;
 mod morton;
 mod pass;
+mod projection;
 
+pub mod autotile;
+pub mod collision;
 pub mod error;
+pub mod fov;
 pub mod iters;
+pub mod ldtk;
+pub mod pathfinding;
 pub mod pod;
+pub mod tiled;
 
 use amethyst_core::math::Vector3;
-pub use error::TileOutOfBoundsError;
+pub use animation::{TileAnimation, TileAnimationFrame, TileAnimations};
+pub use autotile::{AutotileTile, AutotiledTile};
+pub use chunked::ChunkedTileMap;
+pub use diff::{TileChanges, TileEdit, TileMapDiff};
+pub use error::{ChunkDimensionsError, MapTooLargeError, TileOutOfBoundsError};
 pub use iters::{MortonRegion, Region};
 pub use map::{Map, MapStorage, Tile, TileMap};
 pub use morton::{MortonEncoder, MortonEncoder2D};
 pub use pass::{
     DrawTiles2D, DrawTiles2DBounds, DrawTiles2DBoundsCameraCulling, DrawTiles2DBoundsDefault,
-    DrawTiles2DDesc, RenderTiles2D,
+    DrawTiles2DDesc, DynamicTiles, RenderTiles2D,
 };
+pub use projection::{HexLayout, HexOrientation, IsometricLayout, Projection, StaggerIndex};
 
 /// Trait to provide generic access to various encoding schemas. All tile storages use this to encode their coordinates
 /// and provide different spatial encoding algorithms for efficiency.
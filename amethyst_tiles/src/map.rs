@@ -3,15 +3,19 @@
 use amethyst_assets::{Asset, Handle};
 use amethyst_core::{
     ecs::{Resources, World},
-    math::{Matrix4, Point3, Vector3},
+    math::{Matrix4, Point2, Point3, Vector3},
     transform::Transform,
 };
 use amethyst_rendy::{palette::Srgba, SpriteSheet};
 
-use crate::{CoordinateEncoder, TileOutOfBoundsError};
+use crate::{CoordinateEncoder, Projection, TileChanges, TileOutOfBoundsError};
 
 /// Trait providing generic rendering functionality to all tiles. Using a tilemap requires you to provide a `Tile` type,
 /// which must implement this trait to provide the `RenderPass` with the appropriate sprite and tint values.
+///
+/// The tiles of a `TileMap` are drawn again from their sprite and tint when they are in the changes of the map, or
+/// when the region drawn of it changes. Tiles whose sprite or tint depend on anything else need `DynamicTiles` on the
+/// entity of the map.
 pub trait Tile: 'static + Clone + Send + Sync + Default {
     /// Takes an immutable reference to world to process this sprite and return its sprite.
     fn sprite(
@@ -111,15 +115,25 @@ pub trait MapStorage<T: Tile> {
 /// The default encoding scheme is `MortonEncoder2D`, which allows for arbitrary X, Y and Z coordinate sizes while
 /// still spatially partitioning each z-level. For more efficient Z-order encoding, use `MortonEncoder` which requires
 /// cubic map dimensions but provides for much greater spatial efficiency.
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+///
+/// Tiles are laid out orthogonally, unless another `Projection` is given with `with_projection`.
+///
+/// `DrawTiles2D` keeps the drawn tiles between frames, fetching again only the tiles in the changes of the map, so
+/// tiles mutated through `get_mut_nochange` or `get_raw_mut_nochange` keep being drawn as they were.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct TileMap<T: Tile, E: CoordinateEncoder = crate::MortonEncoder2D> {
     pub(crate) origin: Point3<f32>,
     pub(crate) tile_dimensions: Vector3<u32>,
     pub(crate) dimensions: Vector3<u32>,
     pub(crate) transform: Matrix4<f32>,
+    #[serde(default)]
+    pub(crate) projection: Projection,
 
     pub(crate) version: u64,
 
+    #[serde(skip)]
+    pub(crate) changes: TileChanges,
+
     #[serde(skip)]
     pub(crate) sprite_sheet: Option<Handle<SpriteSheet>>,
 
@@ -128,6 +142,21 @@ pub struct TileMap<T: Tile, E: CoordinateEncoder = crate::MortonEncoder2D> {
     #[serde(skip)]
     pub(crate) encoder: E,
 }
+
+/// Maps are equal when they have the same layout and tiles. Their versions and changes only track
+/// when they were changed, and their encoders follow from their dimensions.
+impl<T: Tile + PartialEq, E: CoordinateEncoder> PartialEq for TileMap<T, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.origin == other.origin
+            && self.tile_dimensions == other.tile_dimensions
+            && self.dimensions == other.dimensions
+            && self.transform == other.transform
+            && self.projection == other.projection
+            && self.sprite_sheet == other.sprite_sheet
+            && self.data == other.data
+    }
+}
+
 impl<T: Tile, E: CoordinateEncoder> Asset for TileMap<T, E> {
     fn name() -> &'static str {
         "tiles::map"
@@ -142,6 +171,21 @@ impl<T: Tile, E: CoordinateEncoder> TileMap<T, E> {
         self.version
     }
 
+    /// The tiles of this map which were mutably borrowed since its changes were last drained.
+    #[must_use]
+    pub fn changes(&self) -> &TileChanges {
+        &self.changes
+    }
+
+    /// Takes the tiles of this map which were mutably borrowed since its changes were last
+    /// drained. Changes are only drained once, so this is usually called by a single system each
+    /// frame, such as one sending a `TileMapDiff` to the clients of a server. The systems reading
+    /// the changes with `TileChanges::since` update every tile after changes they missed were
+    /// drained.
+    pub fn drain_changes(&mut self) -> TileChanges {
+        std::mem::replace(&mut self.changes, TileChanges::drained_in(self.version))
+    }
+
     ///Create a new instance of `TileMap`.
     pub fn new(
         dimensions: Vector3<u32>,
@@ -149,7 +193,8 @@ impl<T: Tile, E: CoordinateEncoder> TileMap<T, E> {
         sprite_sheet: Option<Handle<SpriteSheet>>,
     ) -> Self {
         let origin = Point3::new(0.0, 0.0, 0.0);
-        let transform = create_transform(&dimensions, &tile_dimensions);
+        let projection = Projection::Orthogonal;
+        let transform = create_transform(&projection, &dimensions, &tile_dimensions);
 
         // Round the dimensions to the nearest multiplier for morton rounding
         let size = E::allocation_size(dimensions);
@@ -165,10 +210,33 @@ impl<T: Tile, E: CoordinateEncoder> TileMap<T, E> {
             tile_dimensions,
             sprite_sheet,
             transform,
+            projection,
             encoder,
             version: 1,
+            changes: TileChanges::default(),
         }
     }
+
+    /// Lays out the tiles of this map with the given `Projection`.
+    #[must_use]
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.transform = create_transform(&projection, &self.dimensions, &self.tile_dimensions);
+        self.projection = projection;
+        self
+    }
+
+    /// The `Projection` laying out the tiles of this map.
+    #[must_use]
+    pub fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    /// Returns the tiles of this map sharing an edge with the tile at `coord`, on its z-level.
+    pub fn neighbors(&self, coord: &Point3<u32>) -> impl Iterator<Item = Point3<u32>> + '_ {
+        self.projection.neighbors(coord).filter(move |neighbor| {
+            neighbor.x < self.dimensions.x && neighbor.y < self.dimensions.y
+        })
+    }
 }
 
 impl<T: Tile, E: CoordinateEncoder> Map for TileMap<T, E> {
@@ -194,7 +262,7 @@ impl<T: Tile, E: CoordinateEncoder> Map for TileMap<T, E> {
 
     #[inline]
     fn to_world(&self, coord: &Point3<u32>, map_transform: Option<&Transform>) -> Vector3<f32> {
-        to_world(&self.transform, coord, map_transform)
+        to_world(&self.transform, &self.projection, coord, map_transform)
     }
 
     #[inline]
@@ -204,7 +272,13 @@ impl<T: Tile, E: CoordinateEncoder> Map for TileMap<T, E> {
         coord: &Vector3<f32>,
         map_transform: Option<&Transform>,
     ) -> Result<Point3<u32>, TileOutOfBoundsError> {
-        to_tile(&self.transform, coord, self.dimensions(), map_transform)
+        to_tile(
+            &self.transform,
+            &self.projection,
+            coord,
+            self.dimensions(),
+            map_transform,
+        )
     }
 
     #[inline]
@@ -264,6 +338,14 @@ impl<T: Tile, E: CoordinateEncoder> MapStorage<T> for TileMap<T, E> {
     #[inline]
     fn get_raw_mut(&mut self, coord: u32) -> Option<&mut T> {
         self.version += 1;
+        if let Some(point) = self.decode(coord) {
+            if point.x < self.dimensions.x
+                && point.y < self.dimensions.y
+                && point.z < self.dimensions.z
+            {
+                self.changes.insert(point, self.version);
+            }
+        }
         self.data.get_mut(coord as usize)
     }
 
@@ -274,18 +356,20 @@ impl<T: Tile, E: CoordinateEncoder> MapStorage<T> for TileMap<T, E> {
 }
 
 #[allow(clippy::cast_precision_loss)]
-fn create_transform(map_dimensions: &Vector3<u32>, tile_dimensions: &Vector3<u32>) -> Matrix4<f32> {
+fn create_transform(
+    projection: &Projection,
+    map_dimensions: &Vector3<u32>,
+    tile_dimensions: &Vector3<u32>,
+) -> Matrix4<f32> {
     let tile_dimensions = Vector3::new(
         tile_dimensions.x as f32,
         tile_dimensions.y as f32,
         tile_dimensions.z as f32,
     );
 
-    let half_dimensions = Vector3::new(
-        -1.0 * (map_dimensions.x as f32 / 2.0) + 0.5,
-        map_dimensions.y as f32 / 2.0 - 0.5,
-        0.0,
-    );
+    // Center the map on its origin
+    let (min, max) = projection.bounds(map_dimensions);
+    let half_dimensions = Vector3::new(-0.5 * (min.x + max.x), 0.5 * (min.y + max.y), 0.0);
 
     Matrix4::new_translation(&half_dimensions).append_nonuniform_scaling(&tile_dimensions)
 }
@@ -293,10 +377,12 @@ fn create_transform(map_dimensions: &Vector3<u32>, tile_dimensions: &Vector3<u32
 #[allow(clippy::cast_precision_loss)]
 fn to_world(
     transform: &Matrix4<f32>,
+    projection: &Projection,
     coord: &Point3<u32>,
     map_transform: Option<&Transform>,
 ) -> Vector3<f32> {
-    let coord_f = Point3::new(coord.x as f32, -1.0 * coord.y as f32, coord.z as f32);
+    let center = projection.project(coord);
+    let coord_f = Point3::new(center.x, -1.0 * center.y, coord.z as f32);
     let point = transform.transform_point(&coord_f);
     map_transform.map_or(point.coords, |map_trans| {
         map_trans.global_matrix().transform_point(&point).coords
@@ -306,6 +392,7 @@ fn to_world(
 #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
 fn to_tile(
     transform: &Matrix4<f32>,
+    projection: &Projection,
     coord: &Vector3<f32>,
     max_dimensions: &Vector3<u32>,
     map_transform: Option<&Transform>,
@@ -321,8 +408,9 @@ fn to_tile(
         .transform_point(&point)
         .coords;
 
-    inverse.x = inverse.x.round();
-    inverse.y = inverse.y.round() * -1.0;
+    let tile = projection.unproject(&Point2::new(inverse.x, -1.0 * inverse.y));
+    inverse.x = tile.x;
+    inverse.y = tile.y;
     inverse.z = inverse.z.floor();
 
     if inverse.x < 0.0
@@ -355,7 +443,7 @@ mod tests {
     use super::*;
     use crate::{
         morton::{MortonEncoder, MortonEncoder2D},
-        FlatEncoder,
+        FlatEncoder, IsometricLayout,
     };
 
     #[derive(Clone, Debug)]
@@ -442,22 +530,38 @@ mod tests {
     }
 
     pub fn test_coord(transform: &Matrix4<f32>, tile: Point3<u32>, world: Point3<f32>) {
-        let world_result = to_world(transform, &tile, None);
+        let world_result = to_world(transform, &Projection::Orthogonal, &tile, None);
         assert_eq!(world_result, world.coords);
-        let tile_result =
-            to_tile(transform, &world.coords, &Vector3::new(100, 100, 100), None).unwrap();
+        let tile_result = to_tile(
+            transform,
+            &Projection::Orthogonal,
+            &world.coords,
+            &Vector3::new(100, 100, 100),
+            None,
+        )
+        .unwrap();
         assert_eq!(tile_result, tile);
 
-        let world_reverse =
-            to_tile(transform, &world_result, &Vector3::new(100, 100, 100), None).unwrap();
+        let world_reverse = to_tile(
+            transform,
+            &Projection::Orthogonal,
+            &world_result,
+            &Vector3::new(100, 100, 100),
+            None,
+        )
+        .unwrap();
         assert_eq!(world_reverse, tile);
-        let tile_reverse = to_world(transform, &tile_result, None);
+        let tile_reverse = to_world(transform, &Projection::Orthogonal, &tile_result, None);
         assert_eq!(tile_reverse, world.coords);
     }
 
     #[test]
     pub fn tilemap_coord_conversions() {
-        let transform = create_transform(&Vector3::new(64, 64, 64), &Vector3::new(10, 10, 1));
+        let transform = create_transform(
+            &Projection::Orthogonal,
+            &Vector3::new(64, 64, 64),
+            &Vector3::new(10, 10, 1),
+        );
 
         test_coord(
             &transform,
@@ -488,10 +592,16 @@ mod tests {
         world: Point3<f32>,
         map_transform: &Transform,
     ) {
-        let world_result = to_world(transform, &tile, Some(map_transform));
+        let world_result = to_world(
+            transform,
+            &Projection::Orthogonal,
+            &tile,
+            Some(map_transform),
+        );
         assert_eq!(world_result, world.coords);
         let tile_result = to_tile(
             transform,
+            &Projection::Orthogonal,
             &world.coords,
             &Vector3::new(100, 100, 100),
             Some(map_transform),
@@ -501,19 +611,29 @@ mod tests {
 
         let world_reverse = to_tile(
             transform,
+            &Projection::Orthogonal,
             &world_result,
             &Vector3::new(100, 100, 100),
             Some(map_transform),
         )
         .unwrap();
         assert_eq!(world_reverse, tile);
-        let tile_reverse = to_world(transform, &tile_result, Some(map_transform));
+        let tile_reverse = to_world(
+            transform,
+            &Projection::Orthogonal,
+            &tile_result,
+            Some(map_transform),
+        );
         assert_eq!(tile_reverse, world.coords);
     }
 
     #[test]
     pub fn tilemap_coord_conversions_with_map_transform() {
-        let transform = create_transform(&Vector3::new(64, 64, 64), &Vector3::new(10, 10, 1));
+        let transform = create_transform(
+            &Projection::Orthogonal,
+            &Vector3::new(64, 64, 64),
+            &Vector3::new(10, 10, 1),
+        );
         let mut map_transform = Transform::default();
         map_transform.set_translation_xyz(-10.0, 10.0, 0.0);
         map_transform.copy_local_to_global();
@@ -544,9 +664,39 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn isometric_coord_conversions() {
+        let map = TileMap::<TestTile, FlatEncoder>::new(
+            Vector3::new(2, 2, 1),
+            Vector3::new(20, 10, 1),
+            None,
+        )
+        .with_projection(Projection::Isometric(IsometricLayout::Diamond));
+
+        for &(tile, world) in &[
+            (Point3::new(0, 0, 0), Vector3::new(0.0, 5.0, 0.0)),
+            (Point3::new(1, 0, 0), Vector3::new(10.0, 0.0, 0.0)),
+            (Point3::new(0, 1, 0), Vector3::new(-10.0, 0.0, 0.0)),
+            (Point3::new(1, 1, 0), Vector3::new(0.0, -5.0, 0.0)),
+        ] {
+            assert_eq!(map.to_world(&tile, None), world);
+            assert_eq!(map.to_tile(&world, None).unwrap(), tile);
+            assert_eq!(
+                map.to_tile(&(world + Vector3::new(4.0, 2.0, 0.0)), None)
+                    .unwrap(),
+                tile
+            );
+        }
+        assert!(map.to_tile(&Vector3::new(0.0, 12.0, 0.0), None).is_err());
+    }
+
     #[test]
     pub fn tilemap_transform_positioning() {
-        let transform = create_transform(&Vector3::new(1, 2, 3), &Vector3::new(10, 10, 1));
+        let transform = create_transform(
+            &Projection::Orthogonal,
+            &Vector3::new(1, 2, 3),
+            &Vector3::new(10, 10, 1),
+        );
         test_coord(&transform, Point3::new(0, 0, 0), Point3::new(0.0, 5.0, 0.0));
     }
 }
@@ -1,16 +1,16 @@
 #![allow(clippy::default_trait_access, clippy::use_self)]
 #![allow(unused_imports, unused_variables)]
 
-use std::marker::PhantomData;
+use std::{collections::HashMap, marker::PhantomData};
 
-use amethyst_assets::{AssetHandle, AssetStorage, Handle};
+use amethyst_assets::{AssetHandle, AssetStorage, Handle, LoadHandle};
 use amethyst_core::{
     dispatcher::{System, ThreadLocalSystem},
-    ecs::{component, world::World, EntityStore, IntoQuery, Resources, TryRead},
+    ecs::{component, world::World, Entity, EntityStore, IntoQuery, Resources, TryRead},
     geometry::{Plane, Ray},
     math::{self, clamp, convert, Matrix4, Point2, Point3, Vector2, Vector3, Vector4},
     transform::Transform,
-    Hidden,
+    Hidden, Time,
 };
 use amethyst_rendy::{
     batch::{GroupIterator, OneLevelBatch, OrderedTwoLevelBatch},
@@ -34,7 +34,7 @@ use amethyst_rendy::{
         shader::{Shader, ShaderSetBuilder, SpirvShader},
     },
     resources::Tint as TintComponent,
-    sprite::{SpriteRender, SpriteSheet, Sprites},
+    sprite::{Sprite, SpriteRender, SpriteSheet, Sprites},
     sprite_visibility::SpriteVisibility,
     submodules::{
         gather::CameraGatherer, DynamicUniform, DynamicVertexBuffer, FlatEnvironmentSub, TextureId,
@@ -46,15 +46,18 @@ use amethyst_rendy::{
 };
 use amethyst_window::ScreenDimensions;
 use derivative::Derivative;
+use fnv::FnvHashMap;
 use glsl_layout::Uniform;
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
 use crate::{
+    animation::TileAnimations,
+    chunked::ChunkedTileMap,
     iters::Region,
     map::{Map, MapStorage, Tile, TileMap},
     pod::{TileArgs, TileMapArgs},
-    CoordinateEncoder, MortonEncoder2D,
+    CoordinateEncoder, MortonEncoder2D, Projection,
 };
 
 lazy_static::lazy_static! {
@@ -84,6 +87,16 @@ pub trait DrawTiles2DBounds: 'static + std::fmt::Debug + Send + Sync {
         map_transform: Option<&Transform>,
         aux: &GraphAuxData,
     ) -> Region;
+
+    /// Returns the signed tile coordinates to render of a `ChunkedTileMap`, from the minimum
+    /// inclusive to the maximum exclusive, or `None` to render all of its resident chunks.
+    fn chunked_bounds<T: Tile, E: CoordinateEncoder>(
+        map: &ChunkedTileMap<T, E>,
+        map_transform: Option<&Transform>,
+        aux: &GraphAuxData,
+    ) -> Option<(Point3<i32>, Point3<i32>)> {
+        None
+    }
 }
 
 /// Default bounds that returns the entire tilemap
@@ -103,6 +116,37 @@ impl DrawTiles2DBounds for DrawTiles2DBoundsDefault {
 #[derive(Default, Debug)]
 pub struct DrawTiles2DBoundsCameraCulling;
 
+/// Returns the plane of the tiles of a map.
+fn tile_plane(map_transform: Option<&Transform>) -> Plane<f32> {
+    Plane::from_point_normal(
+        &map_transform.map_or(Point3::new(0.0, 0.0, 0.0), |t| {
+            Point3::from(*t.translation())
+        }),
+        &map_transform.map_or(Vector3::new(0.0, 0.0, -1.0), |t| {
+            t.matrix().transform_vector(&Vector3::new(0.0, 0.0, -1.0))
+        }),
+    )
+}
+
+/// Returns the rays cast from the four corners of the screen by the active camera, if there is
+/// one and its entity exists.
+fn camera_corner_rays(aux: &GraphAuxData) -> Option<[Ray<f32>; 4]> {
+    let active_camera = aux.resources.get::<ActiveCamera>()?;
+    let entry = aux.world.entry_ref(active_camera.entity?).ok()?;
+    let camera_transform = entry.get_component::<Transform>().unwrap();
+    let camera = entry.get_component::<Camera>().unwrap();
+    let dimensions = aux.resources.get::<ScreenDimensions>().unwrap();
+    let w = dimensions.width();
+    let h = dimensions.height();
+    let diagonal = Vector2::new(w, h);
+    Some([
+        camera.screen_ray(Point2::new(0.0, 0.0), diagonal, camera_transform),
+        camera.screen_ray(Point2::new(0.0, h), diagonal, camera_transform),
+        camera.screen_ray(Point2::new(w, 0.0), diagonal, camera_transform),
+        camera.screen_ray(Point2::new(w, h), diagonal, camera_transform),
+    ])
+}
+
 fn camera_ray_to_tile_coords<T: Tile, E: CoordinateEncoder>(
     ray: Ray<f32>,
     tile_plane: &Plane<f32>,
@@ -126,82 +170,76 @@ fn camera_ray_to_tile_coords<T: Tile, E: CoordinateEncoder>(
 }
 
 impl DrawTiles2DBounds for DrawTiles2DBoundsCameraCulling {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
     fn bounds<T: Tile, E: CoordinateEncoder>(
         map: &TileMap<T, E>,
         map_transform: Option<&Transform>,
         aux: &GraphAuxData,
     ) -> Region {
-        let active_camera = aux.resources.get::<ActiveCamera>();
-        if active_camera.is_none() {
-            // No active camera
-            return Region::empty();
-        }
-        let active_camera = active_camera.unwrap();
-        if active_camera.entity.is_none() {
-            // No entity in active camera
-            return Region::empty();
-        }
-        if let Ok(entry) = aux.world.entry_ref(active_camera.entity.unwrap()) {
-            let tile_plane = Plane::from_point_normal(
-                &map_transform.map_or(Point3::new(0.0, 0.0, 0.0), |t| {
-                    Point3::from(*t.translation())
-                }),
-                &map_transform.map_or(Vector3::new(0.0, 0.0, -1.0), |t| {
-                    t.matrix().transform_vector(&Vector3::new(0.0, 0.0, -1.0))
-                }),
-            );
-            let camera_transform = entry.get_component::<Transform>().unwrap();
-            let camera = entry.get_component::<Camera>().unwrap();
-            let dimensions = aux.resources.get::<ScreenDimensions>().unwrap();
-            let w = dimensions.width();
-            let h = dimensions.height();
-            let diagonal = Vector2::new(w, h);
-            // Cast 4 rays from the four corners of the camera, and get at which tile they intersect
-            let points = [
-                camera_ray_to_tile_coords(
-                    camera.screen_ray(Point2::new(0.0, 0.0), diagonal, camera_transform),
-                    &tile_plane,
-                    map,
-                    map_transform,
-                ),
-                camera_ray_to_tile_coords(
-                    camera.screen_ray(Point2::new(0.0, h), diagonal, camera_transform),
-                    &tile_plane,
-                    map,
-                    map_transform,
-                ),
-                camera_ray_to_tile_coords(
-                    camera.screen_ray(Point2::new(w, 0.0), diagonal, camera_transform),
-                    &tile_plane,
-                    map,
-                    map_transform,
-                ),
-                camera_ray_to_tile_coords(
-                    camera.screen_ray(Point2::new(w, h), diagonal, camera_transform),
-                    &tile_plane,
-                    map,
-                    map_transform,
-                ),
-            ];
-            let x = i64::from(map.dimensions().x);
-            let y = i64::from(map.dimensions().y);
-            // Cull the tilemap using the min and max coordinates along each axis of the tilemap
-            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-            return Region::new(
-                Point3::new(
-                    points.iter().map(|p| p.x).min().unwrap().max(0).min(x) as u32,
-                    points.iter().map(|p| p.y).min().unwrap().max(0).min(y) as u32,
-                    0,
-                ),
-                Point3::new(
-                    points.iter().map(|p| p.x + 1).max().unwrap().max(0).min(x) as u32,
-                    points.iter().map(|p| p.y + 1).max().unwrap().max(0).min(y) as u32,
-                    map.dimensions().z,
-                ),
-            );
-        }
-        // Active camera exists but its entity is not found
-        Region::empty()
+        let rays = match camera_corner_rays(aux) {
+            Some(rays) => rays,
+            // No active camera, or its entity is not found
+            None => return Region::empty(),
+        };
+        let tile_plane = tile_plane(map_transform);
+        // Cast 4 rays from the four corners of the camera, and get at which tile they intersect
+        let points = rays
+            .iter()
+            .map(|ray| camera_ray_to_tile_coords(*ray, &tile_plane, map, map_transform))
+            .collect::<Vec<_>>();
+        let x = i64::from(map.dimensions().x);
+        let y = i64::from(map.dimensions().y);
+        // The rows and columns of other projections are not aligned with the camera, so widen
+        // their region by a tile to keep the tiles partly in view
+        let margin = if *map.projection() == Projection::Orthogonal {
+            0
+        } else {
+            1
+        };
+        let min_x = points.iter().map(|p| p.x).min().unwrap() - margin;
+        let min_y = points.iter().map(|p| p.y).min().unwrap() - margin;
+        let max_x = points.iter().map(|p| p.x + 1).max().unwrap() + margin;
+        let max_y = points.iter().map(|p| p.y + 1).max().unwrap() + margin;
+        // Cull the tilemap using the min and max coordinates along each axis of the tilemap
+        Region::new(
+            Point3::new(min_x.max(0).min(x) as u32, min_y.max(0).min(y) as u32, 0),
+            Point3::new(
+                max_x.max(0).min(x) as u32,
+                max_y.max(0).min(y) as u32,
+                map.dimensions().z,
+            ),
+        )
+    }
+
+    fn chunked_bounds<T: Tile, E: CoordinateEncoder>(
+        map: &ChunkedTileMap<T, E>,
+        map_transform: Option<&Transform>,
+        aux: &GraphAuxData,
+    ) -> Option<(Point3<i32>, Point3<i32>)> {
+        let rays = match camera_corner_rays(aux) {
+            Some(rays) => rays,
+            None => return Some((Point3::new(0, 0, 0), Point3::new(0, 0, 0))),
+        };
+        let tile_plane = tile_plane(map_transform);
+        let points = rays
+            .iter()
+            .map(|ray| {
+                let distance = ray.intersect_plane(&tile_plane).unwrap_or(0.0);
+                map.world_to_tile(&ray.at_distance(distance).coords, map_transform)
+            })
+            .collect::<Vec<_>>();
+        Some((
+            Point3::new(
+                points.iter().map(|p| p.x).min().unwrap(),
+                points.iter().map(|p| p.y).min().unwrap(),
+                i32::MIN,
+            ),
+            Point3::new(
+                points.iter().map(|p| p.x.saturating_add(1)).max().unwrap(),
+                points.iter().map(|p| p.y.saturating_add(1)).max().unwrap(),
+                i32::MAX,
+            ),
+        ))
     }
 }
 
@@ -255,12 +293,19 @@ impl<B: Backend, T: Tile, E: CoordinateEncoder, Z: DrawTiles2DBounds>
             vertex,
             env: vec![env],
             sprites: Default::default(),
+            caches: HashMap::new(),
             _marker: PhantomData::default(),
             change: Default::default(),
         }))
     }
 }
 
+/// Marks the tiles of the `TileMap` of its entity as dynamic: their `Tile::sprite` or `Tile::tint`
+/// depend on other components or resources, such as tiles tinted by a `Visibility`, so that
+/// `DrawTiles2D` fetches them again every frame instead of keeping them between frames.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DynamicTiles;
+
 /// `RenderGroup` providing culling, drawing and transparency functionality for 2D `TileMap` components.
 ///
 /// Notes on use:
@@ -268,6 +313,12 @@ impl<B: Backend, T: Tile, E: CoordinateEncoder, Z: DrawTiles2DBounds>
 /// for  transparency to occur correctly. If viewed from "underneath", transparency ordering issues will occur.
 ///
 /// In shorter terms, this means that the camera must "Look Down" at the tiles.
+///
+/// The tiles of a `TileMap` are kept between frames, and only the tiles in the changes of the map
+/// are fetched again, unless its drawn region, sprite sheet or `TileAnimations` change. Tiles with
+/// an animation in the `TileAnimations` of their entity are advanced with `Time` in between,
+/// updating only the tiles whose frame changed. The tiles of maps with `DynamicTiles` are fetched
+/// every frame.
 #[derive(Derivative)]
 #[derivative(Debug(bound = ""))]
 pub struct DrawTiles2D<
@@ -285,6 +336,9 @@ pub struct DrawTiles2D<
 
     env: Vec<DynamicUniform<B, TileMapArgs>>,
 
+    #[derivative(Debug = "ignore")]
+    caches: HashMap<Entity, TileMapCache>,
+
     #[derivative(Debug = "ignore")]
     _marker: PhantomData<(T, E, Z)>,
 }
@@ -292,7 +346,7 @@ pub struct DrawTiles2D<
 impl<B: Backend, T: Tile, E: CoordinateEncoder, Z: DrawTiles2DBounds> RenderGroup<B, GraphAuxData>
     for DrawTiles2D<B, T, E, Z>
 {
-    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_wrap)]
     fn prepare(
         &mut self,
         factory: &Factory<B>,
@@ -327,72 +381,200 @@ impl<B: Backend, T: Tile, E: CoordinateEncoder, Z: DrawTiles2DBounds> RenderGrou
 
         let mut tilemap_args = vec![];
 
-        let mut query =
-            <(&TileMap<T, E>, TryRead<Transform>)>::query().filter(!component::<Hidden>());
+        let time = aux
+            .resources
+            .get::<Time>()
+            .map_or(0.0, |time| time.absolute_time().as_secs_f64());
+        let caches = &mut self.caches;
+        for cache in caches.values_mut() {
+            cache.drawn = false;
+        }
 
-        for (tile_map, transform) in query.iter(aux.world) {
-            if let Some(sheet) = tile_map
+        let mut query = <(
+            Entity,
+            &TileMap<T, E>,
+            TryRead<Transform>,
+            TryRead<TileAnimations>,
+            TryRead<DynamicTiles>,
+        )>::query()
+        .filter(!component::<Hidden>());
+
+        for (entity, tile_map, transform, animations, dynamic_tiles) in query.iter(aux.world) {
+            let sheet = match tile_map
                 .sprite_sheet
                 .as_ref()
                 .and_then(|handle| sprite_sheet_storage.get(handle))
             {
-                if let Some(sprites) = sprites_storage.get(&sheet.sprites) {
-                    let sprites = sprites.build_sprites();
-
-                    let tilemap_args_index = tilemap_args.len();
-                    let map_coordinate_transform: [[f32; 4]; 4] = (*tile_map.transform()).into();
-                    let map_transform: [[f32; 4]; 4] = transform.map_or_else(
-                        || Matrix4::identity().into(),
-                        |transform| (*transform.global_matrix()).into(),
-                    );
-
-                    tilemap_args.push(TileMapArgs {
-                        proj: projview.proj,
-                        view: projview.view,
-                        map_coordinate_transform: map_coordinate_transform.into(),
-                        map_transform: map_transform.into(),
-                        sprite_dimensions: [
-                            tile_map.tile_dimensions().x as f32,
-                            tile_map.tile_dimensions().y as f32,
-                        ]
-                        .into(),
-                    });
+                Some(sheet) => sheet,
+                None => continue,
+            };
+            let sheet_sprites = match sprites_storage.get(&sheet.sprites) {
+                Some(sprites) => sprites,
+                None => continue,
+            };
+            let (tex_id, this_changed) = match textures_ref.insert(
+                factory,
+                aux.resources,
+                &sheet.texture,
+                hal::image::Layout::ShaderReadOnlyOptimal,
+            ) {
+                Some(texture) => texture,
+                None => continue,
+            };
+            changed = changed || this_changed;
+
+            let tilemap_args_index = tilemap_args.len();
+            let map_coordinate_transform: [[f32; 4]; 4] = Projection::draw_transform(
+                tile_map.transform(),
+                &tile_map.projection().bounds(tile_map.dimensions()).0,
+            )
+            .into();
+            let map_transform: [[f32; 4]; 4] = transform.map_or_else(
+                || Matrix4::identity().into(),
+                |transform| (*transform.global_matrix()).into(),
+            );
 
-                    compute_region::<T, E, Z>(tile_map, transform, aux)
-                        .iter()
-                        .filter_map(|coord| {
-                            let tile = tile_map.get(&coord).unwrap();
-                            if let Some(sprite_number) =
-                                tile.sprite(coord, aux.world, aux.resources)
-                            {
-                                let batch_data = TileArgs::from_data(
-                                    &sprites,
-                                    sprite_number,
-                                    Some(&TintComponent(tile.tint(
-                                        coord,
-                                        aux.world,
-                                        aux.resources,
-                                    ))),
-                                    &coord,
-                                );
-
-                                let (tex_id, this_changed) = textures_ref.insert(
-                                    factory,
-                                    aux.resources,
-                                    &sheet.texture,
-                                    hal::image::Layout::ShaderReadOnlyOptimal,
-                                )?;
-                                changed = changed || this_changed;
-
-                                return Some((tex_id, batch_data));
-                            }
-                            None
-                        })
-                        .for_each_group(|tex_id, batch_data| {
-                            sprites_ref.insert(tex_id, tilemap_args_index, batch_data.drain(..));
-                        });
+            tilemap_args.push(TileMapArgs {
+                proj: projview.proj,
+                view: projview.view,
+                map_coordinate_transform: map_coordinate_transform.into(),
+                map_transform: map_transform.into(),
+                sprite_dimensions: [
+                    tile_map.tile_dimensions().x as f32,
+                    tile_map.tile_dimensions().y as f32,
+                ]
+                .into(),
+            });
+
+            // The tiles are only fetched again when they changed, or when the drawn region changed,
+            // the frames of animated tiles being patched in place otherwise
+            let region = compute_region::<T, E, Z>(tile_map, transform, aux);
+            let sheet_handle = tile_map.sprite_sheet.as_ref().map(AssetHandle::load_handle);
+            let cache = caches.entry(*entity).or_default();
+            cache.drawn = true;
+            let mut sprites = None;
+            if dynamic_tiles.is_some()
+                || cache.region != Some(region)
+                || cache.sprite_sheet != sheet_handle
+                || cache.animations != animations.map(TileAnimations::version)
+                || (cache.version != tile_map.version()
+                    && !cache.update(
+                        tile_map,
+                        animations,
+                        sprites.get_or_insert_with(|| sheet_sprites.build_sprites()),
+                        aux,
+                    ))
+            {
+                let sprites = sprites.get_or_insert_with(|| sheet_sprites.build_sprites());
+                cache.rebuild(tile_map, region, animations, time, sprites, aux);
+                cache.sprite_sheet = sheet_handle;
+            } else if let Some(animations) = animations {
+                for tile in &mut cache.animated {
+                    let frame = animations.sprite(tile.sprite, time, &tile.coord);
+                    if frame != tile.frame {
+                        let sprites = sprites.get_or_insert_with(|| sheet_sprites.build_sprites());
+                        cache.tiles[tile.index].set_sprite(sprites, frame);
+                        tile.frame = frame;
+                    }
                 }
             }
+
+            sprites_ref.insert(tex_id, tilemap_args_index, cache.tiles.iter().copied());
+        }
+
+        caches.retain(|_, cache| cache.drawn);
+
+        let mut chunked_query = <(
+            &ChunkedTileMap<T, E>,
+            TryRead<Transform>,
+            TryRead<TileAnimations>,
+        )>::query()
+        .filter(!component::<Hidden>());
+
+        for (tile_map, transform, animations) in chunked_query.iter(aux.world) {
+            let sheet = match tile_map
+                .sprite_sheet
+                .as_ref()
+                .and_then(|handle| sprite_sheet_storage.get(handle))
+            {
+                Some(sheet) => sheet,
+                None => continue,
+            };
+            let sprites = match sprites_storage.get(&sheet.sprites) {
+                Some(sprites) => sprites.build_sprites(),
+                None => continue,
+            };
+            let map_transform: [[f32; 4]; 4] = transform.map_or_else(
+                || Matrix4::identity().into(),
+                |transform| (*transform.global_matrix()).into(),
+            );
+            let bounds = Z::chunked_bounds(tile_map, transform, aux);
+
+            // Each resident chunk within the bounds is drawn as a tile map of its own
+            for (chunk, tiles) in tile_map.resident_chunks() {
+                let region = match tile_map.chunk_region(chunk, bounds) {
+                    Some(region) => region,
+                    None => continue,
+                };
+                let origin = tile_map.chunk_origin(chunk);
+
+                let tilemap_args_index = tilemap_args.len();
+                let map_coordinate_transform: [[f32; 4]; 4] =
+                    tile_map.chunk_transform(chunk).into();
+                tilemap_args.push(TileMapArgs {
+                    proj: projview.proj,
+                    view: projview.view,
+                    map_coordinate_transform: map_coordinate_transform.into(),
+                    map_transform: map_transform.into(),
+                    sprite_dimensions: [
+                        tile_map.tile_dimensions.x as f32,
+                        tile_map.tile_dimensions.y as f32,
+                    ]
+                    .into(),
+                });
+
+                region
+                    .iter()
+                    .filter_map(|coord| {
+                        let tile = tiles.get(&coord)?;
+                        // Tiles are given their coordinates in the `Map` of the chunked map
+                        let map_coord = ChunkedTileMap::<T, E>::to_unsigned(&Point3::new(
+                            origin.x + coord.x as i32,
+                            origin.y + coord.y as i32,
+                            origin.z + coord.z as i32,
+                        ));
+                        let mut sprite_number = tile.sprite(map_coord, aux.world, aux.resources)?;
+                        if let Some(animations) = animations {
+                            sprite_number = animations.sprite(sprite_number, time, &map_coord);
+                        }
+                        if sprite_number >= sprites.len() {
+                            return None;
+                        }
+                        let batch_data = TileArgs::from_data(
+                            &sprites,
+                            sprite_number,
+                            Some(&TintComponent(tile.tint(
+                                map_coord,
+                                aux.world,
+                                aux.resources,
+                            ))),
+                            &coord,
+                        );
+
+                        let (tex_id, this_changed) = textures_ref.insert(
+                            factory,
+                            aux.resources,
+                            &sheet.texture,
+                            hal::image::Layout::ShaderReadOnlyOptimal,
+                        )?;
+                        changed = changed || this_changed;
+
+                        Some((tex_id, batch_data))
+                    })
+                    .for_each_group(|tex_id, batch_data| {
+                        sprites_ref.insert(tex_id, tilemap_args_index, batch_data.drain(..));
+                    });
+            }
         }
 
         self.textures.maintain(factory, aux.resources);
@@ -461,6 +643,143 @@ impl<B: Backend, T: Tile, E: CoordinateEncoder, Z: DrawTiles2DBounds> RenderGrou
     }
 }
 
+/// The tiles of a `TileMap` drawn in the last frame, kept until its drawn region changes, the
+/// tiles in the changes of the map being fetched again.
+#[derive(Debug, Default)]
+struct TileMapCache {
+    version: u64,
+    region: Option<Region>,
+    sprite_sheet: Option<LoadHandle>,
+    /// The version of the `TileAnimations` of the map.
+    animations: Option<u64>,
+    tiles: Vec<TileArgs>,
+    /// The indices of the drawn tiles in `tiles`, by coordinates.
+    indices: FnvHashMap<Point3<u32>, usize>,
+    /// The animated tiles, by increasing index.
+    animated: Vec<AnimatedTile>,
+    drawn: bool,
+}
+
+/// A tile of a `TileMapCache` with an animation.
+#[derive(Debug)]
+struct AnimatedTile {
+    /// The index of the tile in the tiles of the cache.
+    index: usize,
+    /// The sprite of the tile, which is animated.
+    sprite: usize,
+    /// The sprite of the drawn frame.
+    frame: usize,
+    coord: Point3<u32>,
+}
+
+impl TileMapCache {
+    /// Fetches the tiles of a region of a map.
+    fn rebuild<T: Tile, E: CoordinateEncoder>(
+        &mut self,
+        tile_map: &TileMap<T, E>,
+        region: Region,
+        animations: Option<&TileAnimations>,
+        time: f64,
+        sprites: &[Sprite],
+        aux: &GraphAuxData,
+    ) {
+        self.version = tile_map.version();
+        self.region = Some(region);
+        self.animations = animations.map(TileAnimations::version);
+        self.tiles.clear();
+        self.indices.clear();
+        self.animated.clear();
+
+        // Tiles are drawn at their center on the plane of the map, in subdivisions of tiles from
+        // the minimum center.
+        let projection = tile_map.projection();
+        let (plane_min, _) = projection.bounds(tile_map.dimensions());
+        let mut coords = region.iter().collect::<Vec<_>>();
+        // Draw overlapping tiles from the back, the region being ordered by rows already for
+        // orthogonal maps
+        if *projection != Projection::Orthogonal {
+            coords.sort_by_cached_key(|coord| projection.draw_order(coord));
+        }
+
+        for coord in coords {
+            let tile = tile_map.get(&coord).unwrap();
+            let sprite = match tile.sprite(coord, aux.world, aux.resources) {
+                Some(sprite) if sprite < sprites.len() => sprite,
+                _ => continue,
+            };
+            let mut frame = sprite;
+            if let Some(animations) = animations.filter(|a| a.get(sprite).is_some()) {
+                frame = animations.sprite(sprite, time, &coord);
+                self.animated.push(AnimatedTile {
+                    index: self.tiles.len(),
+                    sprite,
+                    frame,
+                    coord,
+                });
+            }
+            self.indices.insert(coord, self.tiles.len());
+            self.tiles.push(TileArgs::from_data(
+                sprites,
+                sprite,
+                Some(&TintComponent(tile.tint(coord, aux.world, aux.resources))),
+                &projection.draw_coordinate(&coord, &plane_min),
+            ));
+            // Frames missing from the sprite sheet keep the sprite of the tile
+            self.tiles.last_mut().unwrap().set_sprite(sprites, frame);
+        }
+    }
+
+    /// Fetches again the tiles of the region of the cache in the changes of a map since the cache
+    /// was updated. Returns `false` if every tile must be fetched again instead, as changes were
+    /// drained, or a tile was shown, hidden or animated.
+    fn update<T: Tile, E: CoordinateEncoder>(
+        &mut self,
+        tile_map: &TileMap<T, E>,
+        animations: Option<&TileAnimations>,
+        sprites: &[Sprite],
+        aux: &GraphAuxData,
+    ) -> bool {
+        let (region, changed) = match (self.region, tile_map.changes().since(self.version)) {
+            (Some(region), Some(changed)) => (region, changed),
+            _ => return false,
+        };
+        let projection = tile_map.projection();
+        let (plane_min, _) = projection.bounds(tile_map.dimensions());
+        for coord in changed.filter(|coord| region.contains(coord)) {
+            let tile = match tile_map.get(coord) {
+                Some(tile) => tile,
+                None => continue,
+            };
+            let sprite = tile
+                .sprite(*coord, aux.world, aux.resources)
+                .filter(|sprite| *sprite < sprites.len());
+            let index = match (self.indices.get(coord), sprite) {
+                (Some(index), Some(sprite)) => {
+                    if animations.map_or(false, |a| a.get(sprite).is_some())
+                        || self
+                            .animated
+                            .binary_search_by_key(index, |tile| tile.index)
+                            .is_ok()
+                    {
+                        return false;
+                    }
+                    *index
+                }
+                (None, None) => continue,
+                _ => return false,
+            };
+            self.tiles[index] = TileArgs::from_data(
+                sprites,
+                sprite.unwrap(),
+                Some(&TintComponent(tile.tint(*coord, aux.world, aux.resources))),
+                &projection.draw_coordinate(coord, &plane_min),
+            );
+        }
+        self.version = tile_map.version();
+        true
+    }
+}
+
 fn compute_region<T: Tile, E: CoordinateEncoder, Z: DrawTiles2DBounds>(
     tile_map: &TileMap<T, E>,
     map_transform: Option<&Transform>,
@@ -0,0 +1,666 @@
+//! Pathfinding over the tiles of maps: A* paths between two tiles, Dijkstra maps of the cost of
+//! reaching the nearest of several sources, and flow fields leading many agents to goals.
+//!
+//! Searches take a cost function returning the cost of entering a tile, or `None` if the tile can
+//! not be entered, and move between the tiles of a z-level with a `Connectivity`. The cost
+//! function may be called several times for the same tile.
+//!
+//! Dijkstra maps and flow fields cover whole maps, so they store their tiles in the order of a
+//! `CoordinateEncoder`, by default `MortonEncoder2D`, which keeps the tiles of an area close in
+//! memory as searches spread over it. A* stores the tiles it reaches by their index in the map.
+
+use std::{cmp::Ordering, collections::BinaryHeap, f32::consts::SQRT_2, marker::PhantomData};
+
+use amethyst_core::math::{self, Point3, Vector3};
+use derivative::Derivative;
+
+use crate::{
+    CoordinateEncoder, Map, MapStorage, MapTooLargeError, MortonEncoder2D, Projection, Tile,
+};
+
+/// The index of no tile in the next tiles of a `FlowField`, and in the tiles reached by `astar`.
+const NO_TILE: u32 = u32::MAX;
+
+/// The offsets of the neighbors of a tile along the x and y axes, with the length of the steps to
+/// them.
+const FOUR_NEIGHBORS: [(i64, i64, f32); 4] = [(1, 0, 1.0), (0, 1, 1.0), (-1, 0, 1.0), (0, -1, 1.0)];
+const EIGHT_NEIGHBORS: [(i64, i64, f32); 8] = [
+    (1, 0, 1.0),
+    (0, 1, 1.0),
+    (-1, 0, 1.0),
+    (0, -1, 1.0),
+    (1, 1, SQRT_2),
+    (-1, 1, SQRT_2),
+    (-1, -1, SQRT_2),
+    (1, -1, SQRT_2),
+];
+
+/// The tiles which can be moved to from a tile, on its z-level.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Connectivity {
+    /// The 4 tiles sharing an edge with a tile along the x and y axes.
+    Four,
+    /// The 8 tiles around a tile along the x and y axes. Diagonal steps cost `SQRT_2` times the
+    /// cost of the tile entered, and are only taken when both tiles next to them can be entered.
+    Eight,
+    /// The tiles sharing an edge with a tile in a `Projection`, such as the 6 neighbors of
+    /// hexagonal tiles.
+    Projection(Projection),
+}
+
+impl Default for Connectivity {
+    fn default() -> Self {
+        Connectivity::Four
+    }
+}
+
+impl Connectivity {
+    /// Replaces `neighbors` with the neighbors of a tile within `dimensions`, with the length of
+    /// the steps to them.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn neighbors(
+        &self,
+        coord: &Point3<u32>,
+        dimensions: &Vector3<u32>,
+        neighbors: &mut Vec<(Point3<u32>, f32)>,
+    ) {
+        neighbors.clear();
+        let offsets: &[(i64, i64, f32)] = match self {
+            Connectivity::Four => &FOUR_NEIGHBORS,
+            Connectivity::Eight => &EIGHT_NEIGHBORS,
+            Connectivity::Projection(projection) => {
+                neighbors.extend(
+                    projection
+                        .neighbors(coord)
+                        .filter(|neighbor| contains(dimensions, neighbor))
+                        .map(|neighbor| (neighbor, 1.0)),
+                );
+                return;
+            }
+        };
+        for &(x, y, step) in offsets {
+            let x = i64::from(coord.x) + x;
+            let y = i64::from(coord.y) + y;
+            if x >= 0 && y >= 0 && x < i64::from(dimensions.x) && y < i64::from(dimensions.y) {
+                neighbors.push((Point3::new(x as u32, y as u32, coord.z), step));
+            }
+        }
+    }
+
+    /// Returns the two tiles next to a diagonal step, which must both be entered for the step to
+    /// be taken.
+    fn sides(&self, from: &Point3<u32>, to: &Point3<u32>) -> Option<[Point3<u32>; 2]> {
+        if *self == Connectivity::Eight && from.x != to.x && from.y != to.y {
+            Some([
+                Point3::new(to.x, from.y, from.z),
+                Point3::new(from.x, to.y, from.z),
+            ])
+        } else {
+            None
+        }
+    }
+
+    /// Returns the least number of steps between two tiles of a z-level.
+    #[allow(clippy::cast_precision_loss)]
+    fn heuristic(&self, from: &Point3<u32>, to: &Point3<u32>) -> f32 {
+        let x = (i64::from(from.x) - i64::from(to.x)).abs() as f32;
+        let y = (i64::from(from.y) - i64::from(to.y)).abs() as f32;
+        match self {
+            Connectivity::Four => x + y,
+            Connectivity::Eight => x.max(y) + (SQRT_2 - 1.0) * x.min(y),
+            Connectivity::Projection(projection) => {
+                math::distance(&projection.project(from), &projection.project(to))
+                    / projection.neighbor_distance()
+            }
+        }
+    }
+}
+
+/// Returns whether a tile is within the dimensions of a map.
+fn contains(dimensions: &Vector3<u32>, coord: &Point3<u32>) -> bool {
+    coord.x < dimensions.x && coord.y < dimensions.y && coord.z < dimensions.z
+}
+
+/// A tile to search from, ordered by lowest priority first.
+#[derive(Clone, Copy, Debug)]
+struct Open {
+    priority: f32,
+    cost: f32,
+    coord: Point3<u32>,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Among tiles of the same priority, those which cost the most are the closest to the goal
+        other
+            .priority
+            .partial_cmp(&self.priority)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| {
+                self.cost
+                    .partial_cmp(&other.cost)
+                    .unwrap_or(Ordering::Equal)
+            })
+    }
+}
+
+/// The tiles of a map with the cost function of a search.
+struct Costs<'a, T, M, F> {
+    map: &'a M,
+    cost: F,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T, M, F> Costs<'a, T, M, F>
+where
+    T: Tile,
+    M: Map + MapStorage<T>,
+    F: FnMut(&Point3<u32>, &T) -> Option<f32>,
+{
+    fn new(map: &'a M, cost: F) -> Self {
+        Self {
+            map,
+            cost,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the cost of entering a tile, if it is in the map and can be entered.
+    fn cost(&mut self, coord: &Point3<u32>) -> Option<f32> {
+        if !contains(self.map.dimensions(), coord) {
+            return None;
+        }
+        let tile = self.map.get(coord)?;
+        (self.cost)(coord, tile).filter(|cost| *cost >= 0.0)
+    }
+
+    /// Replaces `steps` with the neighbors of a tile which can be stepped to, without checking
+    /// whether they can be entered, with the length of the steps to them.
+    fn steps(
+        &mut self,
+        connectivity: Connectivity,
+        coord: &Point3<u32>,
+        steps: &mut Vec<(Point3<u32>, f32)>,
+    ) {
+        connectivity.neighbors(coord, self.map.dimensions(), steps);
+        steps.retain(|(neighbor, _)| {
+            connectivity.sides(coord, neighbor).map_or(true, |sides| {
+                sides.iter().all(|side| self.cost(side).is_some())
+            })
+        });
+    }
+}
+
+/// A path between two tiles.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+    /// The tiles of the path, from the start to the goal, both included.
+    pub tiles: Vec<Point3<u32>>,
+    /// The sum of the costs of the tiles entered along the path.
+    pub cost: f32,
+}
+
+/// Finds the cheapest path between two tiles of a z-level of a map with A*, where `cost` returns
+/// the cost of entering a tile, or `None` if it can not be entered. Returns `None` if there is no
+/// path.
+///
+/// Costs are expected to be at least 1, as a lower cost may lead A* to a path which is not the
+/// cheapest one.
+pub fn astar<T, M, F>(
+    map: &M,
+    connectivity: Connectivity,
+    start: &Point3<u32>,
+    goal: &Point3<u32>,
+    cost: F,
+) -> Option<Path>
+where
+    T: Tile,
+    M: Map + MapStorage<T>,
+    F: FnMut(&Point3<u32>, &T) -> Option<f32>,
+{
+    let mut costs = Costs::new(map, cost);
+    if start.z != goal.z || !contains(map.dimensions(), start) || costs.cost(goal).is_none() {
+        return None;
+    }
+    let start_index = map.encode(start)?;
+
+    // The cost of reaching each tile found, with the index of the tile it is reached from, by the
+    // index of the tile in the map. It grows as tiles are found, the indices of a `ChunkedTileMap`
+    // only spanning its resident chunks.
+    let mut reached = Vec::new();
+    reach(&mut reached, start_index, 0.0, NO_TILE);
+    let mut open = BinaryHeap::new();
+    open.push(Open {
+        priority: connectivity.heuristic(start, goal),
+        cost: 0.0,
+        coord: *start,
+    });
+    let mut steps = Vec::new();
+
+    while let Some(Open { cost, coord, .. }) = open.pop() {
+        let index = map.encode(&coord)?;
+        if coord == *goal {
+            let mut tiles = vec![coord];
+            let mut previous = reached[index as usize].1;
+            while previous != NO_TILE {
+                tiles.push(map.decode(previous)?);
+                previous = reached[previous as usize].1;
+            }
+            tiles.reverse();
+            return Some(Path { tiles, cost });
+        }
+        if cost > reached_cost(&reached, index) {
+            continue;
+        }
+
+        costs.steps(connectivity, &coord, &mut steps);
+        for &(neighbor, step) in &steps {
+            let neighbor_cost = match costs.cost(&neighbor) {
+                Some(neighbor_cost) => cost + neighbor_cost * step,
+                None => continue,
+            };
+            let neighbor_index = match map.encode(&neighbor) {
+                Some(neighbor_index) => neighbor_index,
+                None => continue,
+            };
+            if neighbor_cost < reached_cost(&reached, neighbor_index) {
+                reach(&mut reached, neighbor_index, neighbor_cost, index);
+                open.push(Open {
+                    priority: neighbor_cost + connectivity.heuristic(&neighbor, goal),
+                    cost: neighbor_cost,
+                    coord: neighbor,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Records the cost of reaching a tile found by `astar`, with the index of the tile it is reached
+/// from.
+fn reach(reached: &mut Vec<(f32, u32)>, index: u32, cost: f32, previous: u32) {
+    let index = index as usize;
+    if index >= reached.len() {
+        reached.resize(index + 1, (f32::INFINITY, NO_TILE));
+    }
+    reached[index] = (cost, previous);
+}
+
+/// Returns the cost of reaching a tile found by `astar`, or infinity if it was not found.
+fn reached_cost(reached: &[(f32, u32)], index: u32) -> f32 {
+    reached
+        .get(index as usize)
+        .map_or(f32::INFINITY, |(cost, _)| *cost)
+}
+
+/// Returns the cost of reaching the nearest source from every tile of a map, in the order of
+/// `encoder`, filling `next` with the index of the next tile towards it when given.
+#[allow(clippy::cast_possible_truncation)]
+fn dijkstra<T, M, F, E>(
+    map: &M,
+    connectivity: Connectivity,
+    sources: impl IntoIterator<Item = Point3<u32>>,
+    cost: F,
+    encoder: &E,
+    mut next: Option<&mut Vec<u32>>,
+) -> Result<Vec<f32>, MapTooLargeError>
+where
+    T: Tile,
+    M: Map + MapStorage<T>,
+    F: FnMut(&Point3<u32>, &T) -> Option<f32>,
+    E: CoordinateEncoder,
+{
+    MapTooLargeError::check(*map.dimensions())?;
+    let mut distances = vec![f32::INFINITY; E::allocation_size(*map.dimensions())];
+    if let Some(next) = next.as_mut() {
+        next.clear();
+        next.resize(distances.len(), NO_TILE);
+    }
+    let len = distances.len();
+    let index = |coord: &Point3<u32>| {
+        encoder
+            .encode(coord.x, coord.y, coord.z)
+            .map(|index| index as usize)
+            .filter(|index| *index < len)
+    };
+
+    let mut costs = Costs::new(map, cost);
+    let mut open = BinaryHeap::new();
+    for source in sources {
+        if costs.cost(&source).is_none() {
+            continue;
+        }
+        if let Some(index) = index(&source) {
+            distances[index] = 0.0;
+            open.push(Open {
+                priority: 0.0,
+                cost: 0.0,
+                coord: source,
+            });
+        }
+    }
+    let mut steps = Vec::new();
+
+    while let Some(Open { cost, coord, .. }) = open.pop() {
+        let tile = match index(&coord) {
+            Some(tile) if cost <= distances[tile] => tile,
+            _ => continue,
+        };
+        // Searching from the sources, the step from a neighbor to this tile enters this tile
+        let tile_cost = match costs.cost(&coord) {
+            Some(tile_cost) => tile_cost,
+            None => continue,
+        };
+        costs.steps(connectivity, &coord, &mut steps);
+        for &(neighbor, step) in &steps {
+            if costs.cost(&neighbor).is_none() {
+                continue;
+            }
+            let neighbor_index = match index(&neighbor) {
+                Some(neighbor_index) => neighbor_index,
+                None => continue,
+            };
+            let neighbor_cost = cost + tile_cost * step;
+            if neighbor_cost < distances[neighbor_index] {
+                distances[neighbor_index] = neighbor_cost;
+                if let Some(next) = next.as_mut() {
+                    next[neighbor_index] = tile as u32;
+                }
+                open.push(Open {
+                    priority: neighbor_cost,
+                    cost: neighbor_cost,
+                    coord: neighbor,
+                });
+            }
+        }
+    }
+    Ok(distances)
+}
+
+/// The cost of reaching the nearest of several sources from every tile of a map, such as the
+/// distance of monsters to the player or of the player to the exits of a level.
+///
+/// Agents move towards the sources by rolling `downhill`, and away from them by stepping to the
+/// neighbor with the greatest `distance`.
+#[derive(Clone, Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct DijkstraMap<E: CoordinateEncoder = MortonEncoder2D> {
+    connectivity: Connectivity,
+    dimensions: Vector3<u32>,
+    distances: Vec<f32>,
+    #[derivative(Debug = "ignore")]
+    encoder: E,
+}
+
+impl<E: CoordinateEncoder> DijkstraMap<E> {
+    /// Computes the cost of reaching the nearest of `sources` from every tile of a map, where
+    /// `cost` returns the cost of entering a tile, or `None` if it can not be entered.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MapTooLargeError` if the map has more than `MapTooLargeError::MAX_TILES` tiles,
+    /// such as a `ChunkedTileMap`.
+    pub fn new<T, M, F>(
+        map: &M,
+        connectivity: Connectivity,
+        sources: impl IntoIterator<Item = Point3<u32>>,
+        cost: F,
+    ) -> Result<Self, MapTooLargeError>
+    where
+        T: Tile,
+        M: Map + MapStorage<T>,
+        F: FnMut(&Point3<u32>, &T) -> Option<f32>,
+    {
+        let dimensions = *map.dimensions();
+        let encoder = E::from_dimensions(dimensions);
+        let distances = dijkstra(map, connectivity, sources, cost, &encoder, None)?;
+        Ok(Self {
+            connectivity,
+            dimensions,
+            distances,
+            encoder,
+        })
+    }
+
+    /// The dimensions of the map.
+    #[must_use]
+    pub fn dimensions(&self) -> &Vector3<u32> {
+        &self.dimensions
+    }
+
+    /// Returns the cost of reaching the nearest source from a tile, or `None` if no source can be
+    /// reached from it.
+    #[must_use]
+    pub fn distance(&self, coord: &Point3<u32>) -> Option<f32> {
+        if !contains(&self.dimensions, coord) {
+            return None;
+        }
+        self.encoder
+            .encode(coord.x, coord.y, coord.z)
+            .and_then(|index| self.distances.get(index as usize))
+            .copied()
+            .filter(|distance| distance.is_finite())
+    }
+
+    /// Returns the neighbor of a tile with the lowest distance, if it is lower than the distance
+    /// of the tile.
+    #[must_use]
+    pub fn downhill(&self, coord: &Point3<u32>) -> Option<Point3<u32>> {
+        let mut lowest = self.distance(coord)?;
+        let mut downhill = None;
+        let mut neighbors = Vec::new();
+        self.connectivity
+            .neighbors(coord, &self.dimensions, &mut neighbors);
+        for (neighbor, _) in neighbors {
+            let corner_blocked = self
+                .connectivity
+                .sides(coord, &neighbor)
+                .map_or(false, |sides| {
+                    sides.iter().any(|side| self.distance(side).is_none())
+                });
+            match self.distance(&neighbor) {
+                Some(distance) if distance < lowest && !corner_blocked => {
+                    lowest = distance;
+                    downhill = Some(neighbor);
+                }
+                _ => {}
+            }
+        }
+        downhill
+    }
+}
+
+/// The next tile towards the nearest of several goals from every tile of a map, leading any
+/// number of agents to the goals without searching a path for each of them.
+#[derive(Clone, Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct FlowField<E: CoordinateEncoder = MortonEncoder2D> {
+    dimensions: Vector3<u32>,
+    next: Vec<u32>,
+    #[derivative(Debug = "ignore")]
+    encoder: E,
+}
+
+impl<E: CoordinateEncoder> FlowField<E> {
+    /// Computes the cheapest way to the nearest of `goals` from every tile of a map, where `cost`
+    /// returns the cost of entering a tile, or `None` if it can not be entered.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MapTooLargeError` if the map has more than `MapTooLargeError::MAX_TILES` tiles,
+    /// such as a `ChunkedTileMap`.
+    pub fn new<T, M, F>(
+        map: &M,
+        connectivity: Connectivity,
+        goals: impl IntoIterator<Item = Point3<u32>>,
+        cost: F,
+    ) -> Result<Self, MapTooLargeError>
+    where
+        T: Tile,
+        M: Map + MapStorage<T>,
+        F: FnMut(&Point3<u32>, &T) -> Option<f32>,
+    {
+        let dimensions = *map.dimensions();
+        let encoder = E::from_dimensions(dimensions);
+        let mut next = Vec::new();
+        dijkstra(map, connectivity, goals, cost, &encoder, Some(&mut next))?;
+        Ok(Self {
+            dimensions,
+            next,
+            encoder,
+        })
+    }
+
+    /// The dimensions of the map.
+    #[must_use]
+    pub fn dimensions(&self) -> &Vector3<u32> {
+        &self.dimensions
+    }
+
+    /// Returns the tile to move to from a tile towards the nearest goal, or `None` if the tile is
+    /// a goal or no goal can be reached from it.
+    #[must_use]
+    pub fn next(&self, coord: &Point3<u32>) -> Option<Point3<u32>> {
+        if !contains(&self.dimensions, coord) {
+            return None;
+        }
+        let index = self.encoder.encode(coord.x, coord.y, coord.z)?;
+        let next = *self.next.get(index as usize)?;
+        if next == NO_TILE {
+            return None;
+        }
+        let (x, y, z) = self.encoder.decode(next)?;
+        Some(Point3::new(x, y, z))
+    }
+
+    /// Returns the tiles leading from a tile to the nearest goal, without the tile itself.
+    pub fn path(&self, coord: &Point3<u32>) -> impl Iterator<Item = Point3<u32>> + '_ {
+        std::iter::successors(self.next(coord), move |coord| self.next(coord))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FlatEncoder, HexLayout, HexOrientation, StaggerIndex, TileMap};
+
+    #[derive(Clone, Copy, Debug, Default)]
+    struct Cell {
+        wall: bool,
+    }
+
+    impl Tile for Cell {}
+
+    /// A map of 5 by 3 tiles with a wall in the top two rows of the middle column.
+    fn walled_map() -> TileMap<Cell, FlatEncoder> {
+        let mut map = TileMap::new(Vector3::new(5, 3, 1), Vector3::new(16, 16, 1), None);
+        for y in 0..2 {
+            map.get_mut(&Point3::new(2, y, 0)).unwrap().wall = true;
+        }
+        map
+    }
+
+    fn cost(_: &Point3<u32>, cell: &Cell) -> Option<f32> {
+        if cell.wall {
+            None
+        } else {
+            Some(1.0)
+        }
+    }
+
+    #[test]
+    fn finds_paths_around_walls() {
+        let map = walled_map();
+        let start = Point3::new(0, 0, 0);
+        let goal = Point3::new(4, 0, 0);
+
+        let path = astar(&map, Connectivity::Four, &start, &goal, cost).unwrap();
+        assert!((path.cost - 8.0).abs() < f32::EPSILON);
+        assert_eq!(path.tiles.len(), 9);
+        assert_eq!((path.tiles[0], path.tiles[8]), (start, goal));
+
+        // Diagonal steps do not cut the corners of the wall
+        let path = astar(&map, Connectivity::Eight, &start, &goal, cost).unwrap();
+        assert!((path.cost - (4.0 + 2.0 * SQRT_2)).abs() < 1e-5);
+        assert!(path.tiles.contains(&Point3::new(2, 2, 0)));
+
+        assert!(astar(
+            &map,
+            Connectivity::Four,
+            &start,
+            &Point3::new(2, 0, 0),
+            cost
+        )
+        .is_none());
+        assert!(astar(
+            &map,
+            Connectivity::Four,
+            &start,
+            &Point3::new(5, 0, 0),
+            cost
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn finds_hexagonal_paths() {
+        let map =
+            TileMap::<Cell, FlatEncoder>::new(Vector3::new(3, 3, 1), Vector3::new(16, 16, 1), None);
+        let projection =
+            Projection::Hexagonal(HexOrientation::Pointy, HexLayout::Offset(StaggerIndex::Odd));
+        let path = astar(
+            &map,
+            Connectivity::Projection(projection),
+            &Point3::new(0, 0, 0),
+            &Point3::new(2, 2, 0),
+            cost,
+        )
+        .unwrap();
+        assert!((path.cost - 3.0).abs() < f32::EPSILON);
+        for step in path.tiles.windows(2) {
+            assert!(projection.neighbors(&step[0]).any(|tile| tile == step[1]));
+        }
+    }
+
+    #[test]
+    fn maps_distances_to_nearest_source() {
+        let map = walled_map();
+        let sources = vec![Point3::new(0, 0, 0), Point3::new(4, 0, 0)];
+        let dijkstra =
+            DijkstraMap::<MortonEncoder2D>::new(&map, Connectivity::Four, sources, cost).unwrap();
+        assert_eq!(dijkstra.distance(&Point3::new(4, 2, 0)), Some(2.0));
+        assert_eq!(dijkstra.distance(&Point3::new(2, 2, 0)), Some(4.0));
+        assert_eq!(dijkstra.distance(&Point3::new(2, 0, 0)), None);
+
+        let downhill = dijkstra.downhill(&Point3::new(2, 2, 0)).unwrap();
+        assert_eq!(dijkstra.distance(&downhill), Some(3.0));
+        assert_eq!(dijkstra.downhill(&Point3::new(0, 0, 0)), None);
+    }
+
+    #[test]
+    fn leads_flow_to_goals() {
+        let map = walled_map();
+        let goal = Point3::new(4, 0, 0);
+        let flow =
+            FlowField::<MortonEncoder2D>::new(&map, Connectivity::Four, Some(goal), cost).unwrap();
+        let path = flow.path(&Point3::new(0, 0, 0)).collect::<Vec<_>>();
+        assert_eq!(path.len(), 8);
+        assert_eq!(path.last(), Some(&goal));
+        assert_eq!(flow.next(&goal), None);
+        assert_eq!(flow.next(&Point3::new(2, 1, 0)), None);
+    }
+}
@@ -96,4 +96,18 @@ impl TileArgs {
             tile_coordinate: [tile_coordinate.x, tile_coordinate.y, tile_coordinate.z].into(),
         }
     }
+
+    /// Replaces the sprite of this tile, keeping its tint and coordinate. The tile is left as it is
+    /// if the sprite is not in `sprites`.
+    ///
+    /// # Arguments
+    /// * `sprite_number` - The number index of the sprite in the sprite sheet.
+    pub fn set_sprite(&mut self, sprites: &[Sprite], sprite_number: usize) {
+        let sprite = match sprites.get(sprite_number) {
+            Some(sprite) => sprite,
+            None => return,
+        };
+        self.u_offset = [sprite.tex_coords.left, sprite.tex_coords.right].into();
+        self.v_offset = [sprite.tex_coords.top, sprite.tex_coords.bottom].into();
+    }
 }
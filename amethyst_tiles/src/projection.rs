@@ -0,0 +1,385 @@
+//! Projections of the tiles of a map onto its plane.
+
+use amethyst_core::math::{Matrix4, Point2, Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+/// The number of draw coordinates per tile in `DrawTiles2D`. Every projection places tile centers
+/// on multiples of a quarter of a tile, so they are drawn at exact integer coordinates.
+const DRAW_SUBDIVISIONS: f32 = 4.0;
+
+const ORTHOGONAL_NEIGHBORS: [(f32, f32); 4] = [(1.0, 0.0), (0.0, 1.0), (-1.0, 0.0), (0.0, -1.0)];
+const ISOMETRIC_NEIGHBORS: [(f32, f32); 4] = [(0.5, 0.5), (-0.5, 0.5), (-0.5, -0.5), (0.5, -0.5)];
+const POINTY_NEIGHBORS: [(f32, f32); 6] = [
+    (1.0, 0.0),
+    (0.5, 0.75),
+    (-0.5, 0.75),
+    (-1.0, 0.0),
+    (-0.5, -0.75),
+    (0.5, -0.75),
+];
+const FLAT_NEIGHBORS: [(f32, f32); 6] = [
+    (0.75, -0.5),
+    (0.75, 0.5),
+    (0.0, 1.0),
+    (-0.75, 0.5),
+    (-0.75, -0.5),
+    (0.0, -1.0),
+];
+
+/// Which of the rows or columns of a staggered map are shifted by half a tile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StaggerIndex {
+    /// Odd rows are shifted to the right, or odd columns downwards.
+    Odd,
+    /// Even rows are shifted to the right, or even columns downwards.
+    Even,
+}
+
+impl Default for StaggerIndex {
+    fn default() -> Self {
+        StaggerIndex::Odd
+    }
+}
+
+/// The layout of an isometric map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IsometricLayout {
+    /// A diamond-shaped map, with x growing down to the right and y growing down to the left.
+    Diamond,
+    /// A rectangular map of rows half a tile apart, every other row being shifted by half a tile.
+    Staggered(StaggerIndex),
+}
+
+/// The orientation of hexagonal tiles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HexOrientation {
+    /// Hexagons with a vertex at the top, in rows.
+    Pointy,
+    /// Hexagons with an edge at the top, in columns.
+    Flat,
+}
+
+/// The layout of a hexagonal map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HexLayout {
+    /// A rectangular map, every other row of pointy tiles or column of flat tiles being shifted by
+    /// half a tile.
+    Offset(StaggerIndex),
+    /// A rhombus-shaped map, whose x and y axes are two axes of the hexagonal grid, 60 degrees
+    /// apart.
+    Axial,
+}
+
+/// The projection of the tiles of a map onto its plane, used to convert between tile and world
+/// coordinates, to find the neighbors of tiles and to order their drawing.
+///
+/// The tile dimensions of the map are the dimensions of the sprites of its tiles. Isometric tiles
+/// are diamonds filling their sprite, and hexagonal tiles are hexagons filling their sprite, whose
+/// rows of pointy tiles or columns of flat tiles overlap by a quarter of a tile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Projection {
+    /// Rectangular tiles in rows and columns.
+    Orthogonal,
+    /// Diamond tiles.
+    Isometric(IsometricLayout),
+    /// Hexagonal tiles.
+    Hexagonal(HexOrientation, HexLayout),
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection::Orthogonal
+    }
+}
+
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+impl Projection {
+    /// Returns the center of a tile on the plane of the map, in tiles from the center of the tile
+    /// at (0, 0), with y growing downwards.
+    #[must_use]
+    pub fn project(&self, coord: &Point3<u32>) -> Point2<f32> {
+        let x = coord.x as f32;
+        let y = coord.y as f32;
+        match *self {
+            Projection::Orthogonal => Point2::new(x, y),
+            Projection::Isometric(IsometricLayout::Diamond) => {
+                Point2::new((x - y) * 0.5, (x + y) * 0.5)
+            }
+            Projection::Isometric(IsometricLayout::Staggered(stagger)) => {
+                Point2::new(x + shift(stagger, y), y * 0.5)
+            }
+            Projection::Hexagonal(HexOrientation::Pointy, HexLayout::Offset(stagger)) => {
+                Point2::new(x + shift(stagger, y), y * 0.75)
+            }
+            Projection::Hexagonal(HexOrientation::Pointy, HexLayout::Axial) => {
+                Point2::new(x + y * 0.5, y * 0.75)
+            }
+            Projection::Hexagonal(HexOrientation::Flat, HexLayout::Offset(stagger)) => {
+                Point2::new(x * 0.75, y + shift(stagger, x))
+            }
+            Projection::Hexagonal(HexOrientation::Flat, HexLayout::Axial) => {
+                Point2::new(x * 0.75, y + x * 0.5)
+            }
+        }
+    }
+
+    /// Returns the coordinates of the tile containing a point of the plane of the map, as given by
+    /// `project`. The coordinates are whole, and negative for points before the first row or
+    /// column.
+    #[must_use]
+    pub fn unproject(&self, point: &Point2<f32>) -> Point2<f32> {
+        match *self {
+            Projection::Orthogonal => Point2::new(point.x.round(), point.y.round()),
+            Projection::Isometric(IsometricLayout::Diamond) => {
+                Point2::new((point.x + point.y).round(), (point.y - point.x).round())
+            }
+            Projection::Isometric(IsometricLayout::Staggered(stagger)) => {
+                // Staggered tiles have the centers of diamond tiles, numbered by rows
+                let diagonal_x = (point.x + point.y).round();
+                let diagonal_y = (point.y - point.x).round();
+                let row = diagonal_x + diagonal_y;
+                Point2::new((diagonal_x - diagonal_y) * 0.5 - shift(stagger, row), row)
+            }
+            Projection::Hexagonal(HexOrientation::Pointy, layout) => {
+                let r = point.y / 0.75;
+                let (q, r) = round_axial(point.x - r * 0.5, r);
+                match layout {
+                    HexLayout::Axial => Point2::new(q, r),
+                    HexLayout::Offset(stagger) => Point2::new(q + r * 0.5 - shift(stagger, r), r),
+                }
+            }
+            Projection::Hexagonal(HexOrientation::Flat, layout) => {
+                let q = point.x / 0.75;
+                let (q, r) = round_axial(q, point.y - q * 0.5);
+                match layout {
+                    HexLayout::Axial => Point2::new(q, r),
+                    HexLayout::Offset(stagger) => Point2::new(q, r + q * 0.5 - shift(stagger, q)),
+                }
+            }
+        }
+    }
+
+    /// Returns the tiles sharing an edge with a tile, on its z-level: 4 for orthogonal and
+    /// isometric tiles, and 6 for hexagonal ones. Tiles before the first row or column are
+    /// skipped, but not tiles past the dimensions of a map.
+    #[allow(clippy::cast_sign_loss)]
+    pub fn neighbors(&self, coord: &Point3<u32>) -> impl Iterator<Item = Point3<u32>> {
+        let projection = *self;
+        let center = self.project(coord);
+        let z = coord.z;
+        let offsets: &'static [(f32, f32)] = match *self {
+            Projection::Orthogonal => &ORTHOGONAL_NEIGHBORS,
+            Projection::Isometric(_) => &ISOMETRIC_NEIGHBORS,
+            Projection::Hexagonal(HexOrientation::Pointy, _) => &POINTY_NEIGHBORS,
+            Projection::Hexagonal(HexOrientation::Flat, _) => &FLAT_NEIGHBORS,
+        };
+        offsets.iter().filter_map(move |(x, y)| {
+            let tile = projection.unproject(&Point2::new(center.x + x, center.y + y));
+            if tile.x < 0.0 || tile.y < 0.0 {
+                None
+            } else {
+                Some(Point3::new(tile.x as u32, tile.y as u32, z))
+            }
+        })
+    }
+
+    /// Returns the longest distance between the centers of neighboring tiles on the plane.
+    pub(crate) fn neighbor_distance(&self) -> f32 {
+        match *self {
+            Projection::Isometric(_) => std::f32::consts::FRAC_1_SQRT_2,
+            Projection::Orthogonal | Projection::Hexagonal(..) => 1.0,
+        }
+    }
+
+    /// Returns the minimum and maximum tile centers of a map of the given dimensions on its plane.
+    #[must_use]
+    pub fn bounds(&self, dimensions: &Vector3<u32>) -> (Point2<f32>, Point2<f32>) {
+        // The extreme tiles are in the first or last two rows and columns of every layout
+        let columns = edges(dimensions.x);
+        let rows = edges(dimensions.y);
+        let mut min = Point2::new(f32::MAX, f32::MAX);
+        let mut max = Point2::new(f32::MIN, f32::MIN);
+        for &x in &columns {
+            for &y in &rows {
+                let center = self.project(&Point3::new(x, y, 0));
+                min = Point2::new(min.x.min(center.x), min.y.min(center.y));
+                max = Point2::new(max.x.max(center.x), max.y.max(center.y));
+            }
+        }
+        (min, max)
+    }
+
+    /// Returns the key ordering the drawing of tiles from the back to the front: by z-level, then
+    /// from the top row of the plane, then from the left.
+    #[must_use]
+    pub fn draw_order(&self, coord: &Point3<u32>) -> (u32, i64, i64) {
+        let center = self.project(coord);
+        (
+            coord.z,
+            (center.y * DRAW_SUBDIVISIONS) as i64,
+            (center.x * DRAW_SUBDIVISIONS) as i64,
+        )
+    }
+
+    /// Returns the coordinates a tile is drawn at by `DrawTiles2D`, in subdivisions of tiles from
+    /// `min`, the minimum of the `bounds` of its map.
+    #[allow(clippy::cast_sign_loss)]
+    pub(crate) fn draw_coordinate(&self, coord: &Point3<u32>, min: &Point2<f32>) -> Point3<u32> {
+        let center = self.project(coord);
+        Point3::new(
+            ((center.x - min.x) * DRAW_SUBDIVISIONS) as u32,
+            ((center.y - min.y) * DRAW_SUBDIVISIONS) as u32,
+            coord.z,
+        )
+    }
+
+    /// Returns the transform of the coordinates given by `draw_coordinate` to the coordinates of
+    /// a map whose transform is `transform`.
+    pub(crate) fn draw_transform(transform: &Matrix4<f32>, min: &Point2<f32>) -> Matrix4<f32> {
+        transform
+            * Matrix4::new_translation(&Vector3::new(min.x, -min.y, 0.0))
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(
+                1.0 / DRAW_SUBDIVISIONS,
+                1.0 / DRAW_SUBDIVISIONS,
+                1.0,
+            ))
+    }
+}
+
+/// Returns the offset of the row or column at a whole `index` of a staggered map.
+#[allow(clippy::cast_possible_truncation)]
+fn shift(stagger: StaggerIndex, index: f32) -> f32 {
+    if (index as i64) & 1 == 0 {
+        0.0
+    } else {
+        match stagger {
+            StaggerIndex::Odd => 0.5,
+            StaggerIndex::Even => -0.5,
+        }
+    }
+}
+
+/// Rounds fractional axial coordinates to the axial coordinates of the hexagon containing them.
+fn round_axial(q: f32, r: f32) -> (f32, f32) {
+    let s = -q - r;
+    let mut rounded_q = q.round();
+    let mut rounded_r = r.round();
+    let rounded_s = s.round();
+    let q_diff = (rounded_q - q).abs();
+    let r_diff = (rounded_r - r).abs();
+    let s_diff = (rounded_s - s).abs();
+    if q_diff > r_diff && q_diff > s_diff {
+        rounded_q = -rounded_r - rounded_s;
+    } else if r_diff > s_diff {
+        rounded_r = -rounded_q - rounded_s;
+    }
+    (rounded_q, rounded_r)
+}
+
+/// Returns the first two and last two indices of `count` rows or columns.
+fn edges(count: u32) -> [u32; 4] {
+    let last = count.saturating_sub(1);
+    [0, 1.min(last), last.saturating_sub(1), last]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECTIONS: [Projection; 9] = [
+        Projection::Orthogonal,
+        Projection::Isometric(IsometricLayout::Diamond),
+        Projection::Isometric(IsometricLayout::Staggered(StaggerIndex::Odd)),
+        Projection::Isometric(IsometricLayout::Staggered(StaggerIndex::Even)),
+        Projection::Hexagonal(HexOrientation::Pointy, HexLayout::Offset(StaggerIndex::Odd)),
+        Projection::Hexagonal(
+            HexOrientation::Pointy,
+            HexLayout::Offset(StaggerIndex::Even),
+        ),
+        Projection::Hexagonal(HexOrientation::Pointy, HexLayout::Axial),
+        Projection::Hexagonal(HexOrientation::Flat, HexLayout::Offset(StaggerIndex::Odd)),
+        Projection::Hexagonal(HexOrientation::Flat, HexLayout::Axial),
+    ];
+
+    #[test]
+    #[allow(clippy::cast_precision_loss)]
+    fn unprojects_projected_tiles() {
+        for projection in &PROJECTIONS {
+            for x in 0..6 {
+                for y in 0..6 {
+                    let tile = Point3::new(x, y, 0);
+                    let center = projection.project(&tile);
+                    for &(dx, dy) in &[(0.0, 0.0), (0.2, 0.1), (-0.1, -0.2)] {
+                        let point = Point2::new(center.x + dx, center.y + dy);
+                        assert_eq!(
+                            projection.unproject(&point),
+                            Point2::new(x as f32, y as f32),
+                            "{:?} {:?}",
+                            projection,
+                            tile
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn finds_symmetric_neighbors() {
+        for projection in &PROJECTIONS {
+            let tile = Point3::new(3, 3, 2);
+            let neighbors = projection.neighbors(&tile).collect::<Vec<_>>();
+            let expected = match projection {
+                Projection::Hexagonal(..) => 6,
+                _ => 4,
+            };
+            assert_eq!(neighbors.len(), expected, "{:?}", projection);
+            for neighbor in neighbors {
+                assert_eq!(neighbor.z, 2);
+                assert!(projection.neighbors(&neighbor).any(|other| other == tile));
+            }
+        }
+    }
+
+    #[test]
+    fn finds_offset_hex_neighbors() {
+        let projection =
+            Projection::Hexagonal(HexOrientation::Pointy, HexLayout::Offset(StaggerIndex::Odd));
+        let mut neighbors = projection
+            .neighbors(&Point3::new(1, 1, 0))
+            .map(|p| (p.x, p.y))
+            .collect::<Vec<_>>();
+        neighbors.sort_unstable();
+        assert_eq!(neighbors, [(0, 1), (1, 0), (1, 2), (2, 0), (2, 1), (2, 2)]);
+        assert_eq!(projection.neighbors(&Point3::new(0, 0, 0)).count(), 2);
+    }
+
+    #[test]
+    fn orders_isometric_tiles_from_back() {
+        let projection = Projection::Isometric(IsometricLayout::Diamond);
+        let mut tiles = vec![
+            Point3::new(1, 1, 0),
+            Point3::new(0, 0, 1),
+            Point3::new(1, 0, 0),
+            Point3::new(0, 0, 0),
+        ];
+        tiles.sort_by_key(|tile| projection.draw_order(tile));
+        assert_eq!(
+            tiles,
+            [
+                Point3::new(0, 0, 0),
+                Point3::new(1, 0, 0),
+                Point3::new(1, 1, 0),
+                Point3::new(0, 0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn bounds_staggered_maps() {
+        let projection = Projection::Isometric(IsometricLayout::Staggered(StaggerIndex::Even));
+        let (min, max) = projection.bounds(&Vector3::new(3, 4, 1));
+        assert_eq!(min, Point2::new(-0.5, 0.0));
+        assert_eq!(max, Point2::new(2.0, 1.5));
+    }
+}
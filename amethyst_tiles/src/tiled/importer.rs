@@ -0,0 +1,195 @@
+//! Importers of Tiled maps and tilesets.
+
+use std::io::Read;
+
+use amethyst_assets::{
+    distill_importer::{self, ImportOp, Importer, ImporterValue},
+    AssetUuid, Format,
+};
+use amethyst_core::{
+    ecs::World,
+    transform::{Parent, Transform},
+};
+use amethyst_error::{format_err, Error};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use super::{
+    parse::{is_json, parse_tmj, parse_tmx, parse_tsj, parse_tsx},
+    TiledMapData, TiledTileset,
+};
+use crate::editor::import_prefab;
+
+/// Options of the `TiledMapImporter`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[serde(default)]
+#[uuid = "61939427-a387-49d8-91f4-13f4345e3167"]
+pub struct TiledMapOptions {
+    /// The directory of the map, relative to the asset directory, which the paths of its
+    /// tilesets and images are joined to.
+    pub directory: String,
+}
+
+/// A simple state for the `TiledMapImporter` to retain the same UUID between imports.
+#[derive(Default, Debug, Deserialize, Serialize, TypeUuid)]
+#[uuid = "58c52d77-76c9-438b-b928-f1ae39487b4a"]
+pub struct TiledImporterState {
+    /// The UUID of the map prefab.
+    pub id: Option<AssetUuid>,
+}
+
+/// The importer of Tiled `.tmx` and `.tmj` maps, producing a prefab.
+///
+/// The prefab has a map entity with a `TiledMapData` and a `Transform`, and a child of it with a
+/// `Transform` and a `TiledObject` per object of the object layers.
+#[derive(Default, TypeUuid, Debug)]
+#[uuid = "f794d8b6-e3ff-4de3-8953-55dda91b7f3b"]
+pub struct TiledMapImporter;
+
+amethyst_assets::inventory::submit! {
+    amethyst_assets::SourceFileImporter {
+        extension: "tmx",
+        instantiator: || Box::new(TiledMapImporter::default()),
+    }
+}
+
+amethyst_assets::inventory::submit! {
+    amethyst_assets::SourceFileImporter {
+        extension: "tmj",
+        instantiator: || Box::new(TiledMapImporter::default()),
+    }
+}
+
+impl Importer for TiledMapImporter {
+    fn version_static() -> u32 {
+        1
+    }
+
+    fn version(&self) -> u32 {
+        Self::version_static()
+    }
+
+    type Options = TiledMapOptions;
+    type State = TiledImporterState;
+
+    fn import(
+        &self,
+        op: &mut ImportOp,
+        source: &mut dyn Read,
+        options: &Self::Options,
+        state: &mut Self::State,
+    ) -> distill_importer::Result<ImporterValue> {
+        log::info!("Importing Tiled map with options {:?}", options);
+        import_prefab(op, source, &mut state.id, |bytes| {
+            map_world(bytes, &options.directory)
+        })
+    }
+}
+
+/// Creates the world of the prefab of a map.
+fn map_world(bytes: &[u8], directory: &str) -> Result<World, Error> {
+    let text = std::str::from_utf8(bytes).map_err(|_| format_err!("Tiled map is not UTF-8"))?;
+    let map = if is_json(bytes) {
+        parse_tmj(text, directory)?
+    } else {
+        parse_tmx(text, directory)?
+    };
+
+    let mut world = World::default();
+    let transforms = map
+        .objects
+        .iter()
+        .map(|(_, position, rotation)| object_transform(&map.data, *position, *rotation))
+        .collect::<Vec<_>>();
+    let map_entity = world.push((map.data, Transform::default()));
+    for ((object, _, _), transform) in map.objects.into_iter().zip(transforms) {
+        world.push((transform, object, Parent(map_entity)));
+    }
+    Ok(world)
+}
+
+/// Returns the transform of an object at `position` in pixels from the top left of the map,
+/// rotated clockwise by `rotation` degrees. The map is centered on the origin, as its `TileMap`s.
+#[allow(clippy::cast_precision_loss)]
+fn object_transform(data: &TiledMapData, position: [f32; 2], rotation: f32) -> Transform {
+    let half_width = (data.width * data.tile_width) as f32 * 0.5;
+    let half_height = (data.height * data.tile_height) as f32 * 0.5;
+    let mut transform = Transform::default();
+    transform
+        .set_translation_xyz(position[0] - half_width, half_height - position[1], 0.)
+        .set_rotation_2d(-rotation.to_radians());
+    transform
+}
+
+/// Loads Tiled `.tsx` and `.tsj` tilesets.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[uuid = "4a0f58f6-3fa0-4057-84fa-132fab50648e"]
+pub struct TiledTilesetFormat {
+    /// The directory of the tileset, relative to the asset directory, which the path of its image
+    /// is joined to.
+    pub directory: String,
+}
+
+amethyst_assets::register_importer!(".tsx", TiledTilesetFormat);
+amethyst_assets::register_importer!(".tsj", TiledTilesetFormat);
+impl Format<TiledTileset> for TiledTilesetFormat {
+    fn name(&self) -> &'static str {
+        "TiledTileset"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<TiledTileset, Error> {
+        let text =
+            std::str::from_utf8(&bytes).map_err(|_| format_err!("Tiled tileset is not UTF-8"))?;
+        if is_json(&bytes) {
+            parse_tsj(text, &self.directory)
+        } else {
+            parse_tsx(text, &self.directory)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::{
+        ecs::{component, Entity, IntoQuery},
+        math::Vector3,
+    };
+
+    use super::*;
+    use crate::tiled::TiledObject;
+
+    #[test]
+    fn places_objects_from_map_center() {
+        let data = TiledMapData {
+            width: 4,
+            height: 2,
+            tile_width: 16,
+            tile_height: 16,
+            ..TiledMapData::default()
+        };
+        let transform = object_transform(&data, [8., 4.], 0.);
+        assert_eq!(*transform.translation(), Vector3::new(-24., 12., 0.));
+    }
+
+    #[test]
+    fn parents_objects_to_the_map() {
+        let world = map_world(
+            br#"<map width="1" height="1" tilewidth="16" tileheight="16">
+ <objectgroup name="spawns"><object id="1" x="0" y="0"/><object id="2" x="8" y="8"/></objectgroup>
+</map>"#,
+            "",
+        )
+        .unwrap();
+        let map = *<Entity>::query()
+            .filter(component::<TiledMapData>())
+            .iter(&world)
+            .next()
+            .unwrap();
+        let parents = <&Parent>::query()
+            .filter(component::<TiledObject>())
+            .iter(&world)
+            .map(|parent| parent.0)
+            .collect::<Vec<_>>();
+        assert_eq!(parents, vec![map, map]);
+    }
+}
@@ -0,0 +1,307 @@
+//! Loading of maps made with the [Tiled](https://www.mapeditor.org/) level editor.
+//!
+//! `.tmx` and `.tmj` maps are imported as prefabs. The map entity gets a `TiledMapData`, from
+//! which the `TiledMapSystem` spawns a `TileMap<TiledTile>` child per tileset once the tilesets
+//! are loaded, drawing each tile layer at its own z-level. Objects of object layers are spawned
+//! as entities with a `Transform` and a `TiledObject` holding their custom properties.
+//!
+//! `.tsx` and `.tsj` tilesets are imported as `TiledTileset` assets. Each tileset becomes a
+//! `SpriteSheet` of its image, so image collection tilesets are not supported.
+//!
+//! Importers do not know the path of the file they import, so relative paths in a file are
+//! joined to the `directory` option of its importer, which should be set in the `.meta` file
+//! of maps and tilesets outside of the asset directory root.
+
+use std::collections::HashMap;
+
+use amethyst_assets::{
+    prefab::{legion_prefab, register_component_type, serde_diff, SerdeDiff},
+    register_asset_type, Asset, AssetProcessorSystem,
+};
+use amethyst_core::{
+    ecs::{Resources, World},
+    math::Point3,
+};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use crate::{
+    HexLayout, HexOrientation, IsometricLayout, Projection, StaggerIndex, Tile, TileAnimation,
+};
+
+mod importer;
+mod parse;
+mod system;
+
+pub(crate) use self::parse::join_path;
+pub use self::{
+    importer::{TiledImporterState, TiledMapImporter, TiledMapOptions, TiledTilesetFormat},
+    system::TiledMapSystem,
+};
+
+/// The gid bit of tiles flipped horizontally.
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+/// The gid bit of tiles flipped vertically.
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+/// The gid bit of tiles flipped diagonally.
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+/// The gid bits used for flipping and rotating tiles.
+const GID_FLAGS: u32 = 0xF000_0000;
+
+/// A custom property of a map, layer, tileset or object.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TiledProperty {
+    /// A `bool` property.
+    Bool(bool),
+    /// An `int` or `object` property.
+    Int(i64),
+    /// A `float` property.
+    Float(f64),
+    /// A `string`, `color`, `file` or `class` property.
+    String(String),
+}
+
+/// Custom properties, by name.
+pub type TiledProperties = HashMap<String, TiledProperty>;
+
+/// An image used by a tileset.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TiledImage {
+    /// The path of the image, relative to the asset directory.
+    pub source: String,
+    /// The width of the image in pixels.
+    pub width: u32,
+    /// The height of the image in pixels.
+    pub height: u32,
+}
+
+/// A tileset, either embedded in a map or loaded from a `.tsx` or `.tsj` file.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "8762764d-0d6e-4b2d-8792-e76519698570"]
+pub struct TiledTileset {
+    /// The name of the tileset.
+    pub name: String,
+    /// The width of a tile in pixels.
+    pub tile_width: u32,
+    /// The height of a tile in pixels.
+    pub tile_height: u32,
+    /// The space between two tiles of the image, in pixels.
+    pub spacing: u32,
+    /// The space around the tiles of the image, in pixels.
+    pub margin: u32,
+    /// The number of tiles.
+    pub tile_count: u32,
+    /// The number of tile columns of the image.
+    pub columns: u32,
+    /// The image of the tiles.
+    pub image: Option<TiledImage>,
+    /// The custom properties of the tileset.
+    pub properties: TiledProperties,
+    /// The custom properties of the tiles, by tile id.
+    pub tile_properties: HashMap<u32, TiledProperties>,
+    /// The animations of the tiles, by tile id, whose frames are tile ids.
+    pub tile_animations: HashMap<u32, TileAnimation>,
+}
+
+register_asset_type!(TiledTileset => TiledTileset; AssetProcessorSystem<TiledTileset>);
+
+impl Asset for TiledTileset {
+    fn name() -> &'static str {
+        "tiles::TiledTileset"
+    }
+    type Data = Self;
+}
+
+/// Where the tiles of a tileset come from.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TiledTilesetSource {
+    /// A tileset file, by its path relative to the asset directory.
+    External(String),
+    /// A tileset embedded in the map.
+    Embedded(TiledTileset),
+}
+
+/// A tileset used by a map, with the first gid of its tiles.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TiledTilesetRef {
+    /// The gid of the first tile of the tileset.
+    pub first_gid: u32,
+    /// The tileset.
+    pub source: TiledTilesetSource,
+}
+
+/// The orientation of a map.
+///
+/// Maps are drawn with the `Projection` of their `TiledMapData`. Hexagonal maps are drawn with
+/// regular hexagons, whatever their hex side length in Tiled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TiledOrientation {
+    /// Square tiles in rows and columns.
+    Orthogonal,
+    /// Diamond tiles, in a rotated grid.
+    Isometric,
+    /// Diamond tiles, in staggered rows or columns.
+    Staggered,
+    /// Hexagonal tiles, in staggered rows or columns.
+    Hexagonal,
+}
+
+impl Default for TiledOrientation {
+    fn default() -> Self {
+        TiledOrientation::Orthogonal
+    }
+}
+
+/// A tile layer of a map.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TiledTileLayer {
+    /// The name of the layer.
+    pub name: String,
+    /// Whether the layer, and all groups containing it, are visible.
+    pub visible: bool,
+    /// The gids of the tiles, row by row from the top left. A gid of 0 is an empty tile.
+    pub gids: Vec<u32>,
+    /// The custom properties of the layer.
+    pub properties: TiledProperties,
+}
+
+/// The tile layers and tilesets of a Tiled map, on the map entity of its prefab.
+///
+/// The `TiledMapSystem` spawns the `TileMap`s of the map from it, and spawns them again when it
+/// changes, such as when the map is hot-reloaded.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "6c69802f-a1b2-4d74-ab49-54a28abdff91"]
+pub struct TiledMapData {
+    /// The orientation of the map.
+    #[serde_diff(opaque)]
+    pub orientation: TiledOrientation,
+    /// Whether staggered and hexagonal maps are staggered by columns rather than by rows.
+    pub stagger_columns: bool,
+    /// Which rows or columns of staggered and hexagonal maps are shifted.
+    #[serde_diff(opaque)]
+    pub stagger_index: StaggerIndex,
+    /// The width of the map in tiles.
+    pub width: u32,
+    /// The height of the map in tiles.
+    pub height: u32,
+    /// The width of a tile in pixels.
+    pub tile_width: u32,
+    /// The height of a tile in pixels.
+    pub tile_height: u32,
+    /// The tilesets of the map, in order of their first gid.
+    #[serde_diff(opaque)]
+    pub tilesets: Vec<TiledTilesetRef>,
+    /// The tile layers of the map, from the bottom one.
+    #[serde_diff(opaque)]
+    pub layers: Vec<TiledTileLayer>,
+    /// The custom properties of the map.
+    #[serde_diff(opaque)]
+    pub properties: TiledProperties,
+}
+
+register_component_type!(TiledMapData);
+
+impl TiledMapData {
+    /// Returns the `Projection` of the tiles of the map, or `None` for staggered maps staggered by
+    /// columns, which no `Projection` lays out.
+    #[must_use]
+    pub fn projection(&self) -> Option<Projection> {
+        match self.orientation {
+            TiledOrientation::Orthogonal => Some(Projection::Orthogonal),
+            TiledOrientation::Isometric => Some(Projection::Isometric(IsometricLayout::Diamond)),
+            TiledOrientation::Staggered if self.stagger_columns => None,
+            TiledOrientation::Staggered => Some(Projection::Isometric(IsometricLayout::Staggered(
+                self.stagger_index,
+            ))),
+            TiledOrientation::Hexagonal => {
+                let orientation = if self.stagger_columns {
+                    HexOrientation::Flat
+                } else {
+                    HexOrientation::Pointy
+                };
+                Some(Projection::Hexagonal(
+                    orientation,
+                    HexLayout::Offset(self.stagger_index),
+                ))
+            }
+        }
+    }
+}
+
+/// An object of an object layer of a Tiled map.
+///
+/// Its entity has a `Transform` at the position of the object in Tiled, which is the top left of
+/// rectangles and the bottom left of tile objects, relative to the center of the map.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "18bbd64b-a90d-44ca-9d94-6b2d98ab1942"]
+pub struct TiledObject {
+    /// The unique id of the object in the map.
+    pub id: u32,
+    /// The name of the object.
+    pub name: String,
+    /// The class, or type, of the object.
+    pub class: String,
+    /// The name of the object layer of the object.
+    pub layer: String,
+    /// The width of the object in pixels.
+    pub width: f32,
+    /// The height of the object in pixels.
+    pub height: f32,
+    /// The gid of the tile drawn for tile objects.
+    pub gid: Option<u32>,
+    /// Whether the object, and its layer, are visible.
+    pub visible: bool,
+    /// The custom properties of the object.
+    #[serde_diff(opaque)]
+    pub properties: TiledProperties,
+}
+
+register_component_type!(TiledObject);
+
+/// A tile of a `TileMap` spawned for a Tiled map.
+///
+/// The sprite sheet of a tileset has its tiles, followed by their copies flipped horizontally,
+/// vertically and both, so that flipped tiles are drawn flipped. Diagonal flips are not drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TiledTile {
+    /// The index of the sprite of the tile in the sprite sheet of its tileset, including flips.
+    pub sprite: Option<usize>,
+    /// Whether the tile is flipped horizontally.
+    pub flip_horizontal: bool,
+    /// Whether the tile is flipped vertically.
+    pub flip_vertical: bool,
+    /// Whether the tile is flipped diagonally, which rotates it with the other flips.
+    pub flip_diagonal: bool,
+}
+
+impl TiledTile {
+    /// Creates the tile of `gid`, drawn with the tileset of `tile_count` tiles starting at
+    /// `first_gid`.
+    #[must_use]
+    pub fn from_gid(gid: u32, first_gid: u32, tile_count: u32) -> Self {
+        let flip_horizontal = gid & FLIPPED_HORIZONTALLY != 0;
+        let flip_vertical = gid & FLIPPED_VERTICALLY != 0;
+        let flips = u32::from(flip_horizontal) + 2 * u32::from(flip_vertical);
+        Self {
+            sprite: tile_gid(gid)
+                .checked_sub(first_gid)
+                .filter(|&tile| tile < tile_count)
+                .map(|tile| (tile + flips * tile_count) as usize),
+            flip_horizontal,
+            flip_vertical,
+            flip_diagonal: gid & FLIPPED_DIAGONALLY != 0,
+        }
+    }
+}
+
+impl Tile for TiledTile {
+    fn sprite(&self, _: Point3<u32>, _: &World, _: &Resources) -> Option<usize> {
+        self.sprite
+    }
+}
+
+/// Returns the gid of a tile without its flip bits.
+#[must_use]
+pub fn tile_gid(gid: u32) -> u32 {
+    gid & !GID_FLAGS
+}
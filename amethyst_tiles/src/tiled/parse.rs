@@ -0,0 +1,734 @@
+//! Parsing of the XML and JSON formats of Tiled maps and tilesets.
+
+use std::{collections::HashMap, io::Read, str::FromStr};
+
+use amethyst_error::{format_err, Error};
+use roxmltree::Node;
+use serde_json::Value;
+
+use super::{
+    TiledImage, TiledMapData, TiledObject, TiledOrientation, TiledProperties, TiledProperty,
+    TiledTileLayer, TiledTileset, TiledTilesetRef, TiledTilesetSource,
+};
+use crate::{StaggerIndex, TileAnimation, TileAnimationFrame};
+
+/// A parsed map, with the objects of its object layers.
+#[derive(Debug, Default)]
+pub(crate) struct ParsedMap {
+    pub(crate) data: TiledMapData,
+    /// The objects, with their position in pixels from the top left of the map.
+    pub(crate) objects: Vec<(TiledObject, [f32; 2], f32)>,
+}
+
+/// Joins a relative `path` to `directory`, resolving `.` and `..` components.
+pub(crate) fn join_path(directory: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in directory.split('/').chain(path.split('/')) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Returns whether `bytes` hold a JSON document rather than an XML one.
+pub(crate) fn is_json(bytes: &[u8]) -> bool {
+    bytes
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .map_or(false, |b| *b == b'{')
+}
+
+fn orientation(name: &str) -> Result<TiledOrientation, Error> {
+    match name {
+        "orthogonal" => Ok(TiledOrientation::Orthogonal),
+        "isometric" => Ok(TiledOrientation::Isometric),
+        "staggered" => Ok(TiledOrientation::Staggered),
+        "hexagonal" => Ok(TiledOrientation::Hexagonal),
+        _ => Err(format_err!("Unknown Tiled map orientation `{}`", name)),
+    }
+}
+
+fn stagger_index(name: Option<&str>) -> StaggerIndex {
+    if name == Some("even") {
+        StaggerIndex::Even
+    } else {
+        StaggerIndex::Odd
+    }
+}
+
+fn property(kind: &str, value: &str) -> Result<TiledProperty, Error> {
+    let invalid = || format_err!("Invalid Tiled {} property `{}`", kind, value);
+    Ok(match kind {
+        "bool" => TiledProperty::Bool(value == "true"),
+        "int" | "object" => TiledProperty::Int(value.parse().map_err(|_| invalid())?),
+        "float" => TiledProperty::Float(value.parse().map_err(|_| invalid())?),
+        _ => TiledProperty::String(value.to_string()),
+    })
+}
+
+/// Decodes the gids of a tile layer from the text of its data.
+fn decode_gids(text: &str, encoding: &str, compression: &str) -> Result<Vec<u32>, Error> {
+    match encoding {
+        "csv" => text
+            .split(',')
+            .map(str::trim)
+            .filter(|gid| !gid.is_empty())
+            .map(|gid| {
+                gid.parse()
+                    .map_err(|_| format_err!("Invalid Tiled tile gid `{}`", gid))
+            })
+            .collect(),
+        "base64" => {
+            let bytes = base64::decode(text.trim())
+                .map_err(|e| format_err!("Invalid base64 Tiled layer data: {}", e))?;
+            let bytes = match compression {
+                "" => bytes,
+                "zlib" => decompress(flate2::read::ZlibDecoder::new(&bytes[..]))?,
+                "gzip" => decompress(flate2::read::GzDecoder::new(&bytes[..]))?,
+                _ => {
+                    return Err(format_err!(
+                        "Unsupported Tiled layer compression `{}`",
+                        compression
+                    ))
+                }
+            };
+            Ok(bytes
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect())
+        }
+        _ => Err(format_err!(
+            "Unsupported Tiled layer encoding `{}`",
+            encoding
+        )),
+    }
+}
+
+fn decompress<R: Read>(mut decoder: R) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    decoder
+        .read_to_end(&mut bytes)
+        .map_err(|e| format_err!("Invalid compressed Tiled layer data: {}", e))?;
+    Ok(bytes)
+}
+
+fn check_layer_size(layer: &TiledTileLayer, data: &TiledMapData) -> Result<(), Error> {
+    let size = data.width.checked_mul(data.height);
+    if size.map(|size| size as usize) == Some(layer.gids.len()) {
+        Ok(())
+    } else {
+        Err(format_err!(
+            "Tiled layer `{}` has {} tiles instead of {}x{}",
+            layer.name,
+            layer.gids.len(),
+            data.width,
+            data.height
+        ))
+    }
+}
+
+// XML
+
+fn elements<'a, 'input>(node: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(Node::is_element)
+}
+
+fn attr<T: FromStr>(node: Node<'_, '_>, name: &str, default: T) -> Result<T, Error> {
+    node.attribute(name).map_or(Ok(default), |value| {
+        value.parse().map_err(|_| {
+            format_err!(
+                "Invalid value `{}` of `{}` in Tiled `{}`",
+                value,
+                name,
+                node.tag_name().name()
+            )
+        })
+    })
+}
+
+fn xml_properties(node: Node<'_, '_>) -> Result<TiledProperties, Error> {
+    let mut properties = HashMap::new();
+    for properties_node in elements(node).filter(|n| n.has_tag_name("properties")) {
+        for property_node in elements(properties_node).filter(|n| n.has_tag_name("property")) {
+            let kind = property_node.attribute("type").unwrap_or("string");
+            // Multiline string properties are stored as text rather than as a `value`.
+            let value = property_node
+                .attribute("value")
+                .or_else(|| property_node.text())
+                .unwrap_or("");
+            properties.insert(
+                property_node.attribute("name").unwrap_or("").to_string(),
+                property(kind, value)?,
+            );
+        }
+    }
+    Ok(properties)
+}
+
+/// Returns the animation of a tile, with the frames of Tiled animations, whose tiles all start
+/// them at once.
+fn animation_of(frames: Vec<TileAnimationFrame>) -> TileAnimation {
+    TileAnimation {
+        frames,
+        desynchronize: false,
+    }
+}
+
+fn xml_tileset(node: Node<'_, '_>, directory: &str) -> Result<TiledTileset, Error> {
+    let image = elements(node)
+        .find(|n| n.has_tag_name("image"))
+        .map(|image| -> Result<_, Error> {
+            Ok(TiledImage {
+                source: join_path(directory, image.attribute("source").unwrap_or("")),
+                width: attr(image, "width", 0)?,
+                height: attr(image, "height", 0)?,
+            })
+        })
+        .transpose()?;
+
+    let mut tile_properties = HashMap::new();
+    let mut tile_animations = HashMap::new();
+    for tile in elements(node).filter(|n| n.has_tag_name("tile")) {
+        let properties = xml_properties(tile)?;
+        if !properties.is_empty() {
+            tile_properties.insert(attr(tile, "id", 0)?, properties);
+        }
+        if let Some(animation) = elements(tile).find(|n| n.has_tag_name("animation")) {
+            let frames = elements(animation)
+                .filter(|n| n.has_tag_name("frame"))
+                .map(|frame| -> Result<_, Error> {
+                    Ok(TileAnimationFrame {
+                        sprite: attr(frame, "tileid", 0)?,
+                        duration: attr(frame, "duration", 0.)? / 1000.,
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if !frames.is_empty() {
+                tile_animations.insert(attr(tile, "id", 0)?, animation_of(frames));
+            }
+        }
+    }
+
+    Ok(TiledTileset {
+        name: attr(node, "name", String::new())?,
+        tile_width: attr(node, "tilewidth", 0)?,
+        tile_height: attr(node, "tileheight", 0)?,
+        spacing: attr(node, "spacing", 0)?,
+        margin: attr(node, "margin", 0)?,
+        tile_count: attr(node, "tilecount", 0)?,
+        columns: attr(node, "columns", 0)?,
+        image,
+        properties: xml_properties(node)?,
+        tile_properties,
+        tile_animations,
+    })
+}
+
+fn xml_layers(node: Node<'_, '_>, visible: bool, map: &mut ParsedMap) -> Result<(), Error> {
+    for layer in elements(node) {
+        let name = attr(layer, "name", String::new())?;
+        let layer_visible = visible && attr(layer, "visible", 1_u8)? != 0;
+        match layer.tag_name().name() {
+            "layer" => {
+                let data = elements(layer)
+                    .find(|n| n.has_tag_name("data"))
+                    .ok_or_else(|| format_err!("Tiled layer `{}` has no data", name))?;
+                let gids = match data.attribute("encoding") {
+                    Some(encoding) => decode_gids(
+                        data.text().unwrap_or(""),
+                        encoding,
+                        data.attribute("compression").unwrap_or(""),
+                    )?,
+                    None => elements(data)
+                        .filter(|n| n.has_tag_name("tile"))
+                        .map(|tile| attr(tile, "gid", 0))
+                        .collect::<Result<_, _>>()?,
+                };
+                let tile_layer = TiledTileLayer {
+                    name,
+                    visible: layer_visible,
+                    gids,
+                    properties: xml_properties(layer)?,
+                };
+                check_layer_size(&tile_layer, &map.data)?;
+                map.data.layers.push(tile_layer);
+            }
+            "objectgroup" => {
+                for object in elements(layer).filter(|n| n.has_tag_name("object")) {
+                    let class = object
+                        .attribute("class")
+                        .or_else(|| object.attribute("type"))
+                        .unwrap_or("");
+                    let tiled_object = TiledObject {
+                        id: attr(object, "id", 0)?,
+                        name: attr(object, "name", String::new())?,
+                        class: class.to_string(),
+                        layer: name.clone(),
+                        width: attr(object, "width", 0.)?,
+                        height: attr(object, "height", 0.)?,
+                        gid: object
+                            .attribute("gid")
+                            .map(|_| attr(object, "gid", 0))
+                            .transpose()?,
+                        visible: layer_visible && attr(object, "visible", 1_u8)? != 0,
+                        properties: xml_properties(object)?,
+                    };
+                    let position = [attr(object, "x", 0.)?, attr(object, "y", 0.)?];
+                    let rotation = attr(object, "rotation", 0.)?;
+                    map.objects.push((tiled_object, position, rotation));
+                }
+            }
+            "group" => xml_layers(layer, layer_visible, map)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Parses a `.tmx` map. Relative paths are joined to `directory`.
+pub(crate) fn parse_tmx(text: &str, directory: &str) -> Result<ParsedMap, Error> {
+    let doc =
+        roxmltree::Document::parse(text).map_err(|e| format_err!("Invalid Tiled map: {}", e))?;
+    let node = doc.root_element();
+    if !node.has_tag_name("map") {
+        return Err(format_err!("Tiled map has no `map` element"));
+    }
+    if attr(node, "infinite", 0_u8)? != 0 {
+        return Err(format_err!("Infinite Tiled maps are not supported"));
+    }
+
+    let mut map = ParsedMap {
+        data: TiledMapData {
+            orientation: orientation(node.attribute("orientation").unwrap_or("orthogonal"))?,
+            stagger_columns: node.attribute("staggeraxis") == Some("x"),
+            stagger_index: stagger_index(node.attribute("staggerindex")),
+            width: attr(node, "width", 0)?,
+            height: attr(node, "height", 0)?,
+            tile_width: attr(node, "tilewidth", 0)?,
+            tile_height: attr(node, "tileheight", 0)?,
+            properties: xml_properties(node)?,
+            ..TiledMapData::default()
+        },
+        objects: Vec::new(),
+    };
+    for tileset in elements(node).filter(|n| n.has_tag_name("tileset")) {
+        let source = match tileset.attribute("source") {
+            Some(source) => TiledTilesetSource::External(join_path(directory, source)),
+            None => TiledTilesetSource::Embedded(xml_tileset(tileset, directory)?),
+        };
+        map.data.tilesets.push(TiledTilesetRef {
+            first_gid: attr(tileset, "firstgid", 1)?,
+            source,
+        });
+    }
+    xml_layers(node, true, &mut map)?;
+    Ok(map)
+}
+
+/// Parses a `.tsx` tileset. Relative paths are joined to `directory`.
+pub(crate) fn parse_tsx(text: &str, directory: &str) -> Result<TiledTileset, Error> {
+    let doc = roxmltree::Document::parse(text)
+        .map_err(|e| format_err!("Invalid Tiled tileset: {}", e))?;
+    let node = doc.root_element();
+    if !node.has_tag_name("tileset") {
+        return Err(format_err!("Tiled tileset has no `tileset` element"));
+    }
+    xml_tileset(node, directory)
+}
+
+// JSON
+
+fn json_str<'a>(value: &'a Value, key: &str) -> &'a str {
+    value.get(key).and_then(Value::as_str).unwrap_or("")
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn json_u32(value: &Value, key: &str) -> u32 {
+    value.get(key).and_then(Value::as_u64).unwrap_or(0) as u32
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn json_f32(value: &Value, key: &str) -> f32 {
+    value.get(key).and_then(Value::as_f64).unwrap_or(0.) as f32
+}
+
+fn json_bool(value: &Value, key: &str, default: bool) -> bool {
+    value.get(key).and_then(Value::as_bool).unwrap_or(default)
+}
+
+fn json_array<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .map_or(&[], Vec::as_slice)
+}
+
+fn json_properties(value: &Value) -> Result<TiledProperties, Error> {
+    let mut properties = HashMap::new();
+    for property_value in json_array(value, "properties") {
+        let kind = property_value
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or("string");
+        let tiled_property = match property_value.get("value") {
+            Some(Value::Bool(value)) => TiledProperty::Bool(*value),
+            Some(Value::String(value)) => property(kind, value)?,
+            Some(Value::Number(number)) => match number.as_i64() {
+                Some(value) if kind != "float" => TiledProperty::Int(value),
+                _ => TiledProperty::Float(number.as_f64().unwrap_or(0.)),
+            },
+            // Class properties hold their members as an object.
+            Some(value) => TiledProperty::String(value.to_string()),
+            None => TiledProperty::String(String::new()),
+        };
+        properties.insert(json_str(property_value, "name").to_string(), tiled_property);
+    }
+    Ok(properties)
+}
+
+fn json_tileset(value: &Value, directory: &str) -> Result<TiledTileset, Error> {
+    let image = value
+        .get("image")
+        .and_then(Value::as_str)
+        .map(|source| TiledImage {
+            source: join_path(directory, source),
+            width: json_u32(value, "imagewidth"),
+            height: json_u32(value, "imageheight"),
+        });
+
+    let mut tile_properties = HashMap::new();
+    let mut tile_animations = HashMap::new();
+    for tile in json_array(value, "tiles") {
+        let properties = json_properties(tile)?;
+        if !properties.is_empty() {
+            tile_properties.insert(json_u32(tile, "id"), properties);
+        }
+        let frames = json_array(tile, "animation")
+            .iter()
+            .map(|frame| TileAnimationFrame {
+                sprite: json_u32(frame, "tileid") as usize,
+                duration: json_f32(frame, "duration") / 1000.,
+            })
+            .collect::<Vec<_>>();
+        if !frames.is_empty() {
+            tile_animations.insert(json_u32(tile, "id"), animation_of(frames));
+        }
+    }
+
+    Ok(TiledTileset {
+        name: json_str(value, "name").to_string(),
+        tile_width: json_u32(value, "tilewidth"),
+        tile_height: json_u32(value, "tileheight"),
+        spacing: json_u32(value, "spacing"),
+        margin: json_u32(value, "margin"),
+        tile_count: json_u32(value, "tilecount"),
+        columns: json_u32(value, "columns"),
+        image,
+        properties: json_properties(value)?,
+        tile_properties,
+        tile_animations,
+    })
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn json_layers(value: &Value, visible: bool, map: &mut ParsedMap) -> Result<(), Error> {
+    for layer in json_array(value, "layers") {
+        let name = json_str(layer, "name").to_string();
+        let layer_visible = visible && json_bool(layer, "visible", true);
+        match json_str(layer, "type") {
+            "tilelayer" => {
+                let gids = match layer.get("data") {
+                    Some(Value::String(text)) => decode_gids(
+                        text,
+                        json_str(layer, "encoding"),
+                        json_str(layer, "compression"),
+                    )?,
+                    Some(Value::Array(gids)) => gids
+                        .iter()
+                        .map(|gid| gid.as_u64().unwrap_or(0) as u32)
+                        .collect(),
+                    _ => return Err(format_err!("Tiled layer `{}` has no data", name)),
+                };
+                let tile_layer = TiledTileLayer {
+                    name,
+                    visible: layer_visible,
+                    gids,
+                    properties: json_properties(layer)?,
+                };
+                check_layer_size(&tile_layer, &map.data)?;
+                map.data.layers.push(tile_layer);
+            }
+            "objectgroup" => {
+                for object in json_array(layer, "objects") {
+                    let class = object
+                        .get("class")
+                        .or_else(|| object.get("type"))
+                        .and_then(Value::as_str)
+                        .unwrap_or("");
+                    let tiled_object = TiledObject {
+                        id: json_u32(object, "id"),
+                        name: json_str(object, "name").to_string(),
+                        class: class.to_string(),
+                        layer: name.clone(),
+                        width: json_f32(object, "width"),
+                        height: json_f32(object, "height"),
+                        gid: object.get("gid").map(|_| json_u32(object, "gid")),
+                        visible: layer_visible && json_bool(object, "visible", true),
+                        properties: json_properties(object)?,
+                    };
+                    let position = [json_f32(object, "x"), json_f32(object, "y")];
+                    let rotation = json_f32(object, "rotation");
+                    map.objects.push((tiled_object, position, rotation));
+                }
+            }
+            "group" => json_layers(layer, layer_visible, map)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Parses a `.tmj` map. Relative paths are joined to `directory`.
+pub(crate) fn parse_tmj(text: &str, directory: &str) -> Result<ParsedMap, Error> {
+    let value: Value =
+        serde_json::from_str(text).map_err(|e| format_err!("Invalid Tiled map: {}", e))?;
+    if json_bool(&value, "infinite", false) {
+        return Err(format_err!("Infinite Tiled maps are not supported"));
+    }
+
+    let orientation_name = value
+        .get("orientation")
+        .and_then(Value::as_str)
+        .unwrap_or("orthogonal");
+    let mut map = ParsedMap {
+        data: TiledMapData {
+            orientation: orientation(orientation_name)?,
+            stagger_columns: value.get("staggeraxis").and_then(Value::as_str) == Some("x"),
+            stagger_index: stagger_index(value.get("staggerindex").and_then(Value::as_str)),
+            width: json_u32(&value, "width"),
+            height: json_u32(&value, "height"),
+            tile_width: json_u32(&value, "tilewidth"),
+            tile_height: json_u32(&value, "tileheight"),
+            properties: json_properties(&value)?,
+            ..TiledMapData::default()
+        },
+        objects: Vec::new(),
+    };
+    for tileset in json_array(&value, "tilesets") {
+        let source = match tileset.get("source").and_then(Value::as_str) {
+            Some(source) => TiledTilesetSource::External(join_path(directory, source)),
+            None => TiledTilesetSource::Embedded(json_tileset(tileset, directory)?),
+        };
+        map.data.tilesets.push(TiledTilesetRef {
+            first_gid: json_u32(tileset, "firstgid"),
+            source,
+        });
+    }
+    json_layers(&value, true, &mut map)?;
+    Ok(map)
+}
+
+/// Parses a `.tsj` tileset. Relative paths are joined to `directory`.
+pub(crate) fn parse_tsj(text: &str, directory: &str) -> Result<TiledTileset, Error> {
+    let value: Value =
+        serde_json::from_str(text).map_err(|e| format_err!("Invalid Tiled tileset: {}", e))?;
+    json_tileset(&value, directory)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::{IsometricLayout, Projection};
+
+    const TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="16" infinite="0">
+ <properties>
+  <property name="music" type="file" value="town.ogg"/>
+ </properties>
+ <tileset firstgid="1" source="../tilesets/town.tsx"/>
+ <tileset firstgid="101" name="items" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="items.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">
+1,2,3,
+4,2147483653,0
+</data>
+ </layer>
+ <group name="decor" visible="0">
+  <layer id="2" name="props" width="3" height="2">
+   <data>
+    <tile gid="101"/><tile/><tile/><tile/><tile/><tile gid="102"/>
+   </data>
+  </layer>
+ </group>
+ <objectgroup id="3" name="spawns">
+  <object id="7" name="player" type="Spawn" x="24" y="8" width="16" height="16" rotation="90">
+   <properties>
+    <property name="health" type="int" value="3"/>
+    <property name="hero" type="bool" value="true"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+    #[test]
+    fn joins_paths() {
+        assert_eq!(
+            join_path("maps/town", "../tilesets/a.tsx"),
+            "maps/tilesets/a.tsx"
+        );
+        assert_eq!(join_path("", "./a.png"), "a.png");
+    }
+
+    #[test]
+    fn parses_tmx() {
+        let map = parse_tmx(TMX, "maps").unwrap();
+        let data = &map.data;
+        assert_eq!((data.width, data.height), (3, 2));
+        assert_eq!((data.tile_width, data.tile_height), (16, 16));
+        assert_eq!(
+            data.properties["music"],
+            TiledProperty::String("town.ogg".to_string())
+        );
+
+        assert_eq!(data.tilesets.len(), 2);
+        assert_eq!(
+            data.tilesets[0].source,
+            TiledTilesetSource::External("tilesets/town.tsx".to_string())
+        );
+        match &data.tilesets[1].source {
+            TiledTilesetSource::Embedded(tileset) => {
+                assert_eq!(data.tilesets[1].first_gid, 101);
+                assert_eq!((tileset.tile_count, tileset.columns), (4, 2));
+                assert_eq!(tileset.image.as_ref().unwrap().source, "maps/items.png");
+            }
+            source => panic!("Unexpected tileset source {:?}", source),
+        }
+
+        assert_eq!(data.layers.len(), 2);
+        assert_eq!(data.layers[0].gids, vec![1, 2, 3, 4, 0x8000_0005, 0]);
+        assert!(data.layers[0].visible);
+        assert_eq!(data.layers[1].name, "props");
+        assert_eq!(data.layers[1].gids, vec![101, 0, 0, 0, 0, 102]);
+        assert!(!data.layers[1].visible);
+
+        let (object, position, rotation) = &map.objects[0];
+        assert_eq!((object.id, object.name.as_str()), (7, "player"));
+        assert_eq!(
+            (object.class.as_str(), object.layer.as_str()),
+            ("Spawn", "spawns")
+        );
+        assert_eq!((*position, *rotation), ([24., 8.], 90.));
+        assert_eq!(object.properties["health"], TiledProperty::Int(3));
+        assert_eq!(object.properties["hero"], TiledProperty::Bool(true));
+    }
+
+    #[test]
+    fn parses_compressed_layers() {
+        let gids: Vec<u8> = [1_u32, 0, 3, 0x4000_0002]
+            .iter()
+            .flat_map(|gid| gid.to_le_bytes().to_vec())
+            .collect();
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&gids).unwrap();
+        let zlib = base64::encode(encoder.finish().unwrap());
+
+        assert_eq!(
+            decode_gids(&zlib, "base64", "zlib").unwrap(),
+            vec![1, 0, 3, 0x4000_0002]
+        );
+        assert_eq!(
+            decode_gids(&base64::encode(&gids), "base64", "").unwrap(),
+            vec![1, 0, 3, 0x4000_0002]
+        );
+        assert!(decode_gids(&zlib, "base64", "zstd").is_err());
+    }
+
+    #[test]
+    fn parses_tsx() {
+        let tileset = parse_tsx(
+            r#"<tileset name="town" tilewidth="16" tileheight="16" spacing="1" margin="2" tilecount="6" columns="3">
+ <image source="town.png" width="53" height="36"/>
+ <tile id="4"><properties><property name="solid" type="bool" value="true"/></properties></tile>
+ <tile id="5"><animation><frame tileid="5" duration="100"/><frame tileid="3" duration="250"/></animation></tile>
+</tileset>"#,
+            "tilesets",
+        )
+        .unwrap();
+        assert_eq!(tileset.name, "town");
+        assert_eq!((tileset.spacing, tileset.margin), (1, 2));
+        assert_eq!(tileset.image.unwrap().source, "tilesets/town.png");
+        assert_eq!(
+            tileset.tile_properties[&4]["solid"],
+            TiledProperty::Bool(true)
+        );
+        assert_eq!(
+            tileset.tile_animations[&5].frames[1],
+            TileAnimationFrame {
+                sprite: 3,
+                duration: 0.25
+            }
+        );
+        assert!(!tileset.tile_properties.contains_key(&5));
+    }
+
+    #[test]
+    fn parses_tmj() {
+        let map = parse_tmj(
+            r#"{
+ "orientation": "isometric", "width": 2, "height": 1, "tilewidth": 32, "tileheight": 16,
+ "infinite": false,
+ "tilesets": [{ "firstgid": 1, "source": "town.tsj" }],
+ "layers": [
+  { "type": "tilelayer", "name": "ground", "data": [1, 2], "visible": true },
+  { "type": "objectgroup", "name": "spawns", "objects": [
+   { "id": 1, "name": "chest", "class": "Chest", "gid": 2, "x": 32, "y": 16,
+     "width": 32, "height": 16, "rotation": 0, "visible": true,
+     "properties": [{ "name": "gold", "type": "float", "value": 2.5 }] }
+  ] }
+ ]
+}"#,
+            "maps",
+        )
+        .unwrap();
+        assert_eq!(map.data.orientation, TiledOrientation::Isometric);
+        assert_eq!(
+            map.data.projection(),
+            Some(Projection::Isometric(IsometricLayout::Diamond))
+        );
+        assert_eq!(
+            map.data.tilesets[0].source,
+            TiledTilesetSource::External("maps/town.tsj".to_string())
+        );
+        assert_eq!(map.data.layers[0].gids, vec![1, 2]);
+        let (object, position, _) = &map.objects[0];
+        assert_eq!((object.class.as_str(), object.gid), ("Chest", Some(2)));
+        assert_eq!(*position, [32., 16.]);
+        assert_eq!(object.properties["gold"], TiledProperty::Float(2.5));
+    }
+
+    #[test]
+    fn rejects_infinite_and_mismatched_maps() {
+        assert!(parse_tmj(r#"{ "infinite": true }"#, "").is_err());
+        assert!(parse_tmx(
+            r#"<map width="2" height="2"><layer name="a"><data encoding="csv">1,2</data></layer></map>"#,
+            ""
+        )
+        .is_err());
+        assert!(parse_tmx(
+            r#"<map width="65536" height="65536"><layer name="a"><data encoding="csv"></data></layer></map>"#,
+            ""
+        )
+        .is_err());
+    }
+}
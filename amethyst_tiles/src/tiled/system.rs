@@ -0,0 +1,356 @@
+//! Spawning of the `TileMap`s of Tiled maps.
+
+use std::collections::HashMap;
+
+use amethyst_assets::{AssetStorage, DefaultLoader, Handle, Loader, ProcessingQueue};
+use amethyst_core::{
+    ecs::{
+        maybe_changed, CommandBuffer, Entity, IntoQuery, ParallelRunnable, System, SystemBuilder,
+    },
+    math::{Point3, Vector3},
+    transform::{Parent, Transform},
+};
+use amethyst_rendy::{sprite::Sprites, SpriteSheet};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{TiledMapData, TiledTile, TiledTileset, TiledTilesetSource};
+use crate::{
+    editor::{despawn, flipped_sprites, TileGrid},
+    MapStorage, Projection, TileAnimation, TileAnimationFrame, TileAnimations, TileMap,
+};
+
+/// Spawns a `TileMap<TiledTile>` child of each entity with a `TiledMapData` per tileset of the
+/// map, once its tilesets are loaded, and spawns them again when the `TiledMapData` changes or a
+/// tileset is reloaded.
+#[derive(Debug, Default)]
+pub struct TiledMapSystem {
+    /// The tilesets being loaded, by map entity.
+    loading: HashMap<Entity, Vec<Handle<TiledTileset>>>,
+    /// The spawned `TileMap`s, by map entity.
+    spawned: HashMap<Entity, SpawnedMap>,
+}
+
+/// The spawned `TileMap`s of a map.
+#[derive(Debug)]
+struct SpawnedMap {
+    /// The `TileMap` entities.
+    children: Vec<Entity>,
+    /// The tilesets of the map, with the versions the `TileMap`s were spawned from.
+    tilesets: Vec<(Handle<TiledTileset>, u32)>,
+}
+
+impl System for TiledMapSystem {
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("TiledMapSystem")
+                .read_resource::<DefaultLoader>()
+                .read_resource::<AssetStorage<TiledTileset>>()
+                .read_resource::<ProcessingQueue<TiledTileset>>()
+                .read_resource::<ProcessingQueue<Sprites>>()
+                .read_resource::<ProcessingQueue<SpriteSheet>>()
+                .with_query(
+                    <(Entity, &TiledMapData)>::query().filter(maybe_changed::<TiledMapData>()),
+                )
+                .with_query(<(Entity, &TiledMapData)>::query())
+                .build(
+                    move |commands,
+                          world,
+                          (loader, tileset_storage, tileset_queue, sprites_queue, sheet_queue),
+                          (changed_maps, maps)| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("tiled_map_system");
+
+                        for (entity, data) in changed_maps.iter(world) {
+                            despawn(
+                                commands,
+                                self.spawned.remove(entity).map(|map| map.children),
+                            );
+                            let tilesets =
+                                data.tilesets
+                                    .iter()
+                                    .map(|tileset| match &tileset.source {
+                                        TiledTilesetSource::External(path) => loader.load(path),
+                                        TiledTilesetSource::Embedded(tileset) => loader
+                                            .load_from_data(tileset.clone(), (), tileset_queue),
+                                    })
+                                    .collect();
+                            self.loading.insert(*entity, tilesets);
+                        }
+
+                        // Forget the maps which were deleted or lost their `TiledMapData`.
+                        self.loading
+                            .retain(|entity, _| maps.get(world, *entity).is_ok());
+                        let removed = self
+                            .spawned
+                            .keys()
+                            .filter(|entity| maps.get(world, **entity).is_err())
+                            .copied()
+                            .collect::<Vec<_>>();
+                        for entity in removed {
+                            despawn(
+                                commands,
+                                self.spawned.remove(&entity).map(|map| map.children),
+                            );
+                        }
+
+                        // Spawn the maps again once their reloaded tilesets are loaded.
+                        let reloaded = self
+                            .spawned
+                            .iter()
+                            .filter(|(_, map)| {
+                                map.tilesets.iter().any(|(tileset, version)| {
+                                    tileset_storage.get_version(tileset) != Some(*version)
+                                })
+                            })
+                            .map(|(entity, _)| *entity)
+                            .collect::<Vec<_>>();
+                        for entity in reloaded {
+                            if let Some(map) = self.spawned.remove(&entity) {
+                                despawn(commands, Some(map.children));
+                                let tilesets = map.tilesets.into_iter().map(|(tileset, _)| tileset);
+                                self.loading.insert(entity, tilesets.collect());
+                            }
+                        }
+
+                        let loaded = self
+                            .loading
+                            .iter()
+                            .filter(|(_, tilesets)| {
+                                tilesets
+                                    .iter()
+                                    .all(|tileset| tileset_storage.get(tileset).is_some())
+                            })
+                            .map(|(entity, _)| *entity)
+                            .collect::<Vec<_>>();
+                        for entity in loaded {
+                            let tilesets = self.loading.remove(&entity).unwrap_or_default();
+                            if let Ok(map) = maps.get(world, entity) {
+                                let children = spawn_tile_maps(
+                                    commands,
+                                    loader,
+                                    tileset_storage,
+                                    sprites_queue,
+                                    sheet_queue,
+                                    map,
+                                    &tilesets,
+                                );
+                                let tilesets = tilesets
+                                    .into_iter()
+                                    .map(|tileset| {
+                                        let version =
+                                            tileset_storage.get_version(&tileset).unwrap_or(0);
+                                        (tileset, version)
+                                    })
+                                    .collect();
+                                self.spawned
+                                    .insert(entity, SpawnedMap { children, tilesets });
+                            }
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+/// Spawns the `TileMap` children of a map entity, one per tileset with an image.
+fn spawn_tile_maps(
+    commands: &mut CommandBuffer,
+    loader: &DefaultLoader,
+    tileset_storage: &AssetStorage<TiledTileset>,
+    sprites_queue: &ProcessingQueue<Sprites>,
+    sheet_queue: &ProcessingQueue<SpriteSheet>,
+    (entity, data): (&Entity, &TiledMapData),
+    tilesets: &[Handle<TiledTileset>],
+) -> Vec<Entity> {
+    let projection = data.projection().unwrap_or_else(|| {
+        log::warn!("Tiled staggered map with staggered columns is drawn orthogonally");
+        Projection::Orthogonal
+    });
+
+    let mut children = Vec::with_capacity(tilesets.len());
+    for (tileset_ref, handle) in data.tilesets.iter().zip(tilesets) {
+        let tileset = match tileset_storage.get(handle) {
+            Some(tileset) => tileset,
+            None => continue,
+        };
+        let image = match &tileset.image {
+            Some(image) => image,
+            None => {
+                log::warn!(
+                    "Tiled tileset `{}` has no image and is not drawn",
+                    tileset.name
+                );
+                continue;
+            }
+        };
+        let sprite_sheet = SpriteSheet {
+            texture: loader.load(&image.source),
+            sprites: loader.load_from_data(
+                flipped_sprites(&tileset_grid(tileset)),
+                (),
+                sprites_queue,
+            ),
+        };
+        let mut map = tileset_map(data, tileset_ref.first_gid, tileset).with_projection(projection);
+        map.sprite_sheet = Some(loader.load_from_data(sprite_sheet, (), sheet_queue));
+        let child = commands.push((map, Transform::default(), Parent(*entity)));
+        if !tileset.tile_animations.is_empty() {
+            commands.add_component(child, tileset_animations(tileset));
+        }
+        children.push(child);
+    }
+    children
+}
+
+/// Returns the grid of the tiles of a tileset, whose flipped sprites are in the order expected
+/// by `TiledTile::from_gid`.
+fn tileset_grid(tileset: &TiledTileset) -> TileGrid {
+    let (texture_width, texture_height) = tileset
+        .image
+        .as_ref()
+        .map_or((0, 0), |image| (image.width, image.height));
+    TileGrid {
+        texture_width,
+        texture_height,
+        tile_width: tileset.tile_width,
+        tile_height: tileset.tile_height,
+        margin: tileset.margin,
+        spacing: tileset.spacing,
+        columns: tileset.columns,
+        count: tileset.tile_count,
+    }
+}
+
+/// Returns the animations of the tiles of a tileset, for their sprites and their flipped copies.
+pub(crate) fn tileset_animations(tileset: &TiledTileset) -> TileAnimations {
+    let mut animations = TileAnimations::default();
+    for flips in 0..4 {
+        let offset = (flips * tileset.tile_count) as usize;
+        for (tile, animation) in &tileset.tile_animations {
+            let frames = animation
+                .frames
+                .iter()
+                .filter(|frame| frame.sprite < tileset.tile_count as usize)
+                .map(|frame| TileAnimationFrame {
+                    sprite: frame.sprite + offset,
+                    duration: frame.duration,
+                })
+                .collect();
+            animations.insert(
+                *tile as usize + offset,
+                TileAnimation {
+                    frames,
+                    ..animation.clone()
+                },
+            );
+        }
+    }
+    animations
+}
+
+/// Returns the `TileMap` of the tiles of a map drawn with the tileset starting at `first_gid`,
+/// with a z-level per tile layer. The tiles of hidden layers, and of other tilesets, are empty.
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn tileset_map(
+    data: &TiledMapData,
+    first_gid: u32,
+    tileset: &TiledTileset,
+) -> TileMap<TiledTile> {
+    let mut map = TileMap::new(
+        Vector3::new(data.width, data.height, data.layers.len().max(1) as u32),
+        Vector3::new(data.tile_width, data.tile_height, 1),
+        None,
+    );
+    for (z, layer) in data.layers.iter().enumerate() {
+        if !layer.visible {
+            continue;
+        }
+        for (index, gid) in layer.gids.iter().enumerate() {
+            let tile = TiledTile::from_gid(*gid, first_gid, tileset.tile_count);
+            if tile.sprite.is_none() {
+                continue;
+            }
+            let index = index as u32;
+            let coordinates = Point3::new(index % data.width, index / data.width, z as u32);
+            if let Some(target) = map.get_mut(&coordinates) {
+                *target = tile;
+            }
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiled::{TiledImage, TiledTileLayer, FLIPPED_HORIZONTALLY};
+
+    fn tileset() -> TiledTileset {
+        TiledTileset {
+            tile_width: 16,
+            tile_height: 16,
+            spacing: 2,
+            margin: 1,
+            tile_count: 4,
+            columns: 2,
+            ..TiledTileset::default()
+        }
+    }
+
+    #[test]
+    fn grids_tiles_of_tileset_image() {
+        let mut tileset = tileset();
+        tileset.image = Some(TiledImage {
+            source: "tiles.png".to_string(),
+            width: 36,
+            height: 36,
+        });
+        let grid = tileset_grid(&tileset);
+        assert_eq!((grid.texture_width, grid.texture_height), (36, 36));
+        assert_eq!(
+            (grid.margin, grid.spacing, grid.columns, grid.count),
+            (1, 2, 2, 4)
+        );
+    }
+
+    #[test]
+    fn animates_tiles_and_flips() {
+        let mut tileset = tileset();
+        tileset
+            .tile_animations
+            .insert(1, TileAnimation::uniform(&[1, 2, 9], 0.5));
+        let animations = tileset_animations(&tileset);
+        assert_eq!(animations.len(), 4);
+        assert_eq!(animations.sprite(1, 0.6, &Point3::new(0, 0, 0)), 2);
+        assert_eq!(animations.sprite(13, 0.0, &Point3::new(0, 0, 0)), 13);
+        assert_eq!(animations.sprite(13, 0.6, &Point3::new(0, 0, 0)), 14);
+    }
+
+    #[test]
+    fn fills_tiles_of_tileset() {
+        let data = TiledMapData {
+            width: 2,
+            height: 1,
+            tile_width: 16,
+            tile_height: 16,
+            layers: vec![
+                TiledTileLayer {
+                    visible: true,
+                    gids: vec![10, 12 | FLIPPED_HORIZONTALLY],
+                    ..TiledTileLayer::default()
+                },
+                TiledTileLayer {
+                    visible: false,
+                    gids: vec![10, 10],
+                    ..TiledTileLayer::default()
+                },
+            ],
+            ..TiledMapData::default()
+        };
+        let map = tileset_map(&data, 10, &tileset());
+        assert_eq!(map.get(&Point3::new(0, 0, 0)).unwrap().sprite, Some(0));
+        assert_eq!(map.get(&Point3::new(1, 0, 0)).unwrap().sprite, Some(6));
+        assert_eq!(map.get(&Point3::new(0, 0, 1)).unwrap().sprite, None);
+    }
+}
@@ -6,10 +6,28 @@ layout(set = 1, binding = 0) uniform sampler2D tex;
 layout(location = 0) in vec2 in_tex_coords;
 layout(location = 1) in vec4 in_color;
 layout(location = 2) in vec4 in_color_bias;
+// The center, start angle and amount of the radial fill, the amount is negative counterclockwise.
+layout(location = 3) flat in vec4 in_radial_fill;
+layout(location = 4) in vec2 in_position;
 
 layout(location = 0) out vec4 out_color;
 
+const float TAU = 6.28318530718;
+
 void main() {
+    float amount = abs(in_radial_fill.w);
+    if (amount < 1.0) {
+        vec2 from_center = in_position - in_radial_fill.xy;
+        // Clockwise from the top, from the start angle.
+        float angle = atan(from_center.x, from_center.y) - in_radial_fill.z;
+        if (in_radial_fill.w < 0.0) {
+            angle = -angle;
+        }
+        if (mod(angle, TAU) >= amount * TAU) {
+            discard;
+        }
+    }
+
     vec4 color = (texture(tex, in_tex_coords) + in_color_bias) * in_color;
     if (color.a == 0.0) {
         discard;
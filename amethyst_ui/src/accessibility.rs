@@ -0,0 +1,646 @@
+//! Module exporting the semantics of the ui to assistive technologies.
+
+use std::{cmp::Ordering, collections::HashMap, marker::PhantomData};
+
+use amethyst_core::{
+    ecs::{component, maybe_changed, Entity, IntoQuery, ParallelRunnable, System, SystemBuilder},
+    transform::Parent,
+    Hidden, HiddenPropagate,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{Interactable, Selectable, Selected, TextEditing, UiText, UiTransform};
+
+/// The role of an accessible ui element, telling assistive technologies how to present it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccessRole {
+    /// An element activated by clicking it.
+    Button,
+    /// A text which can't be edited.
+    Label,
+    /// An editable text field.
+    TextInput,
+    /// An image conveying information.
+    Image,
+    /// An element grouping other elements.
+    Group,
+}
+
+/// Overrides the accessibility node inferred from the components of an ui element.
+///
+/// Elements are part of the accessibility tree if they have a `UiText`, an `Interactable` or a
+/// `Selectable` component. Adding this component to any other element with a `UiTransform` adds
+/// it to the tree as well.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UiAccessible {
+    /// The role of the element, `None` infers it from its components.
+    pub role: Option<AccessRole>,
+    /// The name read to the user, `None` infers it from the text of the element.
+    pub name: Option<String>,
+    /// Removes the element, but not its children, from the accessibility tree.
+    pub ignored: bool,
+}
+
+impl UiAccessible {
+    /// Creates an `UiAccessible` with the given role.
+    #[must_use]
+    pub fn new(role: AccessRole) -> Self {
+        Self {
+            role: Some(role),
+            ..Self::default()
+        }
+    }
+
+    /// Set the name read to the user.
+    #[must_use]
+    pub fn with_name<S: ToString>(mut self, name: &S) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Creates an `UiAccessible` removing a decorative element from the accessibility tree.
+    #[must_use]
+    pub fn ignored() -> Self {
+        Self {
+            ignored: true,
+            ..Self::default()
+        }
+    }
+}
+
+/// A node of the accessibility tree, one for each accessible ui element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessNode {
+    /// The role of the element.
+    pub role: AccessRole,
+    /// The name of the element, such as the text of a button.
+    pub name: Option<String>,
+    /// The value of the element, such as the content of a text field.
+    pub value: Option<String>,
+    /// The closest accessible ancestor of the element.
+    pub parent: Option<Entity>,
+    /// The accessible elements having this one as parent, in reading order.
+    pub children: Vec<Entity>,
+    /// Left, bottom, right and top edges of the element, in physical pixels from the bottom left
+    /// of the screen.
+    pub bounds: [f32; 4],
+    /// Whether the element can receive the focus.
+    pub focusable: bool,
+}
+
+/// Changes of the accessibility tree, modeled after the `TreeUpdate` of AccessKit.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccessTreeUpdate {
+    /// The nodes added or changed since the previous update.
+    pub nodes: Vec<(Entity, AccessNode)>,
+    /// The nodes removed since the previous update.
+    pub removed: Vec<Entity>,
+    /// The nodes without accessible parent, in reading order. `None` if they did not change.
+    pub roots: Option<Vec<Entity>>,
+    /// The focused node.
+    pub focus: Option<Entity>,
+}
+
+/// Receives the accessibility tree of the ui, to forward it to the accessibility API of the
+/// platform, for example through AccessKit.
+///
+/// The adapter is a resource, inserted by the `UiAccessibilityBundle`.
+pub trait AccessibilityAdapter: Send + Sync + 'static {
+    /// Applies the changes since the previous update. The first update contains the whole tree.
+    fn update(&mut self, update: AccessTreeUpdate);
+}
+
+/// An `AccessibilityAdapter` keeping the tree in memory, to inspect it without a platform
+/// accessibility API, such as in tests.
+#[derive(Debug, Default)]
+pub struct InMemoryAccessAdapter {
+    nodes: HashMap<Entity, AccessNode>,
+    roots: Vec<Entity>,
+    focus: Option<Entity>,
+    update_count: usize,
+}
+
+impl InMemoryAccessAdapter {
+    /// Returns the node of `entity`, if it's accessible.
+    #[must_use]
+    pub fn node(&self, entity: Entity) -> Option<&AccessNode> {
+        self.nodes.get(&entity)
+    }
+
+    /// Returns the first node named `name`.
+    #[must_use]
+    pub fn find_by_name(&self, name: &str) -> Option<(Entity, &AccessNode)> {
+        self.nodes
+            .iter()
+            .find(|(_, node)| node.name.as_deref() == Some(name))
+            .map(|(entity, node)| (*entity, node))
+    }
+
+    /// Returns the nodes without accessible parent, in reading order.
+    #[must_use]
+    pub fn roots(&self) -> &[Entity] {
+        &self.roots
+    }
+
+    /// Returns the focused node.
+    #[must_use]
+    pub fn focus(&self) -> Option<Entity> {
+        self.focus
+    }
+
+    /// Returns the number of nodes in the tree.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if the tree has no nodes.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the number of updates received.
+    #[must_use]
+    pub fn update_count(&self) -> usize {
+        self.update_count
+    }
+}
+
+impl AccessibilityAdapter for InMemoryAccessAdapter {
+    fn update(&mut self, update: AccessTreeUpdate) {
+        for entity in &update.removed {
+            self.nodes.remove(entity);
+        }
+        self.nodes.extend(update.nodes);
+        if let Some(roots) = update.roots {
+            self.roots = roots;
+        }
+        self.focus = update.focus;
+        self.update_count += 1;
+    }
+}
+
+/// Orders elements from top to bottom, then from left to right.
+fn reading_order(a: &AccessNode, b: &AccessNode) -> Ordering {
+    b.bounds[3]
+        .partial_cmp(&a.bounds[3])
+        .and_then(|order| {
+            if order == Ordering::Equal {
+                a.bounds[0].partial_cmp(&b.bounds[0])
+            } else {
+                Some(order)
+            }
+        })
+        .expect("Unexpected NaN")
+}
+
+/// Fills the children of `nodes` from their parent and returns the roots, in reading order.
+fn link_children(nodes: &mut HashMap<Entity, AccessNode>) -> Vec<Entity> {
+    let mut children: HashMap<Entity, Vec<Entity>> = HashMap::new();
+    let mut roots = Vec::new();
+    for (entity, node) in nodes.iter() {
+        match node.parent {
+            Some(parent) if nodes.contains_key(&parent) => {
+                children.entry(parent).or_default().push(*entity)
+            }
+            _ => roots.push(*entity),
+        }
+    }
+
+    for (parent, mut entities) in children {
+        entities.sort_by(|a, b| reading_order(&nodes[a], &nodes[b]));
+        if let Some(node) = nodes.get_mut(&parent) {
+            node.children = entities;
+        }
+    }
+    roots.sort_by(|a, b| reading_order(&nodes[a], &nodes[b]));
+    roots
+}
+
+/// The accessibility tree sent last, to send only its changes.
+#[derive(Debug, Default)]
+pub(crate) struct AccessTree {
+    nodes: HashMap<Entity, AccessNode>,
+    roots: Vec<Entity>,
+    focus: Option<Entity>,
+    sent: bool,
+}
+
+impl AccessTree {
+    /// Replaces the tree with `nodes`, whose children are filled from their parent.
+    /// Returns the changes, or `None` if nothing changed.
+    pub(crate) fn update(
+        &mut self,
+        mut nodes: HashMap<Entity, AccessNode>,
+        focus: Option<Entity>,
+    ) -> Option<AccessTreeUpdate> {
+        let roots = link_children(&mut nodes);
+        let focus = focus.filter(|entity| nodes.contains_key(entity));
+
+        let changed: Vec<(Entity, AccessNode)> = nodes
+            .iter()
+            .filter(|(entity, node)| self.nodes.get(entity) != Some(node))
+            .map(|(entity, node)| (*entity, node.clone()))
+            .collect();
+        let removed: Vec<Entity> = self
+            .nodes
+            .keys()
+            .filter(|entity| !nodes.contains_key(entity))
+            .copied()
+            .collect();
+        let roots_changed = roots != self.roots || !self.sent;
+
+        if changed.is_empty() && removed.is_empty() && !roots_changed && focus == self.focus {
+            return None;
+        }
+
+        self.nodes = nodes;
+        self.focus = focus;
+        self.sent = true;
+        Some(AccessTreeUpdate {
+            nodes: changed,
+            removed,
+            roots: if roots_changed {
+                self.roots = roots.clone();
+                Some(roots)
+            } else {
+                None
+            },
+            focus,
+        })
+    }
+
+    /// Moves the focus without changing the nodes.
+    /// Returns the change, or `None` if the focus did not change.
+    pub(crate) fn update_focus(&mut self, focus: Option<Entity>) -> Option<AccessTreeUpdate> {
+        let focus = focus.filter(|entity| self.nodes.contains_key(entity));
+        if focus == self.focus {
+            return None;
+        }
+        self.focus = focus;
+        Some(AccessTreeUpdate {
+            focus,
+            ..AccessTreeUpdate::default()
+        })
+    }
+}
+
+/// An accessible ui element, before it's linked to its accessible parent.
+#[derive(Debug, Clone)]
+struct AccessElement {
+    node: AccessNode,
+    /// Whether the element has a `UiText`, buttons without one are named after their label.
+    has_text: bool,
+}
+
+/// Returns the accessible element of an ui element with the given components, `None` if it's not
+/// part of the accessibility tree.
+fn access_element(
+    transform: &UiTransform,
+    text: Option<&UiText>,
+    editing: Option<&TextEditing>,
+    interactable: Option<&Interactable>,
+    selectable: bool,
+    accessible: Option<&UiAccessible>,
+) -> Option<AccessElement> {
+    if text.is_none() && interactable.is_none() && !selectable && accessible.is_none() {
+        return None;
+    }
+    if accessible.map_or(false, |accessible| accessible.ignored) {
+        return None;
+    }
+
+    let role = accessible
+        .and_then(|a| a.role)
+        .unwrap_or(if editing.is_some() {
+            AccessRole::TextInput
+        } else if interactable.is_some() || selectable {
+            AccessRole::Button
+        } else if text.is_some() {
+            AccessRole::Label
+        } else {
+            AccessRole::Group
+        });
+    let content = text.map(|text| {
+        if text.password {
+            "*".repeat(text.text.chars().count())
+        } else {
+            text.text.clone()
+        }
+    });
+    let (name, value) = if role == AccessRole::TextInput {
+        (accessible.and_then(|a| a.name.clone()), content)
+    } else {
+        (accessible.and_then(|a| a.name.clone()).or(content), None)
+    };
+
+    let half_width = transform.pixel_width * 0.5;
+    let half_height = transform.pixel_height * 0.5;
+    Some(AccessElement {
+        node: AccessNode {
+            role,
+            name,
+            value,
+            parent: None,
+            children: Vec::new(),
+            bounds: [
+                transform.pixel_x - half_width,
+                transform.pixel_y - half_height,
+                transform.pixel_x + half_width,
+                transform.pixel_y + half_height,
+            ],
+            focusable: selectable || editing.is_some(),
+        },
+        has_text: text.is_some(),
+    })
+}
+
+/// Returns the nodes of `elements` linked to the closest of their ancestors which is accessible,
+/// walking up the hierarchy with `parent_of`.
+///
+/// Labels of unnamed buttons name the button instead of being nodes.
+fn link_elements<F>(
+    elements: &HashMap<Entity, AccessElement>,
+    mut parent_of: F,
+) -> HashMap<Entity, AccessNode>
+where
+    F: FnMut(Entity) -> Option<Entity>,
+{
+    let mut nodes: HashMap<Entity, AccessNode> = elements
+        .iter()
+        .map(|(entity, element)| (*entity, element.node.clone()))
+        .collect();
+
+    let linked: Vec<(Entity, Option<Entity>)> = nodes
+        .keys()
+        .map(|entity| {
+            let mut current = *entity;
+            while let Some(parent) = parent_of(current) {
+                if nodes.contains_key(&parent) {
+                    return (*entity, Some(parent));
+                }
+                current = parent;
+            }
+            (*entity, None)
+        })
+        .collect();
+
+    let mut merged: HashMap<Entity, Entity> = HashMap::new();
+    for (entity, parent) in &linked {
+        let parent = match parent {
+            Some(parent) => *parent,
+            None => continue,
+        };
+        let is_label = nodes[entity].role == AccessRole::Label;
+        let unnamed_button =
+            nodes[&parent].role == AccessRole::Button && !elements[&parent].has_text;
+        if is_label && unnamed_button {
+            merged.insert(*entity, parent);
+        }
+    }
+    for (label, button) in &merged {
+        if let Some(label) = nodes.remove(label) {
+            let button = nodes.get_mut(button).expect("Unreachable");
+            if button.name.is_none() {
+                button.name = label.name;
+            }
+        }
+    }
+    for (entity, parent) in linked {
+        if let Some(node) = nodes.get_mut(&entity) {
+            node.parent = parent.map(|parent| merged.get(&parent).copied().unwrap_or(parent));
+        }
+    }
+    nodes
+}
+
+/// System building the accessibility tree of the visible ui elements and sending its changes to
+/// the `AccessibilityAdapter` resource `A`.
+///
+/// Buttons are named after the text of their child labels, which are removed from the tree.
+/// The text of password fields is masked.
+///
+/// Only the elements whose components changed are read again, and the tree is linked and diffed
+/// only when an element or the hierarchy changed.
+#[derive(Debug)]
+pub struct UiAccessibilitySystem<A, G> {
+    tree: AccessTree,
+    /// The accessible elements, as of their last change.
+    elements: HashMap<Entity, AccessElement>,
+    _marker: PhantomData<(A, G)>,
+}
+
+impl<A, G> UiAccessibilitySystem<A, G> {
+    /// Creates a new `UiAccessibilitySystem`.
+    pub fn new() -> Self {
+        Self {
+            tree: AccessTree::default(),
+            elements: HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A, G> System for UiAccessibilitySystem<A, G>
+where
+    A: AccessibilityAdapter,
+    G: Send + Sync + 'static,
+{
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("UiAccessibilitySystem")
+                .write_resource::<A>()
+                .with_query(
+                    <(
+                        Entity,
+                        &UiTransform,
+                        Option<&UiText>,
+                        Option<&TextEditing>,
+                        Option<&Interactable>,
+                        Option<&Selectable<G>>,
+                        Option<&UiAccessible>,
+                    )>::query()
+                    .filter(
+                        !component::<Hidden>()
+                            & !component::<HiddenPropagate>()
+                            & (maybe_changed::<UiTransform>()
+                                | maybe_changed::<UiText>()
+                                | maybe_changed::<TextEditing>()
+                                | maybe_changed::<Interactable>()
+                                | maybe_changed::<Selectable<G>>()
+                                | maybe_changed::<UiAccessible>()),
+                    ),
+                )
+                .with_query(
+                    <&UiTransform>::query()
+                        .filter(!component::<Hidden>() & !component::<HiddenPropagate>()),
+                )
+                .with_query(
+                    <&Parent>::query()
+                        .filter(component::<UiTransform>() & maybe_changed::<Parent>()),
+                )
+                .with_query(<(Entity, &Selected)>::query())
+                .with_query(<&Parent>::query())
+                .build(
+                    move |_commands,
+                          world,
+                          adapter,
+                          (
+                        changed_elements,
+                        visible_elements,
+                        changed_parents,
+                        selected,
+                        parents,
+                    )| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("ui_accessibility_system");
+
+                        let mut dirty = !self.tree.sent;
+                        for (
+                            entity,
+                            transform,
+                            text,
+                            editing,
+                            interactable,
+                            selectable,
+                            accessible,
+                        ) in changed_elements.iter(world)
+                        {
+                            dirty = true;
+                            match access_element(
+                                transform,
+                                text,
+                                editing,
+                                interactable,
+                                selectable.is_some(),
+                                accessible,
+                            ) {
+                                Some(element) => {
+                                    self.elements.insert(*entity, element);
+                                }
+                                None => {
+                                    self.elements.remove(entity);
+                                }
+                            }
+                        }
+
+                        // Forget the elements which were deleted or hidden.
+                        let count = self.elements.len();
+                        self.elements
+                            .retain(|entity, _| visible_elements.get(world, *entity).is_ok());
+                        dirty |= self.elements.len() != count;
+                        dirty |= changed_parents.iter(world).next().is_some();
+
+                        let focus = selected
+                            .iter(world)
+                            .map(|(entity, _)| *entity)
+                            .find(|entity| self.elements.contains_key(entity));
+                        let update = if dirty {
+                            let nodes = link_elements(&self.elements, |entity| {
+                                parents.get(world, entity).ok().map(|parent| parent.0)
+                            });
+                            self.tree.update(nodes, focus)
+                        } else {
+                            self.tree.update_focus(focus)
+                        };
+                        if let Some(update) = update {
+                            adapter.update(update);
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::World;
+
+    use super::*;
+
+    fn node(role: AccessRole, name: &str, parent: Option<Entity>, top: f32) -> AccessNode {
+        AccessNode {
+            role,
+            name: Some(name.to_string()),
+            value: None,
+            parent,
+            children: Vec::new(),
+            bounds: [0., top - 10., 100., top],
+            focusable: role == AccessRole::Button,
+        }
+    }
+
+    #[test]
+    fn sends_only_changes() {
+        let mut world = World::default();
+        let (menu, play, quit) = (world.push(()), world.push(()), world.push(()));
+        let mut tree = AccessTree::default();
+        let mut adapter = InMemoryAccessAdapter::default();
+
+        let mut nodes = HashMap::new();
+        nodes.insert(menu, node(AccessRole::Group, "menu", None, 100.));
+        nodes.insert(play, node(AccessRole::Button, "play", Some(menu), 80.));
+        nodes.insert(quit, node(AccessRole::Button, "quit", Some(menu), 60.));
+        adapter.update(tree.update(nodes.clone(), Some(play)).unwrap());
+        assert_eq!(adapter.len(), 3);
+        assert_eq!(adapter.roots(), &[menu]);
+        assert_eq!(adapter.node(menu).unwrap().children, vec![play, quit]);
+        assert_eq!(adapter.focus(), Some(play));
+
+        assert!(tree.update(nodes.clone(), Some(play)).is_none());
+
+        nodes.remove(&quit);
+        nodes.get_mut(&play).unwrap().name = Some("resume".to_string());
+        let update = tree.update(nodes, Some(play)).unwrap();
+        assert_eq!(update.removed, vec![quit]);
+        assert_eq!(update.nodes.len(), 2);
+        assert!(update.roots.is_none());
+        adapter.update(update);
+        assert_eq!(adapter.find_by_name("resume").map(|(e, _)| e), Some(play));
+        assert_eq!(adapter.node(menu).unwrap().children, vec![play]);
+        assert_eq!(adapter.update_count(), 2);
+
+        let update = tree.update_focus(Some(menu)).unwrap();
+        assert!(update.nodes.is_empty() && update.removed.is_empty() && update.roots.is_none());
+        assert_eq!(update.focus, Some(menu));
+        assert!(tree.update_focus(Some(menu)).is_none());
+        // Entities outside of the tree can't be focused.
+        assert!(tree.update_focus(Some(quit)).is_none());
+    }
+
+    #[test]
+    fn names_buttons_after_their_label() {
+        let mut world = World::default();
+        let (button, label, title) = (world.push(()), world.push(()), world.push(()));
+        let element = |role, name: Option<&str>| {
+            AccessElement {
+                node: AccessNode {
+                    name: name.map(str::to_string),
+                    ..node(role, "", None, 0.)
+                },
+                has_text: name.is_some(),
+            }
+        };
+
+        let mut elements = HashMap::new();
+        elements.insert(button, element(AccessRole::Button, None));
+        elements.insert(label, element(AccessRole::Label, Some("play")));
+        elements.insert(title, element(AccessRole::Label, Some("title")));
+        let nodes = link_elements(
+            &elements,
+            |entity| {
+                if entity == label {
+                    Some(button)
+                } else {
+                    None
+                }
+            },
+        );
+
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[&button].name.as_deref(), Some("play"));
+        assert_eq!(nodes[&title].parent, None);
+    }
+}
@@ -8,22 +8,32 @@ use amethyst_core::{
     shrev::EventChannel,
 };
 use amethyst_error::Error;
+use amethyst_input::InputEvent;
 use amethyst_rendy::types::DefaultBackend;
 use derive_new::new;
 use winit::event::Event;
 
 use crate::{
+    accessibility::{AccessibilityAdapter, UiAccessibilitySystem},
     button::{ui_button_action_retrigger_event_system, UiButtonSystem},
-    drag::DragWidgetSystem,
+    context_menu::UiContextMenuSystem,
+    drag::{DragPayloadSystem, DragWidgetSystem},
     event::UiMouseSystem,
     glyphs::{GlyphTextureData, GlyphTextureProcessorSystem},
-    layout::UiTransformSystem,
+    layout::{UiScale, UiTransformSystem},
+    markup::{UiMarkupLinkSystem, UiMarkupResources},
+    modal::{UiConfirmDialogSystem, UiModalSystem},
     resize::ResizeSystem,
+    reveal::UiTextRevealSystem,
     selection::{SelectionKeyboardSystem, SelectionMouseSystem},
     selection_order_cache::CacheSelectionSystem,
     sound::{ui_sound_event_retrigger_system, UiSoundSystem},
     text::TextEditingMouseSystem,
     text_editing::TextEditingInputSystem,
+    theme::UiStyleSystem,
+    tooltip::UiTooltipSystem,
+    tween::UiTweenSystem,
+    world_anchor::UiWorldAnchorSystem,
     BlinkSystem, CachedSelectionOrderResource, UiButtonAction, UiEvent, UiLabel, UiPlaySoundAction,
     WidgetId, Widgets,
 };
@@ -57,6 +67,12 @@ where
         resources.insert(EventChannel::<UiEvent>::new());
         resources.insert(Widgets::<UiLabel, W>::new());
         resources.insert(CachedSelectionOrderResource::default());
+        if !resources.contains::<UiMarkupResources>() {
+            resources.insert(UiMarkupResources::default());
+        }
+        if !resources.contains::<UiScale>() {
+            resources.insert(UiScale::default());
+        }
 
         resources.insert(ProcessingQueue::<GlyphTextureData>::default());
         builder.add_system(GlyphTextureProcessorSystem::<DefaultBackend>::default());
@@ -94,9 +110,34 @@ where
             .get_mut::<EventChannel<UiEvent>>()
             .unwrap()
             .register_reader();
+        let markup_link_reader = resources
+            .get_mut::<EventChannel<UiEvent>>()
+            .unwrap()
+            .register_reader();
+        let text_reveal_reader = resources
+            .get_mut::<EventChannel<UiEvent>>()
+            .unwrap()
+            .register_reader();
+        let text_reveal_input_reader = resources
+            .get_mut_or_default::<EventChannel<InputEvent>>()
+            .register_reader();
+        let tooltip_reader = resources
+            .get_mut::<EventChannel<UiEvent>>()
+            .unwrap()
+            .register_reader();
+        let context_menu_reader = resources
+            .get_mut::<EventChannel<UiEvent>>()
+            .unwrap()
+            .register_reader();
+        let confirm_dialog_reader = resources
+            .get_mut::<EventChannel<UiEvent>>()
+            .unwrap()
+            .register_reader();
 
         log::debug!("Adding UI Systems to Dispatcher");
         builder
+            .add_system(UiWorldAnchorSystem::default())
+            .add_system(UiTweenSystem)
             .add_system(UiTransformSystem::new())
             .add_system(UiMouseSystem::new())
             .add_system(UiButtonSystem::new(ui_btn_reader))
@@ -108,10 +149,28 @@ where
             .add_system(SelectionMouseSystem::<G>::new(selection_mouse_reader))
             .add_system(SelectionKeyboardSystem::<G>::new(selection_keyboard_reader))
             .add_system(TextEditingInputSystem::new(text_editing_input_reader))
+            .add_system(UiStyleSystem)
             .add_system(ResizeSystem::new())
             .add_system(DragWidgetSystem::new(drag_widget_reader))
+            .add_system(UiMarkupLinkSystem::new(markup_link_reader))
+            .add_system(UiTextRevealSystem::new(
+                text_reveal_reader,
+                text_reveal_input_reader,
+            ))
+            .add_system(UiTooltipSystem::new(tooltip_reader))
+            .add_system(UiContextMenuSystem::<G, W>::new(context_menu_reader))
+            .add_system(UiModalSystem::default())
+            .add_system(UiConfirmDialogSystem::<W>::new(confirm_dialog_reader))
             .add_system(BlinkSystem);
 
+        #[cfg(feature = "locale")]
+        {
+            if !resources.contains::<amethyst_locale::ActiveLocale>() {
+                resources.insert(amethyst_locale::ActiveLocale::default());
+            }
+            builder.add_system(crate::localized::UiLocalizedTextSystem);
+        }
+
         Ok(())
     }
 
@@ -160,3 +219,99 @@ impl SystemBundle for AudioUiBundle {
         Ok(())
     }
 }
+
+/// Drag payload bundle
+///
+/// Will register the `DragPayloadSystem<T>`, emitting the drop target events of the
+/// `DragPayload<T>` components. Add one for each payload type.
+///
+/// Will fail if the `UiBundle` is not added before.
+#[derive(new, Debug, Default)]
+pub struct DragPayloadBundle<T> {
+    #[new(default)]
+    _marker: PhantomData<T>,
+}
+
+impl<T> SystemBundle for DragPayloadBundle<T>
+where
+    T: Send + Sync + 'static,
+{
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        builder.add_system(DragPayloadSystem::<T>::new(
+            resources
+                .get_mut::<EventChannel<UiEvent>>()
+                .unwrap()
+                .register_reader(),
+        ));
+        Ok(())
+    }
+}
+
+/// Accessibility UI bundle
+///
+/// Will insert the `AccessibilityAdapter` as a resource and register the
+/// `UiAccessibilitySystem`, sending it the accessibility tree of the ui.
+/// The generic type G is the selection group of the `Selectable` components, as in `UiBundle`.
+#[derive(Debug)]
+pub struct UiAccessibilityBundle<A, G = ()> {
+    adapter: Option<A>,
+    _marker: PhantomData<G>,
+}
+
+impl<A, G> UiAccessibilityBundle<A, G> {
+    /// Creates a new `UiAccessibilityBundle` sending the tree to `adapter`.
+    pub fn new(adapter: A) -> Self {
+        Self {
+            adapter: Some(adapter),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A, G> SystemBundle for UiAccessibilityBundle<A, G>
+where
+    A: AccessibilityAdapter,
+    G: Send + Sync + 'static,
+{
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        if let Some(adapter) = self.adapter.take() {
+            resources.insert(adapter);
+        }
+        builder.add_system(UiAccessibilitySystem::<A, G>::new());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_input::InputBundle;
+
+    use super::*;
+
+    #[test]
+    fn loads_without_input_event_channel() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let mut builder = DispatcherBuilder::default();
+        resources.insert(EventChannel::<Event<'static, ()>>::new());
+
+        InputBundle::new()
+            .load(&mut world, &mut resources, &mut builder)
+            .unwrap();
+        UiBundle::<u32, ()>::new()
+            .load(&mut world, &mut resources, &mut builder)
+            .unwrap();
+
+        assert!(resources.contains::<EventChannel<InputEvent>>());
+    }
+}
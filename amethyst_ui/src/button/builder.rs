@@ -15,7 +15,8 @@ use crate::{
     Anchor, FontAsset, Interactable, LineMode, Selectable, Stretch, UiButton, UiButtonAction,
     UiButtonActionRetrigger,
     UiButtonActionType::{self, SetImage, SetTextColor, UnsetTextColor, UnsetTexture},
-    UiImage, UiPlaySoundAction, UiSoundRetrigger, UiText, UiTransform, WidgetId, Widgets,
+    UiImage, UiPlaySoundAction, UiSoundRetrigger, UiStyle, UiText, UiTheme, UiTransform, WidgetId,
+    Widgets,
 };
 
 const DEFAULT_Z: f32 = 1.0;
@@ -47,6 +48,7 @@ pub struct UiButtonBuilder<G, I: WidgetId> {
     line_mode: LineMode,
     align: Anchor,
     image: Option<UiImage>,
+    style: Option<UiStyle>,
     parent: Option<Entity>,
     on_click_start_sound: Option<UiPlaySoundAction>,
     on_click_stop_sound: Option<UiPlaySoundAction>,
@@ -82,6 +84,7 @@ where
             line_mode: LineMode::Single,
             align: Anchor::Middle,
             image: None,
+            style: None,
             parent: None,
             on_click_start_sound: None,
             on_click_stop_sound: None,
@@ -238,6 +241,13 @@ impl<'a, G: PartialEq + Send + Sync + 'static, I: WidgetId> UiButtonBuilder<G, I
         self
     }
 
+    /// Style the button with the style `name` of `theme`.
+    /// The style is applied over the colors, font and images set on this builder.
+    pub fn with_style<S: ToString>(mut self, theme: Handle<UiTheme>, name: &S) -> Self {
+        self.style = Some(UiStyle::new(theme, name));
+        self
+    }
+
     /// Sound emitted when this button is hovered over
     pub fn with_hover_sound(mut self, sound: SourceHandle) -> Self {
         self.on_hover_sound = Some(UiPlaySoundAction(sound));
@@ -383,6 +393,13 @@ impl<'a, G: PartialEq + Send + Sync + 'static, I: WidgetId> UiButtonBuilder<G, I
             .expect("Unreachable: Inserting newly created entity")
             .add_component(Interactable);
 
+        if let Some(style) = self.style.take() {
+            world
+                .entry(image_entity)
+                .expect("Unreachable: Inserting newly created entity")
+                .add_component(style);
+        }
+
         if let Some(parent) = self.parent.take() {
             world
                 .entry(image_entity)
@@ -0,0 +1,407 @@
+//! Module for the `UiContextMenu` component and `UiContextMenuSystem`.
+
+use std::marker::PhantomData;
+
+use amethyst_assets::Handle;
+use amethyst_core::{
+    ecs::{component, CommandBuffer, Entity, IntoQuery, ParallelRunnable, System, SystemBuilder},
+    shrev::{EventChannel, ReaderId},
+    Hidden, HiddenPropagate,
+};
+use amethyst_input::InputHandler;
+use amethyst_window::ScreenDimensions;
+use derivative::Derivative;
+use winit::event::MouseButton;
+
+use crate::{
+    modal::modal_layer, targeted, tooltip::popup_position, Anchor, FontAsset, Interactable,
+    UiButton, UiButtonBuilder, UiEvent, UiEventType, UiImage, UiModal, UiScale, UiStyle, UiTheme,
+    UiTransform, WidgetId, Widgets,
+};
+
+const MENU_Z: f32 = 900.;
+const DEFAULT_ITEM_WIDTH: f32 = 160.;
+const DEFAULT_ITEM_HEIGHT: f32 = 28.;
+const DEFAULT_TXT_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+const DEFAULT_BKGD_COLOR: [f32; 4] = [0.82, 0.83, 0.83, 1.0];
+
+/// An entry of a `UiContextMenu`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UiContextMenuItem {
+    /// Identifies the item in the `UiEventType::ContextMenuSelect` event.
+    pub id: String,
+    /// The text of the item button.
+    pub text: String,
+}
+
+/// Opens a vertical list of `UiButton`s at the cursor when the entity this is attached to is right
+/// clicked. Clicking one of the buttons emits a `UiEventType::ContextMenuSelect` event targeting
+/// this entity. The menu is closed by clicking anywhere, or when this entity is deleted or covered
+/// by a `UiModal`.
+///
+/// The entity needs an `Interactable` component to be targeted.
+#[derive(Debug, Clone)]
+pub struct UiContextMenu {
+    /// The items of the menu, from top to bottom.
+    pub items: Vec<UiContextMenuItem>,
+    /// Width of the item buttons.
+    pub item_width: f32,
+    /// Height of the item buttons.
+    pub item_height: f32,
+    /// Font of the item buttons, `None` uses the default font.
+    pub font: Option<Handle<FontAsset>>,
+    /// Font size of the item buttons.
+    pub font_size: f32,
+    /// Text color of the item buttons.
+    pub text_color: [f32; 4],
+    /// Background color of the item buttons.
+    pub background_color: [f32; 4],
+    /// Style applied to the item buttons, over the properties above.
+    pub style: Option<UiStyle>,
+}
+
+impl Default for UiContextMenu {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            item_width: DEFAULT_ITEM_WIDTH,
+            item_height: DEFAULT_ITEM_HEIGHT,
+            font: None,
+            font_size: 16.,
+            text_color: DEFAULT_TXT_COLOR,
+            background_color: DEFAULT_BKGD_COLOR,
+            style: None,
+        }
+    }
+}
+
+impl UiContextMenu {
+    /// Adds an item to the bottom of the menu.
+    #[must_use]
+    pub fn with_item<S: ToString, T: ToString>(mut self, id: &S, text: &T) -> Self {
+        self.items.push(UiContextMenuItem {
+            id: id.to_string(),
+            text: text.to_string(),
+        });
+        self
+    }
+
+    /// Set the size of the item buttons.
+    #[must_use]
+    pub fn with_item_size(mut self, width: f32, height: f32) -> Self {
+        self.item_width = width;
+        self.item_height = height;
+        self
+    }
+
+    /// Style the item buttons with the style `name` of `theme`.
+    #[must_use]
+    pub fn with_style<S: ToString>(mut self, theme: Handle<UiTheme>, name: &S) -> Self {
+        self.style = Some(UiStyle::new(theme, name));
+        self
+    }
+}
+
+/// A button of the open `UiContextMenu`, on the image entity of the button.
+#[derive(Debug, Clone)]
+struct ContextMenuButton<I: WidgetId> {
+    owner: Entity,
+    item: String,
+    widget: I,
+    button: UiButton,
+}
+
+#[derive(Debug)]
+struct OpenMenu {
+    owner: Entity,
+    /// Left, bottom, right and top edges, in physical pixels.
+    rect: [f32; 4],
+}
+
+impl OpenMenu {
+    fn contains(&self, (x, y): (f32, f32)) -> bool {
+        x >= self.rect[0] && y >= self.rect[1] && x <= self.rect[2] && y <= self.rect[3]
+    }
+}
+
+/// System opening and closing `UiContextMenu`s.
+///
+/// It's automatically registered with the `UiBundle`.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct UiContextMenuSystem<G, I: WidgetId> {
+    event_reader: ReaderId<UiEvent>,
+    open: Option<OpenMenu>,
+    right_was_down: bool,
+    left_was_down: bool,
+    #[derivative(Debug = "ignore")]
+    _marker: PhantomData<(G, I)>,
+}
+
+impl<G, I: WidgetId> UiContextMenuSystem<G, I> {
+    /// Creates a new `UiContextMenuSystem`.
+    pub fn new(event_reader: ReaderId<UiEvent>) -> Self {
+        Self {
+            event_reader,
+            open: None,
+            right_was_down: false,
+            left_was_down: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<G, I> System for UiContextMenuSystem<G, I>
+where
+    G: PartialEq + Send + Sync + 'static,
+    I: WidgetId,
+{
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("UiContextMenuSystem")
+                .write_resource::<EventChannel<UiEvent>>()
+                .read_resource::<InputHandler>()
+                .read_resource::<ScreenDimensions>()
+                .read_resource::<UiScale>()
+                .with_query(
+                    <(Entity, &UiTransform, Option<&Interactable>)>::query()
+                        .filter(!component::<Hidden>() & !component::<HiddenPropagate>()),
+                )
+                .with_query(<(&UiTransform, &UiContextMenu)>::query())
+                .with_query(
+                    <(&UiTransform, &UiModal)>::query()
+                        .filter(!component::<Hidden>() & !component::<HiddenPropagate>()),
+                )
+                .with_query(<(Entity, &ContextMenuButton<I>)>::query())
+                .build(
+                    move |commands,
+                          world,
+                          (events, input, screen_dimensions, ui_scale),
+                          (interactables, menus, modals, buttons)| {
+                        let layer = modal_layer(modals.iter(world));
+
+                        let mut selected = None;
+                        for event in events.read(&mut self.event_reader) {
+                            if event.event_type != UiEventType::Click {
+                                continue;
+                            }
+                            if let Ok((_, button)) = buttons.get(world, event.target) {
+                                selected = Some(UiEvent::new(
+                                    UiEventType::ContextMenuSelect {
+                                        id: button.item.clone(),
+                                    },
+                                    button.owner,
+                                ));
+                            }
+                        }
+                        if let Some(event) = selected {
+                            events.single_write(event);
+                            self.open = None;
+                        }
+
+                        // Close the menu of an owner which was deleted, lost its menu or is
+                        // covered by a modal.
+                        let owner_lost = self.open.as_ref().map_or(false, |open| {
+                            menus.get(world, open.owner).map_or(true, |(transform, _)| {
+                                layer.map_or(false, |z| transform.global_z < z)
+                            })
+                        });
+                        if owner_lost {
+                            self.open = None;
+                        }
+
+                        let right_down = input.mouse_button_is_down(MouseButton::Right);
+                        let left_down = input.mouse_button_is_down(MouseButton::Left);
+                        let right_clicked = right_down && !self.right_was_down;
+                        let left_clicked = left_down && !self.left_was_down;
+                        self.right_was_down = right_down;
+                        self.left_was_down = left_down;
+
+                        let cursor = input
+                            .mouse_position()
+                            .map(|(x, y)| (x, screen_dimensions.height() - y));
+
+                        if left_clicked || right_clicked {
+                            let inside = match (&self.open, cursor) {
+                                (Some(open), Some(cursor)) => open.contains(cursor),
+                                _ => false,
+                            };
+                            if !inside {
+                                self.open = None;
+                            }
+                        }
+
+                        if self.open.is_none() {
+                            close(commands, buttons.iter(world));
+                        }
+
+                        let cursor = match cursor {
+                            Some(cursor) if right_clicked && self.open.is_none() => cursor,
+                            _ => return,
+                        };
+
+                        let targets = targeted(
+                            cursor,
+                            interactables
+                                .iter(world)
+                                .filter(|(_, t, _)| layer.map_or(true, |z| t.global_z >= z)),
+                        );
+                        let target = targets
+                            .iter()
+                            .filter_map(|e| menus.get(world, *e).ok().map(|m| (*e, m)))
+                            .max_by(|(_, (t1, _)), (_, (t2, _))| {
+                                t1.global_z
+                                    .partial_cmp(&t2.global_z)
+                                    .expect("Unexpected NaN")
+                            });
+
+                        if let Some((owner, (_, menu))) = target {
+                            self.open = Some(open_menu::<G, I>(
+                                commands,
+                                owner,
+                                menu,
+                                cursor,
+                                screen_dimensions,
+                                ui_scale.factor(screen_dimensions),
+                            ));
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+/// Deletes the buttons of the open menu and their widgets.
+fn close<'a, I: WidgetId>(
+    commands: &mut CommandBuffer,
+    buttons: impl Iterator<Item = (&'a Entity, &'a ContextMenuButton<I>)>,
+) {
+    for (_, button) in buttons {
+        commands.remove(button.button.image_entity);
+        commands.remove(button.button.text_entity);
+        let widget = button.widget.clone();
+        commands.exec_mut(move |_, resources| {
+            if let Some(mut widgets) = resources.get_mut::<Widgets<UiButton, I>>() {
+                widgets.remove(&widget);
+            }
+        });
+    }
+}
+
+/// Returns the left and top edges of the buttons of a menu opened at `cursor`, in ui units, and
+/// the rectangle of the menu in physical pixels.
+#[allow(clippy::cast_precision_loss)]
+fn menu_layout(
+    menu: &UiContextMenu,
+    cursor: (f32, f32),
+    screen_dimensions: &ScreenDimensions,
+    factor: f32,
+) -> (f32, f32, [f32; 4]) {
+    let height = menu.item_height * menu.items.len() as f32;
+    let (center_x, center_y) = popup_position(
+        cursor,
+        [0., 0.],
+        (menu.item_width, height),
+        screen_dimensions,
+        factor,
+    );
+    let left = center_x - menu.item_width * 0.5;
+    let top = center_y + height * 0.5;
+    let rect = [
+        left * factor,
+        (top - height) * factor,
+        (left + menu.item_width) * factor,
+        top * factor,
+    ];
+    (left, top, rect)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn open_menu<G, I>(
+    commands: &mut CommandBuffer,
+    owner: Entity,
+    menu: &UiContextMenu,
+    cursor: (f32, f32),
+    screen_dimensions: &ScreenDimensions,
+    factor: f32,
+) -> OpenMenu
+where
+    G: PartialEq + Send + Sync + 'static,
+    I: WidgetId,
+{
+    let (left, top, rect) = menu_layout(menu, cursor, screen_dimensions, factor);
+
+    let menu = menu.clone();
+    commands.exec_mut(move |world, resources| {
+        for (i, item) in menu.items.iter().enumerate() {
+            let mut builder = UiButtonBuilder::<G, I>::new(&item.text)
+                .with_anchor(Anchor::BottomLeft)
+                .with_position(
+                    left + menu.item_width * 0.5,
+                    top - menu.item_height * (i as f32 + 0.5),
+                )
+                .with_layer(MENU_Z)
+                .with_size(menu.item_width, menu.item_height)
+                .with_font_size(menu.font_size)
+                .with_text_color(menu.text_color)
+                .with_image(UiImage::SolidColor(menu.background_color));
+            if let Some(font) = &menu.font {
+                builder = builder.with_font(font.clone());
+            }
+            if let Some(style) = &menu.style {
+                builder = builder.with_style(style.theme.clone(), &style.name);
+            }
+
+            let (widget, button) = builder.build_from_world_and_resources(world, resources);
+            if let Some(mut entry) = world.entry(button.image_entity) {
+                entry.add_component(ContextMenuButton {
+                    owner,
+                    item: item.id.clone(),
+                    widget,
+                    button,
+                });
+            }
+        }
+    });
+
+    OpenMenu { owner, rect }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_menus_inside_the_screen() {
+        let screen = ScreenDimensions::new(800, 600);
+        let menu = UiContextMenu::default()
+            .with_item(&"copy", &"Copy")
+            .with_item(&"paste", &"Paste")
+            .with_item_size(100., 20.);
+
+        let (left, top, rect) = menu_layout(&menu, (10., 500.), &screen, 1.);
+        assert!((left - 10.).abs() < f32::EPSILON);
+        assert!((top - 500.).abs() < f32::EPSILON);
+        assert_eq!(rect, [10., 460., 110., 500.]);
+
+        // Opened in the bottom right corner, the menu is moved up and left.
+        let (left, top, _) = menu_layout(&menu, (790., 5.), &screen, 1.);
+        assert!((left - 700.).abs() < f32::EPSILON);
+        assert!((top - 40.).abs() < f32::EPSILON);
+
+        // Ui units are scaled by the factor.
+        let (_, _, rect) = menu_layout(&menu, (10., 500.), &screen, 2.);
+        assert_eq!(rect, [10., 420., 210., 500.]);
+    }
+
+    #[test]
+    fn clicks_inside_open_menus() {
+        let open = OpenMenu {
+            owner: amethyst_core::ecs::World::default().push(()),
+            rect: [10., 460., 110., 500.],
+        };
+        assert!(open.contains((50., 480.)));
+        assert!(!open.contains((50., 520.)));
+        assert!(!open.contains((5., 480.)));
+    }
+}
@@ -1,4 +1,8 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    any::TypeId,
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+};
 
 use amethyst_core::{
     ecs::{component, Entity, IntoQuery, ParallelRunnable, System, SystemBuilder},
@@ -12,15 +16,66 @@ use amethyst_window::ScreenDimensions;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    get_parent_pixel_size, targeted_below, Interactable, ScaleMode, UiEvent, UiEventType,
-    UiTransform,
+    get_parent_pixel_size, targeted_below, Anchor, Interactable, ScaleMode, UiEvent, UiEventType,
+    UiImage, UiScale, UiTransform,
 };
 
+const GHOST_Z: f32 = 950.;
+
 /// Component that denotes whether a given ui widget is draggable.
 /// Requires `UiTransform` to work.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Draggable;
 
+/// Component making a `Draggable` stay in place while a copy of its `UiImage` follows the cursor.
+/// The copy is deleted when it is dropped.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DragGhost;
+
+/// Typed data carried by a `Draggable`, which `DropTarget`s accept or reject based on its type.
+///
+/// A `DragPayloadSystem<T>` is needed for each payload type, see `DragPayloadBundle`.
+#[derive(Debug, Clone)]
+pub struct DragPayload<T> {
+    /// The carried data.
+    pub value: T,
+}
+
+impl<T> DragPayload<T> {
+    /// Creates a `DragPayload` carrying `value`.
+    pub fn new(value: T) -> Self {
+        Self { value }
+    }
+}
+
+/// Component marking an ui element on which `DragPayload`s can be dropped.
+/// The entity needs an `Interactable` component to be found under the cursor.
+#[derive(Debug, Clone, Default)]
+pub struct DropTarget {
+    accepted: HashSet<TypeId>,
+}
+
+impl DropTarget {
+    /// Creates a `DropTarget` rejecting every payload.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accepts the payloads of type `T`.
+    #[must_use]
+    pub fn accepting<T: 'static>(mut self) -> Self {
+        self.accepted.insert(TypeId::of::<T>());
+        self
+    }
+
+    /// Returns true if payloads of type `T` can be dropped on this target.
+    #[must_use]
+    pub fn accepts<T: 'static>(&self) -> bool {
+        self.accepted.contains(&TypeId::of::<T>())
+    }
+}
+
 /// System responsible of drag'n drop
 #[derive(Debug)]
 pub struct DragWidgetSystem {
@@ -31,6 +86,10 @@ pub struct DragWidgetSystem {
     /// the original mouse position when drag first started,
     /// and second element the mouse position one frame ago
     record: HashMap<Entity, (Vector2<f32>, Vector2<f32>)>,
+
+    /// `DragGhost` entities being dragged, with their ghost and the ghost position when drag
+    /// first started
+    ghosts: HashMap<Entity, (Entity, Vector2<f32>)>,
 }
 
 impl DragWidgetSystem {
@@ -39,6 +98,7 @@ impl DragWidgetSystem {
         Self {
             event_reader,
             record: HashMap::new(),
+            ghosts: HashMap::new(),
         }
     }
 }
@@ -50,7 +110,8 @@ impl System for DragWidgetSystem {
                 .write_resource::<EventChannel<UiEvent>>()
                 .read_resource::<InputHandler>()
                 .read_resource::<ScreenDimensions>()
-                .with_query(<(Entity, &Draggable)>::query())
+                .read_resource::<UiScale>()
+                .with_query(<(&Draggable, Option<&DragGhost>, Option<&UiImage>)>::query())
                 .with_query(<&Hidden>::query())
                 .with_query(<&HiddenPropagate>::query())
                 .with_query(<Option<&Parent>>::query())
@@ -61,9 +122,9 @@ impl System for DragWidgetSystem {
                         .filter(!component::<Hidden>() & !component::<HiddenPropagate>()),
                 )
                 .build(
-                    move |_commands,
+                    move |commands,
                           world,
-                          (ui_events, input, screen_dimensions),
+                          (ui_events, input, screen_dimensions, ui_scale),
                           (
                         draggables,
                         hiddens,
@@ -76,13 +137,41 @@ impl System for DragWidgetSystem {
                         let mouse_pos = input.mouse_position().unwrap_or((0., 0.));
                         let mouse_pos =
                             Vector2::new(mouse_pos.0, screen_dimensions.height() - mouse_pos.1);
+                        // Mouse movements are in physical pixels, positions are in ui units.
+                        let factor = ui_scale.factor(screen_dimensions);
                         let mut click_stopped: HashSet<Entity> = HashSet::new();
-                        let event_reader = &mut self.event_reader;
-                        ui_events.read(event_reader).for_each(|event| {
+                        for event in ui_events.read(&mut self.event_reader) {
                             match event.event_type {
                                 UiEventType::ClickStart => {
-                                    if draggables.iter(world).any(|(e, _)| *e == event.target) {
-                                        self.record.insert(event.target, (mouse_pos, mouse_pos));
+                                    let (ghost, image) = match draggables.get(world, event.target) {
+                                        Ok((_, ghost, image)) => (ghost.is_some(), image.cloned()),
+                                        Err(_) => continue,
+                                    };
+                                    self.record.insert(event.target, (mouse_pos, mouse_pos));
+
+                                    let transform = maybe_ui_transform
+                                        .get(world, event.target)
+                                        .ok()
+                                        .and_then(|(_, transform)| transform);
+                                    if let (true, Some(image), Some(transform)) =
+                                        (ghost, image, transform)
+                                    {
+                                        let start =
+                                            Vector2::new(transform.pixel_x, transform.pixel_y)
+                                                / factor;
+                                        let ghost_transform = UiTransform::new(
+                                            "drag_ghost".to_string(),
+                                            Anchor::BottomLeft,
+                                            Anchor::Middle,
+                                            start[0],
+                                            start[1],
+                                            GHOST_Z,
+                                            transform.pixel_width / factor,
+                                            transform.pixel_height / factor,
+                                        )
+                                        .into_transparent();
+                                        let ghost = commands.push((ghost_transform, image));
+                                        self.ghosts.insert(event.target, (ghost, start));
                                     }
                                 }
                                 UiEventType::ClickStop => {
@@ -92,7 +181,7 @@ impl System for DragWidgetSystem {
                                 }
                                 _ => (),
                             }
-                        });
+                        }
 
                         for entity in self.record.keys() {
                             if hiddens.get(world, *entity).is_ok()
@@ -111,6 +200,18 @@ impl System for DragWidgetSystem {
                                 *entity,
                             ));
 
+                            // Move the ghost instead of the entity, the ghost is a root entity
+                            // positioned from the bottom left of the screen.
+                            if let Some((ghost, start)) = self.ghosts.get(entity) {
+                                if let Ok(ghost_transform) = ui_transforms.get_mut(world, *ghost) {
+                                    let position = start + (mouse_pos - *first) / factor;
+                                    ghost_transform.local_x = position[0];
+                                    ghost_transform.local_y = position[1];
+                                }
+                                *prev = mouse_pos;
+                                continue;
+                            }
+
                             let change = mouse_pos - *prev;
 
                             let (parent_width, parent_height) = {
@@ -126,7 +227,7 @@ impl System for DragWidgetSystem {
 
                             let ui_transform = ui_transforms.get_mut(world, *entity).unwrap();
                             let (scale_x, scale_y) = match ui_transform.scale_mode {
-                                ScaleMode::Pixel => (1.0, 1.0),
+                                ScaleMode::Pixel => (factor, factor),
                                 ScaleMode::Percent => (parent_width, parent_height),
                             };
 
@@ -149,9 +250,243 @@ impl System for DragWidgetSystem {
                             ));
 
                             self.record.remove(entity);
+                            if let Some((ghost, _)) = self.ghosts.remove(entity) {
+                                commands.remove(ghost);
+                            }
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+#[derive(Debug)]
+struct PayloadDrag {
+    /// Local position of the dragged entity when the drag started.
+    start: (f32, f32),
+    /// The `DropTarget` currently hovered.
+    hovered: Option<Entity>,
+}
+
+/// Returns the event of the drop of a payload of type `T` dragged by `dragged` on `dropped_on`,
+/// whose `DropTarget` is `target`, with the local position `dragged` snaps back to if the drop
+/// is rejected.
+fn resolve_drop<T: 'static>(
+    dragged: Entity,
+    drag: &PayloadDrag,
+    dropped_on: Option<Entity>,
+    target: Option<&DropTarget>,
+    ghosted: bool,
+) -> (UiEvent, Option<(f32, f32)>) {
+    match (dropped_on, target) {
+        (Some(dropped_on), Some(target)) if target.accepts::<T>() => {
+            (
+                UiEvent::new(UiEventType::DropAccepted { dragged }, dropped_on),
+                None,
+            )
+        }
+        _ => {
+            (
+                UiEvent::new(UiEventType::DropRejected { dropped_on }, dragged),
+                if ghosted { None } else { Some(drag.start) },
+            )
+        }
+    }
+}
+
+/// System emitting the hover and drop events of `DragPayload<T>`s over `DropTarget`s.
+///
+/// Rejected drops snap the dragged entity back to where the drag started, unless it was dragged
+/// with a `DragGhost`, which already leaves it in place.
+#[derive(Debug)]
+pub struct DragPayloadSystem<T> {
+    event_reader: ReaderId<UiEvent>,
+    dragged: HashMap<Entity, PayloadDrag>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> DragPayloadSystem<T> {
+    /// Constructs a new `DragPayloadSystem<T>`
+    pub fn new(event_reader: ReaderId<UiEvent>) -> Self {
+        Self {
+            event_reader,
+            dragged: HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static> System for DragPayloadSystem<T> {
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("DragPayloadSystem")
+                .write_resource::<EventChannel<UiEvent>>()
+                .with_query(<(&DragPayload<T>, &Draggable, Option<&DragGhost>)>::query())
+                .with_query(<&DropTarget>::query())
+                .with_query(<&mut UiTransform>::query())
+                .with_query(
+                    <(Entity, &UiTransform, Option<&Interactable>)>::query()
+                        .filter(!component::<Hidden>() & !component::<HiddenPropagate>()),
+                )
+                .build(
+                    move |_commands,
+                          world,
+                          ui_events,
+                          (payloads, drop_targets, ui_transforms, not_hidden_ui_transforms)| {
+                        let mut emitted = Vec::new();
+                        for event in ui_events.read(&mut self.event_reader) {
+                            let dragged = event.target;
+                            match &event.event_type {
+                                UiEventType::ClickStart => {
+                                    if payloads.get(world, dragged).is_err() {
+                                        continue;
+                                    }
+                                    if let Ok(transform) = ui_transforms.get_mut(world, dragged) {
+                                        let start = (transform.local_x, transform.local_y);
+                                        self.dragged.insert(
+                                            dragged,
+                                            PayloadDrag {
+                                                start,
+                                                hovered: None,
+                                            },
+                                        );
+                                    }
+                                }
+                                UiEventType::Dragging { new_position, .. } => {
+                                    let drag = match self.dragged.get_mut(&dragged) {
+                                        Some(drag) => drag,
+                                        None => continue,
+                                    };
+                                    let height = match ui_transforms.get_mut(world, dragged) {
+                                        Ok(transform) => transform.global_z,
+                                        Err(_) => continue,
+                                    };
+                                    let hovered = targeted_below(
+                                        (new_position[0], new_position[1]),
+                                        height,
+                                        not_hidden_ui_transforms.iter(world),
+                                    )
+                                    .filter(|target| drop_targets.get(world, *target).is_ok());
+                                    if hovered == drag.hovered {
+                                        continue;
+                                    }
+
+                                    if let Some(previous) = drag.hovered {
+                                        emitted.push(UiEvent::new(
+                                            UiEventType::DragHoverStop { dragged },
+                                            previous,
+                                        ));
+                                    }
+                                    if let Some(target) = hovered {
+                                        let event_type =
+                                            if drop_targets.get(world, target).unwrap().accepts::<T>()
+                                            {
+                                                UiEventType::DragHoverAccept { dragged }
+                                            } else {
+                                                UiEventType::DragHoverReject { dragged }
+                                            };
+                                        emitted.push(UiEvent::new(event_type, target));
+                                    }
+                                    drag.hovered = hovered;
+                                }
+                                UiEventType::Dropped { dropped_on } => {
+                                    let drag = match self.dragged.remove(&dragged) {
+                                        Some(drag) => drag,
+                                        None => continue,
+                                    };
+                                    if let Some(previous) = drag.hovered {
+                                        emitted.push(UiEvent::new(
+                                            UiEventType::DragHoverStop { dragged },
+                                            previous,
+                                        ));
+                                    }
+
+                                    let target = match dropped_on {
+                                        Some(target) => drop_targets.get(world, *target).ok(),
+                                        None => None,
+                                    };
+                                    let ghosted = payloads
+                                        .get(world, dragged)
+                                        .map_or(false, |(_, _, ghost)| ghost.is_some());
+                                    let (event, snap_back) = resolve_drop::<T>(
+                                        dragged,
+                                        &drag,
+                                        *dropped_on,
+                                        target,
+                                        ghosted,
+                                    );
+                                    emitted.push(event);
+                                    if let Some((x, y)) = snap_back {
+                                        if let Ok(transform) = ui_transforms.get_mut(world, dragged)
+                                        {
+                                            transform.local_x = x;
+                                            transform.local_y = y;
+                                        }
+                                    }
+                                }
+                                _ => (),
+                            }
                         }
+                        ui_events.iter_write(emitted);
                     },
                 ),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::World;
+
+    use super::*;
+
+    struct Item;
+    struct Spell;
+
+    fn drag() -> PayloadDrag {
+        PayloadDrag {
+            start: (10., 20.),
+            hovered: None,
+        }
+    }
+
+    #[test]
+    fn targets_accept_their_payload_types() {
+        let target = DropTarget::new().accepting::<Item>();
+        assert!(target.accepts::<Item>());
+        assert!(!target.accepts::<Spell>());
+        assert!(!DropTarget::new().accepts::<Item>());
+    }
+
+    #[test]
+    fn rejected_drops_snap_back() {
+        let mut world = World::default();
+        let (dragged, slot) = (world.push(()), world.push(()));
+        let target = DropTarget::new().accepting::<Item>();
+
+        let (event, snap_back) =
+            resolve_drop::<Item>(dragged, &drag(), Some(slot), Some(&target), false);
+        assert_eq!(event.target, slot);
+        assert_eq!(event.event_type, UiEventType::DropAccepted { dragged });
+        assert_eq!(snap_back, None);
+
+        let (event, snap_back) =
+            resolve_drop::<Spell>(dragged, &drag(), Some(slot), Some(&target), false);
+        assert_eq!(event.target, dragged);
+        assert_eq!(
+            event.event_type,
+            UiEventType::DropRejected {
+                dropped_on: Some(slot)
+            }
+        );
+        assert_eq!(snap_back, Some((10., 20.)));
+
+        let (_, snap_back) = resolve_drop::<Item>(dragged, &drag(), None, None, false);
+        assert_eq!(snap_back, Some((10., 20.)));
+
+        // Ghosts leave the dragged entity in place.
+        let (_, snap_back) =
+            resolve_drop::<Spell>(dragged, &drag(), Some(slot), Some(&target), true);
+        assert_eq!(snap_back, None);
+    }
+}
@@ -11,7 +11,7 @@ use amethyst_window::ScreenDimensions;
 use serde::{Deserialize, Serialize};
 use winit::event::MouseButton;
 
-use crate::transform::UiTransform;
+use crate::{modal::modal_layer, transform::UiTransform, UiModal};
 
 /// An event that pertains to a specific `Entity`, for example a `UiEvent` for clicking on a widget
 /// entity.
@@ -57,6 +57,54 @@ pub enum UiEventType {
     Focus,
     /// When an editable UiText element has lost focus.
     Blur,
+    /// When a link span of a markup UiText element is clicked.
+    LinkClick {
+        /// The id given to the link span, as in `[link=id]`.
+        id: String,
+    },
+    /// When a `UiTextReveal` has revealed all of its text.
+    TextRevealed,
+    /// When an item of a `UiContextMenu` is clicked. Targets the entity the menu was opened on.
+    ContextMenuSelect {
+        /// The id of the clicked item.
+        id: String,
+    },
+    /// When a dragged `DragPayload` starts hovering a `DropTarget` accepting it.
+    /// Targets the drop target.
+    DragHoverAccept {
+        /// The dragged entity.
+        dragged: Entity,
+    },
+    /// When a dragged `DragPayload` starts hovering a `DropTarget` rejecting it.
+    /// Targets the drop target.
+    DragHoverReject {
+        /// The dragged entity.
+        dragged: Entity,
+    },
+    /// When a dragged `DragPayload` stops hovering a `DropTarget`, or is dropped on it.
+    /// Targets the drop target.
+    DragHoverStop {
+        /// The dragged entity.
+        dragged: Entity,
+    },
+    /// When a `DragPayload` is dropped on a `DropTarget` accepting it. Targets the drop target.
+    DropAccepted {
+        /// The dropped entity.
+        dragged: Entity,
+    },
+    /// When a `DragPayload` is dropped anywhere but on a `DropTarget` accepting it.
+    /// Targets the dropped entity.
+    DropRejected {
+        /// The entity on which the payload was dropped.
+        dropped_on: Option<Entity>,
+    },
+    /// When a button of a `UiConfirmDialog` is clicked. Targets the root entity of the dialog.
+    DialogResult {
+        /// Whether the confirm button was clicked, rather than the cancel button.
+        confirmed: bool,
+    },
+    /// When a `Tween` has played as many times as its `TweenLoop` requires.
+    TweenCompleted,
 }
 
 /// A ui event instance.
@@ -121,11 +169,16 @@ impl System for UiMouseSystem {
                     <(Entity, &UiTransform, Option<&Interactable>)>::query()
                         .filter(!component::<Hidden>() & !component::<HiddenPropagate>()),
                 )
+                // Visible modals, blocking the entities below them
+                .with_query(
+                    <(&UiTransform, &UiModal)>::query()
+                        .filter(!component::<Hidden>() & !component::<HiddenPropagate>()),
+                )
                 .build(
                     move |_commands,
                           world,
                           (events, input, screen_dimensions),
-                          interactables_entities| {
+                          (interactables_entities, modals)| {
                         let down = input.mouse_button_is_down(MouseButton::Left);
                         // FIXME: To replace on InputHandler generate OnMouseDown and OnMouseUp events See #2496
                         let click_started = down && !self.was_down;
@@ -134,7 +187,13 @@ impl System for UiMouseSystem {
                             let x = pos_x as f32;
                             let y = screen_dimensions.height() - pos_y as f32;
 
-                            let targets = targeted((x, y), interactables_entities.iter(world));
+                            let layer = modal_layer(modals.iter(world));
+                            let targets = targeted(
+                                (x, y),
+                                interactables_entities
+                                    .iter(world)
+                                    .filter(|(_, t, _)| layer.map_or(true, |z| t.global_z >= z)),
+                            );
 
                             for target in targets.difference(&self.last_targets) {
                                 events.single_write(UiEvent::new(UiEventType::HoverStart, *target));
@@ -0,0 +1,230 @@
+//! Module for the `UiFill` component, partially drawing an `UiImage`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::pass::NO_RADIAL_FILL;
+
+/// How an image is filled by a `UiFill`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum UiFillMode {
+    /// Fills from the left edge, or from the right edge if `reversed`.
+    Horizontal {
+        /// Fills from the right edge.
+        reversed: bool,
+    },
+    /// Fills from the bottom edge, or from the top edge if `reversed`.
+    Vertical {
+        /// Fills from the top edge.
+        reversed: bool,
+    },
+    /// Fills around the center of the image.
+    Radial {
+        /// Angle the fill starts at, in radians clockwise from the top.
+        start_angle: f32,
+        /// Fills clockwise, or counterclockwise if false.
+        clockwise: bool,
+    },
+}
+
+/// Draws only a part of the `UiImage` of this entity, such as for health bars, loading bars or
+/// cooldown indicators.
+///
+/// The `amount` can be animated with the `UiFillChannel` of `amethyst_animation`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct UiFill {
+    /// How the image is filled.
+    pub mode: UiFillMode,
+    /// The filled proportion of the image, from `0.` to `1.`.
+    pub amount: f32,
+}
+
+impl UiFill {
+    /// Creates an `UiFill` filling `amount` of the image from the left edge.
+    #[must_use]
+    pub fn horizontal(amount: f32) -> Self {
+        Self {
+            mode: UiFillMode::Horizontal { reversed: false },
+            amount,
+        }
+    }
+
+    /// Creates an `UiFill` filling `amount` of the image from the bottom edge.
+    #[must_use]
+    pub fn vertical(amount: f32) -> Self {
+        Self {
+            mode: UiFillMode::Vertical { reversed: false },
+            amount,
+        }
+    }
+
+    /// Creates an `UiFill` filling `amount` of the image clockwise from the top.
+    #[must_use]
+    pub fn radial(amount: f32) -> Self {
+        Self {
+            mode: UiFillMode::Radial {
+                start_angle: 0.,
+                clockwise: true,
+            },
+            amount,
+        }
+    }
+
+    /// Returns the parts of `quad` which are filled, for an image with `bounds` as left, bottom,
+    /// right and top edges.
+    ///
+    /// Radial fills return the whole quad, the fragment shader discards the pixels outside of
+    /// the `radial_fill` angles.
+    pub(crate) fn apply(&self, quad: FillQuad, bounds: [f32; 4]) -> Vec<FillQuad> {
+        let amount = self.amount.max(0.).min(1.);
+        let [left, bottom, right, top] = bounds;
+        let width = (right - left) * amount;
+        let height = (top - bottom) * amount;
+        match self.mode {
+            UiFillMode::Horizontal { reversed: false } => {
+                quad.clip([left, bottom, left + width, top])
+                    .into_iter()
+                    .collect()
+            }
+            UiFillMode::Horizontal { reversed: true } => {
+                quad.clip([right - width, bottom, right, top])
+                    .into_iter()
+                    .collect()
+            }
+            UiFillMode::Vertical { reversed: false } => {
+                quad.clip([left, bottom, right, bottom + height])
+                    .into_iter()
+                    .collect()
+            }
+            UiFillMode::Vertical { reversed: true } => {
+                quad.clip([left, top - height, right, top])
+                    .into_iter()
+                    .collect()
+            }
+            UiFillMode::Radial { .. } if amount <= 0. => Vec::new(),
+            UiFillMode::Radial { .. } => vec![quad],
+        }
+    }
+
+    /// Returns the `radial_fill` of the `UiArgs` of an image with `bounds` as left, bottom,
+    /// right and top edges.
+    pub(crate) fn radial_fill(&self, bounds: [f32; 4]) -> [f32; 4] {
+        match self.mode {
+            UiFillMode::Radial {
+                start_angle,
+                clockwise,
+            } => {
+                let amount = self.amount.max(0.).min(1.);
+                [
+                    (bounds[0] + bounds[2]) * 0.5,
+                    (bounds[1] + bounds[3]) * 0.5,
+                    start_angle,
+                    if clockwise { amount } else { -amount },
+                ]
+            }
+            _ => NO_RADIAL_FILL,
+        }
+    }
+}
+
+/// A textured rectangle: its left, bottom, right and top edges in pixels, and the left, top,
+/// right and bottom texture coordinates, as in `UiArgs`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct FillQuad {
+    pub(crate) rect: [f32; 4],
+    pub(crate) tex_coords: [f32; 4],
+}
+
+impl FillQuad {
+    /// Creates a `FillQuad` from its center and dimensions.
+    pub(crate) fn new(coords: [f32; 2], dimensions: [f32; 2], tex_coords: [f32; 4]) -> Self {
+        Self {
+            rect: [
+                coords[0] - dimensions[0] * 0.5,
+                coords[1] - dimensions[1] * 0.5,
+                coords[0] + dimensions[0] * 0.5,
+                coords[1] + dimensions[1] * 0.5,
+            ],
+            tex_coords,
+        }
+    }
+
+    /// Returns the center of the rectangle.
+    pub(crate) fn coords(&self) -> [f32; 2] {
+        [
+            (self.rect[0] + self.rect[2]) * 0.5,
+            (self.rect[1] + self.rect[3]) * 0.5,
+        ]
+    }
+
+    /// Returns the width and height of the rectangle.
+    pub(crate) fn dimensions(&self) -> [f32; 2] {
+        [self.rect[2] - self.rect[0], self.rect[3] - self.rect[1]]
+    }
+
+    /// Returns the part of this inside `rect`, with its texture coordinates, if there is any.
+    fn clip(&self, rect: [f32; 4]) -> Option<Self> {
+        let [left, bottom, right, top] = self.rect;
+        let clipped = [
+            left.max(rect[0]),
+            bottom.max(rect[1]),
+            right.min(rect[2]),
+            top.min(rect[3]),
+        ];
+        if clipped[0] >= clipped[2] || clipped[1] >= clipped[3] {
+            return None;
+        }
+
+        let [tex_left, tex_top, tex_right, tex_bottom] = self.tex_coords;
+        let u = |x: f32| tex_left + (tex_right - tex_left) * (x - left) / (right - left);
+        let v = |y: f32| tex_bottom + (tex_top - tex_bottom) * (y - bottom) / (top - bottom);
+        Some(Self {
+            rect: clipped,
+            tex_coords: [u(clipped[0]), v(clipped[3]), u(clipped[2]), v(clipped[1])],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn horizontal_fill_clips_texture() {
+        let quad = FillQuad::new([50., 10.], [100., 20.], [0., 0., 1., 1.]);
+        let quads = UiFill::horizontal(0.25).apply(quad, [0., 0., 100., 20.]);
+        assert_eq!(quads.len(), 1);
+        assert_eq!(quads[0].rect, [0., 0., 25., 20.]);
+        assert_eq!(quads[0].tex_coords, [0., 0., 0.25, 1.]);
+
+        let mut fill = UiFill::vertical(0.5);
+        fill.mode = UiFillMode::Vertical { reversed: true };
+        let quads = fill.apply(quad, [0., 0., 100., 20.]);
+        assert_eq!(quads[0].rect, [0., 10., 100., 20.]);
+        assert_eq!(quads[0].tex_coords, [0., 0., 1., 0.5]);
+
+        assert!(UiFill::horizontal(0.)
+            .apply(quad, [0., 0., 100., 20.])
+            .is_empty());
+    }
+
+    #[test]
+    fn radial_fill_is_drawn_by_the_shader() {
+        let quad = FillQuad::new([50., 50.], [100., 100.], [0., 0., 1., 1.]);
+        let bounds = [0., 0., 100., 100.];
+
+        let fill = UiFill::radial(0.25);
+        assert_eq!(fill.apply(quad, bounds), vec![quad]);
+        assert_eq!(fill.radial_fill(bounds), [50., 50., 0., 0.25]);
+        assert!(UiFill::radial(0.).apply(quad, bounds).is_empty());
+
+        let fill = UiFill {
+            mode: UiFillMode::Radial {
+                start_angle: 1.,
+                clockwise: false,
+            },
+            amount: 2.,
+        };
+        assert_eq!(fill.radial_fill(bounds), [50., 50., 1., -1.]);
+        assert_eq!(UiFill::horizontal(0.5).radial_fill(bounds), NO_RADIAL_FILL);
+    }
+}
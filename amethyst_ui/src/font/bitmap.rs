@@ -0,0 +1,762 @@
+//! Bitmap fonts in the `BMFont` (`AngelCode`) format, drawn from pre-rendered page textures.
+
+use std::{collections::HashMap, convert::TryFrom, str::FromStr};
+
+use amethyst_assets::{
+    register_asset_type, Asset, AssetStorage, DefaultLoader, Format, Handle, Loader,
+    ProcessingQueue, ProcessingState,
+};
+use amethyst_core::ecs::{ParallelRunnable, System, SystemBuilder};
+use amethyst_error::{format_err, Error};
+use amethyst_rendy::Texture;
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use crate::{text::CachedGlyph, Anchor, LineMode};
+
+/// A character of a bitmap font, in pixels of its page texture.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BitmapChar {
+    /// The left of the character in its page.
+    pub x: u16,
+    /// The top of the character in its page.
+    pub y: u16,
+    /// The width of the character in its page.
+    pub width: u16,
+    /// The height of the character in its page.
+    pub height: u16,
+    /// The offset from the pen position to the left of the drawn character.
+    pub x_offset: i16,
+    /// The offset from the top of the line to the top of the drawn character.
+    pub y_offset: i16,
+    /// How far the pen moves after drawing the character.
+    pub x_advance: i16,
+    /// The index of the page texture having the character.
+    pub page: u8,
+}
+
+/// The contents of a `BMFont` `.fnt` file, in either its text or binary format.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "5b1a8f0e-3c7d-4e62-9a4f-2d8e6c1b7a93"]
+pub struct BitmapFontData {
+    /// The name of the font the characters were rendered from.
+    pub face: String,
+    /// The size the characters were rendered at.
+    pub size: u16,
+    /// The distance between two lines.
+    pub line_height: u16,
+    /// The distance from the top of a line to its baseline.
+    pub base: u16,
+    /// The width of the page textures.
+    pub scale_w: u16,
+    /// The height of the page textures.
+    pub scale_h: u16,
+    /// The paths of the page textures, relative to `pages_directory`.
+    pub pages: Vec<String>,
+    /// The directory of the page textures, relative to the asset directory, or `None` for the
+    /// directory of the path the font was loaded by.
+    #[serde(default)]
+    pub pages_directory: Option<String>,
+    /// The characters of the font.
+    pub chars: HashMap<char, BitmapChar>,
+    /// Adjustments to the advance between two characters.
+    pub kernings: HashMap<(char, char), i16>,
+}
+
+impl BitmapFontData {
+    /// Parses the text or binary format of a `.fnt` file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file is malformed, or is a binary file of another version than 3.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.starts_with(b"BMF") {
+            parse_binary(bytes)
+        } else {
+            let text = std::str::from_utf8(bytes)
+                .map_err(|_| format_err!("Bitmap font is neither binary nor UTF-8 text"))?;
+            parse_text(text)
+        }
+    }
+
+    /// Returns the character drawn for `c`, which is `?` for the characters the font lacks.
+    #[must_use]
+    pub fn char(&self, c: char) -> Option<&BitmapChar> {
+        self.chars.get(&c).or_else(|| self.chars.get(&'?'))
+    }
+
+    /// Returns the adjustment to the advance from `first` to `second`.
+    #[must_use]
+    pub fn kerning(&self, first: char, second: char) -> i16 {
+        self.kernings.get(&(first, second)).copied().unwrap_or(0)
+    }
+}
+
+/// Splits a line of the text format into its tag and `key=value` pairs, removing the quotes of
+/// quoted values.
+fn line_pairs(line: &str) -> (&str, Vec<(&str, &str)>) {
+    let line = line.trim();
+    let tag_end = line.find(char::is_whitespace).unwrap_or_else(|| line.len());
+    let (tag, mut rest) = line.split_at(tag_end);
+
+    let mut pairs = Vec::new();
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].trim();
+        rest = &rest[eq + 1..];
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or_else(|| quoted.len());
+            rest = quoted.get(end + 1..).unwrap_or("");
+            &quoted[..end]
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or_else(|| rest.len());
+            let value = &rest[..end];
+            rest = &rest[end..];
+            value
+        };
+        pairs.push((key, value));
+    }
+    (tag, pairs)
+}
+
+/// Returns the value of `key` in `pairs`, or the default value if it is missing.
+fn value<T: FromStr + Default>(pairs: &[(&str, &str)], key: &str) -> Result<T, Error> {
+    pairs
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map_or(Ok(T::default()), |(_, v)| {
+            v.parse()
+                .map_err(|_| format_err!("Invalid value `{}` of `{}` in bitmap font", v, key))
+        })
+}
+
+/// Returns the character of a character id, which may be negative for an invalid character.
+fn char_id(id: i64) -> Option<char> {
+    u32::try_from(id).ok().and_then(std::char::from_u32)
+}
+
+fn parse_text(text: &str) -> Result<BitmapFontData, Error> {
+    let mut data = BitmapFontData::default();
+    let mut page_count = 0;
+    for line in text.lines() {
+        let (tag, pairs) = line_pairs(line);
+        match tag {
+            "info" => {
+                data.face = value(&pairs, "face")?;
+                // A negative size means the size matches the height of the characters.
+                let size: i32 = value(&pairs, "size")?;
+                data.size = u16::try_from(size.abs()).unwrap_or(u16::MAX);
+            }
+            "common" => {
+                data.line_height = value(&pairs, "lineHeight")?;
+                data.base = value(&pairs, "base")?;
+                data.scale_w = value(&pairs, "scaleW")?;
+                data.scale_h = value(&pairs, "scaleH")?;
+                page_count = value(&pairs, "pages")?;
+                data.pages.resize(page_count, String::new());
+            }
+            "page" => {
+                let id: usize = value(&pairs, "id")?;
+                if id >= page_count {
+                    return Err(format_err!(
+                        "Page {} of bitmap font is not below its page count {}",
+                        id,
+                        page_count
+                    ));
+                }
+                data.pages[id] = value(&pairs, "file")?;
+            }
+            "char" => {
+                if let Some(c) = char_id(value(&pairs, "id")?) {
+                    let bitmap_char = BitmapChar {
+                        x: value(&pairs, "x")?,
+                        y: value(&pairs, "y")?,
+                        width: value(&pairs, "width")?,
+                        height: value(&pairs, "height")?,
+                        x_offset: value(&pairs, "xoffset")?,
+                        y_offset: value(&pairs, "yoffset")?,
+                        x_advance: value(&pairs, "xadvance")?,
+                        page: value(&pairs, "page")?,
+                    };
+                    data.chars.insert(c, bitmap_char);
+                }
+            }
+            "kerning" => {
+                let first = char_id(value(&pairs, "first")?);
+                let second = char_id(value(&pairs, "second")?);
+                if let (Some(first), Some(second)) = (first, second) {
+                    data.kernings
+                        .insert((first, second), value(&pairs, "amount")?);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if data.line_height == 0 || data.scale_w == 0 || data.scale_h == 0 {
+        return Err(format_err!("Bitmap font is missing its `common` line"));
+    }
+    Ok(data)
+}
+
+/// Returns the `len` bytes of `bytes` at `offset`.
+fn read_bytes(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], Error> {
+    bytes
+        .get(offset..offset + len)
+        .ok_or_else(|| format_err!("Unexpected end of binary bitmap font"))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, Error> {
+    read_bytes(bytes, offset, 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_i16(bytes: &[u8], offset: usize) -> Result<i16, Error> {
+    read_bytes(bytes, offset, 2).map(|b| i16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    read_bytes(bytes, offset, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Splits null terminated strings.
+fn read_strings(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .split(|b| *b == 0)
+        .filter(|s| !s.is_empty())
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .collect()
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<BitmapFontData, Error> {
+    const CHAR_SIZE: usize = 20;
+    const KERNING_SIZE: usize = 10;
+
+    match bytes.get(3) {
+        Some(3) => {}
+        version => {
+            return Err(format_err!(
+                "Unsupported binary bitmap font version {:?}, only version 3 is supported",
+                version
+            ))
+        }
+    }
+
+    let mut data = BitmapFontData::default();
+    let mut offset = 4;
+    while offset < bytes.len() {
+        let block_type = bytes[offset];
+        let block_size = read_u32(bytes, offset + 1)? as usize;
+        let block = read_bytes(bytes, offset + 5, block_size)?;
+        offset += 5 + block_size;
+
+        match block_type {
+            1 => {
+                // A negative size means the size matches the height of the characters.
+                let size = i32::from(read_i16(block, 0)?);
+                data.size = u16::try_from(size.abs()).unwrap_or(u16::MAX);
+                data.face = read_strings(block.get(14..).unwrap_or(&[]))
+                    .into_iter()
+                    .next()
+                    .unwrap_or_default();
+            }
+            2 => {
+                data.line_height = read_u16(block, 0)?;
+                data.base = read_u16(block, 2)?;
+                data.scale_w = read_u16(block, 4)?;
+                data.scale_h = read_u16(block, 6)?;
+            }
+            3 => data.pages = read_strings(block),
+            4 => {
+                for start in (0..block.len() / CHAR_SIZE).map(|i| i * CHAR_SIZE) {
+                    if let Some(c) = std::char::from_u32(read_u32(block, start)?) {
+                        let bitmap_char = BitmapChar {
+                            x: read_u16(block, start + 4)?,
+                            y: read_u16(block, start + 6)?,
+                            width: read_u16(block, start + 8)?,
+                            height: read_u16(block, start + 10)?,
+                            x_offset: read_i16(block, start + 12)?,
+                            y_offset: read_i16(block, start + 14)?,
+                            x_advance: read_i16(block, start + 16)?,
+                            page: block[start + 18],
+                        };
+                        data.chars.insert(c, bitmap_char);
+                    }
+                }
+            }
+            5 => {
+                for start in (0..block.len() / KERNING_SIZE).map(|i| i * KERNING_SIZE) {
+                    let first = std::char::from_u32(read_u32(block, start)?);
+                    let second = std::char::from_u32(read_u32(block, start + 4)?);
+                    if let (Some(first), Some(second)) = (first, second) {
+                        data.kernings
+                            .insert((first, second), read_i16(block, start + 8)?);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if data.line_height == 0 || data.scale_w == 0 || data.scale_h == 0 {
+        return Err(format_err!("Bitmap font is missing its common block"));
+    }
+    Ok(data)
+}
+
+/// Loads `BMFont` (`AngelCode`) `.fnt` files, in their text or binary format.
+///
+/// The page textures are loaded from the directory of the path the font was loaded by, unless
+/// `pages_directory` is set.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[uuid = "c4f0e7a2-81d5-4b3e-a6c9-7e2f1d0b5a48"]
+pub struct BitmapFontFormat {
+    /// The directory of the page textures, relative to the asset directory, overriding the
+    /// directory of the font when it is not empty.
+    pub pages_directory: String,
+}
+
+amethyst_assets::register_importer!(".fnt", BitmapFontFormat);
+impl Format<BitmapFontData> for BitmapFontFormat {
+    fn name(&self) -> &'static str {
+        "BMFont"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<BitmapFontData, Error> {
+        let mut data = BitmapFontData::from_bytes(&bytes)?;
+        if !self.pages_directory.is_empty() {
+            data.pages_directory = Some(self.pages_directory.trim_end_matches('/').to_string());
+        }
+        Ok(data)
+    }
+}
+
+/// A loaded bitmap font, with the handles of its page textures.
+///
+/// A `UiText` using it is drawn from the page textures rather than rasterized, so its characters
+/// keep their exact pixels. The text is drawn with a scale of its font size over the line height
+/// of the font, times the factor of the `UiScale`.
+#[derive(Clone, Debug, TypeUuid)]
+#[uuid = "9d3e6b71-0a4c-4f8d-b2e5-6c1a7f3d9e02"]
+pub struct BitmapFont {
+    /// The characters and metrics of the font.
+    pub data: BitmapFontData,
+    /// The page textures, in the order of `data.pages`.
+    pub pages: Vec<Handle<Texture>>,
+}
+
+register_asset_type!(BitmapFontData => BitmapFont; BitmapFontProcessorSystem);
+
+impl Asset for BitmapFont {
+    fn name() -> &'static str {
+        "ui::BitmapFont"
+    }
+    type Data = BitmapFontData;
+}
+
+impl BitmapFont {
+    /// Creates a bitmap font from its data and page textures.
+    #[must_use]
+    pub fn new(data: BitmapFontData, pages: Vec<Handle<Texture>>) -> Self {
+        Self { data, pages }
+    }
+}
+
+/// Processes loaded `BitmapFontData` into `BitmapFont`s, loading their page textures.
+#[derive(Debug, Default)]
+pub struct BitmapFontProcessorSystem;
+
+impl System for BitmapFontProcessorSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("BitmapFontProcessorSystem")
+                .write_resource::<ProcessingQueue<BitmapFontData>>()
+                .write_resource::<AssetStorage<BitmapFont>>()
+                .read_resource::<DefaultLoader>()
+                .build(
+                    move |_commands, _world, (processing_queue, font_storage, loader), _| {
+                        processing_queue.process(font_storage, |data, _, handle| {
+                            log::debug!("Loading bitmap font {}", data.face);
+                            let pages = page_paths(&data, loader.path(*handle).as_deref())
+                                .iter()
+                                .map(|page| loader.load(page))
+                                .collect();
+                            Ok(ProcessingState::Loaded(BitmapFont::new(data, pages)))
+                        });
+                        font_storage.process_custom_drop(|_| {});
+                    },
+                ),
+        )
+    }
+}
+
+/// Returns the paths of the page textures of a font loaded by `font_path`.
+fn page_paths(data: &BitmapFontData, font_path: Option<&str>) -> Vec<String> {
+    let directory = data
+        .pages_directory
+        .as_deref()
+        .or_else(|| font_path.and_then(|path| path.rfind('/').map(|end| &path[..end])))
+        .filter(|directory| !directory.is_empty());
+    data.pages
+        .iter()
+        .map(|page| {
+            match directory {
+                Some(directory) if !page.starts_with('/') => format!("{}/{}", directory, page),
+                _ => page.clone(),
+            }
+        })
+        .collect()
+}
+
+/// A character of a laid out bitmap text.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct BitmapGlyph {
+    /// The index of the character in the text.
+    pub(crate) index: usize,
+    /// The index of the page texture.
+    pub(crate) page: usize,
+    /// The center of the quad, in ui pixels.
+    pub(crate) coords: [f32; 2],
+    /// The size of the quad, in ui pixels.
+    pub(crate) dimensions: [f32; 2],
+    /// The left, top, right and bottom of the character in its page texture.
+    pub(crate) tex_coords: [f32; 4],
+}
+
+/// A bitmap text laid out in the rectangle of its `UiTransform`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BitmapLayout {
+    /// The drawn characters, in the order of the text.
+    pub(crate) glyphs: Vec<BitmapGlyph>,
+    /// The positions of all characters, with their baselines.
+    pub(crate) cached_glyphs: Vec<CachedGlyph>,
+    /// The height of a line.
+    pub(crate) line_height: f32,
+    /// The offset from the baseline to the middle of a line.
+    pub(crate) middle_offset: f32,
+    /// The advance of a space.
+    pub(crate) space_width: f32,
+}
+
+/// Returns the unscaled advance of `c`. Control characters, such as the new lines of single line
+/// texts, are not drawn.
+fn char_advance(font: &BitmapFontData, c: char) -> f32 {
+    if c.is_control() {
+        0.
+    } else {
+        font.char(c)
+            .map_or(0., |bitmap_char| f32::from(bitmap_char.x_advance))
+    }
+}
+
+/// Splits `chars` into lines, as ranges of indices. New lines end lines unless `wrap` is `None`,
+/// and lines wider than the width given by `wrap` are broken after their last whitespace.
+fn bitmap_lines(
+    font: &BitmapFontData,
+    chars: &[char],
+    scale: f32,
+    wrap: Option<f32>,
+) -> Vec<(usize, usize)> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut width = 0.;
+    // The index after the last whitespace of the line, where it can be broken.
+    let mut break_at = None;
+    let mut previous = None;
+    for (index, &c) in chars.iter().enumerate() {
+        if c == '\n' && wrap.is_some() {
+            lines.push((start, index + 1));
+            start = index + 1;
+            width = 0.;
+            break_at = None;
+            previous = None;
+            continue;
+        }
+
+        let kerning = previous.map_or(0., |previous| f32::from(font.kerning(previous, c)));
+        width += (kerning + char_advance(font, c)) * scale;
+        previous = Some(c);
+
+        if c.is_whitespace() {
+            break_at = Some(index + 1);
+        } else if wrap.map_or(false, |max_width| width > max_width) && index > start {
+            let end = break_at.filter(|end| *end > start).unwrap_or(index);
+            lines.push((start, end));
+            start = end;
+            break_at = None;
+            width = chars[start..=index]
+                .iter()
+                .map(|&c| char_advance(font, c) * scale)
+                .sum();
+        }
+    }
+    lines.push((start, chars.len()));
+    lines
+}
+
+/// Lays out `text` drawn with `font`, in the rectangle centered on `center` with `size`.
+/// Lines start on whole pixels, so characters keep their exact pixels at whole scales.
+pub(crate) fn layout_bitmap_text(
+    font: &BitmapFontData,
+    text: &str,
+    scale: f32,
+    line_mode: LineMode,
+    align: Anchor,
+    center: [f32; 2],
+    size: [f32; 2],
+) -> BitmapLayout {
+    let chars: Vec<char> = text.chars().collect();
+    let wrap = match line_mode {
+        LineMode::Single => None,
+        LineMode::Wrap => Some(size[0]),
+    };
+    let lines = bitmap_lines(font, &chars, scale, wrap);
+
+    let line_height = f32::from(font.line_height) * scale;
+    let base = f32::from(font.base) * scale;
+    #[allow(clippy::cast_precision_loss)]
+    let block_height = line_height * lines.len() as f32;
+    let (align_x, align_y) = align.norm_offset();
+    let top = (center[1] + size[1] * align_y + block_height * (0.5 - align_y)).round();
+
+    let (scale_w, scale_h) = (f32::from(font.scale_w), f32::from(font.scale_h));
+    let mut layout = BitmapLayout {
+        line_height,
+        middle_offset: base - line_height * 0.5,
+        space_width: font
+            .char(' ')
+            .map_or(0., |c| f32::from(c.x_advance) * scale),
+        ..BitmapLayout::default()
+    };
+    for (line, &(start, end)) in lines.iter().enumerate() {
+        let line_chars = &chars[start..end];
+        // Trailing whitespace does not move the aligned text.
+        let visible = line_chars
+            .iter()
+            .rposition(|c| !c.is_whitespace())
+            .map_or(0, |last| last + 1);
+        let mut previous = None;
+        let width: f32 = line_chars[..visible]
+            .iter()
+            .map(|&c| {
+                let kerning = previous.map_or(0., |previous| f32::from(font.kerning(previous, c)));
+                previous = Some(c);
+                kerning + char_advance(font, c)
+            })
+            .sum::<f32>()
+            * scale;
+
+        #[allow(clippy::cast_precision_loss)]
+        let line_top = top - line_height * line as f32;
+        let baseline = line_top - base;
+        let mut pen = (center[0] + size[0] * align_x - width * (align_x + 0.5)).round();
+        let mut previous = None;
+        for (offset, &c) in line_chars.iter().enumerate() {
+            if let Some(previous) = previous {
+                pen += f32::from(font.kerning(previous, c)) * scale;
+            }
+            previous = Some(c);
+
+            let bitmap_char = if c.is_control() {
+                BitmapChar::default()
+            } else {
+                font.char(c).copied().unwrap_or_default()
+            };
+            let advance_width = f32::from(bitmap_char.x_advance) * scale;
+            layout.cached_glyphs.push(CachedGlyph {
+                x: pen,
+                y: baseline,
+                advance_width,
+            });
+
+            if bitmap_char.width > 0 && bitmap_char.height > 0 && !c.is_whitespace() {
+                let (w, h) = (f32::from(bitmap_char.width), f32::from(bitmap_char.height));
+                let left = pen + f32::from(bitmap_char.x_offset) * scale;
+                let char_top = line_top - f32::from(bitmap_char.y_offset) * scale;
+                let (x, y) = (f32::from(bitmap_char.x), f32::from(bitmap_char.y));
+                layout.glyphs.push(BitmapGlyph {
+                    index: start + offset,
+                    page: usize::from(bitmap_char.page),
+                    coords: [left + w * scale * 0.5, char_top - h * scale * 0.5],
+                    dimensions: [w * scale, h * scale],
+                    tex_coords: [
+                        x / scale_w,
+                        y / scale_h,
+                        (x + w) / scale_w,
+                        (y + h) / scale_h,
+                    ],
+                });
+            }
+            pen += advance_width;
+        }
+    }
+
+    layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT_FONT: &str = r#"info face="Pixel Font" size=-8 bold=0 italic=0 padding=0,0,0,0
+common lineHeight=10 base=8 scaleW=64 scaleH=32 pages=1 packed=0
+page id=0 file="pixel font_0.png"
+chars count=3
+char id=65   x=0     y=0     width=6     height=8     xoffset=0     yoffset=0     xadvance=7     page=0  chnl=15
+char id=86   x=8     y=0     width=6     height=8     xoffset=0     yoffset=0     xadvance=7     page=0  chnl=15
+char id=32   x=0     y=0     width=0     height=0     xoffset=0     yoffset=0     xadvance=4     page=0  chnl=15
+kernings count=1
+kerning first=65 second=86 amount=-2
+"#;
+
+    fn text_font() -> BitmapFontData {
+        BitmapFontData::from_bytes(TEXT_FONT.as_bytes()).unwrap()
+    }
+
+    fn block(block_type: u8, contents: &[u8]) -> Vec<u8> {
+        let mut block = vec![block_type];
+        block.extend(&u32::try_from(contents.len()).unwrap().to_le_bytes());
+        block.extend(contents);
+        block
+    }
+
+    #[test]
+    fn parses_text_format() {
+        let font = text_font();
+        assert_eq!(font.face, "Pixel Font");
+        assert_eq!(font.size, 8);
+        assert_eq!((font.line_height, font.base), (10, 8));
+        assert_eq!((font.scale_w, font.scale_h), (64, 32));
+        assert_eq!(font.pages, vec!["pixel font_0.png".to_string()]);
+        assert_eq!(font.chars.len(), 3);
+        assert_eq!(
+            font.chars[&'V'],
+            BitmapChar {
+                x: 8,
+                y: 0,
+                width: 6,
+                height: 8,
+                x_offset: 0,
+                y_offset: 0,
+                x_advance: 7,
+                page: 0,
+            }
+        );
+        assert_eq!(font.kerning('A', 'V'), -2);
+        assert_eq!(font.kerning('V', 'A'), 0);
+    }
+
+    #[test]
+    fn parses_binary_format() {
+        let mut bytes = b"BMF\x03".to_vec();
+        let mut info = vec![0; 14];
+        info[..2].copy_from_slice(&12_i16.to_le_bytes());
+        info.extend(b"Pixel\0");
+        bytes.extend(block(1, &info));
+        let common: Vec<u8> = [10_u16, 8, 64, 32, 1]
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .chain(vec![0; 5])
+            .collect();
+        bytes.extend(block(2, &common));
+        bytes.extend(block(3, b"page_0.png\0"));
+        let mut chars = 65_u32.to_le_bytes().to_vec();
+        for v in &[1_u16, 2, 6, 8] {
+            chars.extend(&v.to_le_bytes());
+        }
+        for v in &[-1_i16, 1, 7] {
+            chars.extend(&v.to_le_bytes());
+        }
+        chars.extend(&[0, 15]);
+        bytes.extend(block(4, &chars));
+        let mut kernings = 65_u32.to_le_bytes().to_vec();
+        kernings.extend(&65_u32.to_le_bytes());
+        kernings.extend(&(-1_i16).to_le_bytes());
+        bytes.extend(block(5, &kernings));
+
+        let font = BitmapFontData::from_bytes(&bytes).unwrap();
+        assert_eq!(font.face, "Pixel");
+        assert_eq!(font.size, 12);
+        assert_eq!((font.line_height, font.base), (10, 8));
+        assert_eq!(font.pages, vec!["page_0.png".to_string()]);
+        assert_eq!(
+            font.chars[&'A'],
+            BitmapChar {
+                x: 1,
+                y: 2,
+                width: 6,
+                height: 8,
+                x_offset: -1,
+                y_offset: 1,
+                x_advance: 7,
+                page: 0,
+            }
+        );
+        assert_eq!(font.kerning('A', 'A'), -1);
+    }
+
+    #[test]
+    fn rejects_invalid_fonts() {
+        assert!(BitmapFontData::from_bytes(b"BMF\x02").is_err());
+        assert!(BitmapFontData::from_bytes(b"info face=\"x\"").is_err());
+        let extra_page = TEXT_FONT.replace("chars count", "page id=1 file=\"x.png\"\nchars count");
+        assert!(BitmapFontData::from_bytes(extra_page.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn loads_pages_from_the_font_directory() {
+        let mut font = text_font();
+        assert_eq!(
+            page_paths(&font, Some("fonts/pixel.fnt")),
+            vec!["fonts/pixel font_0.png".to_string()]
+        );
+        assert_eq!(
+            page_paths(&font, Some("pixel.fnt")),
+            vec!["pixel font_0.png".to_string()]
+        );
+        font.pages_directory = Some("textures".to_string());
+        assert_eq!(
+            page_paths(&font, Some("fonts/pixel.fnt")),
+            vec!["textures/pixel font_0.png".to_string()]
+        );
+    }
+
+    #[test]
+    fn lays_out_with_kerning() {
+        let font = text_font();
+        let layout = layout_bitmap_text(
+            &font,
+            "AV A",
+            2.,
+            LineMode::Single,
+            Anchor::TopLeft,
+            [50., 50.],
+            [100., 100.],
+        );
+        let pens: Vec<_> = layout.cached_glyphs.iter().map(|g| g.x).collect();
+        assert_eq!(pens, vec![0., 10., 24., 32.]);
+        assert_eq!(layout.cached_glyphs[0].y, 100. - 16.);
+        assert_eq!(layout.glyphs.len(), 3);
+        assert_eq!(layout.glyphs[1].index, 1);
+        assert_eq!(layout.glyphs[1].coords, [16., 92.]);
+        assert_eq!(layout.glyphs[1].dimensions, [12., 16.]);
+        assert_eq!(layout.glyphs[1].tex_coords, [0.125, 0., 0.21875, 0.25]);
+    }
+
+    #[test]
+    fn wraps_lines_at_whitespace() {
+        let font = text_font();
+        let layout = layout_bitmap_text(
+            &font,
+            "AA AA",
+            1.,
+            LineMode::Wrap,
+            Anchor::TopLeft,
+            [10., 10.],
+            [20., 20.],
+        );
+        let lines: Vec<_> = layout.cached_glyphs.iter().map(|g| (g.x, g.y)).collect();
+        assert_eq!(
+            lines,
+            vec![(0., 12.), (7., 12.), (14., 12.), (0., 2.), (7., 2.)]
+        );
+    }
+}
@@ -0,0 +1,333 @@
+//! Font families, drawing the characters missing from a font with fallback fonts.
+
+use std::collections::HashMap;
+
+use amethyst_assets::{register_asset_type, Asset, AssetProcessorSystem, Handle};
+use amethyst_rendy::SpriteRender;
+use glyph_brush::{rusttype::Font, FontId, SectionText};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+use unicode_normalization::char::is_combining_mark;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{FontAsset, MarkupSegment};
+
+/// A face of a `FontFamily`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FontFace {
+    /// The upright face of normal weight.
+    Regular,
+    /// The bold face.
+    Bold,
+    /// The italic face.
+    Italic,
+    /// The bold italic face.
+    BoldItalic,
+}
+
+impl FontFace {
+    /// Returns the face which is bold and italic as requested.
+    #[must_use]
+    pub fn new(bold: bool, italic: bool) -> Self {
+        match (bold, italic) {
+            (false, false) => FontFace::Regular,
+            (true, false) => FontFace::Bold,
+            (false, true) => FontFace::Italic,
+            (true, true) => FontFace::BoldItalic,
+        }
+    }
+}
+
+/// A list of fonts used in order to draw a `UiText`, so scripts and symbols which a font lacks are
+/// drawn with the next font having them. Latin, CJK and symbol fonts can be mixed in one text.
+///
+/// Each face has its own fonts, and falls back to the fonts of the faces closest to it, ending
+/// with the regular fonts. The font of the `UiText` and the default font are the last fallbacks.
+///
+/// Emoji and other color or bitmap glyphs are drawn from sprites, like the inline images of
+/// markup. They are not drawn in password or editable texts.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[uuid = "0e1bd2a6-9e4e-4c53-9d9c-3b3d5a1c7f42"]
+pub struct FontFamily {
+    /// The fonts of the regular face, in order of preference.
+    pub regular: Vec<Handle<FontAsset>>,
+    /// The fonts of the bold face, in order of preference.
+    pub bold: Vec<Handle<FontAsset>>,
+    /// The fonts of the italic face, in order of preference.
+    pub italic: Vec<Handle<FontAsset>>,
+    /// The fonts of the bold italic face, in order of preference.
+    pub bold_italic: Vec<Handle<FontAsset>>,
+    /// Sprites drawn in place of the given graphemes, such as emoji.
+    ///
+    /// They are serialized as the handle of their sprite sheet and their sprite number.
+    #[serde(default, with = "color_glyphs")]
+    pub color_glyphs: HashMap<String, SpriteRender>,
+}
+
+register_asset_type!(FontFamily => FontFamily; AssetProcessorSystem<FontFamily>);
+
+/// Serialization shim for the `color_glyphs` of a `FontFamily`, as `SpriteRender` is not
+/// serializable.
+mod color_glyphs {
+    use std::collections::HashMap;
+
+    use amethyst_assets::Handle;
+    use amethyst_rendy::{SpriteRender, SpriteSheet};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct ColorGlyph {
+        sprite_sheet: Handle<SpriteSheet>,
+        sprite_number: usize,
+    }
+
+    /// Serializes the sprites as the handle of their sprite sheet and their sprite number.
+    pub fn serialize<S: Serializer>(
+        glyphs: &HashMap<String, SpriteRender>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        glyphs
+            .iter()
+            .map(|(grapheme, sprite)| {
+                (
+                    grapheme,
+                    ColorGlyph {
+                        sprite_sheet: sprite.sprite_sheet.clone(),
+                        sprite_number: sprite.sprite_number,
+                    },
+                )
+            })
+            .collect::<HashMap<_, _>>()
+            .serialize(s)
+    }
+
+    /// Deserializes the sprites from the handle of their sprite sheet and their sprite number.
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        de: D,
+    ) -> Result<HashMap<String, SpriteRender>, D::Error> {
+        Ok(HashMap::<String, ColorGlyph>::deserialize(de)?
+            .into_iter()
+            .map(|(grapheme, glyph)| {
+                (
+                    grapheme,
+                    SpriteRender::new(glyph.sprite_sheet, glyph.sprite_number),
+                )
+            })
+            .collect())
+    }
+}
+
+impl Asset for FontFamily {
+    fn name() -> &'static str {
+        "ui::FontFamily"
+    }
+    type Data = Self;
+}
+
+impl FontFamily {
+    /// Creates a `FontFamily` with `font` as its first regular font.
+    #[must_use]
+    pub fn new(font: Handle<FontAsset>) -> Self {
+        Self {
+            regular: vec![font],
+            ..Self::default()
+        }
+    }
+
+    /// Adds a regular font, used for the characters which the previous fonts lack.
+    #[must_use]
+    pub fn with_fallback(mut self, font: Handle<FontAsset>) -> Self {
+        self.regular.push(font);
+        self
+    }
+
+    /// Adds a font of the bold face.
+    #[must_use]
+    pub fn with_bold(mut self, font: Handle<FontAsset>) -> Self {
+        self.bold.push(font);
+        self
+    }
+
+    /// Adds a font of the italic face.
+    #[must_use]
+    pub fn with_italic(mut self, font: Handle<FontAsset>) -> Self {
+        self.italic.push(font);
+        self
+    }
+
+    /// Adds a font of the bold italic face.
+    #[must_use]
+    pub fn with_bold_italic(mut self, font: Handle<FontAsset>) -> Self {
+        self.bold_italic.push(font);
+        self
+    }
+
+    /// Draws `sprite` in place of the grapheme `grapheme`, such as an emoji.
+    #[must_use]
+    pub fn with_color_glyph<S: ToString>(mut self, grapheme: &S, sprite: SpriteRender) -> Self {
+        self.color_glyphs.insert(grapheme.to_string(), sprite);
+        self
+    }
+
+    /// Returns the fonts used for `face`, in order of preference.
+    #[must_use]
+    pub fn fonts(&self, face: FontFace) -> Vec<&Handle<FontAsset>> {
+        let faces: &[&Vec<Handle<FontAsset>>] = match face {
+            FontFace::Regular => &[&self.regular],
+            FontFace::Bold => &[&self.bold, &self.regular],
+            FontFace::Italic => &[&self.italic, &self.regular],
+            FontFace::BoldItalic => &[&self.bold_italic, &self.bold, &self.italic, &self.regular],
+        };
+        faces.iter().flat_map(|fonts| fonts.iter()).collect()
+    }
+}
+
+/// Returns whether `font` has a glyph for `c`, rather than drawing it as the missing glyph.
+pub(crate) fn has_glyph(font: &Font<'_>, c: char) -> bool {
+    font.glyph(c).id().0 != 0
+}
+
+/// Splits the sections into runs of characters drawn with the same font, as given by `font_for`
+/// for the font of the section and a character.
+/// Whitespace and combining marks stay in the run of the character before them.
+pub(crate) fn split_by_font<'a, F>(
+    sections: &[SectionText<'a>],
+    mut font_for: F,
+) -> Vec<SectionText<'a>>
+where
+    F: FnMut(FontId, char) -> FontId,
+{
+    let mut runs = Vec::with_capacity(sections.len());
+    for section in sections {
+        let run = |text, font_id| SectionText {
+            text,
+            scale: section.scale,
+            color: section.color,
+            font_id,
+        };
+
+        let mut start = 0;
+        let mut current = None;
+        for (index, c) in section.text.char_indices() {
+            if current.is_some() && (c.is_whitespace() || is_combining_mark(c)) {
+                continue;
+            }
+            let font_id = font_for(section.font_id, c);
+            match current {
+                Some(current_id) if current_id != font_id => {
+                    runs.push(run(&section.text[start..index], current_id));
+                    start = index;
+                }
+                _ => {}
+            }
+            current = Some(font_id);
+        }
+        runs.push(run(
+            &section.text[start..],
+            current.unwrap_or(section.font_id),
+        ));
+    }
+    runs
+}
+
+/// Returns whether `text` has a grapheme drawn from a sprite.
+pub(crate) fn has_color_glyph<F>(text: &str, is_color_glyph: F) -> bool
+where
+    F: Fn(&str) -> bool,
+{
+    text.graphemes(true).any(is_color_glyph)
+}
+
+/// Replaces the graphemes of the text segments which are drawn from a sprite by image segments,
+/// named after the grapheme.
+pub(crate) fn split_color_glyphs<F>(
+    segments: Vec<MarkupSegment>,
+    is_color_glyph: F,
+) -> Vec<MarkupSegment>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut result = Vec::with_capacity(segments.len());
+    for segment in segments {
+        let (text, style) = match segment {
+            MarkupSegment::Text { text, style } if has_color_glyph(&text, &is_color_glyph) => {
+                (text, style)
+            }
+            segment => {
+                result.push(segment);
+                continue;
+            }
+        };
+
+        let mut run = String::new();
+        for grapheme in text.graphemes(true) {
+            if is_color_glyph(grapheme) {
+                if !run.is_empty() {
+                    result.push(MarkupSegment::Text {
+                        text: std::mem::take(&mut run),
+                        style: style.clone(),
+                    });
+                }
+                result.push(MarkupSegment::Image {
+                    name: grapheme.to_string(),
+                    style: style.clone(),
+                });
+            } else {
+                run.push_str(grapheme);
+            }
+        }
+        if !run.is_empty() {
+            result.push(MarkupSegment::Text { text: run, style });
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use glyph_brush::rusttype::Scale;
+
+    use super::*;
+    use crate::MarkupStyle;
+
+    #[test]
+    fn splits_runs_by_font() {
+        let sections = [SectionText {
+            text: "ab 漢字\u{301} c",
+            scale: Scale::uniform(10.),
+            color: [1., 1., 1., 1.],
+            font_id: FontId(0),
+        }];
+        let runs = split_by_font(
+            &sections,
+            |font_id, c| {
+                if c.is_ascii() {
+                    font_id
+                } else {
+                    FontId(1)
+                }
+            },
+        );
+        let runs: Vec<_> = runs.iter().map(|run| (run.text, run.font_id)).collect();
+        assert_eq!(
+            runs,
+            vec![
+                ("ab ", FontId(0)),
+                ("漢字\u{301} ", FontId(1)),
+                ("c", FontId(0))
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_color_glyphs() {
+        let segments = vec![MarkupSegment::Text {
+            text: "hi 😀!".to_string(),
+            style: MarkupStyle::default(),
+        }];
+        let segments = split_color_glyphs(segments, |grapheme| grapheme == "😀");
+        assert_eq!(segments.len(), 3);
+        assert!(matches!(&segments[1], MarkupSegment::Image { name, .. } if name == "😀"));
+        assert!(matches!(&segments[2], MarkupSegment::Text { text, .. } if text == "!"));
+    }
+}
@@ -1,2 +1,4 @@
+pub mod bitmap;
 pub mod default;
+pub mod family;
 pub mod systemfont;
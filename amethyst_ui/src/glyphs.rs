@@ -1,6 +1,10 @@
 //! Module containing the system managing glyphbrush state for visible UI Text components.
 
-use std::{collections::HashMap, marker::PhantomData, ops::Deref};
+use std::{
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+    ops::Deref,
+};
 
 use amethyst_assets::{
     AssetHandle, AssetStorage, DefaultLoader, Handle, LoadHandle, Loader, ProcessingQueue,
@@ -20,9 +24,11 @@ use amethyst_rendy::{
     resources::Tint,
     Backend, Texture,
 };
+use amethyst_window::ScreenDimensions;
 use glyph_brush::{
-    rusttype::Scale, BrushAction, BrushError, BuiltInLineBreaker, FontId, GlyphBrush,
-    GlyphBrushBuilder, GlyphCruncher, Layout, LineBreak, LineBreaker, SectionText, VariedSection,
+    rusttype::{Font, Scale},
+    BrushAction, BrushError, BuiltInLineBreaker, FontId, GlyphBrush, GlyphBrushBuilder,
+    GlyphCruncher, Layout, LineBreak, LineBreaker, SectionText, VariedSection,
 };
 use log::debug;
 use serde::Deserialize;
@@ -30,8 +36,17 @@ use type_uuid::TypeUuid;
 use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
-    format::FontData, get_default_font, pass::UiArgs, text::CachedGlyph, FontAsset, LineMode,
-    Selected, TextEditing, UiText, UiTransform,
+    font::{
+        bitmap::layout_bitmap_text,
+        family::{has_color_glyph, has_glyph, split_by_font, split_color_glyphs},
+    },
+    format::FontData,
+    get_default_font,
+    markup::{markup_sections, parse_markup, UiMarkupResources},
+    pass::{UiArgs, NO_RADIAL_FILL},
+    text::CachedGlyph,
+    BitmapFont, FontAsset, FontFace, FontFamily, LineMode, MarkupSegment, MarkupStyle, Selected,
+    TextEditing, UiScale, UiText, UiTransform,
 };
 
 #[derive(Debug)]
@@ -65,6 +80,9 @@ impl UiGlyphsResource {
 pub struct UiGlyphs {
     pub(crate) sel_vertices: Vec<UiArgs>,
     pub(crate) vertices: Vec<UiArgs>,
+    // the page textures and glyphs of texts drawn with a bitmap font
+    pub(crate) bitmap_pages: Vec<Handle<Texture>>,
+    pub(crate) bitmap_vertices: Vec<(usize, UiArgs)>,
     // props below are only filled for selected fields
     pub(crate) cursor_pos: (f32, f32),
     pub(crate) height: f32,
@@ -124,6 +142,90 @@ impl<B: Backend> Default for UiGlyphsSystem<B> {
     }
 }
 
+impl<B: Backend> UiGlyphsSystem<B> {
+    /// Returns the glyph brush id of a font, adding the font to the glyph brush on first use.
+    fn font_id(
+        &mut self,
+        font_handle: &Handle<FontAsset>,
+        font_storage: &AssetStorage<FontAsset>,
+    ) -> Option<FontId> {
+        if let FontState::NotFound = self
+            .fonts_map
+            .entry(font_handle.load_handle())
+            .or_insert(FontState::NotFound)
+        {
+            if let Some(font) = font_storage.get(font_handle) {
+                log::debug!("Adding font to glyph brush.");
+                let new_font = FontState::Ready(self.glyph_brush.add_font(font.0.clone()));
+                self.fonts_map.insert(font_handle.load_handle(), new_font);
+            }
+        }
+
+        self.fonts_map.get(&font_handle.load_handle()).unwrap().id()
+    }
+
+    /// Returns the fonts of `handles` which are loaded, with their glyph brush ids.
+    fn font_chain(
+        &mut self,
+        handles: &[&Handle<FontAsset>],
+        font_storage: &AssetStorage<FontAsset>,
+    ) -> Vec<(FontId, Font<'static>)> {
+        let mut chain: Vec<(FontId, Font<'static>)> = Vec::with_capacity(handles.len());
+        for handle in handles {
+            if let (Some(id), Some(font)) =
+                (self.font_id(handle, font_storage), font_storage.get(handle))
+            {
+                if chain.iter().all(|(chain_id, _)| *chain_id != id) {
+                    chain.push((id, font.0.clone()));
+                }
+            }
+        }
+        chain
+    }
+}
+
+/// Returns the fonts of a face of `ui_text`, in order of preference.
+/// The font of the text and the default font are the last fallbacks.
+fn face_fonts<'a>(
+    ui_text: &'a UiText,
+    family: Option<&'a FontFamily>,
+    face: FontFace,
+    markup_res: &'a UiMarkupResources,
+    default_font: &'a Handle<FontAsset>,
+) -> Vec<&'a Handle<FontAsset>> {
+    let mut fonts = match family {
+        Some(family) => family.fonts(face),
+        None => {
+            match face {
+                FontFace::Regular => vec![],
+                FontFace::Bold | FontFace::BoldItalic => markup_res.bold_font.iter().collect(),
+                FontFace::Italic => markup_res.italic_font.iter().collect(),
+            }
+        }
+    };
+    fonts.extend(ui_text.font.as_ref());
+    fonts.push(default_font);
+    fonts
+}
+
+/// Returns the first loaded font of the face of `ui_text`, which gives its line metrics.
+fn primary_font<'a>(
+    ui_text: &UiText,
+    family_storage: &AssetStorage<FontFamily>,
+    markup_res: &UiMarkupResources,
+    default_font: &Handle<FontAsset>,
+    font_storage: &'a AssetStorage<FontAsset>,
+) -> Option<&'a FontAsset> {
+    let family = ui_text
+        .font_family
+        .as_ref()
+        .and_then(|family| family_storage.get(family));
+    let face = FontFace::new(ui_text.bold, ui_text.italic);
+    face_fonts(ui_text, family, face, markup_res, default_font)
+        .into_iter()
+        .find_map(|font| font_storage.get(font))
+}
+
 use derivative::Derivative;
 
 #[derive(Debug, Clone, TypeUuid, Deserialize)]
@@ -193,8 +295,13 @@ impl<B: Backend> System for UiGlyphsSystem<B> {
                 .write_resource::<AssetStorage<Texture>>()
                 .write_resource::<ProcessingQueue<GlyphTextureData>>()
                 .read_resource::<AssetStorage<FontAsset>>()
+                .read_resource::<AssetStorage<FontFamily>>()
+                .read_resource::<AssetStorage<BitmapFont>>()
                 .write_resource::<UiGlyphsResource>()
                 .read_resource::<DefaultLoader>()
+                .read_resource::<UiMarkupResources>()
+                .read_resource::<UiScale>()
+                .read_resource::<ScreenDimensions>()
                 .with_query(
                     <(
                         Entity,
@@ -226,8 +333,13 @@ impl<B: Backend> System for UiGlyphsSystem<B> {
                         tex_storage,
                         tex_queue,
                         font_storage,
+                        family_storage,
+                        bitmap_storage,
                         glyphs_res,
                         loader,
+                        markup_res,
+                        ui_scale,
+                        screen_dimensions,
                     ),
                           (
                         texts_not_hidden_query_with_optional_editing,
@@ -236,6 +348,9 @@ impl<B: Backend> System for UiGlyphsSystem<B> {
                         selected_query,
                     )| {
                         let queue = **fetch_queue.deref();
+                        // Fonts are rasterized at their size in physical pixels, so text stays
+                        // sharp when the ui is scaled up.
+                        let factor = ui_scale.factor(screen_dimensions);
 
                         let glyph_tex = {
                             glyphs_res.glyph_tex.get_or_insert_with(|| {
@@ -251,38 +366,58 @@ impl<B: Backend> System for UiGlyphsSystem<B> {
                                 world.split_for_query(glyphs_query);
                             let (mut selected_world, mut else_world) =
                                 else_world.split_for_query(selected_query);
+                            // Texts drawn with a bitmap font do not go through the glyph brush.
+                            let mut bitmap_entities = HashSet::new();
 
                             texts_not_hidden_query_with_optional_editing.for_each_mut(
                                 &mut else_world,
                                 |(entity, transform, ui_text, editing, tint)| {
                                     ui_text.cached_glyphs.clear();
-                                    let font_handle =
-                                        ui_text.font.as_ref().unwrap_or(&glyphs_res.default_font);
-
-                                    let font_asset =
-                                        font_storage.get(font_handle).map(|font| font.0.clone());
-
-                                    if let FontState::NotFound = self
-                                        .fonts_map
-                                        .entry(font_handle.load_handle())
-                                        .or_insert(FontState::NotFound)
+                                    ui_text.cached_icons.clear();
+                                    ui_text.cached_links.clear();
+                                    if let Some(bitmap_font) = ui_text
+                                        .bitmap_font
+                                        .as_ref()
+                                        .and_then(|font| bitmap_storage.get(font))
                                     {
-                                        if let Some(font) = font_storage.get(font_handle) {
-                                            log::debug!("Adding font to glyph brush.");
-                                            let new_font = FontState::Ready(
-                                                self.glyph_brush.add_font(font.0.clone()),
-                                            );
-                                            self.fonts_map
-                                                .insert(font_handle.load_handle(), new_font);
+                                        let selected = selected_query
+                                            .get_mut(&mut selected_world, *entity)
+                                            .is_ok();
+                                        let glyphs = bitmap_glyphs(
+                                            bitmap_font,
+                                            transform,
+                                            ui_text,
+                                            editing,
+                                            tint,
+                                            selected,
+                                            factor,
+                                        );
+                                        if let Ok((_, glyph_data)) =
+                                            glyphs_query.get_mut(&mut glyph_world, *entity)
+                                        {
+                                            *glyph_data = glyphs;
+                                        } else {
+                                            commands.add_component(*entity, glyphs);
                                         }
+                                        bitmap_entities.insert(*entity);
+                                        return;
                                     }
-
-                                    let font_lookup =
-                                        self.fonts_map.get(&font_handle.load_handle()).unwrap();
-
-                                    if let (Some(font_id), Some(font_asset)) =
-                                        (font_lookup.id(), font_asset)
-                                    {
+                                    let family = ui_text
+                                        .font_family
+                                        .as_ref()
+                                        .and_then(|family| family_storage.get(family));
+                                    let (bold, italic) = (ui_text.bold, ui_text.italic);
+                                    let face = FontFace::new(bold, italic);
+                                    let fonts = face_fonts(
+                                        ui_text,
+                                        family,
+                                        face,
+                                        markup_res,
+                                        &glyphs_res.default_font,
+                                    );
+                                    let chain = self.font_chain(&fonts, font_storage);
+
+                                    if let Some((font_id, font_asset)) = chain.first().cloned() {
                                         let tint_color = tint.map_or([1., 1., 1., 1.], |t| {
                                             let (r, g, b, a) = t.0.into_components();
                                             [r, g, b, a]
@@ -290,9 +425,92 @@ impl<B: Backend> System for UiGlyphsSystem<B> {
 
                                         let base_color = mul_blend(&ui_text.color, &tint_color);
 
-                                        let scale = Scale::uniform(ui_text.font_size);
+                                        let scale = Scale::uniform(ui_text.font_size * factor);
+
+                                        // Color glyphs are drawn as the inline images of markup.
+                                        let color_glyphs = family
+                                            .map(|family| &family.color_glyphs)
+                                            .filter(|color_glyphs| !color_glyphs.is_empty());
+                                        let is_color_glyph = |grapheme: &str| {
+                                            color_glyphs.map_or(false, |color_glyphs| {
+                                                color_glyphs.contains_key(grapheme)
+                                            })
+                                        };
+                                        let markup_segments = if ui_text.password
+                                            || editing.is_some()
+                                        {
+                                            None
+                                        } else if ui_text.markup {
+                                            Some(split_color_glyphs(
+                                                parse_markup(&ui_text.text),
+                                                is_color_glyph,
+                                            ))
+                                        } else if has_color_glyph(&ui_text.text, is_color_glyph) {
+                                            let segment = MarkupSegment::Text {
+                                                text: ui_text.text.clone(),
+                                                style: MarkupStyle::default(),
+                                            };
+                                            Some(split_color_glyphs(vec![segment], is_color_glyph))
+                                        } else {
+                                            None
+                                        };
+
+                                        let mut chains = vec![(face, chain)];
+                                        if markup_segments.is_some() {
+                                            for &(style_bold, style_italic) in
+                                                &[(true, false), (false, true), (true, true)]
+                                            {
+                                                let style_face = FontFace::new(
+                                                    bold || style_bold,
+                                                    italic || style_italic,
+                                                );
+                                                if chains
+                                                    .iter()
+                                                    .all(|(face, _)| *face != style_face)
+                                                {
+                                                    let fonts = face_fonts(
+                                                        ui_text,
+                                                        family,
+                                                        style_face,
+                                                        markup_res,
+                                                        &glyphs_res.default_font,
+                                                    );
+                                                    let chain =
+                                                        self.font_chain(&fonts, font_storage);
+                                                    chains.push((style_face, chain));
+                                                }
+                                            }
+                                        }
+
+                                        let markup = markup_segments.as_ref().map(|segments| {
+                                            let face_font_id = |style_face: FontFace| {
+                                                chains
+                                                    .iter()
+                                                    .find(|(face, _)| *face == style_face)
+                                                    .and_then(|(_, chain)| chain.first())
+                                                    .map_or(font_id, |(id, _)| *id)
+                                            };
+                                            markup_sections(
+                                                segments,
+                                                ui_text,
+                                                tint_color,
+                                                &font_asset,
+                                                markup_res,
+                                                color_glyphs,
+                                                factor,
+                                                |style| {
+                                                    face_font_id(FontFace::new(
+                                                        bold || style.bold,
+                                                        italic || style.italic,
+                                                    ))
+                                                },
+                                            )
+                                        });
 
                                         let text = match (ui_text.password, editing) {
+                                            _ if markup.is_some() => {
+                                                markup.as_ref().unwrap().sections.clone()
+                                            }
                                             (false, None) => {
                                                 vec![SectionText {
                                                     text: &ui_text.text,
@@ -390,6 +608,24 @@ impl<B: Backend> System for UiGlyphsSystem<B> {
                                             }
                                         };
 
+                                        // Each character is drawn with the first font of its face having it.
+                                        let text = split_by_font(&text, |section_font, c| {
+                                            chains
+                                                .iter()
+                                                .map(|(_, chain)| chain)
+                                                .find(|chain| {
+                                                    chain.first().map_or(false, |(id, _)| {
+                                                        *id == section_font
+                                                    })
+                                                })
+                                                .and_then(|chain| {
+                                                    chain
+                                                        .iter()
+                                                        .find(|(_, font)| has_glyph(font, c))
+                                                })
+                                                .map_or(section_font, |(id, _)| *id)
+                                        });
+
                                         let layout = match ui_text.line_mode {
                                             LineMode::Single => {
                                                 Layout::SingleLine {
@@ -448,48 +684,65 @@ impl<B: Backend> System for UiGlyphsSystem<B> {
                                         // For support, see:
                                         //
                                         // <https://github.com/alexheretic/glyph-brush/issues/80>
-                                        let mut nonempty_cached_glyphs = self
-                                            .glyph_brush
-                                            .glyphs_custom_layout(&section, &layout)
-                                            .map(|g| {
-                                                let pos = g.position();
-                                                let advance_width =
-                                                    g.unpositioned().h_metrics().advance_width;
-                                                CachedGlyph {
-                                                    x: pos.x,
-                                                    y: -pos.y,
-                                                    advance_width,
-                                                }
-                                            });
-
-                                        let mut last_cached_glyph: Option<CachedGlyph> = None;
-                                        let all_glyphs = ui_text.text.chars().filter_map(|c| {
-                                            if c.is_whitespace() {
-                                                let (x, y) = last_cached_glyph.map_or((0.0, 0.0), |last_cached_glyph| {
-                                                    let x = last_cached_glyph.x
-                                                        + last_cached_glyph.advance_width;
-                                                    let y = last_cached_glyph.y;
-                                                    (x, y)
+                                        if let Some(markup) = &markup {
+                                            let glyphs: Vec<_> = self
+                                                .glyph_brush
+                                                .glyphs_custom_layout(&section, &layout)
+                                                .map(|g| {
+                                                    let pos = g.position();
+                                                    let advance_width =
+                                                        g.unpositioned().h_metrics().advance_width;
+                                                    (pos.x, -pos.y, advance_width)
+                                                })
+                                                .collect();
+                                            let (icons, links) =
+                                                markup.resolve(&glyphs, &font_asset);
+                                            ui_text.cached_icons = icons;
+                                            ui_text.cached_links = links;
+                                        } else {
+                                            let mut nonempty_cached_glyphs = self
+                                                .glyph_brush
+                                                .glyphs_custom_layout(&section, &layout)
+                                                .map(|g| {
+                                                    let pos = g.position();
+                                                    let advance_width =
+                                                        g.unpositioned().h_metrics().advance_width;
+                                                    CachedGlyph {
+                                                        x: pos.x,
+                                                        y: -pos.y,
+                                                        advance_width,
+                                                    }
                                                 });
 
-                                                let advance_width = font_asset
-                                                    .glyph(c)
-                                                    .scaled(scale)
-                                                    .h_metrics()
-                                                    .advance_width;
-
-                                                let cached_glyph = CachedGlyph {
-                                                    x,
-                                                    y,
-                                                    advance_width,
-                                                };
-                                                last_cached_glyph = Some(cached_glyph);
-                                            } else {
-                                                last_cached_glyph = nonempty_cached_glyphs.next();
-                                            }
-                                            last_cached_glyph
-                                        });
-                                        ui_text.cached_glyphs.extend(all_glyphs);
+                                            let mut last_cached_glyph: Option<CachedGlyph> = None;
+                                            let all_glyphs = ui_text.text.chars().filter_map(|c| {
+                                                if c.is_whitespace() {
+                                                    let (x, y) = last_cached_glyph.map_or((0.0, 0.0), |last_cached_glyph| {
+                                                        let x = last_cached_glyph.x
+                                                            + last_cached_glyph.advance_width;
+                                                        let y = last_cached_glyph.y;
+                                                        (x, y)
+                                                    });
+
+                                                    let advance_width = font_asset
+                                                        .glyph(c)
+                                                        .scaled(scale)
+                                                        .h_metrics()
+                                                        .advance_width;
+
+                                                    let cached_glyph = CachedGlyph {
+                                                        x,
+                                                        y,
+                                                        advance_width,
+                                                    };
+                                                    last_cached_glyph = Some(cached_glyph);
+                                                } else {
+                                                    last_cached_glyph = nonempty_cached_glyphs.next();
+                                                }
+                                                last_cached_glyph
+                                            });
+                                            ui_text.cached_glyphs.extend(all_glyphs);
+                                        }
 
                                         self.glyph_brush.queue_custom_layout(section, &layout);
                                     }
@@ -605,6 +858,7 @@ impl<B: Backend> System for UiGlyphsSystem<B> {
                                                 tex_coord_bounds: tex_coord_bounds.into(),
                                                 color: glyph.color.into(),
                                                 color_bias: [1., 1., 1., 0.].into(),
+                                                radial_fill: NO_RADIAL_FILL.into(),
                                             },
                                         )
                                     },
@@ -618,16 +872,22 @@ impl<B: Backend> System for UiGlyphsSystem<B> {
 
                                         // make sure to erase all glyphs, even if not queued this frame
 
-                                        for (_, glyph_data) in
+                                        for (entity, glyph_data) in
                                             glyphs_query.iter_mut(&mut glyph_world)
                                         {
-                                            glyph_data.vertices.clear();
-                                            glyph_data.sel_vertices.clear();
+                                            if !bitmap_entities.contains(entity) {
+                                                glyph_data.vertices.clear();
+                                                glyph_data.sel_vertices.clear();
+                                                glyph_data.bitmap_vertices.clear();
+                                            }
                                         }
 
                                         texts_not_hidden_query_with_optional_editing.for_each_mut(
                                             &mut else_world,
                                             |(entity, transform, ui_text, editing, tint)| {
+                                                if bitmap_entities.contains(entity) {
+                                                    return;
+                                                }
                                                 let len = vertices[glyph_ctr..]
                                                     .iter()
                                                     .take_while(|(id, _)| {
@@ -651,6 +911,8 @@ impl<B: Backend> System for UiGlyphsSystem<B> {
                                                         UiGlyphs {
                                                             vertices: entity_verts.collect(),
                                                             sel_vertices: vec![],
+                                                            bitmap_pages: vec![],
+                                                            bitmap_vertices: vec![],
                                                             cursor_pos: (0., 0.),
                                                             height: 0.,
                                                             space_width: 0.,
@@ -658,17 +920,17 @@ impl<B: Backend> System for UiGlyphsSystem<B> {
                                                     );
                                                 }
 
-                                                let font_handle = ui_text
-                                                    .font
-                                                    .as_ref()
-                                                    .unwrap_or(&glyphs_res.default_font);
-
                                                 if let Some(editing) = editing {
-                                                    if let Some(font) =
-                                                        font_storage.get(font_handle)
-                                                    {
-                                                        let scale =
-                                                            Scale::uniform(ui_text.font_size);
+                                                    if let Some(font) = primary_font(
+                                                        ui_text,
+                                                        family_storage,
+                                                        markup_res,
+                                                        &glyphs_res.default_font,
+                                                        font_storage,
+                                                    ) {
+                                                        let scale = Scale::uniform(
+                                                            ui_text.font_size * factor,
+                                                        );
                                                         let v_metrics = font.0.v_metrics(scale);
                                                         let height =
                                                             v_metrics.ascent - v_metrics.descent;
@@ -729,6 +991,8 @@ impl<B: Backend> System for UiGlyphsSystem<B> {
                                                                     color: bg_color.into(),
                                                                     color_bias: [1., 1., 1., 0.]
                                                                         .into(),
+                                                                    radial_fill: NO_RADIAL_FILL
+                                                                        .into(),
                                                                 }
                                                             });
 
@@ -760,14 +1024,19 @@ impl<B: Backend> System for UiGlyphsSystem<B> {
                                     Ok(BrushAction::ReDraw) => {
                                         not_hidden_glyphs_query_with_editing.for_each_mut(
                                             world,
-                                            |(_, glyph_data, transform, ui_text, editing)| {
-                                                let font_handle = ui_text
-                                                    .font
-                                                    .as_ref()
-                                                    .unwrap_or(&glyphs_res.default_font);
-
-                                                if let Some(font) = font_storage.get(font_handle) {
-                                                    let scale = Scale::uniform(ui_text.font_size);
+                                            |(entity, glyph_data, transform, ui_text, editing)| {
+                                                if bitmap_entities.contains(entity) {
+                                                    return;
+                                                }
+                                                if let Some(font) = primary_font(
+                                                    ui_text,
+                                                    family_storage,
+                                                    markup_res,
+                                                    &glyphs_res.default_font,
+                                                    font_storage,
+                                                ) {
+                                                    let scale =
+                                                        Scale::uniform(ui_text.font_size * factor);
                                                     let v_metrics = font.0.v_metrics(scale);
                                                     let pos = editing.cursor_position;
                                                     let offset = (v_metrics.ascent
@@ -808,6 +1077,119 @@ impl<B: Backend> System for UiGlyphsSystem<B> {
     }
 }
 
+/// Lays out a text drawn with a bitmap font, returning its glyphs.
+fn bitmap_glyphs(
+    font: &BitmapFont,
+    transform: &UiTransform,
+    ui_text: &mut UiText,
+    editing: Option<&TextEditing>,
+    tint: Option<&Tint>,
+    selected: bool,
+    factor: f32,
+) -> UiGlyphs {
+    let text = if ui_text.password {
+        let dot = if font.data.chars.contains_key(&'\u{2022}') {
+            '\u{2022}'
+        } else {
+            '*'
+        };
+        std::iter::repeat(dot)
+            .take(ui_text.text.graphemes(true).count())
+            .collect()
+    } else {
+        ui_text.text.clone()
+    };
+    let scale = ui_text.font_size * factor / f32::from(font.data.line_height.max(1));
+    let layout = layout_bitmap_text(
+        &font.data,
+        &text,
+        scale,
+        ui_text.line_mode,
+        ui_text.align,
+        [transform.pixel_x(), transform.pixel_y()],
+        [transform.pixel_width, transform.pixel_height],
+    );
+
+    let tint_color = tint.map_or([1., 1., 1., 1.], |t| {
+        let (r, g, b, a) = t.0.into_components();
+        [r, g, b, a]
+    });
+    let base_color = mul_blend(&ui_text.color, &tint_color);
+    let total_len = layout.cached_glyphs.len();
+    let selection = editing.map(|editing| {
+        let pos = editing.cursor_position;
+        let pos_highlight = editing.cursor_position + editing.highlight_vector;
+        let start = (pos.min(pos_highlight) as usize).min(total_len);
+        let end = (pos.max(pos_highlight) as usize).min(total_len);
+        (
+            start..end,
+            mul_blend(&editing.selected_text_color, &tint_color),
+        )
+    });
+
+    // Bitmap pages are colored textures, so their color is not biased.
+    let bitmap_vertices = layout
+        .glyphs
+        .iter()
+        .map(|glyph| {
+            let color = match &selection {
+                Some((range, selected_color)) if range.contains(&glyph.index) => *selected_color,
+                _ => base_color,
+            };
+            let args = UiArgs {
+                coords: glyph.coords.into(),
+                dimensions: glyph.dimensions.into(),
+                tex_coord_bounds: glyph.tex_coords.into(),
+                color: color.into(),
+                color_bias: [0., 0., 0., 0.].into(),
+                radial_fill: NO_RADIAL_FILL.into(),
+            };
+            (glyph.page, args)
+        })
+        .collect();
+    ui_text.cached_glyphs = layout.cached_glyphs;
+
+    let mut glyphs = UiGlyphs {
+        sel_vertices: vec![],
+        vertices: vec![],
+        bitmap_pages: font.pages.clone(),
+        bitmap_vertices,
+        cursor_pos: (0., 0.),
+        height: layout.line_height,
+        space_width: layout.space_width,
+    };
+
+    if let (Some(editing), Some((range, _))) = (editing, selection) {
+        let bg_color = editing.selected_background_color;
+        let bg_color = if selected {
+            bg_color
+        } else {
+            mul_blend(&bg_color, &[0.5, 0.5, 0.5, 0.5])
+        };
+        let bg_color = mul_blend(&tint_color, &bg_color);
+        let offset = layout.middle_offset;
+        glyphs.sel_vertices = ui_text.cached_glyphs[range]
+            .iter()
+            .map(|g| UiArgs {
+                coords: [g.x + g.advance_width * 0.5, g.y + offset].into(),
+                dimensions: [g.advance_width, layout.line_height].into(),
+                tex_coord_bounds: [0., 0., 1., 1.].into(),
+                color: bg_color.into(),
+                color_bias: [1., 1., 1., 0.].into(),
+                radial_fill: NO_RADIAL_FILL.into(),
+            })
+            .collect();
+        update_cursor_position(
+            &mut glyphs,
+            ui_text,
+            transform,
+            editing.cursor_position as usize,
+            offset,
+        );
+    }
+    glyphs
+}
+
 fn update_cursor_position(
     glyph_data: &mut UiGlyphs,
     ui_text: &UiText,
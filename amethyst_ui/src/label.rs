@@ -7,8 +7,8 @@ use amethyst_core::{
 };
 
 use crate::{
-    define_widget, Anchor, FontAsset, LineMode, Selectable, Stretch, UiText, UiTransform, WidgetId,
-    Widgets,
+    define_widget, Anchor, FontAsset, LineMode, Selectable, Stretch, UiStyle, UiText, UiTheme,
+    UiTransform, WidgetId, Widgets,
 };
 
 const DEFAULT_Z: f32 = 1.0;
@@ -61,6 +61,7 @@ pub struct UiLabelBuilder<G, I: WidgetId> {
     align: Anchor,
     parent: Option<Entity>,
     selectable: Option<u32>,
+    style: Option<UiStyle>,
     _phantom: PhantomData<G>,
 }
 
@@ -86,6 +87,7 @@ where
             align: Anchor::Middle,
             parent: None,
             selectable: None,
+            style: None,
             _phantom: PhantomData,
         }
     }
@@ -197,6 +199,13 @@ impl<'a, G: PartialEq + Send + Sync + 'static, I: WidgetId> UiLabelBuilder<G, I>
         self
     }
 
+    /// Style the label with the style `name` of `theme`.
+    /// The style is applied over the color, font and font size set on this builder.
+    pub fn with_style<S: ToString>(mut self, theme: Handle<UiTheme>, name: &S) -> Self {
+        self.style = Some(UiStyle::new(theme, name));
+        self
+    }
+
     /// Build this with the `UiLabelBuilderResources`.
     pub fn build_from_world_and_resources(
         mut self,
@@ -249,6 +258,10 @@ impl<'a, G: PartialEq + Send + Sync + 'static, I: WidgetId> UiLabelBuilder<G, I>
             text_entry.add_component(Selectable::<G>::new(order));
         }
 
+        if let Some(style) = self.style.take() {
+            text_entry.add_component(style);
+        }
+
         if let Some(parent) = self.parent.take() {
             text_entry.add_component(Parent(parent));
         }
@@ -26,6 +26,99 @@ pub enum ScaleMode {
     Percent,
 }
 
+/// How the units of `UiTransform`s are converted to the physical pixels of the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum UiScaleMode {
+    /// One unit is one physical pixel, as before `UiScale` existed.
+    Physical,
+    /// One unit is one logical pixel, multiplied by the scale factor of the window so the ui
+    /// keeps its size on high-DPI displays.
+    Logical,
+    /// The ui is designed for a reference resolution and scaled uniformly to fit the screen.
+    ReferenceResolution {
+        /// The width the ui was designed for.
+        width: f32,
+        /// The height the ui was designed for.
+        height: f32,
+        /// Blends between fitting the width, at `0.`, and fitting the height, at `1.`.
+        match_height: f32,
+    },
+}
+
+/// Global scaling applied to the whole ui tree.
+///
+/// Positions, sizes, margins and font sizes are multiplied by `factor`, which converts them to
+/// physical pixels. `Percent` positions and sizes are relative to their parent, so they are not
+/// affected.
+///
+/// The default does not scale the ui, use `UiScaleMode::Logical` to keep its size on high-DPI
+/// displays.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct UiScale {
+    /// How ui units are converted to physical pixels.
+    pub mode: UiScaleMode,
+    /// An additional multiplier, such as a user preference.
+    pub scale: f32,
+}
+
+impl Default for UiScale {
+    fn default() -> Self {
+        Self {
+            mode: UiScaleMode::Physical,
+            scale: 1.,
+        }
+    }
+}
+
+impl UiScale {
+    /// The smallest factor returned by `factor`, so ui units can always be converted back from
+    /// physical pixels.
+    pub const MIN_FACTOR: f32 = 0.001;
+
+    /// Creates a `UiScale` scaling the ui designed for `width` x `height` to the screen.
+    #[must_use]
+    pub fn reference_resolution(width: f32, height: f32, match_height: f32) -> Self {
+        Self {
+            mode: UiScaleMode::ReferenceResolution {
+                width,
+                height,
+                match_height,
+            },
+            scale: 1.,
+        }
+    }
+
+    /// Returns the number of physical pixels per ui unit on `screen`.
+    ///
+    /// The factor is always positive and finite: it is clamped to `MIN_FACTOR` when the scale
+    /// or the screen is empty, and falls back to `1.` when the reference resolution is empty.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn factor(&self, screen: &ScreenDimensions) -> f32 {
+        let factor = match self.mode {
+            UiScaleMode::Physical => 1.,
+            UiScaleMode::Logical => screen.scale_factor() as f32,
+            UiScaleMode::ReferenceResolution {
+                width,
+                height,
+                match_height,
+            } => {
+                // Blending logarithmically keeps the scale symmetric: a screen twice as wide
+                // and half as tall as the reference is not scaled when blending evenly.
+                let match_height = match_height.max(0.).min(1.);
+                (screen.width() / width).powf(1. - match_height)
+                    * (screen.height() / height).powf(match_height)
+            }
+        };
+        let factor = factor * self.scale;
+        if factor.is_finite() {
+            factor.max(Self::MIN_FACTOR)
+        } else {
+            1.
+        }
+    }
+}
+
 /// Indicated where the anchor is, relative to the parent (or to the screen, if there is no parent).
 /// Follow a normal english Y,X naming.
 #[derive(Derivative, Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize, SerdeDiff)]
@@ -126,6 +219,7 @@ pub enum Stretch {
 #[derive(Debug)]
 pub struct UiTransformSystem {
     screen_size: (f32, f32),
+    factor: f32,
     modified_last_iter: HashSet<Entity>,
 }
 
@@ -134,6 +228,7 @@ impl UiTransformSystem {
     pub fn new() -> Self {
         Self {
             screen_size: (0.0, 0.0),
+            factor: 0.0,
             modified_last_iter: HashSet::default(),
         }
     }
@@ -144,6 +239,7 @@ impl System for UiTransformSystem {
         Box::new(
             SystemBuilder::new("UiTransformSystem")
                 .read_resource::<ScreenDimensions>()
+                .read_resource::<UiScale>()
                 .with_query(
                     <(Entity, &mut UiTransform)>::query().filter(maybe_changed::<UiTransform>()),
                 )
@@ -159,7 +255,7 @@ impl System for UiTransformSystem {
                 .build(
                     move |_commands,
                           world,
-                          screen_dimensions,
+                          (screen_dimensions, ui_scale),
                           (
                         changed_transforms_query,
                         all_transforms_query,
@@ -186,8 +282,12 @@ impl System for UiTransformSystem {
                         let current_screen_size =
                             (screen_dimensions.width(), screen_dimensions.height());
 
-                        let screen_resized = current_screen_size != self.screen_size;
+                        let factor = ui_scale.factor(screen_dimensions);
+
+                        let screen_resized = current_screen_size != self.screen_size
+                            || (factor - self.factor).abs() > f32::EPSILON;
                         self.screen_size = current_screen_size;
+                        self.factor = factor;
                         if screen_resized {
                             // Then we process for everyone
                             process_root_iter(
@@ -195,10 +295,12 @@ impl System for UiTransformSystem {
                                     .iter_mut(world)
                                     .map(|(_, t, _)| t),
                                 &*screen_dimensions,
+                                factor,
                             );
                             process_root_iter(
                                 transform_isolated_query.iter_mut(world).map(|(_, t)| t),
                                 &*screen_dimensions,
+                                factor,
                             );
                         } else {
                             // We process only modified
@@ -208,6 +310,7 @@ impl System for UiTransformSystem {
                                     .filter(|(e, _, _)| modified_entities.contains(e))
                                     .map(|(_, t, _)| t),
                                 &*screen_dimensions,
+                                factor,
                             );
                             process_root_iter(
                                 transform_isolated_query
@@ -215,6 +318,7 @@ impl System for UiTransformSystem {
                                     .filter(|(e, _)| modified_entities.contains(e))
                                     .map(|(_, t)| t),
                                 &*screen_dimensions,
+                                factor,
                             );
                         }
 
@@ -253,6 +357,9 @@ impl System for UiTransformSystem {
                                     (Some(v1), Some(v2)) => (v1, v2),
                                     _ => continue,
                                 };
+                            // The size of the parent in ui units, which the margins are in.
+                            let parent_width = parent_transform_copy.pixel_width / factor;
+                            let parent_height = parent_transform_copy.pixel_height / factor;
                             let norm = transform.anchor.norm_offset();
                             transform.pixel_x = parent_transform_copy.pixel_x
                                 + parent_transform_copy.pixel_width * norm.0;
@@ -263,16 +370,10 @@ impl System for UiTransformSystem {
                             let new_size = match transform.stretch {
                                 Stretch::NoStretch => (transform.width, transform.height),
                                 Stretch::X { x_margin } => {
-                                    (
-                                        parent_transform_copy.pixel_width - x_margin * 2.0,
-                                        transform.height,
-                                    )
+                                    (parent_width - x_margin * 2.0, transform.height)
                                 }
                                 Stretch::Y { y_margin } => {
-                                    (
-                                        transform.width,
-                                        parent_transform_copy.pixel_height - y_margin * 2.0,
-                                    )
+                                    (transform.width, parent_height - y_margin * 2.0)
                                 }
                                 Stretch::XY {
                                     keep_aspect_ratio: false,
@@ -280,8 +381,8 @@ impl System for UiTransformSystem {
                                     y_margin,
                                 } => {
                                     (
-                                        parent_transform_copy.pixel_width - x_margin * 2.0,
-                                        parent_transform_copy.pixel_height - y_margin * 2.0,
+                                        parent_width - x_margin * 2.0,
+                                        parent_height - y_margin * 2.0,
                                     )
                                 }
                                 Stretch::XY {
@@ -290,10 +391,8 @@ impl System for UiTransformSystem {
                                     y_margin,
                                 } => {
                                     let scale = f32::min(
-                                        (parent_transform_copy.pixel_width - x_margin * 2.0)
-                                            / transform.width,
-                                        (parent_transform_copy.pixel_height - y_margin * 2.0)
-                                            / transform.height,
+                                        (parent_width - x_margin * 2.0) / transform.width,
+                                        (parent_height - y_margin * 2.0) / transform.height,
                                     );
 
                                     (transform.width * scale, transform.height * scale)
@@ -303,10 +402,10 @@ impl System for UiTransformSystem {
                             transform.height = new_size.1;
                             match transform.scale_mode {
                                 ScaleMode::Pixel => {
-                                    transform.pixel_x += transform.local_x;
-                                    transform.pixel_y += transform.local_y;
-                                    transform.pixel_width = transform.width;
-                                    transform.pixel_height = transform.height;
+                                    transform.pixel_x += transform.local_x * factor;
+                                    transform.pixel_y += transform.local_y * factor;
+                                    transform.pixel_width = transform.width * factor;
+                                    transform.pixel_height = transform.height * factor;
                                 }
                                 ScaleMode::Percent => {
                                     transform.pixel_x +=
@@ -338,10 +437,13 @@ impl System for UiTransformSystem {
     }
 }
 
-fn process_root_iter<'a, I>(iter: I, screen_dim: &ScreenDimensions)
+fn process_root_iter<'a, I>(iter: I, screen_dim: &ScreenDimensions, factor: f32)
 where
     I: Iterator<Item = &'a mut UiTransform>,
 {
+    // The size of the screen in ui units, which the margins are in.
+    let screen_width = screen_dim.width() / factor;
+    let screen_height = screen_dim.height() / factor;
     for transform in iter {
         let norm = transform.anchor.norm_offset();
         transform.pixel_x = screen_dim.width() / 2.0 + screen_dim.width() * norm.0;
@@ -350,16 +452,16 @@ where
 
         let new_size = match transform.stretch {
             Stretch::NoStretch => (transform.width, transform.height),
-            Stretch::X { x_margin } => (screen_dim.width() - x_margin * 2.0, transform.height),
-            Stretch::Y { y_margin } => (transform.width, screen_dim.height() - y_margin * 2.0),
+            Stretch::X { x_margin } => (screen_width - x_margin * 2.0, transform.height),
+            Stretch::Y { y_margin } => (transform.width, screen_height - y_margin * 2.0),
             Stretch::XY {
                 keep_aspect_ratio: false,
                 x_margin,
                 y_margin,
             } => {
                 (
-                    screen_dim.width() - x_margin * 2.0,
-                    screen_dim.height() - y_margin * 2.0,
+                    screen_width - x_margin * 2.0,
+                    screen_height - y_margin * 2.0,
                 )
             }
             Stretch::XY {
@@ -368,8 +470,8 @@ where
                 y_margin,
             } => {
                 let scale = f32::min(
-                    (screen_dim.width() - x_margin * 2.0) / transform.width,
-                    (screen_dim.height() - y_margin * 2.0) / transform.height,
+                    (screen_width - x_margin * 2.0) / transform.width,
+                    (screen_height - y_margin * 2.0) / transform.height,
                 );
 
                 (transform.width * scale, transform.height * scale)
@@ -379,10 +481,10 @@ where
         transform.height = new_size.1;
         match transform.scale_mode {
             ScaleMode::Pixel => {
-                transform.pixel_x += transform.local_x;
-                transform.pixel_y += transform.local_y;
-                transform.pixel_width = transform.width;
-                transform.pixel_height = transform.height;
+                transform.pixel_x += transform.local_x * factor;
+                transform.pixel_y += transform.local_y * factor;
+                transform.pixel_width = transform.width * factor;
+                transform.pixel_height = transform.height * factor;
             }
             ScaleMode::Percent => {
                 transform.pixel_x += transform.local_x * screen_dim.width();
@@ -396,3 +498,54 @@ where
         transform.pixel_y += transform.pixel_height * -pivot_norm.1;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ui_scale_factor() {
+        let mut screen = ScreenDimensions::new(1920, 1080);
+        screen.update_scale_factor(2.);
+        assert!((UiScale::default().factor(&screen) - 1.).abs() < f32::EPSILON);
+
+        let logical = UiScale {
+            mode: UiScaleMode::Logical,
+            scale: 1.,
+        };
+        assert!((logical.factor(&screen) - 2.).abs() < f32::EPSILON);
+
+        let physical = UiScale {
+            mode: UiScaleMode::Physical,
+            scale: 1.5,
+        };
+        assert!((physical.factor(&screen) - 1.5).abs() < f32::EPSILON);
+
+        let fit_width = UiScale::reference_resolution(960., 540., 0.);
+        assert!((fit_width.factor(&screen) - 2.).abs() < 0.001);
+
+        // Twice as wide and half as tall as the reference, blending evenly does not scale.
+        let blended = UiScale::reference_resolution(960., 2160., 0.5);
+        assert!((blended.factor(&screen) - 1.).abs() < 0.001);
+    }
+
+    #[test]
+    fn ui_scale_factor_is_positive() {
+        let mut screen = ScreenDimensions::new(1920, 1080);
+        screen.update_scale_factor(0.);
+        let logical = UiScale {
+            mode: UiScaleMode::Logical,
+            scale: 1.,
+        };
+        assert!((logical.factor(&screen) - UiScale::MIN_FACTOR).abs() < f32::EPSILON);
+
+        let empty = UiScale::reference_resolution(0., 0., 0.5);
+        assert!((empty.factor(&screen) - 1.).abs() < f32::EPSILON);
+
+        let zero = UiScale {
+            mode: UiScaleMode::Physical,
+            scale: 0.,
+        };
+        assert!((zero.factor(&screen) - UiScale::MIN_FACTOR).abs() < f32::EPSILON);
+    }
+}
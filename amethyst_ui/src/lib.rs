@@ -19,52 +19,85 @@
 )]
 
 pub use self::{
+    accessibility::{
+        AccessNode, AccessRole, AccessTreeUpdate, AccessibilityAdapter, InMemoryAccessAdapter,
+        UiAccessibilitySystem, UiAccessible,
+    },
     blink::*,
-    bundle::{AudioUiBundle, UiBundle},
+    bundle::{AudioUiBundle, DragPayloadBundle, UiAccessibilityBundle, UiBundle},
     button::{
         UiButton, UiButtonAction, UiButtonActionRetrigger, UiButtonActionType, UiButtonBuilder,
     },
-    drag::{DragWidgetSystem, Draggable},
+    context_menu::{UiContextMenu, UiContextMenuItem, UiContextMenuSystem},
+    drag::{DragGhost, DragPayload, DragPayloadSystem, DragWidgetSystem, Draggable, DropTarget},
     event::{targeted, targeted_below, Interactable, TargetedEvent, UiEvent, UiEventType},
     event_retrigger::{EventReceiver, EventRetrigger},
+    fill::{UiFill, UiFillMode},
     font::{
+        bitmap::{
+            BitmapChar, BitmapFont, BitmapFontData, BitmapFontFormat, BitmapFontProcessorSystem,
+        },
         default::get_default_font,
+        family::{FontFace, FontFamily},
         systemfont::{default_system_font, get_all_font_handles, list_system_font_families},
     },
     format::{FontAsset, TtfFormat},
     glyphs::UiGlyphsSystem,
     image::UiImage,
     label::{UiLabel, UiLabelBuilder},
-    layout::{Anchor, ScaleMode, Stretch},
+    layout::{Anchor, ScaleMode, Stretch, UiScale, UiScaleMode},
+    markup::{parse_markup, MarkupSegment, MarkupStyle, UiMarkupLinkSystem, UiMarkupResources},
+    modal::{
+        UiConfirmDialog, UiConfirmDialogBuilder, UiConfirmDialogSystem, UiModal, UiModalSystem,
+    },
     pass::{DrawUi, DrawUiDesc, RenderUi},
     resize::{ResizeSystem, UiResize},
+    reveal::{parse_pause_markers, RevealPause, RevealSkip, UiTextReveal, UiTextRevealSystem},
     selection::{Selectable, Selected, SelectionKeyboardSystem, SelectionMouseSystem},
     selection_order_cache::{CacheSelectionSystem, CachedSelectionOrderResource},
     sound::{UiPlaySoundAction, UiSoundRetrigger, UiSoundSystem},
-    text::{LineMode, TextEditing, TextEditingMouseSystem, UiText},
+    text::{LineMode, TextEditing, TextEditingMouseSystem, TextFilter, TextValidation, UiText},
     text_editing::TextEditingInputSystem,
+    theme::{UiStyle, UiStyleSystem, UiTheme, UiThemeImage, UiThemeStyle},
+    tooltip::{UiTooltip, UiTooltipSystem},
     transform::{get_parent_pixel_size, UiFinder, UiTransform},
+    tween::{Easing, Tween, TweenLoop, TweenProperty, UiTweenSystem},
     widgets::{Widget, WidgetId, Widgets},
+    world_anchor::{DistanceScaling, UiWorldAnchor, UiWorldAnchorSystem},
 };
+#[cfg(feature = "locale")]
+pub use self::localized::{LocalizedArg, UiLocalizedText, UiLocalizedTextSystem};
 
+mod accessibility;
 mod blink;
 mod bundle;
 mod button;
+mod context_menu;
 mod drag;
 mod event;
 mod event_retrigger;
+mod fill;
 mod font;
 mod format;
 mod glyphs;
 mod image;
 mod label;
 mod layout;
+#[cfg(feature = "locale")]
+mod localized;
+mod markup;
+mod modal;
 mod pass;
 mod resize;
+mod reveal;
 mod selection;
 mod selection_order_cache;
 mod sound;
 mod text;
 mod text_editing;
+mod theme;
+mod tooltip;
 mod transform;
+mod tween;
 mod widgets;
+mod world_anchor;
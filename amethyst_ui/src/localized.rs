@@ -0,0 +1,204 @@
+//! Module for the `UiLocalizedText` component and `UiLocalizedTextSystem`.
+
+use std::collections::HashMap;
+
+use amethyst_assets::{AssetHandle, AssetStorage, LoadHandle};
+use amethyst_core::ecs::{IntoQuery, ParallelRunnable, System, SystemBuilder};
+use amethyst_locale::{ActiveLocale, FluentArgs, FluentValue, Locale};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::UiText;
+
+/// A variable given to a Fluent message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LocalizedArg {
+    /// A string variable.
+    String(String),
+    /// A number variable, formatted and matched against plural categories by Fluent.
+    Number(f64),
+}
+
+impl From<&LocalizedArg> for FluentValue<'static> {
+    fn from(arg: &LocalizedArg) -> Self {
+        match arg {
+            LocalizedArg::String(value) => FluentValue::from(value.clone()),
+            LocalizedArg::Number(value) => FluentValue::from(*value),
+        }
+    }
+}
+
+/// Sets the `UiText` of this entity to a message of the `ActiveLocale`.
+///
+/// The message is resolved again when the key, the arguments or the active locale change.
+/// Messages missing from the locale are displayed as their key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UiLocalizedText {
+    /// The id of the Fluent message.
+    pub key: String,
+    /// The variables of the message, by name.
+    pub args: HashMap<String, LocalizedArg>,
+    /// Locale, locale version, key and arguments resolved last.
+    resolved: Option<(LoadHandle, u32, String, HashMap<String, LocalizedArg>)>,
+}
+
+impl UiLocalizedText {
+    /// Creates a `UiLocalizedText` displaying the message `key`.
+    pub fn new<S: ToString>(key: &S) -> Self {
+        Self {
+            key: key.to_string(),
+            args: HashMap::new(),
+            resolved: None,
+        }
+    }
+
+    /// Adds a string variable to the message.
+    #[must_use]
+    pub fn with_arg<S: ToString, T: ToString>(mut self, name: &S, value: &T) -> Self {
+        self.args
+            .insert(name.to_string(), LocalizedArg::String(value.to_string()));
+        self
+    }
+
+    /// Adds a number variable to the message.
+    #[must_use]
+    pub fn with_number_arg<S: ToString>(mut self, name: &S, value: f64) -> Self {
+        self.args
+            .insert(name.to_string(), LocalizedArg::Number(value));
+        self
+    }
+
+    /// Sets a variable of the message, resolving it again.
+    pub fn set_arg<S: ToString>(&mut self, name: &S, value: LocalizedArg) {
+        self.args.insert(name.to_string(), value);
+    }
+}
+
+/// Formats the message `key` of `locale`, returns `None` if the locale has no such message.
+fn format_message(
+    locale: &Locale,
+    key: &str,
+    args: &HashMap<String, LocalizedArg>,
+) -> Option<String> {
+    let pattern = locale.bundle.get_message(key)?.value()?;
+
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(name.clone(), FluentValue::from(value));
+    }
+
+    let mut errors = Vec::new();
+    let text = locale
+        .bundle
+        .format_pattern(pattern, Some(&fluent_args), &mut errors)
+        .into_owned();
+    for error in errors {
+        log::warn!("Error formatting the message '{}': {:?}", key, error);
+    }
+    Some(text)
+}
+
+/// System resolving the `UiLocalizedText` components against the `ActiveLocale`.
+///
+/// It's registered with the `UiBundle` when the `locale` feature is enabled.
+#[derive(Debug, Default)]
+pub struct UiLocalizedTextSystem;
+
+impl System for UiLocalizedTextSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("UiLocalizedTextSystem")
+                .read_resource::<ActiveLocale>()
+                .read_resource::<AssetStorage<Locale>>()
+                .with_query(<(&mut UiLocalizedText, &mut UiText)>::query())
+                .build(move |_commands, world, (active_locale, locales), texts| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("ui_localized_text_system");
+
+                    let (handle, locale, version) =
+                        match active_locale.locale.as_ref().and_then(|handle| {
+                            locales
+                                .get_asset_with_version(handle)
+                                .map(|(locale, version)| (handle, locale, version))
+                        }) {
+                            Some(active) => active,
+                            None => return,
+                        };
+
+                    texts.for_each_mut(world, |(localized, text)| {
+                        let resolved = localized.resolved.as_ref();
+                        if resolved.map_or(false, |(h, v, key, args)| {
+                            *h == handle.load_handle()
+                                && *v == version
+                                && *key == localized.key
+                                && *args == localized.args
+                        }) {
+                            return;
+                        }
+
+                        text.text = format_message(locale, &localized.key, &localized.args)
+                            .unwrap_or_else(|| {
+                                log::warn!("Missing localized message '{}'", localized.key);
+                                localized.key.clone()
+                            });
+                        localized.resolved = Some((
+                            handle.load_handle(),
+                            version,
+                            localized.key.clone(),
+                            localized.args.clone(),
+                        ));
+                    });
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_locale::{FluentBundle, FluentResource};
+    use unic_langid::langid;
+
+    use super::*;
+
+    fn locale() -> Locale {
+        let resource = FluentResource::try_new(
+            r#"
+hello = Hello, { $name }!
+apples = { $count ->
+    [one] One apple
+   *[other] { $count } apples
+}
+"#
+            .to_string(),
+        )
+        .unwrap();
+        let mut bundle = FluentBundle::new(vec![langid!("en")]);
+        bundle.set_use_isolating(false);
+        bundle.add_resource(resource).unwrap();
+        Locale { bundle }
+    }
+
+    #[test]
+    fn formats_messages_with_args() {
+        let locale = locale();
+
+        let text = UiLocalizedText::new(&"hello").with_arg(&"name", &"Ferris");
+        assert_eq!(
+            format_message(&locale, &text.key, &text.args).as_deref(),
+            Some("Hello, Ferris!")
+        );
+
+        let text = UiLocalizedText::new(&"apples").with_number_arg(&"count", 1.);
+        assert_eq!(
+            format_message(&locale, &text.key, &text.args).as_deref(),
+            Some("One apple")
+        );
+        let text = UiLocalizedText::new(&"apples").with_number_arg(&"count", 3.);
+        assert_eq!(
+            format_message(&locale, &text.key, &text.args).as_deref(),
+            Some("3 apples")
+        );
+
+        assert_eq!(format_message(&locale, "missing", &HashMap::new()), None);
+    }
+}
@@ -0,0 +1,577 @@
+//! Rich text markup for `UiText`.
+
+use std::{collections::HashMap, ops::Range};
+
+use amethyst_assets::Handle;
+use amethyst_core::{
+    ecs::{IntoQuery, ParallelRunnable, System, SystemBuilder},
+    shrev::{EventChannel, ReaderId},
+};
+use amethyst_input::InputHandler;
+use amethyst_rendy::SpriteRender;
+use amethyst_window::ScreenDimensions;
+use glyph_brush::{
+    rusttype::{Font, Scale},
+    FontId, SectionText,
+};
+
+use crate::{FontAsset, UiEvent, UiEventType, UiText};
+
+/// Text drawn in place of an inline image, so the layout reserves room for it.
+/// It is rendered fully transparent.
+const ICON_PLACEHOLDER: &str = "M";
+
+/// Resources used to resolve markup tags which refer to external assets.
+#[derive(Debug, Default)]
+pub struct UiMarkupResources {
+    /// Sprites which can be inlined with `[img=name]`.
+    pub icons: HashMap<String, SpriteRender>,
+    /// Font used for `[b]` spans and bold texts without a `FontFamily`.
+    /// Falls back to the text font when `None`.
+    pub bold_font: Option<Handle<FontAsset>>,
+    /// Font used for `[i]` spans and italic texts without a `FontFamily`.
+    /// Falls back to the text font when `None`.
+    pub italic_font: Option<Handle<FontAsset>>,
+}
+
+/// The style applied to a segment of markup text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarkupStyle {
+    /// Color override, `None` uses `UiText::color`.
+    pub color: Option<[f32; 4]>,
+    /// Whether the segment is inside a `[b]` tag.
+    pub bold: bool,
+    /// Whether the segment is inside an `[i]` tag.
+    pub italic: bool,
+    /// Font size override, `None` uses `UiText::font_size`.
+    pub font_size: Option<f32>,
+    /// The id of the enclosing link span, if any.
+    pub link: Option<String>,
+}
+
+/// A segment of parsed markup.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarkupSegment {
+    /// A run of text sharing a single style.
+    Text {
+        /// The text of this segment, with the tags removed.
+        text: String,
+        /// The style of this segment.
+        style: MarkupStyle,
+    },
+    /// An inline image.
+    Image {
+        /// The name of the icon in `UiMarkupResources::icons`.
+        name: String,
+        /// The style in effect where the image appears.
+        style: MarkupStyle,
+    },
+}
+
+enum Tag<'a> {
+    Bold,
+    Italic,
+    Color([f32; 4]),
+    Size(f32),
+    Link(&'a str),
+    Image(&'a str),
+    Close(&'a str),
+}
+
+/// Parses a markup string into styled segments.
+///
+/// When `UiText::markup` is enabled the text is parsed with this function and laid out by the
+/// `UiGlyphsSystem` as multiple glyph sections. The supported tags are:
+///
+/// * `[color=#rgb]`, `[color=#rrggbb]`, `[color=#rrggbbaa]` ... `[/color]`
+/// * `[b]` ... `[/b]` and `[i]` ... `[/i]`, using the fonts of `UiMarkupResources`
+/// * `[size=24]` ... `[/size]`
+/// * `[link=id]` ... `[/link]` (or `[url=id]` ... `[/url]`), which emits
+///   `UiEventType::LinkClick` when the span is clicked
+/// * `[img=name]`, an inline sprite registered in `UiMarkupResources::icons`
+///
+/// A literal `[` is written as `[[`. Tags that are not recognized are kept as plain text.
+#[must_use]
+pub fn parse_markup(source: &str) -> Vec<MarkupSegment> {
+    let mut segments = Vec::new();
+    let mut stack: Vec<(&str, MarkupStyle)> = Vec::new();
+    let mut style = MarkupStyle::default();
+    let mut current = String::new();
+    let mut rest = source;
+
+    while let Some(open) = rest.find('[') {
+        current.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+
+        if let Some(escaped) = after.strip_prefix('[') {
+            current.push('[');
+            rest = escaped;
+            continue;
+        }
+
+        let tag = after
+            .find(']')
+            .and_then(|close| parse_tag(&after[..close]).map(|tag| (tag, &after[close + 1..])))
+            .filter(|(tag, _)| {
+                // Closing a tag which was never opened is kept as text.
+                match tag {
+                    Tag::Close(name) => stack.iter().any(|(open, _)| open == name),
+                    _ => true,
+                }
+            });
+
+        match tag {
+            Some((tag, remaining)) => {
+                flush(&mut segments, &mut current, &style);
+                match tag {
+                    Tag::Bold => {
+                        stack.push(("b", style.clone()));
+                        style.bold = true;
+                    }
+                    Tag::Italic => {
+                        stack.push(("i", style.clone()));
+                        style.italic = true;
+                    }
+                    Tag::Color(color) => {
+                        stack.push(("color", style.clone()));
+                        style.color = Some(color);
+                    }
+                    Tag::Size(size) => {
+                        stack.push(("size", style.clone()));
+                        style.font_size = Some(size);
+                    }
+                    Tag::Link(id) => {
+                        stack.push(("link", style.clone()));
+                        style.link = Some(id.to_string());
+                    }
+                    Tag::Image(name) => {
+                        segments.push(MarkupSegment::Image {
+                            name: name.to_string(),
+                            style: style.clone(),
+                        });
+                    }
+                    Tag::Close(name) => {
+                        // Closing a tag also closes anything opened after it.
+                        if let Some(pos) = stack.iter().rposition(|(open, _)| *open == name) {
+                            style = stack[pos].1.clone();
+                            stack.truncate(pos);
+                        }
+                    }
+                }
+                rest = remaining;
+            }
+            None => {
+                current.push('[');
+                rest = after;
+            }
+        }
+    }
+    current.push_str(rest);
+    flush(&mut segments, &mut current, &style);
+
+    segments
+}
+
+fn flush(segments: &mut Vec<MarkupSegment>, current: &mut String, style: &MarkupStyle) {
+    if !current.is_empty() {
+        segments.push(MarkupSegment::Text {
+            text: std::mem::take(current),
+            style: style.clone(),
+        });
+    }
+}
+
+fn parse_tag(tag: &str) -> Option<Tag<'_>> {
+    if let Some(name) = tag.strip_prefix('/') {
+        return match name {
+            "b" | "i" | "color" | "size" | "link" => Some(Tag::Close(name)),
+            "url" => Some(Tag::Close("link")),
+            _ => None,
+        };
+    }
+
+    let (name, value) = match tag.find('=') {
+        Some(i) => (&tag[..i], Some(&tag[i + 1..])),
+        None => (tag, None),
+    };
+
+    match (name, value) {
+        ("b", None) => Some(Tag::Bold),
+        ("i", None) => Some(Tag::Italic),
+        ("color", Some(value)) => parse_hex_color(value).map(Tag::Color),
+        ("size", Some(value)) => {
+            value
+                .parse::<f32>()
+                .ok()
+                .filter(|size| *size > 0.)
+                .map(Tag::Size)
+        }
+        ("link", Some(id)) | ("url", Some(id)) if !id.is_empty() => Some(Tag::Link(id)),
+        ("img", Some(name)) if !name.is_empty() => Some(Tag::Image(name)),
+        _ => None,
+    }
+}
+
+/// Parses `#rgb`, `#rrggbb` and `#rrggbbaa` colors into a 0.0 to 1.0 range per channel.
+fn parse_hex_color(value: &str) -> Option<[f32; 4]> {
+    let hex = value.strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok().map(|v| f32::from(v) / 255.);
+    match hex.len() {
+        3 => {
+            let short = |i: usize| channel(&hex[i..=i].repeat(2));
+            Some([short(0)?, short(1)?, short(2)?, 1.])
+        }
+        6 | 8 => {
+            Some([
+                channel(&hex[0..2])?,
+                channel(&hex[2..4])?,
+                channel(&hex[4..6])?,
+                if hex.len() == 8 {
+                    channel(&hex[6..8])?
+                } else {
+                    1.
+                },
+            ])
+        }
+        _ => None,
+    }
+}
+
+/// Inline image positioned by the `UiGlyphsSystem`, drawn by `DrawUi`.
+#[derive(Clone, Debug)]
+pub(crate) struct CachedIcon {
+    /// Index of the placeholder glyph, counting only visible glyphs.
+    pub(crate) glyph: usize,
+    pub(crate) sprite: SpriteRender,
+    pub(crate) coords: [f32; 2],
+    pub(crate) dimensions: [f32; 2],
+}
+
+/// Screen rectangle of one glyph belonging to a link span.
+#[derive(Clone, Debug)]
+pub(crate) struct CachedLink {
+    pub(crate) id: String,
+    /// Left, bottom, right and top edges.
+    pub(crate) rect: [f32; 4],
+}
+
+pub(crate) struct PendingIcon {
+    glyph: usize,
+    sprite: SpriteRender,
+    size: f32,
+}
+
+pub(crate) struct PendingLink {
+    id: String,
+    glyphs: Range<usize>,
+    scale: Scale,
+}
+
+/// Glyph sections for a parsed markup text, along with what needs to be resolved once the
+/// sections have been laid out.
+pub(crate) struct MarkupSections<'a> {
+    pub(crate) sections: Vec<SectionText<'a>>,
+    icons: Vec<PendingIcon>,
+    links: Vec<PendingLink>,
+}
+
+/// Builds the glyph sections for markup segments. `font_for` resolves the font of a style.
+/// Font sizes are multiplied by `factor`, the ui scale. Images are looked up in the markup icons,
+/// then in the `color_glyphs` of the font family.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn markup_sections<'a, F>(
+    segments: &'a [MarkupSegment],
+    ui_text: &UiText,
+    tint: [f32; 4],
+    font: &Font<'static>,
+    resources: &UiMarkupResources,
+    color_glyphs: Option<&HashMap<String, SpriteRender>>,
+    factor: f32,
+    mut font_for: F,
+) -> MarkupSections<'a>
+where
+    F: FnMut(&MarkupStyle) -> FontId,
+{
+    let mut sections = Vec::with_capacity(segments.len());
+    let mut icons = Vec::new();
+    let mut links: Vec<PendingLink> = Vec::new();
+    // Whitespace is not returned by the glyph layout, so only visible characters are counted.
+    let mut glyph_count = 0;
+
+    for segment in segments {
+        let (style, text, scale, color) = match segment {
+            MarkupSegment::Text { text, style } => {
+                let scale = Scale::uniform(style.font_size.unwrap_or(ui_text.font_size) * factor);
+                let color = mul_blend(&style.color.unwrap_or(ui_text.color), &tint);
+                (style, text.as_str(), scale, color)
+            }
+            MarkupSegment::Image { name, style } => {
+                let sprite = resources
+                    .icons
+                    .get(name)
+                    .or_else(|| color_glyphs.and_then(|color_glyphs| color_glyphs.get(name)));
+                let sprite = if let Some(sprite) = sprite {
+                    sprite
+                } else {
+                    log::warn!("Unknown markup icon '{}'", name);
+                    continue;
+                };
+                let size = style.font_size.unwrap_or(ui_text.font_size) * factor;
+                let advance = font
+                    .glyph(ICON_PLACEHOLDER.chars().next().unwrap())
+                    .scaled(Scale::uniform(1.))
+                    .h_metrics()
+                    .advance_width;
+                let scale = Scale {
+                    x: if advance > 0. { size / advance } else { size },
+                    y: size,
+                };
+                icons.push(PendingIcon {
+                    glyph: glyph_count,
+                    sprite: sprite.clone(),
+                    size,
+                });
+                (style, ICON_PLACEHOLDER, scale, [0., 0., 0., 0.])
+            }
+        };
+
+        let visible = text.chars().filter(|c| !c.is_whitespace()).count();
+        if let Some(id) = &style.link {
+            match links.last_mut() {
+                Some(link) if link.id == *id && link.glyphs.end == glyph_count => {
+                    link.glyphs.end += visible;
+                }
+                _ => {
+                    links.push(PendingLink {
+                        id: id.clone(),
+                        glyphs: glyph_count..glyph_count + visible,
+                        scale,
+                    });
+                }
+            }
+        }
+        glyph_count += visible;
+
+        sections.push(SectionText {
+            text,
+            scale,
+            color,
+            font_id: font_for(style),
+        });
+    }
+
+    MarkupSections {
+        sections,
+        icons,
+        links,
+    }
+}
+
+impl MarkupSections<'_> {
+    /// Positions the icons and links using the laid out glyphs, given as
+    /// `(x, baseline y, advance width)` in screen space.
+    pub(crate) fn resolve(
+        &self,
+        glyphs: &[(f32, f32, f32)],
+        font: &Font<'static>,
+    ) -> (Vec<CachedIcon>, Vec<CachedLink>) {
+        let icons = self
+            .icons
+            .iter()
+            .filter_map(|icon| {
+                let (x, y, _) = glyphs.get(icon.glyph)?;
+                let v_metrics = font.v_metrics(Scale::uniform(icon.size));
+                Some(CachedIcon {
+                    glyph: icon.glyph,
+                    sprite: icon.sprite.clone(),
+                    coords: [
+                        x + icon.size * 0.5,
+                        y + (v_metrics.ascent + v_metrics.descent) * 0.5,
+                    ],
+                    dimensions: [icon.size, icon.size],
+                })
+            })
+            .collect();
+
+        let links = self
+            .links
+            .iter()
+            .flat_map(|link| {
+                let v_metrics = font.v_metrics(link.scale);
+                glyphs
+                    .get(link.glyphs.clone())
+                    .unwrap_or(&[])
+                    .iter()
+                    .map(move |(x, y, advance)| {
+                        CachedLink {
+                            id: link.id.clone(),
+                            rect: [
+                                *x,
+                                y + v_metrics.descent,
+                                x + advance,
+                                y + v_metrics.ascent,
+                            ],
+                        }
+                    })
+            })
+            .collect();
+
+        (icons, links)
+    }
+}
+
+fn mul_blend(a: &[f32; 4], b: &[f32; 4]) -> [f32; 4] {
+    [a[0] * b[0], a[1] * b[1], a[2] * b[2], a[3] * b[3]]
+}
+
+/// Emits a `UiEventType::LinkClick` event when a link span of a markup `UiText` is clicked.
+///
+/// The text entity needs an `Interactable` component to receive clicks.
+#[derive(Debug)]
+pub struct UiMarkupLinkSystem {
+    event_reader: ReaderId<UiEvent>,
+}
+
+impl UiMarkupLinkSystem {
+    /// Creates a new `UiMarkupLinkSystem`.
+    pub fn new(event_reader: ReaderId<UiEvent>) -> Self {
+        Self { event_reader }
+    }
+}
+
+impl System for UiMarkupLinkSystem {
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("UiMarkupLinkSystem")
+                .write_resource::<EventChannel<UiEvent>>()
+                .read_resource::<InputHandler>()
+                .read_resource::<ScreenDimensions>()
+                .with_query(<&UiText>::query())
+                .build(
+                    move |_commands, world, (ui_events, input, screen_dimensions), texts| {
+                        let mouse_pos = input
+                            .mouse_position()
+                            .map(|(x, y)| (x, screen_dimensions.height() - y));
+
+                        let mut link_events = Vec::new();
+                        for event in ui_events.read(&mut self.event_reader) {
+                            if event.event_type != UiEventType::Click {
+                                continue;
+                            }
+                            if let (Some((x, y)), Ok(text)) =
+                                (mouse_pos, texts.get(world, event.target))
+                            {
+                                if let Some(id) = text.link_at(x, y) {
+                                    link_events.push(UiEvent::new(
+                                        UiEventType::LinkClick { id: id.to_string() },
+                                        event.target,
+                                    ));
+                                }
+                            }
+                        }
+                        ui_events.iter_write(link_events);
+                    },
+                ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str, style: MarkupStyle) -> MarkupSegment {
+        MarkupSegment::Text {
+            text: text.to_string(),
+            style,
+        }
+    }
+
+    #[test]
+    fn plain_text() {
+        assert_eq!(
+            parse_markup("Hello world"),
+            vec![text("Hello world", MarkupStyle::default())]
+        );
+        assert!(parse_markup("").is_empty());
+    }
+
+    #[test]
+    fn nested_styles() {
+        let segments = parse_markup("a[color=#f00]b[size=24][b]c[/b]d[/size][/color]e");
+        let red = MarkupStyle {
+            color: Some([1., 0., 0., 1.]),
+            ..MarkupStyle::default()
+        };
+        let red_big = MarkupStyle {
+            font_size: Some(24.),
+            ..red.clone()
+        };
+        assert_eq!(
+            segments,
+            vec![
+                text("a", MarkupStyle::default()),
+                text("b", red.clone()),
+                text(
+                    "c",
+                    MarkupStyle {
+                        bold: true,
+                        ..red_big.clone()
+                    }
+                ),
+                text("d", red_big),
+                text("e", MarkupStyle::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn closing_outer_tag_closes_inner() {
+        let segments = parse_markup("[b]a[i]b[/b]c");
+        assert_eq!(segments[2], text("c", MarkupStyle::default()));
+    }
+
+    #[test]
+    fn links_and_images() {
+        let segments = parse_markup("[url=quest]Go [img=arrow][/url]!");
+        let link = MarkupStyle {
+            link: Some("quest".to_string()),
+            ..MarkupStyle::default()
+        };
+        assert_eq!(
+            segments,
+            vec![
+                text("Go ", link.clone()),
+                MarkupSegment::Image {
+                    name: "arrow".to_string(),
+                    style: link,
+                },
+                text("!", MarkupStyle::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes_and_unknown_tags() {
+        assert_eq!(
+            parse_markup("[[b] [foo] [color=red] [/i] [b"),
+            vec![text(
+                "[b] [foo] [color=red] [/i] [b",
+                MarkupStyle::default()
+            )]
+        );
+    }
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(parse_hex_color("#fff"), Some([1., 1., 1., 1.]));
+        assert_eq!(parse_hex_color("#000000"), Some([0., 0., 0., 1.]));
+        assert_eq!(parse_hex_color("#ff000000"), Some([1., 0., 0., 0.]));
+        assert_eq!(parse_hex_color("fff"), None);
+        assert_eq!(parse_hex_color("#ffff"), None);
+        assert_eq!(parse_hex_color("#gggggg"), None);
+    }
+}
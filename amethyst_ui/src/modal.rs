@@ -0,0 +1,475 @@
+//! Module for modal ui layers and confirm dialogs.
+
+use std::{collections::HashMap, marker::PhantomData};
+
+use amethyst_assets::Handle;
+use amethyst_core::{
+    ecs::{
+        component, Entity, IntoQuery, ParallelRunnable, Resources, System, SystemBuilder, World,
+    },
+    shrev::{EventChannel, ReaderId},
+    transform::Transform,
+    Hidden, HiddenPropagate,
+};
+use derivative::Derivative;
+
+use crate::{
+    Anchor, Stretch, UiButton, UiButtonBuilder, UiEvent, UiEventType, UiImage, UiLabel,
+    UiLabelBuilder, UiTheme, UiTransform, WidgetId, Widgets,
+};
+
+const DEFAULT_DIM_COLOR: [f32; 4] = [0., 0., 0., 0.5];
+const DEFAULT_DIALOG_Z: f32 = 800.;
+const DEFAULT_DIALOG_WIDTH: f32 = 400.;
+const DEFAULT_DIALOG_HEIGHT: f32 = 200.;
+const DEFAULT_BKGD_COLOR: [f32; 4] = [0.25, 0.25, 0.25, 1.0];
+const DEFAULT_TXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const BUTTON_WIDTH: f32 = 128.;
+const BUTTON_HEIGHT: f32 = 48.;
+const BUTTON_MARGIN: f32 = 16.;
+
+/// Marks an ui element as modal.
+///
+/// While a modal is visible, mouse events only reach the entities on its z-layer or above it,
+/// which are usually the modal and its children, and `Tab` navigation only cycles through the
+/// `Selectable`s of that layer. Everything below is covered by a backdrop of `dim_color`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UiModal {
+    /// Color of the backdrop covering the ui below the modal. Fully transparent disables dimming.
+    pub dim_color: [f32; 4],
+}
+
+impl Default for UiModal {
+    fn default() -> Self {
+        Self {
+            dim_color: DEFAULT_DIM_COLOR,
+        }
+    }
+}
+
+/// Returns the global z of the top visible modal, if there is any.
+pub(crate) fn modal_layer<'a, I>(modals: I) -> Option<f32>
+where
+    I: Iterator<Item = (&'a UiTransform, &'a UiModal)>,
+{
+    modals
+        .map(|(transform, _)| transform.global_z)
+        .max_by(|z1, z2| z1.partial_cmp(z2).expect("Unexpected NaN"))
+}
+
+/// System managing the backdrops of `UiModal`s.
+///
+/// It's automatically registered with the `UiBundle`.
+#[derive(Debug, Default)]
+pub struct UiModalSystem {
+    /// Modal entities with their backdrop.
+    backdrops: HashMap<Entity, Entity>,
+}
+
+impl System for UiModalSystem {
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("UiModalSystem")
+                .with_query(
+                    <(Entity, &UiTransform, &UiModal)>::query()
+                        .filter(!component::<Hidden>() & !component::<HiddenPropagate>()),
+                )
+                .with_query(<(&mut UiTransform, &mut UiImage)>::query())
+                .build(move |commands, world, _, (modals, backdrops)| {
+                    let visible: Vec<(Entity, f32, [f32; 4])> = modals
+                        .iter(world)
+                        .map(|(entity, transform, modal)| {
+                            (*entity, transform.global_z, modal.dim_color)
+                        })
+                        .collect();
+
+                    let previous = std::mem::take(&mut self.backdrops);
+                    for (entity, modal_z, dim_color) in visible {
+                        // Just below the modal, so it does not cover it.
+                        let z = modal_z - 0.01;
+                        let existing = previous.get(&entity).copied().filter(|backdrop| {
+                            backdrops
+                                .get_mut(world, *backdrop)
+                                .map(|(transform, image)| {
+                                    transform.local_z = z;
+                                    *image = UiImage::SolidColor(dim_color);
+                                })
+                                .is_ok()
+                        });
+
+                        let backdrop = existing.unwrap_or_else(|| {
+                            let transform = UiTransform::new(
+                                "modal_backdrop".to_string(),
+                                Anchor::Middle,
+                                Anchor::Middle,
+                                0.,
+                                0.,
+                                z,
+                                0.,
+                                0.,
+                            )
+                            .into_transparent()
+                            .with_stretch(Stretch::XY {
+                                x_margin: 0.,
+                                y_margin: 0.,
+                                keep_aspect_ratio: false,
+                            });
+                            commands.push((transform, UiImage::SolidColor(dim_color)))
+                        });
+                        self.backdrops.insert(entity, backdrop);
+                    }
+
+                    // Remove the backdrops of modals which were hidden or deleted.
+                    for (entity, backdrop) in previous {
+                        if !self.backdrops.contains_key(&entity) {
+                            commands.remove(backdrop);
+                        }
+                    }
+                }),
+        )
+    }
+}
+
+/// A dialog spawned by a `UiConfirmDialogBuilder`.
+///
+/// Clicking one of its buttons emits a `UiEventType::DialogResult` event targeting the root
+/// entity, then deletes the dialog.
+#[derive(Debug, Clone)]
+pub struct UiConfirmDialog {
+    /// The root entity, holding the `UiModal` and the background image.
+    pub root: Entity,
+    /// The message label.
+    pub message: UiLabel,
+    /// The confirm button.
+    pub confirm: UiButton,
+    /// The cancel button.
+    pub cancel: UiButton,
+}
+
+impl UiConfirmDialog {
+    fn entities(&self) -> [Entity; 6] {
+        [
+            self.root,
+            self.message.text_entity,
+            self.confirm.image_entity,
+            self.confirm.text_entity,
+            self.cancel.image_entity,
+            self.cancel.text_entity,
+        ]
+    }
+}
+
+/// The ids of the widgets of a `UiConfirmDialog`, on its root entity.
+#[derive(Debug, Clone)]
+struct ConfirmDialogWidgets<I: WidgetId> {
+    message: I,
+    confirm: I,
+    cancel: I,
+}
+
+/// Convenience structure for building a modal dialog with a message, a confirm button and a
+/// cancel button.
+#[derive(Debug, Clone)]
+pub struct UiConfirmDialogBuilder<G, I: WidgetId> {
+    message: String,
+    confirm_text: String,
+    cancel_text: String,
+    width: f32,
+    height: f32,
+    z: f32,
+    font_size: f32,
+    text_color: [f32; 4],
+    background: UiImage,
+    modal: UiModal,
+    style: Option<(Handle<UiTheme>, String)>,
+    _phantom: PhantomData<(G, I)>,
+}
+
+impl<G: PartialEq + Send + Sync + 'static, I: WidgetId> UiConfirmDialogBuilder<G, I> {
+    /// Construct a new `UiConfirmDialogBuilder` showing `message`.
+    pub fn new<S: ToString>(message: &S) -> Self {
+        Self {
+            message: message.to_string(),
+            confirm_text: "OK".to_string(),
+            cancel_text: "Cancel".to_string(),
+            width: DEFAULT_DIALOG_WIDTH,
+            height: DEFAULT_DIALOG_HEIGHT,
+            z: DEFAULT_DIALOG_Z,
+            font_size: 24.,
+            text_color: DEFAULT_TXT_COLOR,
+            background: UiImage::SolidColor(DEFAULT_BKGD_COLOR),
+            modal: UiModal::default(),
+            style: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Set the text of the confirm button.
+    #[must_use]
+    pub fn with_confirm_text<S: ToString>(mut self, text: &S) -> Self {
+        self.confirm_text = text.to_string();
+        self
+    }
+
+    /// Set the text of the cancel button.
+    #[must_use]
+    pub fn with_cancel_text<S: ToString>(mut self, text: &S) -> Self {
+        self.cancel_text = text.to_string();
+        self
+    }
+
+    /// Set the dialog size.
+    #[must_use]
+    pub fn with_size(mut self, width: f32, height: f32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Provide a Z position, i.e UI layer.
+    #[must_use]
+    pub fn with_layer(mut self, z: f32) -> Self {
+        self.z = z;
+        self
+    }
+
+    /// Set the font size of the message and the buttons.
+    #[must_use]
+    pub fn with_font_size(mut self, size: f32) -> Self {
+        self.font_size = size;
+        self
+    }
+
+    /// Set the text color of the message.
+    #[must_use]
+    pub fn with_text_color(mut self, text_color: [f32; 4]) -> Self {
+        self.text_color = text_color;
+        self
+    }
+
+    /// Replace the default background with `image`.
+    #[must_use]
+    pub fn with_image(mut self, image: UiImage) -> Self {
+        self.background = image;
+        self
+    }
+
+    /// Set the color of the backdrop dimming the ui below the dialog.
+    #[must_use]
+    pub fn with_dim_color(mut self, dim_color: [f32; 4]) -> Self {
+        self.modal.dim_color = dim_color;
+        self
+    }
+
+    /// Style the message and the buttons with the style `name` of `theme`.
+    #[must_use]
+    pub fn with_style<S: ToString>(mut self, theme: Handle<UiTheme>, name: &S) -> Self {
+        self.style = Some((theme, name.to_string()));
+        self
+    }
+
+    /// Build this with the `UiConfirmDialogBuilderResources`.
+    pub fn build_from_world_and_resources(
+        self,
+        world: &mut World,
+        resources: &mut Resources,
+    ) -> UiConfirmDialog {
+        let root = world.push((
+            UiTransform::new(
+                "confirm_dialog".to_string(),
+                Anchor::Middle,
+                Anchor::Middle,
+                0.,
+                0.,
+                self.z,
+                self.width,
+                self.height,
+            ),
+            self.background,
+            self.modal,
+            Transform::default(),
+        ));
+
+        let mut message = UiLabelBuilder::<G, I>::new(&self.message)
+            .with_parent(root)
+            .with_anchor(Anchor::Middle)
+            .with_position(0., (BUTTON_HEIGHT + BUTTON_MARGIN) * 0.5)
+            .with_size(
+                self.width - BUTTON_MARGIN * 2.,
+                self.height - BUTTON_HEIGHT - BUTTON_MARGIN * 3.,
+            )
+            .with_layer(1.)
+            .with_font_size(self.font_size)
+            .with_text_color(self.text_color);
+
+        let button = |text: &String, x: f32| {
+            UiButtonBuilder::<G, I>::new(text)
+                .with_parent(root)
+                .with_anchor(Anchor::BottomMiddle)
+                .with_position(x, BUTTON_MARGIN + BUTTON_HEIGHT * 0.5)
+                .with_size(BUTTON_WIDTH, BUTTON_HEIGHT)
+                .with_layer(1.)
+                .with_font_size(self.font_size)
+        };
+        let offset = (BUTTON_WIDTH + BUTTON_MARGIN) * 0.5;
+        let mut confirm = button(&self.confirm_text, offset);
+        let mut cancel = button(&self.cancel_text, -offset);
+
+        if let Some((theme, name)) = self.style {
+            message = message.with_style(theme.clone(), &name);
+            confirm = confirm.with_style(theme.clone(), &name);
+            cancel = cancel.with_style(theme, &name);
+        }
+
+        let (message_id, message) = message.build_from_world_and_resources(world, resources);
+        let (confirm_id, confirm) = confirm.build_from_world_and_resources(world, resources);
+        let (cancel_id, cancel) = cancel.build_from_world_and_resources(world, resources);
+
+        let dialog = UiConfirmDialog {
+            root,
+            message,
+            confirm,
+            cancel,
+        };
+        let mut entry = world
+            .entry(root)
+            .expect("Unreachable: Inserting newly created entity");
+        entry.add_component(dialog.clone());
+        entry.add_component(ConfirmDialogWidgets {
+            message: message_id,
+            confirm: confirm_id,
+            cancel: cancel_id,
+        });
+
+        dialog
+    }
+}
+
+/// System emitting the result of `UiConfirmDialog`s and deleting them, along with their widgets.
+///
+/// It's automatically registered with the `UiBundle`.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct UiConfirmDialogSystem<I: WidgetId> {
+    event_reader: ReaderId<UiEvent>,
+    #[derivative(Debug = "ignore")]
+    _marker: PhantomData<I>,
+}
+
+impl<I: WidgetId> UiConfirmDialogSystem<I> {
+    /// Creates a new `UiConfirmDialogSystem`.
+    pub fn new(event_reader: ReaderId<UiEvent>) -> Self {
+        Self {
+            event_reader,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<I: WidgetId> System for UiConfirmDialogSystem<I> {
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("UiConfirmDialogSystem")
+                .write_resource::<EventChannel<UiEvent>>()
+                .with_query(<(&UiConfirmDialog, Option<&ConfirmDialogWidgets<I>>)>::query())
+                .build(move |commands, world, events, dialogs| {
+                    let mut results = Vec::new();
+                    for event in events.read(&mut self.event_reader) {
+                        if event.event_type != UiEventType::Click {
+                            continue;
+                        }
+                        for (dialog, widgets) in dialogs.iter(world) {
+                            let confirmed = match dialog_result(dialog, event.target) {
+                                Some(confirmed) => confirmed,
+                                None => continue,
+                            };
+                            results.push(UiEvent::new(
+                                UiEventType::DialogResult { confirmed },
+                                dialog.root,
+                            ));
+                            for entity in &dialog.entities() {
+                                commands.remove(*entity);
+                            }
+                            if let Some(widgets) = widgets.cloned() {
+                                commands.exec_mut(move |_, resources| {
+                                    if let Some(mut labels) =
+                                        resources.get_mut::<Widgets<UiLabel, I>>()
+                                    {
+                                        labels.remove(&widgets.message);
+                                    }
+                                    if let Some(mut buttons) =
+                                        resources.get_mut::<Widgets<UiButton, I>>()
+                                    {
+                                        buttons.remove(&widgets.confirm);
+                                        buttons.remove(&widgets.cancel);
+                                    }
+                                });
+                            }
+                        }
+                    }
+                    events.iter_write(results);
+                }),
+        )
+    }
+}
+
+/// Returns whether clicking `target` confirms or cancels `dialog`, if it is one of its buttons.
+fn dialog_result(dialog: &UiConfirmDialog, target: Entity) -> Option<bool> {
+    if target == dialog.confirm.image_entity {
+        Some(true)
+    } else if target == dialog.cancel.image_entity {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform(z: f32) -> UiTransform {
+        UiTransform::new(
+            "modal".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            0.,
+            0.,
+            z,
+            10.,
+            10.,
+        )
+    }
+
+    #[test]
+    fn top_modal_is_the_layer() {
+        let mut low = transform(0.);
+        low.global_z = 5.;
+        let mut high = transform(0.);
+        high.global_z = 20.;
+        let modal = UiModal::default();
+
+        assert_eq!(modal_layer(std::iter::empty()), None);
+        assert_eq!(
+            modal_layer(vec![(&low, &modal), (&high, &modal)].into_iter()),
+            Some(20.)
+        );
+    }
+
+    #[test]
+    fn buttons_give_the_result() {
+        let mut world = World::default();
+        let entities: Vec<Entity> = (0..6).map(|_| world.push(())).collect();
+        let dialog = UiConfirmDialog {
+            root: entities[0],
+            message: UiLabel::new(entities[1]),
+            confirm: UiButton::new(entities[3], entities[2]),
+            cancel: UiButton::new(entities[5], entities[4]),
+        };
+
+        assert_eq!(dialog_result(&dialog, entities[2]), Some(true));
+        assert_eq!(dialog_result(&dialog, entities[4]), Some(false));
+        assert_eq!(dialog_result(&dialog, entities[3]), None);
+        assert_eq!(dialog.entities().to_vec(), entities);
+    }
+}
@@ -34,7 +34,7 @@ use amethyst_rendy::{
     submodules::{DynamicUniform, DynamicVertexBuffer, TextureId, TextureSub},
     system::GraphAuxData,
     types::{Backend, Texture, TextureData},
-    ChangeDetection, SpriteSheet,
+    ChangeDetection, SpriteRender, SpriteSheet,
 };
 use amethyst_window::ScreenDimensions;
 use derivative::Derivative;
@@ -43,8 +43,10 @@ use glsl_layout::{vec2, vec4, Uniform};
 use thread_profiler::profile_scope;
 
 use crate::{
+    fill::FillQuad,
     glyphs::{UiGlyphs, UiGlyphsResource},
-    Selected, TextEditing, UiImage, UiTransform,
+    markup::CachedIcon,
+    Selected, TextEditing, UiFill, UiImage, UiText, UiTextReveal, UiTransform,
 };
 
 /// A [`RenderPlugin`] for rendering UI elements.
@@ -102,8 +104,14 @@ pub(crate) struct UiArgs {
     pub(crate) tex_coord_bounds: vec4,
     pub(crate) color: vec4,
     pub(crate) color_bias: vec4,
+    /// The center, start angle and amount of the radial `UiFill` the fragment shader draws,
+    /// with a negative amount filling counterclockwise.
+    pub(crate) radial_fill: vec4,
 }
 
+/// The `radial_fill` of the `UiArgs` drawn entirely.
+pub(crate) const NO_RADIAL_FILL: [f32; 4] = [0., 0., 0., 1.];
+
 impl AsVertex for UiArgs {
     fn vertex() -> VertexFormat {
         VertexFormat::new((
@@ -112,6 +120,7 @@ impl AsVertex for UiArgs {
             (Format::Rgba32Sfloat, "tex_coord_bounds"),
             (Format::Rgba32Sfloat, "color"),
             (Format::Rgba32Sfloat, "color_bias"),
+            (Format::Rgba32Sfloat, "radial_fill"),
         ))
     }
 }
@@ -338,8 +347,11 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawUi<B> {
             Option<&UiGlyphs>,
             Option<&Selected>,
             Option<&TextEditing>,
+            Option<&UiText>,
+            Option<&UiTextReveal>,
         )>::query()
         .filter(!component::<Hidden>() & !component::<HiddenPropagate>());
+        let mut query_fills = <&UiFill>::query();
 
         for &(_z, entity) in &self.cached_draw_order.cache {
             let (
@@ -349,6 +361,8 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawUi<B> {
                 maybe_glyph,
                 maybe_selected,
                 maybe_txt_editing,
+                maybe_text,
+                maybe_reveal,
             ) = query
                 .get(*world, entity)
                 .expect("Unreachable: Entity is guaranteed to be present based on earlier actions");
@@ -358,12 +372,16 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawUi<B> {
                 [r, g, b, a]
             });
 
+            // Glyphs are laid out in full, a reveal only limits how many of them are drawn.
+            let revealed = maybe_reveal.map_or(usize::max_value(), UiTextReveal::revealed);
+
             if let Some(image) = maybe_image {
                 let this_changed = render_image(
                     factory,
                     aux,
                     transform,
                     image,
+                    query_fills.get(*world, entity).ok(),
                     &tint,
                     white_tex_id,
                     &mut self.textures,
@@ -418,14 +436,47 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawUi<B> {
                                 tex_coord_bounds: [0., 0., 1., 1.].into(),
                                 color: tint.unwrap_or([1., 1., 1., 1.]).into(),
                                 color_bias: [0., 0., 0., 0.].into(),
+                                radial_fill: NO_RADIAL_FILL.into(),
                             }),
                         );
                     }
                 }
 
-                if !glyph_data.vertices.is_empty() {
-                    self.batches
-                        .insert(glyph_tex_id, glyph_data.vertices.iter().copied());
+                if !glyph_data.vertices.is_empty() && revealed > 0 {
+                    self.batches.insert(
+                        glyph_tex_id,
+                        glyph_data.vertices.iter().take(revealed).copied(),
+                    );
+                }
+
+                for (page, args) in glyph_data.bitmap_vertices.iter().take(revealed) {
+                    if let Some((tex_id, this_changed)) =
+                        glyph_data.bitmap_pages.get(*page).and_then(|page| {
+                            self.textures.insert(
+                                factory,
+                                resources,
+                                page,
+                                hal::image::Layout::ShaderReadOnlyOptimal,
+                            )
+                        })
+                    {
+                        self.batches.insert(tex_id, Some(*args));
+                        changed = changed || this_changed;
+                    }
+                }
+            }
+
+            if let Some(text) = maybe_text {
+                for icon in text.cached_icons.iter().filter(|i| i.glyph < revealed) {
+                    let this_changed = render_icon(
+                        factory,
+                        aux,
+                        icon,
+                        &tint,
+                        &mut self.textures,
+                        &mut self.batches,
+                    );
+                    changed = changed || this_changed;
                 }
             }
         }
@@ -543,11 +594,66 @@ fn mul_blend(a: &[f32; 4], b: &[f32; 4]) -> [f32; 4] {
     [a[0] * b[0], a[1] * b[1], a[2] * b[2], a[3] * b[3]]
 }
 
+fn sprite_tex_coords(aux: &GraphAuxData, sprite_renderer: &SpriteRender) -> [f32; 4] {
+    let sprite_sheets = aux.resources.get::<AssetStorage<SpriteSheet>>().unwrap();
+    if let Some(sprite_sheet) = sprite_sheets.get(&sprite_renderer.sprite_sheet) {
+        let sprites_storage = aux.resources.get::<AssetStorage<Sprites>>().unwrap();
+        let sprites = sprites_storage.get(&sprite_sheet.sprites).unwrap();
+        let tex_coord = &sprites.build_sprites()[sprite_renderer.sprite_number].tex_coords;
+        [
+            tex_coord.left,
+            tex_coord.top,
+            tex_coord.right,
+            tex_coord.bottom,
+        ]
+    } else {
+        [0.0_f32, 0., 1., 1.]
+    }
+}
+
+fn render_icon<B: Backend>(
+    factory: &Factory<B>,
+    aux: &GraphAuxData,
+    icon: &CachedIcon,
+    tint: &Option<[f32; 4]>,
+    textures: &mut TextureSub<B>,
+    batches: &mut OrderedOneLevelBatch<TextureId, UiArgs>,
+) -> bool {
+    let sprite_sheets = aux.resources.get::<AssetStorage<SpriteSheet>>().unwrap();
+    let sprite_sheet = if let Some(sprite_sheet) = sprite_sheets.get(&icon.sprite.sprite_sheet) {
+        sprite_sheet
+    } else {
+        return false;
+    };
+
+    let args = UiArgs {
+        coords: icon.coords.into(),
+        dimensions: icon.dimensions.into(),
+        tex_coord_bounds: sprite_tex_coords(aux, &icon.sprite).into(),
+        color: tint.unwrap_or([1., 1., 1., 1.]).into(),
+        color_bias: [0., 0., 0., 0.].into(),
+        radial_fill: NO_RADIAL_FILL.into(),
+    };
+
+    if let Some((tex_id, this_changed)) = textures.insert(
+        factory,
+        aux.resources,
+        &sprite_sheet.texture,
+        hal::image::Layout::ShaderReadOnlyOptimal,
+    ) {
+        batches.insert(tex_id, Some(args));
+        this_changed
+    } else {
+        false
+    }
+}
+
 fn render_image<B: Backend>(
     factory: &Factory<B>,
     aux: &GraphAuxData,
     transform: &UiTransform,
     raw_image: &UiImage,
+    fill: Option<&UiFill>,
     tint: &Option<[f32; 4]>,
     white_tex_id: TextureId,
     textures: &mut TextureSub<B>,
@@ -561,22 +667,7 @@ fn render_image<B: Backend>(
     };
 
     let tex_coords = match raw_image {
-        UiImage::Sprite(sprite_renderer) => {
-            let sprite_sheets = aux.resources.get::<AssetStorage<SpriteSheet>>().unwrap();
-            if let Some(sprite_sheet) = sprite_sheets.get(&sprite_renderer.sprite_sheet) {
-                let sprites_storage = aux.resources.get::<AssetStorage<Sprites>>().unwrap();
-                let sprites = sprites_storage.get(&sprite_sheet.sprites).unwrap();
-                let tex_coord = &sprites.build_sprites()[sprite_renderer.sprite_number].tex_coords;
-                [
-                    tex_coord.left,
-                    tex_coord.top,
-                    tex_coord.right,
-                    tex_coord.bottom,
-                ]
-            } else {
-                [0.0_f32, 0., 1., 1.]
-            }
-        }
+        UiImage::Sprite(sprite_renderer) => sprite_tex_coords(aux, sprite_renderer),
         UiImage::PartialTexture {
             left,
             right,
@@ -593,7 +684,14 @@ fn render_image<B: Backend>(
         tex_coord_bounds: tex_coords.into(),
         color: color.into(),
         color_bias: [0., 0., 0., 0.].into(),
+        radial_fill: NO_RADIAL_FILL.into(),
     };
+    let quad = FillQuad::new(
+        [transform.pixel_x(), transform.pixel_y()],
+        [transform.pixel_width, transform.pixel_height],
+        tex_coords,
+    );
+    let fill = fill.map(|fill| (fill, quad.rect));
 
     match raw_image {
         UiImage::Texture(tex) | UiImage::PartialTexture { tex, .. } => {
@@ -603,7 +701,7 @@ fn render_image<B: Backend>(
                 tex,
                 hal::image::Layout::ShaderReadOnlyOptimal,
             ) {
-                batches.insert(tex_id, Some(args));
+                insert_image_args(batches, tex_id, args, quad, fill);
                 this_changed
             } else {
                 false
@@ -618,7 +716,7 @@ fn render_image<B: Backend>(
                     &sprite_sheet.texture,
                     hal::image::Layout::ShaderReadOnlyOptimal,
                 ) {
-                    batches.insert(tex_id, Some(args));
+                    insert_image_args(batches, tex_id, args, quad, fill);
                     this_changed
                 } else {
                     false
@@ -686,17 +784,22 @@ fn render_image<B: Backend>(
                 // loop through left to right, the top to bottom and batch each slice to render
                 for x in 0..3 {
                     for y in 0..3 {
-                        let mut temp_args = args;
-                        temp_args.tex_coord_bounds = [
+                        let slice_tex_coords = [
                             x_tex_coord_bound[x],
                             y_tex_coord_bound[y],
                             x_tex_coord_bound[x + 1],
                             y_tex_coord_bound[y + 1],
-                        ]
-                        .into();
+                        ];
+                        let mut temp_args = args;
+                        temp_args.tex_coord_bounds = slice_tex_coords.into();
                         temp_args.dimensions = [x_dimensions[x], y_dimensions[y]].into();
                         temp_args.coords = [x_coords[x], y_coords[y]].into();
-                        batches.insert(tex_id, Some(temp_args));
+                        let slice = FillQuad::new(
+                            [x_coords[x], y_coords[y]],
+                            [x_dimensions[x], y_dimensions[y]],
+                            slice_tex_coords,
+                        );
+                        insert_image_args(batches, tex_id, temp_args, slice, fill);
                     }
                 }
 
@@ -706,8 +809,37 @@ fn render_image<B: Backend>(
             }
         }
         UiImage::SolidColor(_) => {
-            batches.insert(white_tex_id, Some(args));
+            insert_image_args(batches, white_tex_id, args, quad, fill);
             false
         }
     }
 }
+
+/// Inserts `args`, which draws `quad`, or only the parts of `quad` filled by a `UiFill` of an
+/// image with the given bounds.
+fn insert_image_args(
+    batches: &mut OrderedOneLevelBatch<TextureId, UiArgs>,
+    tex_id: TextureId,
+    args: UiArgs,
+    quad: FillQuad,
+    fill: Option<(&UiFill, [f32; 4])>,
+) {
+    match fill {
+        Some((fill, bounds)) => {
+            let radial_fill = fill.radial_fill(bounds).into();
+            batches.insert(
+                tex_id,
+                fill.apply(quad, bounds).into_iter().map(|part| {
+                    UiArgs {
+                        coords: part.coords().into(),
+                        dimensions: part.dimensions().into(),
+                        tex_coord_bounds: part.tex_coords.into(),
+                        radial_fill,
+                        ..args
+                    }
+                }),
+            );
+        }
+        None => batches.insert(tex_id, Some(args)),
+    }
+}
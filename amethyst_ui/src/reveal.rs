@@ -0,0 +1,419 @@
+//! Module for the `UiTextReveal` component and `UiTextRevealSystem`.
+
+use amethyst_core::{
+    ecs::{Entity, IntoQuery, ParallelRunnable, System, SystemBuilder},
+    shrev::{EventChannel, ReaderId},
+    Time,
+};
+use amethyst_input::InputEvent;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{glyphs::UiGlyphs, parse_markup, MarkupSegment, UiEvent, UiEventType, UiText};
+
+/// A pause in the reveal of a `UiTextReveal`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RevealPause {
+    /// Number of revealed characters after which the pause happens.
+    pub at: usize,
+    /// Duration of the pause, in seconds.
+    pub duration: f32,
+}
+
+/// What reveals all of the text of a `UiTextReveal` at once.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RevealSkip {
+    /// The text is only revealed at once by calling `UiTextReveal::skip`.
+    Never,
+    /// Clicking the text. The entity needs an `Interactable` component to receive clicks.
+    Click,
+    /// Pressing any key, mouse button or controller button.
+    Input,
+}
+
+impl Default for RevealSkip {
+    fn default() -> Self {
+        RevealSkip::Input
+    }
+}
+
+/// # Text Reveal Component
+/// Progressively reveals the `UiText` of the entity this is attached to, as a typewriter would.
+///
+/// The whole text is laid out up front and only the glyphs which are revealed are drawn, so the
+/// text does not shift while it is being revealed. Whitespace is not counted as a character.
+///
+/// A `UiEventType::TextRevealed` event is emitted once all of the text is revealed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UiTextReveal {
+    /// Number of characters revealed each second. Can be changed while revealing.
+    pub chars_per_second: f32,
+    /// Pauses made while revealing, sorted by position.
+    pub pauses: Vec<RevealPause>,
+    /// What reveals all of the text at once.
+    pub skip_on: RevealSkip,
+    /// Whether to use the scaled or unscaled time.
+    pub absolute_time: bool,
+    progress: f32,
+    pause_timer: f32,
+    next_pause: usize,
+    skip_requested: bool,
+    complete: bool,
+}
+
+impl UiTextReveal {
+    /// Creates a `UiTextReveal` revealing `chars_per_second` characters each second.
+    #[must_use]
+    pub fn new(chars_per_second: f32) -> Self {
+        Self {
+            chars_per_second,
+            pauses: Vec::new(),
+            skip_on: RevealSkip::default(),
+            absolute_time: false,
+            progress: 0.,
+            pause_timer: 0.,
+            next_pause: 0,
+            skip_requested: false,
+            complete: false,
+        }
+    }
+
+    /// Adds a pause of `duration` seconds once `at` characters have been revealed.
+    #[must_use]
+    pub fn with_pause(mut self, at: usize, duration: f32) -> Self {
+        let index = self
+            .pauses
+            .iter()
+            .position(|pause| pause.at > at)
+            .unwrap_or_else(|| self.pauses.len());
+        self.pauses.insert(index, RevealPause { at, duration });
+        self
+    }
+
+    /// Adds the pauses returned by `parse_pause_markers`.
+    #[must_use]
+    pub fn with_pauses(mut self, pauses: impl IntoIterator<Item = RevealPause>) -> Self {
+        self.pauses.extend(pauses);
+        self.pauses.sort_by_key(|pause| pause.at);
+        self
+    }
+
+    /// Reveals all of the text on the next update.
+    pub fn skip(&mut self) {
+        self.skip_requested = true;
+    }
+
+    /// Hides the text and starts revealing it again, e.g. after the text was changed.
+    pub fn restart(&mut self) {
+        self.progress = 0.;
+        self.pause_timer = 0.;
+        self.next_pause = 0;
+        self.skip_requested = false;
+        self.complete = false;
+    }
+
+    /// Number of characters currently revealed.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn revealed(&self) -> usize {
+        self.progress as usize
+    }
+
+    /// Returns true once all of the text is revealed.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Advances the reveal by `delta` seconds, for a text of `total` characters.
+    /// Returns true if the reveal completed during this update.
+    #[allow(clippy::cast_precision_loss)]
+    fn advance(&mut self, mut delta: f32, total: usize) -> bool {
+        if self.complete {
+            return false;
+        }
+
+        if self.skip_requested {
+            self.skip_requested = false;
+            self.progress = total as f32;
+            self.next_pause = self.pauses.len();
+        }
+
+        loop {
+            if self.progress >= total as f32 {
+                self.progress = total as f32;
+                self.complete = true;
+                return true;
+            }
+
+            if self.pause_timer > 0. {
+                let used = delta.min(self.pause_timer);
+                self.pause_timer -= used;
+                delta -= used;
+                if self.pause_timer > 0. {
+                    return false;
+                }
+            }
+
+            let pause = self.pauses[self.next_pause.min(self.pauses.len())..]
+                .iter()
+                .position(|pause| pause.at as f32 >= self.progress && pause.at < total)
+                .map(|offset| self.next_pause + offset);
+            let target = pause.map_or(total, |index| self.pauses[index].at) as f32;
+
+            if self.chars_per_second <= 0. || delta <= 0. {
+                return false;
+            }
+
+            let needed = (target - self.progress) / self.chars_per_second;
+            if delta < needed {
+                self.progress += delta * self.chars_per_second;
+                return false;
+            }
+
+            delta -= needed;
+            self.progress = target;
+            if let Some(index) = pause {
+                self.pause_timer = self.pauses[index].duration;
+                self.next_pause = index + 1;
+            }
+        }
+    }
+}
+
+/// Removes the `[pause=seconds]` markers of a text, returning the text to display and the pauses
+/// to give to `UiTextReveal::with_pauses`.
+///
+/// If `markup` is true, markup tags are not counted as characters. See `parse_markup`.
+#[must_use]
+pub fn parse_pause_markers(text: &str, markup: bool) -> (String, Vec<RevealPause>) {
+    const MARKER: &str = "[pause=";
+
+    let mut stripped = String::with_capacity(text.len());
+    let mut pauses = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find(MARKER) {
+        let after = &rest[start + MARKER.len()..];
+        let duration = after
+            .find(']')
+            .and_then(|end| after[..end].parse::<f32>().ok().map(|d| (d, end)));
+
+        stripped.push_str(&rest[..start]);
+        match duration {
+            Some((duration, end)) => {
+                pauses.push(RevealPause {
+                    at: count_chars(&stripped, markup),
+                    duration,
+                });
+                rest = &after[end + 1..];
+            }
+            None => {
+                stripped.push_str(MARKER);
+                rest = after;
+            }
+        }
+    }
+    stripped.push_str(rest);
+
+    (stripped, pauses)
+}
+
+fn count_chars(text: &str, markup: bool) -> usize {
+    let visible = |text: &str| text.chars().filter(|c| !c.is_whitespace()).count();
+    if markup {
+        parse_markup(text)
+            .iter()
+            .map(|segment| {
+                match segment {
+                    MarkupSegment::Text { text, .. } => visible(text),
+                    MarkupSegment::Image { .. } => 1,
+                }
+            })
+            .sum()
+    } else {
+        visible(text)
+    }
+}
+
+/// Returns the number of glyphs to reveal, or `None` while the `UiGlyphsSystem` has not laid
+/// out the glyphs of a text with visible characters yet.
+fn glyph_count(glyphs: Option<&UiGlyphs>, text: &UiText) -> Option<usize> {
+    let total = glyphs.map_or(0, |glyphs| {
+        glyphs.vertices.len() + glyphs.bitmap_vertices.len()
+    });
+    if total == 0 && count_chars(&text.text, text.markup) > 0 {
+        None
+    } else {
+        Some(total)
+    }
+}
+
+/// Returns whether an input event skips the reveal of texts with `RevealSkip::Input`.
+fn is_skip_input(event: &InputEvent) -> bool {
+    matches!(
+        event,
+        InputEvent::KeyPressed { .. }
+            | InputEvent::MouseButtonPressed(_)
+            | InputEvent::ControllerButtonPressed { .. }
+    )
+}
+
+/// System updating the `UiTextReveal` component.
+#[derive(Debug)]
+pub struct UiTextRevealSystem {
+    event_reader: ReaderId<UiEvent>,
+    input_reader: ReaderId<InputEvent>,
+}
+
+impl UiTextRevealSystem {
+    /// Creates a new `UiTextRevealSystem`.
+    pub fn new(event_reader: ReaderId<UiEvent>, input_reader: ReaderId<InputEvent>) -> Self {
+        Self {
+            event_reader,
+            input_reader,
+        }
+    }
+}
+
+impl System for UiTextRevealSystem {
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("UiTextRevealSystem")
+                .read_resource::<Time>()
+                .read_resource::<EventChannel<InputEvent>>()
+                .write_resource::<EventChannel<UiEvent>>()
+                .with_query(<(Entity, &mut UiTextReveal, &UiText, Option<&UiGlyphs>)>::query())
+                .build(move |_commands, world, (time, inputs, events), reveals| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("ui_text_reveal_system");
+
+                    for event in events.read(&mut self.event_reader) {
+                        if event.event_type == UiEventType::Click {
+                            if let Ok((_, reveal, _, _)) = reveals.get_mut(world, event.target) {
+                                if reveal.skip_on == RevealSkip::Click {
+                                    reveal.skip();
+                                }
+                            }
+                        }
+                    }
+
+                    if inputs.read(&mut self.input_reader).any(is_skip_input) {
+                        for (_, reveal, _, _) in reveals.iter_mut(world) {
+                            if reveal.skip_on == RevealSkip::Input {
+                                reveal.skip();
+                            }
+                        }
+                    }
+
+                    let abs_sec = time.delta_time().as_secs_f32();
+                    let abs_unscaled_sec = time.delta_real_time().as_secs_f32();
+
+                    let mut completed = Vec::new();
+                    for (entity, reveal, text, glyphs) in reveals.iter_mut(world) {
+                        let total = match glyph_count(glyphs, text) {
+                            Some(total) => total,
+                            None => continue,
+                        };
+                        let delta = if reveal.absolute_time {
+                            abs_unscaled_sec
+                        } else {
+                            abs_sec
+                        };
+                        if reveal.advance(delta, total) {
+                            completed.push(UiEvent::new(UiEventType::TextRevealed, *entity));
+                        }
+                    }
+                    events.iter_write(completed);
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Anchor, LineMode};
+
+    #[test]
+    fn reveals_over_time() {
+        let mut reveal = UiTextReveal::new(10.);
+        assert!(!reveal.advance(0.25, 5));
+        assert_eq!(reveal.revealed(), 2);
+        assert!(reveal.advance(0.5, 5));
+        assert_eq!(reveal.revealed(), 5);
+        assert!(reveal.is_complete());
+        assert!(!reveal.advance(1., 5));
+    }
+
+    #[test]
+    fn pauses() {
+        let mut reveal = UiTextReveal::new(10.).with_pause(2, 1.);
+        assert!(!reveal.advance(0.5, 5));
+        assert_eq!(reveal.revealed(), 2);
+        assert!(!reveal.advance(0.7, 5));
+        assert_eq!(reveal.revealed(), 2);
+        assert!(!reveal.advance(0.2, 5));
+        assert_eq!(reveal.revealed(), 4);
+        assert!(reveal.advance(1., 5));
+    }
+
+    #[test]
+    fn skip_and_restart() {
+        let mut reveal = UiTextReveal::new(1.).with_pause(1, 10.);
+        reveal.skip();
+        assert!(reveal.advance(0., 8));
+        assert_eq!(reveal.revealed(), 8);
+        reveal.restart();
+        assert_eq!(reveal.revealed(), 0);
+        assert!(!reveal.is_complete());
+    }
+
+    #[test]
+    fn waits_for_glyphs() {
+        let text = UiText::new(
+            None,
+            "Hello".to_string(),
+            [1.; 4],
+            10.,
+            LineMode::Single,
+            Anchor::Middle,
+        );
+        assert_eq!(glyph_count(None, &text), None);
+
+        let blank = UiText::new(
+            None,
+            " ".to_string(),
+            [1.; 4],
+            10.,
+            LineMode::Single,
+            Anchor::Middle,
+        );
+        assert_eq!(glyph_count(None, &blank), Some(0));
+    }
+
+    #[test]
+    fn skips_on_presses() {
+        assert!(!is_skip_input(&InputEvent::KeyTyped('a')));
+        assert!(is_skip_input(&InputEvent::MouseButtonPressed(
+            winit::event::MouseButton::Left
+        )));
+    }
+
+    #[test]
+    fn pause_markers() {
+        let (text, pauses) = parse_pause_markers("Hi there.[pause=0.5] Bye[pause=x]", false);
+        assert_eq!(text, "Hi there. Bye[pause=x]");
+        assert_eq!(
+            pauses,
+            vec![RevealPause {
+                at: 8,
+                duration: 0.5
+            }]
+        );
+
+        let (text, pauses) = parse_pause_markers("[b]Hi[/b][pause=1] [img=coin]", true);
+        assert_eq!(text, "[b]Hi[/b] [img=coin]");
+        assert_eq!(pauses[0].at, 2);
+    }
+}
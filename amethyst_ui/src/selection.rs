@@ -1,15 +1,18 @@
 use std::marker::PhantomData;
 
 use amethyst_core::{
-    ecs::{Entity, IntoQuery, ParallelRunnable, System, SystemBuilder},
+    ecs::{component, Entity, IntoQuery, ParallelRunnable, System, SystemBuilder},
     shrev::{EventChannel, ReaderId},
+    Hidden, HiddenPropagate,
 };
 use amethyst_input::InputHandler;
 use derive_new::new;
 use serde::{Deserialize, Serialize};
 use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
 
-use crate::{CachedSelectionOrderResource, UiEvent, UiEventType};
+use crate::{
+    modal::modal_layer, CachedSelectionOrderResource, UiEvent, UiEventType, UiModal, UiTransform,
+};
 
 // TODO: If none selected and there is a Selectable in the World, select the lower ordered one automatically?
 
@@ -44,6 +47,7 @@ pub struct Selected;
 /// System managing the selection of entities.
 /// Reacts to `UiEvent`.
 /// Reacts to Tab and Shift+Tab.
+/// While a `UiModal` is visible, only the entities on its layer or above it can be selected.
 #[derive(Debug)]
 pub struct SelectionKeyboardSystem<G>
 where
@@ -77,9 +81,11 @@ where
             .write_resource::<EventChannel<UiEvent>>()
             .read_resource::<InputHandler>()
             .with_query(<(Entity, &mut Selected)>::query())
+            .with_query(<(&UiTransform, &UiModal)>::query().filter(!component::<Hidden>() & !component::<HiddenPropagate>()))
+            .with_query(<&UiTransform>::query())
             .build(move |commands, world,
                          ( window_events, cached,ui_events, inputs),
-                         selected_query| {
+                         (selected_query, modals, transforms)| {
                 /*
                        Algorithm in use:
 
@@ -116,6 +122,18 @@ where
                         ..
                     } = *event
                     {
+                        // Trap the navigation in the top modal, if any.
+                        let trapped;
+                        let cached = if let Some(z) = modal_layer(modals.iter(world)) {
+                            trapped = CachedSelectionOrderResource {
+                                cache: cached.cache.iter().filter(|(_, e)| transforms.get(world, *e).map_or(false, |t| t.global_z >= z)).copied().collect(),
+                                ..Default::default()
+                            };
+                            &trapped
+                        } else {
+                            &*cached
+                        };
+
                         // Get index of highest selected ui element
                         let highest = cached.highest_order_selected_index(selected_query.iter_mut(world));
 
@@ -147,6 +165,11 @@ where
                             ui_events.single_write(UiEvent::new(UiEventType::Focus, target.1));
                         } else if let Some(lowest) = cached.cache.first() {
                             // If None, nothing was selected. Try to take lowest if it exists.
+                            // Entities selected below a modal are not in the cache, unselect them.
+                            selected_query.for_each_mut(world, |(entity, _)| {
+                                ui_events.single_write(UiEvent::new(UiEventType::Blur, *entity));
+                                commands.remove_component::<Selected>(*entity);
+                            });
                             commands.add_component(lowest.1, Selected);
                             ui_events.single_write(UiEvent::new(UiEventType::Focus, lowest.1));
                         }
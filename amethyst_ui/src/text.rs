@@ -1,23 +1,31 @@
 //! Module holding the components related to text and text editing.
 
+use std::ops::Range;
+
 use amethyst_assets::{
     prefab::{legion_prefab, register_component_type, serde_diff, SerdeDiff},
     Handle,
 };
 use amethyst_core::{
-    ecs::{IntoQuery, ParallelRunnable, System, SystemBuilder},
+    ecs::{component, IntoQuery, ParallelRunnable, System, SystemBuilder},
     shrev::{EventChannel, ReaderId},
-    Time,
+    Hidden, HiddenPropagate, Time,
 };
 use amethyst_window::ScreenDimensions;
 use derivative::Derivative;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use type_uuid::TypeUuid;
 use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use unicode_segmentation::UnicodeSegmentation;
 use winit::event::{ElementState, Event, MouseButton, WindowEvent};
 
-use super::{FontAsset, Selected};
-use crate::Anchor;
+use super::{BitmapFont, FontAsset, FontFamily, Selected};
+use crate::{
+    markup::{CachedIcon, CachedLink},
+    modal::modal_layer,
+    Anchor, UiModal, UiTransform,
+};
 
 /// How lines should behave when they are longer than the maximum line length.
 #[derive(Debug, Derivative, Clone, Copy, Eq, PartialEq, Deserialize, Serialize, SerdeDiff)]
@@ -48,16 +56,43 @@ pub struct UiText {
     #[serde(skip)]
     #[serde_diff(opaque)]
     pub font: Option<Handle<FontAsset>>,
+    /// The font family used for rendering, drawing the characters missing from a font with the
+    /// next font of the family. Takes precedence over `font`, which becomes its last fallback.
+    #[serde(skip)]
+    #[serde_diff(opaque)]
+    pub font_family: Option<Handle<FontFamily>>,
+    /// The bitmap font used for rendering, drawing the characters from its page textures. Takes
+    /// precedence over `font` and `font_family`. Markup is not supported with bitmap fonts.
+    #[serde(skip)]
+    #[serde_diff(opaque)]
+    pub bitmap_font: Option<Handle<BitmapFont>>,
+    /// If true the bold face of the font family is used.
+    /// Without a font family, the bold font of the `UiMarkupResources` is used.
+    pub bold: bool,
+    /// If true the italic face of the font family is used.
+    /// Without a font family, the italic font of the `UiMarkupResources` is used.
+    pub italic: bool,
     /// If true this will be rendered as dots instead of the text.
     pub password: bool,
     /// How the text should handle new lines.
     pub line_mode: LineMode,
     /// How to align the text within its `UiTransform`.
     pub align: Anchor,
+    /// If true the text is parsed as rich text markup, see `parse_markup` for the syntax.
+    /// Markup is ignored for password and editable texts.
+    pub markup: bool,
     /// Cached glyph positions including invisible characters, used to process mouse highlighting.
     #[serde(skip)]
     #[serde_diff(skip)]
     pub(crate) cached_glyphs: Vec<CachedGlyph>,
+    /// Cached inline images of markup text.
+    #[serde(skip)]
+    #[serde_diff(skip)]
+    pub(crate) cached_icons: Vec<CachedIcon>,
+    /// Cached glyph rectangles of markup link spans.
+    #[serde(skip)]
+    #[serde_diff(skip)]
+    pub(crate) cached_links: Vec<CachedLink>,
 }
 
 register_component_type!(UiText);
@@ -99,6 +134,52 @@ impl UiText {
             ..UiText::default()
         }
     }
+
+    /// Draws this text with a font family.
+    #[must_use]
+    pub fn with_font_family(mut self, font_family: Handle<FontFamily>) -> Self {
+        self.font_family = Some(font_family);
+        self
+    }
+
+    /// Draws this text with a bitmap font.
+    #[must_use]
+    pub fn with_bitmap_font(mut self, bitmap_font: Handle<BitmapFont>) -> Self {
+        self.bitmap_font = Some(bitmap_font);
+        self
+    }
+
+    /// Draws this text with the bold face.
+    #[must_use]
+    pub fn with_bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    /// Draws this text with the italic face.
+    #[must_use]
+    pub fn with_italic(mut self) -> Self {
+        self.italic = true;
+        self
+    }
+
+    /// Enables rich text markup for this text.
+    #[must_use]
+    pub fn with_markup(mut self) -> Self {
+        self.markup = true;
+        self
+    }
+
+    /// Returns the id of the markup link span at the given screen position, if any.
+    #[must_use]
+    pub fn link_at(&self, x: f32, y: f32) -> Option<&str> {
+        self.cached_links
+            .iter()
+            .find(|link| {
+                x >= link.rect[0] && y >= link.rect[1] && x < link.rect[2] && y < link.rect[3]
+            })
+            .map(|link| link.id.as_str())
+    }
 }
 
 /// If this component is attached to an entity with a `UiText` then that `UiText` is editable.
@@ -148,7 +229,112 @@ impl TextEditing {
     }
 }
 
+/// The characters or pattern the text of a `TextValidation` must follow.
+#[derive(Debug, Derivative, Clone)]
+#[derivative(Default)]
+pub enum TextFilter {
+    /// Any text is valid.
+    #[derivative(Default)]
+    Any,
+    /// A decimal number, optionally negative.
+    Numeric,
+    /// An integer, optionally negative.
+    Integer,
+    /// The whole text must match the regex.
+    Regex(Regex),
+}
+
+/// Restricts what can be typed in an editable `UiText`.
+/// Edits which would make the text invalid are rejected.
+///
+/// Attach this to an entity with a `TextEditing` component.
+#[derive(Debug, Clone, Default)]
+pub struct TextValidation {
+    /// The characters or pattern the text must follow.
+    pub filter: TextFilter,
+    /// The maximum number of lines, separated by line breaks.
+    ///
+    /// Lines wrapped by `LineMode::Wrap` are not counted, as the text is validated before it is
+    /// laid out.
+    pub max_lines: Option<usize>,
+}
+
+impl TextValidation {
+    /// Only accepts decimal numbers.
+    #[must_use]
+    pub fn numeric() -> Self {
+        Self {
+            filter: TextFilter::Numeric,
+            max_lines: None,
+        }
+    }
+
+    /// Only accepts integers.
+    #[must_use]
+    pub fn integer() -> Self {
+        Self {
+            filter: TextFilter::Integer,
+            max_lines: None,
+        }
+    }
+
+    /// Only accepts texts matching `pattern` entirely.
+    ///
+    /// The text is validated after every keystroke, so the pattern must also match the text
+    /// while it is being typed, e.g. `[a-z]{0,8}` rather than `[a-z]{8}`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pattern` is not a valid regex.
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            filter: TextFilter::Regex(Regex::new(&format!("^(?:{})$", pattern))?),
+            max_lines: None,
+        })
+    }
+
+    /// Limits the number of lines.
+    #[must_use]
+    pub fn with_max_lines(mut self, max_lines: usize) -> Self {
+        self.max_lines = Some(max_lines);
+        self
+    }
+
+    /// Returns true if `text` is allowed.
+    #[must_use]
+    pub fn is_valid(&self, text: &str) -> bool {
+        let filtered = match &self.filter {
+            TextFilter::Any => true,
+            TextFilter::Numeric => is_number(text, true),
+            TextFilter::Integer => is_number(text, false),
+            TextFilter::Regex(regex) => regex.is_match(text),
+        };
+        filtered
+            && self
+                .max_lines
+                .map_or(true, |max_lines| text.split('\n').count() <= max_lines)
+    }
+}
+
+/// Also accepts the incomplete numbers typed on the way, such as "-" or "1.".
+fn is_number(text: &str, decimal: bool) -> bool {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let mut point = false;
+    digits.chars().all(|c| {
+        c.is_ascii_digit() || (decimal && c == '.' && !std::mem::replace(&mut point, true))
+    })
+}
+
+/// Maximum delay between the clicks of a double or triple click, in seconds.
+const MULTI_CLICK_DELAY: f32 = 0.4;
+/// Maximum distance between the clicks of a double or triple click, in pixels.
+const MULTI_CLICK_DISTANCE: f32 = 4.;
+/// Glyphs whose y position differs by less than this are on the same line.
+const LINE_TOLERANCE: f32 = 0.5;
+
 /// This system processes the underlying UI data as needed.
+///
+/// Double clicking selects a word, triple clicking selects a line.
 #[derive(Debug)]
 pub struct TextEditingMouseSystem {
     /// A reader for winit events.
@@ -157,6 +343,10 @@ pub struct TextEditingMouseSystem {
     left_mouse_button_pressed: bool,
     /// The screen coordinates of the mouse
     mouse_position: (f32, f32),
+    /// Position of the last click and time elapsed since, in seconds.
+    last_click: ((f32, f32), f32),
+    /// Number of successive clicks, 2 for a double click.
+    click_count: u32,
 }
 
 impl TextEditingMouseSystem {
@@ -166,6 +356,8 @@ impl TextEditingMouseSystem {
             event_reader,
             left_mouse_button_pressed: false,
             mouse_position: (0., 0.),
+            last_click: ((0., 0.), MULTI_CLICK_DELAY),
+            click_count: 0,
         }
     }
 }
@@ -179,12 +371,21 @@ impl System for TextEditingMouseSystem {
                 .read_resource::<ScreenDimensions>()
                 .with_query(<&mut UiText>::query())
                 .with_query(<(&mut TextEditing, &Selected)>::query())
-                .with_query(<(&mut UiText, &mut TextEditing, Option<&Selected>)>::query())
+                .with_query(<(
+                    &mut UiText,
+                    &mut TextEditing,
+                    Option<&Selected>,
+                    Option<&UiTransform>,
+                )>::query())
+                .with_query(
+                    <(&UiTransform, &UiModal)>::query()
+                        .filter(!component::<Hidden>() & !component::<HiddenPropagate>()),
+                )
                 .build(
                     move |_commands,
                           world,
                           (time, events, screen_dimensions),
-                          (texts, selected_text_editings, maybe_selected_texts)| {
+                          (texts, selected_text_editings, maybe_selected_texts, modals)| {
                         // Normalize text to ensure we can properly count the characters.
                         // TODO: Possible improvement to be made if this can be moved only when inserting characters into ui text.
                         texts.for_each_mut(world, |mut text| {
@@ -204,6 +405,7 @@ impl System for TextEditingMouseSystem {
 
                         let mut just_pressed = false;
                         let mut moved_while_pressed = false;
+                        self.last_click.1 += time.delta_real_time().as_secs_f32();
 
                         let event_reader = &mut self.event_reader;
 
@@ -215,7 +417,7 @@ impl System for TextEditingMouseSystem {
                                     event: WindowEvent::CursorMoved { position, .. },
                                     ..
                                 } => {
-                                    // TODO : Check why the hidpi has been deleted ? let hidpi = screen_dimensions.hidpi_factor() as f32;
+                                    // Both the cursor and the glyphs are in physical pixels.
                                     self.mouse_position = (
                                         position.x as f32,
                                         (screen_dimensions.height() - position.y as f32),
@@ -236,6 +438,15 @@ impl System for TextEditingMouseSystem {
                                     ElementState::Pressed => {
                                         just_pressed = true;
                                         self.left_mouse_button_pressed = true;
+
+                                        let ((last_x, last_y), elapsed) = self.last_click;
+                                        let (mouse_x, mouse_y) = self.mouse_position;
+                                        let repeated = elapsed <= MULTI_CLICK_DELAY
+                                            && (mouse_x - last_x).abs() <= MULTI_CLICK_DISTANCE
+                                            && (mouse_y - last_y).abs() <= MULTI_CLICK_DISTANCE;
+                                        self.click_count =
+                                            if repeated { self.click_count % 3 + 1 } else { 1 };
+                                        self.last_click = (self.mouse_position, 0.);
                                     }
                                     ElementState::Released => {
                                         self.left_mouse_button_pressed = false;
@@ -245,12 +456,18 @@ impl System for TextEditingMouseSystem {
                             }
                         }
 
-                        maybe_selected_texts.for_each_mut(world, |(text, mut text_editing, selected)| {
+                        // Texts covered by a modal ignore the mouse.
+                        let layer = modal_layer(modals.iter(world));
+                        maybe_selected_texts.for_each_mut(world, |(text, mut text_editing, selected, transform)| {
+                            let covered = match (layer, transform) {
+                                (Some(z), Some(transform)) => transform.global_z < z,
+                                _ => false,
+                            };
                             if selected.is_none() {
                                 // If an editable text field is no longer selected, we should reset
                                 // the highlight vector.
                                 text_editing.highlight_vector = 0;
-                            } else if just_pressed {
+                            } else if just_pressed && !covered {
                                 // If we focused an editable text field be sure to position the cursor
                                 // in it.
                                 let (mouse_x, mouse_y) = self.mouse_position;
@@ -266,7 +483,19 @@ impl System for TextEditingMouseSystem {
                                 if should_advance_to_end(mouse_x, text_editing, text) {
                                     text_editing.cursor_position += 1;
                                 }
-                            } else if moved_while_pressed {
+
+                                let cursor = text_editing.cursor_position as usize;
+                                let selection = match self.click_count {
+                                    2 => Some(word_range(&text.text, cursor)),
+                                    3 => Some(line_range(&text.cached_glyphs, cursor)),
+                                    _ => None,
+                                };
+                                if let Some(selection) = selection {
+                                    text_editing.cursor_position = selection.end as isize;
+                                    text_editing.highlight_vector =
+                                        selection.start as isize - selection.end as isize;
+                                }
+                            } else if moved_while_pressed && !covered {
                                 let (mouse_x, mouse_y) = self.mouse_position;
                                 text_editing.highlight_vector =
                                     closest_glyph_index_to_mouse(mouse_x, mouse_y, &text.cached_glyphs)
@@ -314,3 +543,157 @@ fn closest_glyph_index_to_mouse(mouse_x: f32, mouse_y: f32, glyphs: &[CachedGlyp
         })
         .map_or(0, |(i, _)| i) as isize
 }
+
+/// Returns the range of graphemes of the word at the grapheme `index`, or of the last word if
+/// `index` is past the end of the text.
+pub(crate) fn word_range(text: &str, index: usize) -> Range<usize> {
+    let mut start = 0;
+    let mut last = 0..0;
+    for word in text.split_word_bounds() {
+        let end = start + word.graphemes(true).count();
+        last = start..end;
+        if index < end {
+            break;
+        }
+        start = end;
+    }
+    last
+}
+
+/// Returns the range of glyphs on the same line as the glyph `index`, or as the last glyph if
+/// `index` is past the end of the text.
+pub(crate) fn line_range(glyphs: &[CachedGlyph], index: usize) -> Range<usize> {
+    if glyphs.is_empty() {
+        return 0..0;
+    }
+    let index = index.min(glyphs.len() - 1);
+    let y = glyphs[index].y;
+    let other_line = |glyph: &CachedGlyph| (glyph.y - y).abs() >= LINE_TOLERANCE;
+    let start = glyphs[..index]
+        .iter()
+        .rposition(other_line)
+        .map_or(0, |i| i + 1);
+    let end = glyphs[index..]
+        .iter()
+        .position(other_line)
+        .map_or(glyphs.len(), |i| index + i);
+    start..end
+}
+
+/// Returns the cursor position on the line above or below the cursor, as close as possible to
+/// the current horizontal position, or `None` if there is no such line.
+pub(crate) fn vertical_move(glyphs: &[CachedGlyph], cursor: usize, up: bool) -> Option<usize> {
+    let (x, y) = match glyphs.get(cursor) {
+        Some(glyph) => (glyph.x, glyph.y),
+        None => {
+            let last = glyphs.last()?;
+            (last.x + last.advance_width, last.y)
+        }
+    };
+
+    // Glyph positions are in screen space, lines above have a greater y.
+    let line_y = glyphs
+        .iter()
+        .map(|glyph| glyph.y)
+        .filter(|line_y| {
+            if up {
+                *line_y >= y + LINE_TOLERANCE
+            } else {
+                *line_y <= y - LINE_TOLERANCE
+            }
+        })
+        .min_by(|y1, y2| {
+            (y1 - y)
+                .abs()
+                .partial_cmp(&(y2 - y).abs())
+                .expect("Unexpected NaN!")
+        })?;
+
+    let (index, glyph) = glyphs
+        .iter()
+        .enumerate()
+        .filter(|(_, glyph)| (glyph.y - line_y).abs() < LINE_TOLERANCE)
+        .min_by(|(_, g1), (_, g2)| {
+            (g1.x - x)
+                .abs()
+                .partial_cmp(&(g2.x - x).abs())
+                .expect("Unexpected NaN!")
+        })?;
+
+    // As with the mouse, the end of the text is a valid position too.
+    if index + 1 == glyphs.len() && x - glyph.x > glyph.advance_width / 2.0 {
+        Some(glyphs.len())
+    } else {
+        Some(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyph(x: f32, y: f32) -> CachedGlyph {
+        CachedGlyph {
+            x,
+            y,
+            advance_width: 10.,
+        }
+    }
+
+    #[test]
+    fn validation() {
+        let numeric = TextValidation::numeric();
+        assert!(numeric.is_valid("-12.5"));
+        assert!(numeric.is_valid("-"));
+        assert!(!numeric.is_valid("1.2.3"));
+        assert!(!numeric.is_valid("1e3"));
+
+        let integer = TextValidation::integer();
+        assert!(integer.is_valid("42"));
+        assert!(!integer.is_valid("4.2"));
+
+        let regex = TextValidation::regex("[a-z]{0,3}").unwrap();
+        assert!(regex.is_valid("abc"));
+        assert!(!regex.is_valid("abcd"));
+        assert!(!regex.is_valid("Ab"));
+
+        let lines = TextValidation::default().with_max_lines(2);
+        assert!(lines.is_valid("one\ntwo"));
+        assert!(!lines.is_valid("one\ntwo\n"));
+    }
+
+    #[test]
+    fn word_and_line_ranges() {
+        assert_eq!(word_range("hello big world", 7), 6..9);
+        assert_eq!(word_range("hello big world", 15), 10..15);
+        assert_eq!(word_range("", 0), 0..0);
+
+        let glyphs = [
+            glyph(0., 0.),
+            glyph(10., 0.),
+            glyph(0., -20.),
+            glyph(10., -20.),
+            glyph(20., -20.),
+        ];
+        assert_eq!(line_range(&glyphs, 1), 0..2);
+        assert_eq!(line_range(&glyphs, 3), 2..5);
+        assert_eq!(line_range(&glyphs, 9), 2..5);
+    }
+
+    #[test]
+    fn vertical_moves() {
+        let glyphs = [
+            glyph(0., 0.),
+            glyph(10., 0.),
+            glyph(20., 0.),
+            glyph(0., -20.),
+            glyph(10., -20.),
+        ];
+        assert_eq!(vertical_move(&glyphs, 1, false), Some(4));
+        assert_eq!(vertical_move(&glyphs, 2, false), Some(5));
+        assert_eq!(vertical_move(&glyphs, 4, true), Some(1));
+        assert_eq!(vertical_move(&glyphs, 5, true), Some(2));
+        assert_eq!(vertical_move(&glyphs, 1, true), None);
+        assert_eq!(vertical_move(&glyphs, 4, false), None);
+    }
+}
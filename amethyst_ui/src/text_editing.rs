@@ -1,4 +1,4 @@
-use std::ops::Range;
+use std::{collections::HashMap, ops::Range};
 
 use amethyst_core::{
     ecs::{Entity, IntoQuery, ParallelRunnable, System, SystemBuilder},
@@ -11,22 +11,132 @@ use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 use unicode_segmentation::UnicodeSegmentation;
 use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
 
-use crate::{LineMode, Selected, TextEditing, UiEvent, UiEventType, UiText};
+use crate::{
+    text::vertical_move, LineMode, Selected, TextEditing, TextValidation, UiEvent, UiEventType,
+    UiText,
+};
+
+/// Maximum number of edits which can be undone.
+const MAX_HISTORY: usize = 100;
+
+/// The text and cursor of an editable text before or after an edit.
+#[derive(Debug, Clone, PartialEq)]
+struct EditSnapshot {
+    text: String,
+    cursor_position: isize,
+    highlight_vector: isize,
+}
+
+impl EditSnapshot {
+    fn new(edit: &TextEditing, text: &UiText) -> Self {
+        Self {
+            text: text.text.clone(),
+            cursor_position: edit.cursor_position,
+            highlight_vector: edit.highlight_vector,
+        }
+    }
+
+    fn restore(self, edit: &mut TextEditing, text: &mut UiText) {
+        text.text = self.text;
+        edit.cursor_position = self.cursor_position;
+        edit.highlight_vector = self.highlight_vector;
+        edit.cursor_blink_timer = 0.0;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EditKind {
+    /// A typed character, successive ones are undone together.
+    Typing,
+    /// An undo or a redo.
+    History,
+    Other,
+}
+
+/// The undo and redo stacks of an editable text.
+#[derive(Debug, Default)]
+struct EditHistory {
+    undo: Vec<EditSnapshot>,
+    redo: Vec<EditSnapshot>,
+    /// Whether the last recorded edit was a typed character.
+    typing: bool,
+    /// The text after the last edit, to notice the changes made by other systems.
+    text: String,
+}
+
+impl EditHistory {
+    /// Records the state before an edit.
+    fn record(&mut self, before: EditSnapshot, kind: EditKind) {
+        let typing = kind == EditKind::Typing;
+        if !(typing && self.typing) {
+            if self.undo.len() == MAX_HISTORY {
+                self.undo.remove(0);
+            }
+            self.undo.push(before);
+        }
+        self.typing = typing;
+        self.redo.clear();
+    }
+
+    /// Forgets the edits if `text` was changed since the last edit, as they can't be undone
+    /// over it.
+    fn sync(&mut self, text: &str) {
+        if self.text != text {
+            self.undo.clear();
+            self.redo.clear();
+            self.typing = false;
+            self.text = text.to_string();
+        }
+    }
+
+    /// Reverts the last edit, returns false if there is nothing to undo.
+    fn undo(&mut self, edit: &mut TextEditing, text: &mut UiText) -> bool {
+        Self::swap(&mut self.undo, &mut self.redo, edit, text)
+    }
+
+    /// Applies the last undone edit again, returns false if there is nothing to redo.
+    fn redo(&mut self, edit: &mut TextEditing, text: &mut UiText) -> bool {
+        Self::swap(&mut self.redo, &mut self.undo, edit, text)
+    }
+
+    fn swap(
+        from: &mut Vec<EditSnapshot>,
+        to: &mut Vec<EditSnapshot>,
+        edit: &mut TextEditing,
+        text: &mut UiText,
+    ) -> bool {
+        if let Some(snapshot) = from.pop() {
+            to.push(EditSnapshot::new(edit, text));
+            snapshot.restore(edit, text);
+            true
+        } else {
+            false
+        }
+    }
+}
+
 /// System managing the keyboard inputs for the editable text fields.
 /// ## Features
 /// * Adds and removes text.
-/// * Moves selection cursor.
+/// * Moves selection cursor, across lines with the up and down keys.
 /// * Grows and shrinks selected text zone.
+/// * Undoes and redoes edits.
+/// * Rejects the edits failing the `TextValidation` of the text.
 #[derive(Debug)]
 pub struct TextEditingInputSystem {
     /// A reader for winit events.
     reader: ReaderId<Event<'static, ()>>,
+    /// The edit history of the editable texts, dropped when they are deleted.
+    history: HashMap<Entity, EditHistory>,
 }
 
 impl TextEditingInputSystem {
     /// Creates a new instance of this system
     pub fn new(reader: ReaderId<Event<'static, ()>>) -> Self {
-        Self { reader }
+        Self {
+            reader,
+            history: HashMap::new(),
+        }
     }
 }
 
@@ -37,21 +147,39 @@ impl System for TextEditingInputSystem {
                 .read_resource::<EventChannel<Event<'static, ()>>>()
                 .write_resource::<EventChannel<UiEvent>>()
                 .read_resource::<InputHandler>()
-                .with_query(<&mut UiText>::query())
-                .with_query(<(Entity, &mut UiText, &mut TextEditing, &Selected)>::query())
+                .with_query(<(Entity, &mut UiText)>::query())
+                .with_query(<(&UiText, &TextEditing)>::query())
+                .with_query(<(Entity, &mut UiText, &mut TextEditing, &Selected, Option<&TextValidation>)>::query())
                 .build(move |_commands, world, (events, ui_events, inputs),
-                             (ui_texts_query, selected_ui_texts_query)| {
+                             (ui_texts_query, editables_query, selected_ui_texts_query)| {
 
-                    ui_texts_query.for_each_mut(world, |mut text| {
+                    // Forget the history of the deleted texts, and of the texts changed by other
+                    // systems.
+                    self.history.retain(|entity, history| match editables_query.get(world, *entity) {
+                        Ok((text, _)) => {
+                            history.sync(&text.text);
+                            true
+                        }
+                        Err(_) => false,
+                    });
+
+                    for (entity, text) in ui_texts_query.iter_mut(world) {
                         if (*text.text).chars().any(is_combining_mark) {
                             let normalized = text.text.nfd().collect::<String>();
+                            if let Some(history) = self.history.get_mut(entity) {
+                                history.text = normalized.clone();
+                            }
                             text.text = normalized;
                         }
-                    });
+                    }
 
                     for event in events.read(&mut self.reader) {
-                        if let Some((entity, ref mut focused_text, ref mut focused_edit, _)) =
+                        if let Some((entity, ref mut focused_text, ref mut focused_edit, _, validation)) =
                         selected_ui_texts_query.iter_mut(world).next() {
+                            let history = self.history.entry(*entity).or_default();
+                            let before = EditSnapshot::new(focused_edit, focused_text);
+                            let mut kind = EditKind::Other;
+
                             match *event {
                                 Event::WindowEvent {
                                     event: WindowEvent::ReceivedCharacter(input),
@@ -75,9 +203,7 @@ impl System for TextEditingInputSystem {
                                     if focused_text.text.graphemes(true).count() < focused_edit.max_length {
                                         focused_text.text.insert(start_byte, input);
                                         focused_edit.cursor_position += 1;
-
-                                        ui_events
-                                            .single_write(UiEvent::new(UiEventType::ValueChange, *entity));
+                                        kind = EditKind::Typing;
                                     }
                                 }
                                 Event::WindowEvent {
@@ -93,7 +219,7 @@ impl System for TextEditingInputSystem {
                                     },
                                     ..
                                 } => match v_keycode {
-                                    VirtualKeyCode::Home | VirtualKeyCode::Up => {
+                                    VirtualKeyCode::Home => {
                                         focused_edit.highlight_vector = if inputs.modifiers.shift() {
                                             focused_edit.cursor_position
                                         } else {
@@ -102,7 +228,7 @@ impl System for TextEditingInputSystem {
                                         focused_edit.cursor_position = 0;
                                         focused_edit.cursor_blink_timer = 0.0;
                                     }
-                                    VirtualKeyCode::End | VirtualKeyCode::Down => {
+                                    VirtualKeyCode::End => {
                                         let glyph_len = focused_text.text.graphemes(true).count() as isize;
                                         focused_edit.highlight_vector = if inputs.modifiers.shift() {
                                             focused_edit.cursor_position - glyph_len
@@ -112,6 +238,20 @@ impl System for TextEditingInputSystem {
                                         focused_edit.cursor_position = glyph_len;
                                         focused_edit.cursor_blink_timer = 0.0;
                                     }
+                                    VirtualKeyCode::Up | VirtualKeyCode::Down => {
+                                        // Without a line above or below, go to the start or the end.
+                                        let up = v_keycode == VirtualKeyCode::Up;
+                                        let target = vertical_move(&focused_text.cached_glyphs, focused_edit.cursor_position as usize, up)
+                                            .map_or_else(|| if up { 0 } else { focused_text.text.graphemes(true).count() as isize }, |i| i as isize);
+                                        let anchor = focused_edit.cursor_position + focused_edit.highlight_vector;
+                                        focused_edit.highlight_vector = if inputs.modifiers.shift() {
+                                            anchor - target
+                                        } else {
+                                            0
+                                        };
+                                        focused_edit.cursor_position = target;
+                                        focused_edit.cursor_blink_timer = 0.0;
+                                    }
                                     VirtualKeyCode::Back => {
                                         if !delete_highlighted(focused_edit, focused_text)
                                             && focused_edit.cursor_position > 0
@@ -214,17 +354,10 @@ impl System for TextEditingInputSystem {
                                         if ctrl_or_cmd(&inputs.modifiers) {
                                             let new_clip = extract_highlighted(focused_edit, focused_text);
                                             if !new_clip.is_empty() {
-                                                match ClipboardContext::new().and_then(
+                                                if let Err(e) = ClipboardContext::new().and_then(
                                                     |mut ctx: ClipboardContext| ctx.set_contents(new_clip),
                                                 ) {
-                                                    Ok(_) => ui_events.single_write(UiEvent::new(
-                                                        UiEventType::ValueChange,
-                                                        *entity,
-                                                    )),
-                                                    Err(e) => error!(
-                                                        "Error occured when cutting to clipboard: {:?}",
-                                                        e
-                                                    ),
+                                                    error!("Error occured when cutting to clipboard: {:?}", e);
                                                 }
                                             }
                                         }
@@ -264,11 +397,6 @@ impl System for TextEditingInputSystem {
                                                     focused_text.text.insert_str(index, &contents);
                                                     focused_edit.cursor_position +=
                                                         contents.graphemes(true).count() as isize;
-
-                                                    ui_events.single_write(UiEvent::new(
-                                                        UiEventType::ValueChange,
-                                                        *entity,
-                                                    ));
                                                 }
                                                 Err(e) => error!(
                                                     "Error occured when pasting contents of clipboard: {:?}",
@@ -298,11 +426,6 @@ impl System for TextEditingInputSystem {
 
                                                         focused_text.text.insert(start_byte, '\n');
                                                         focused_edit.cursor_position += 1;
-
-                                                        ui_events.single_write(UiEvent::new(
-                                                            UiEventType::ValueChange,
-                                                            *entity,
-                                                        ));
                                                     }
                                                 } else {
                                                     ui_events.single_write(UiEvent::new(
@@ -313,10 +436,39 @@ impl System for TextEditingInputSystem {
                                             }
                                         }
                                     }
+                                    VirtualKeyCode::Z => {
+                                        if ctrl_or_cmd(&inputs.modifiers) {
+                                            kind = EditKind::History;
+                                            if inputs.modifiers.shift() {
+                                                history.redo(focused_edit, focused_text);
+                                            } else {
+                                                history.undo(focused_edit, focused_text);
+                                            }
+                                        }
+                                    }
+                                    VirtualKeyCode::Y => {
+                                        if ctrl_or_cmd(&inputs.modifiers) {
+                                            kind = EditKind::History;
+                                            history.redo(focused_edit, focused_text);
+                                        }
+                                    }
                                     _ => {}
                                 },
                                 _ => {}
                             }
+
+                            if focused_text.text != before.text {
+                                if kind == EditKind::History {
+                                    history.typing = false;
+                                } else if validation.map_or(true, |v| v.is_valid(&focused_text.text)) {
+                                    history.record(before, kind);
+                                } else {
+                                    before.restore(focused_edit, focused_text);
+                                    continue;
+                                }
+                                history.text = focused_text.text.clone();
+                                ui_events.single_write(UiEvent::new(UiEventType::ValueChange, *entity));
+                            }
                         }
                     }
 
@@ -398,3 +550,65 @@ fn should_skip_char(input: char) -> bool {
         || ('\u{F0000}'..='\u{FFFFF}').contains(&input)
         || ('\u{100000}'..='\u{10FFFF}').contains(&input)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(
+        edit: &mut TextEditing,
+        text: &mut UiText,
+        history: &mut EditHistory,
+        kind: EditKind,
+        new: &str,
+    ) {
+        history.record(EditSnapshot::new(edit, text), kind);
+        text.text = new.to_string();
+        edit.cursor_position = new.len() as isize;
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut edit = TextEditing::new(20, [0.; 4], [0.; 4], false);
+        let mut text = UiText::default();
+        let mut history = EditHistory::default();
+
+        apply(&mut edit, &mut text, &mut history, EditKind::Typing, "a");
+        apply(&mut edit, &mut text, &mut history, EditKind::Typing, "ab");
+        apply(&mut edit, &mut text, &mut history, EditKind::Other, "");
+        apply(&mut edit, &mut text, &mut history, EditKind::Typing, "c");
+
+        assert!(history.undo(&mut edit, &mut text));
+        assert_eq!(text.text, "");
+        assert!(history.undo(&mut edit, &mut text));
+        assert_eq!(text.text, "ab");
+        assert_eq!(edit.cursor_position, 2);
+        // Successive typed characters are undone together.
+        assert!(history.undo(&mut edit, &mut text));
+        assert_eq!(text.text, "");
+        assert!(!history.undo(&mut edit, &mut text));
+
+        assert!(history.redo(&mut edit, &mut text));
+        assert_eq!(text.text, "ab");
+        apply(&mut edit, &mut text, &mut history, EditKind::Other, "abd");
+        assert!(!history.redo(&mut edit, &mut text));
+    }
+
+    #[test]
+    fn forgets_edits_when_the_text_is_set() {
+        let mut edit = TextEditing::new(20, [0.; 4], [0.; 4], false);
+        let mut text = UiText::default();
+        let mut history = EditHistory::default();
+
+        apply(&mut edit, &mut text, &mut history, EditKind::Other, "a");
+        history.text = text.text.clone();
+        history.sync(&text.text);
+        assert!(!history.undo.is_empty());
+
+        // The text was set by another system.
+        text.text = "b".to_string();
+        history.sync(&text.text);
+        assert!(!history.undo(&mut edit, &mut text));
+        assert_eq!(text.text, "b");
+    }
+}
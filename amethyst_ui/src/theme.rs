@@ -0,0 +1,362 @@
+//! Module for the `UiTheme` asset and the `UiStyle` component.
+
+use std::collections::HashMap;
+
+use amethyst_assets::{
+    distill_importer,
+    distill_importer::{typetag, SerdeImportable},
+    prefab::{legion_prefab, register_component_type, serde_diff, SerdeDiff},
+    register_asset_type, Asset, AssetHandle, AssetProcessorSystem, AssetStorage, Handle,
+    LoadHandle,
+};
+use amethyst_core::{
+    ecs::{Entity, IntoQuery, ParallelRunnable, System, SystemBuilder},
+    transform::Children,
+};
+use amethyst_rendy::{SpriteRender, SpriteSheet, Texture};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use crate::{
+    FontAsset, UiButtonAction, UiButtonActionRetrigger,
+    UiButtonActionType::{SetImage, SetTextColor, UnsetTextColor, UnsetTexture},
+    UiImage, UiText,
+};
+
+/// A set of named styles which widgets refer to with a `UiStyle` component.
+///
+/// Reloading the theme, or pointing a `UiStyle` to another theme, restyles the widgets using it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, TypeUuid, SerdeImportable)]
+#[uuid = "c7c1e6b4-3a4f-4b8e-9a53-6d0f2e1b7a94"]
+pub struct UiTheme {
+    /// The styles of this theme, by name.
+    pub styles: HashMap<String, UiThemeStyle>,
+}
+
+register_asset_type!(UiTheme => UiTheme; AssetProcessorSystem<UiTheme>);
+
+impl Asset for UiTheme {
+    fn name() -> &'static str {
+        "ui::Theme"
+    }
+    type Data = Self;
+}
+
+impl UiTheme {
+    /// Adds a named style to this theme.
+    #[must_use]
+    pub fn with_style<S: ToString>(mut self, name: &S, style: UiThemeStyle) -> Self {
+        self.styles.insert(name.to_string(), style);
+        self
+    }
+}
+
+/// A named style of a `UiTheme`. Properties left to `None` are not changed on the widget.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiThemeStyle {
+    /// The text color.
+    pub text_color: Option<[f32; 4]>,
+    /// The text color when the mouse is hovering over a button.
+    pub hover_text_color: Option<[f32; 4]>,
+    /// The text color when a button is pressed.
+    pub press_text_color: Option<[f32; 4]>,
+    /// The font of the text.
+    pub font: Option<Handle<FontAsset>>,
+    /// The font size of the text.
+    pub font_size: Option<f32>,
+    /// The image of the widget.
+    pub image: Option<UiThemeImage>,
+    /// The image of a button when the mouse is hovering over it.
+    pub hover_image: Option<UiThemeImage>,
+    /// The image of a button when it is pressed.
+    pub press_image: Option<UiThemeImage>,
+}
+
+/// An image of a `UiThemeStyle`, see `UiImage`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum UiThemeImage {
+    /// An image backed by a texture.
+    Texture(Handle<Texture>),
+    /// An image backed by a sprite of a sprite sheet.
+    Sprite {
+        /// The sprite sheet.
+        sprite_sheet: Handle<SpriteSheet>,
+        /// Index of the sprite in the sprite sheet.
+        sprite_number: usize,
+    },
+    /// An image entirely covered by a single solid color, in linear RGBA.
+    SolidColor([f32; 4]),
+}
+
+impl From<&UiThemeImage> for UiImage {
+    fn from(image: &UiThemeImage) -> Self {
+        match image {
+            UiThemeImage::Texture(tex) => UiImage::Texture(tex.clone()),
+            UiThemeImage::Sprite {
+                sprite_sheet,
+                sprite_number,
+            } => {
+                UiImage::Sprite(SpriteRender {
+                    sprite_sheet: sprite_sheet.clone(),
+                    sprite_number: *sprite_number,
+                })
+            }
+            UiThemeImage::SolidColor(color) => UiImage::SolidColor(*color),
+        }
+    }
+}
+
+/// Styles the `UiText` and `UiImage` of this entity using a style of a `UiTheme`.
+///
+/// Text properties are applied to the `UiText` of the entity, or to the `UiText` of its children
+/// when it has none, as is the case for buttons. The hover and press properties of the style are
+/// merged into the `UiButtonActionRetrigger` of the entity, replacing its actions of the same
+/// kind and keeping the others.
+#[derive(Clone, Debug, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "5f0b9c0e-2d6e-4d8a-b1f7-8c3e4a6d2b15"]
+pub struct UiStyle {
+    /// The theme containing the style.
+    #[serde_diff(opaque)]
+    pub theme: Handle<UiTheme>,
+    /// The name of the style in the theme.
+    pub name: String,
+    /// Theme, style name and theme version applied last.
+    #[serde(skip)]
+    #[serde_diff(skip)]
+    applied: Option<(LoadHandle, String, u32)>,
+}
+
+register_component_type!(UiStyle);
+
+impl UiStyle {
+    /// Creates a `UiStyle` using the style `name` of `theme`.
+    pub fn new<S: ToString>(theme: Handle<UiTheme>, name: &S) -> Self {
+        Self {
+            theme,
+            name: name.to_string(),
+            applied: None,
+        }
+    }
+}
+
+/// System applying `UiTheme` styles to entities with a `UiStyle` component.
+///
+/// Styles are applied again whenever the theme is reloaded or the `UiStyle` changes.
+#[derive(Debug, Default)]
+pub struct UiStyleSystem;
+
+impl System for UiStyleSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("UiStyleSystem")
+                .read_resource::<AssetStorage<UiTheme>>()
+                .with_query(<(Entity, &mut UiStyle, Option<&Children>)>::query())
+                .with_query(<&mut UiText>::query())
+                .with_query(<&mut UiImage>::query())
+                .with_query(<&mut UiButtonActionRetrigger>::query())
+                .build(
+                    move |commands, world, themes, (styled, texts, images, retriggers)| {
+                        let (mut styled_world, mut world) = world.split_for_query(styled);
+
+                        styled.for_each_mut(&mut styled_world, |(entity, ui_style, children)| {
+                            let (theme, version) =
+                                match themes.get_asset_with_version(&ui_style.theme) {
+                                    Some(theme) => theme,
+                                    None => return,
+                                };
+                            let key =
+                                (ui_style.theme.load_handle(), ui_style.name.clone(), version);
+                            if ui_style.applied.as_ref() == Some(&key) {
+                                return;
+                            }
+                            ui_style.applied = Some(key);
+
+                            let style = if let Some(style) = theme.styles.get(&ui_style.name) {
+                                style
+                            } else {
+                                log::warn!("Unknown ui style '{}'", ui_style.name);
+                                return;
+                            };
+
+                            let text_entities = if texts.get_mut(&mut world, *entity).is_ok() {
+                                vec![*entity]
+                            } else {
+                                children.map_or_else(Vec::new, |c| c.0.to_vec())
+                            };
+                            for text_entity in text_entities {
+                                if let Ok(text) = texts.get_mut(&mut world, text_entity) {
+                                    apply_text_style(style, text);
+                                }
+                            }
+
+                            if let Some(image) = &style.image {
+                                if let Ok(current) = images.get_mut(&mut world, *entity) {
+                                    *current = image.into();
+                                } else {
+                                    commands.add_component(*entity, UiImage::from(image));
+                                }
+                            }
+
+                            if let Ok(current) = retriggers.get_mut(&mut world, *entity) {
+                                merge_button_retrigger(style, *entity, current);
+                            } else if has_button_actions(style) {
+                                let mut retrigger = UiButtonActionRetrigger::default();
+                                merge_button_retrigger(style, *entity, &mut retrigger);
+                                commands.add_component(*entity, retrigger);
+                            }
+                        });
+                    },
+                ),
+        )
+    }
+}
+
+fn apply_text_style(style: &UiThemeStyle, text: &mut UiText) {
+    if let Some(color) = style.text_color {
+        text.color = color;
+    }
+    if let Some(font) = &style.font {
+        text.font = Some(font.clone());
+    }
+    if let Some(font_size) = style.font_size {
+        text.font_size = font_size;
+    }
+}
+
+fn has_button_actions(style: &UiThemeStyle) -> bool {
+    style.hover_text_color.is_some()
+        || style.hover_image.is_some()
+        || style.press_text_color.is_some()
+        || style.press_image.is_some()
+}
+
+/// Replaces the actions of `retrigger` of the kinds set by the hover and press properties of
+/// `style` with the actions of the style.
+fn merge_button_retrigger(
+    style: &UiThemeStyle,
+    target: Entity,
+    retrigger: &mut UiButtonActionRetrigger,
+) {
+    let action = |event_type| UiButtonAction { target, event_type };
+    let is_color =
+        |action: &UiButtonAction| matches!(action.event_type, SetTextColor(_) | UnsetTextColor(_));
+    let is_image =
+        |action: &UiButtonAction| matches!(action.event_type, SetImage(_) | UnsetTexture(_));
+
+    if let Some(color) = style.hover_text_color {
+        retrigger.on_hover_start.retain(|a| !is_color(a));
+        retrigger.on_hover_stop.retain(|a| !is_color(a));
+        retrigger.on_hover_start.push(action(SetTextColor(color)));
+        retrigger.on_hover_stop.push(action(UnsetTextColor(color)));
+    }
+    if let Some(image) = &style.hover_image {
+        retrigger.on_hover_start.retain(|a| !is_image(a));
+        retrigger.on_hover_stop.retain(|a| !is_image(a));
+        retrigger
+            .on_hover_start
+            .push(action(SetImage(image.into())));
+        retrigger
+            .on_hover_stop
+            .push(action(UnsetTexture(image.into())));
+    }
+    if let Some(color) = style.press_text_color {
+        retrigger.on_click_start.retain(|a| !is_color(a));
+        retrigger.on_click_stop.retain(|a| !is_color(a));
+        retrigger.on_click_start.push(action(SetTextColor(color)));
+        retrigger.on_click_stop.push(action(UnsetTextColor(color)));
+    }
+    if let Some(image) = &style.press_image {
+        retrigger.on_click_start.retain(|a| !is_image(a));
+        retrigger.on_click_stop.retain(|a| !is_image(a));
+        retrigger
+            .on_click_start
+            .push(action(SetImage(image.into())));
+        retrigger
+            .on_click_stop
+            .push(action(UnsetTexture(image.into())));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::World;
+
+    use super::*;
+    use crate::{Anchor, LineMode};
+
+    fn text() -> UiText {
+        UiText::new(
+            None,
+            "Play".to_string(),
+            [1.; 4],
+            10.,
+            LineMode::Single,
+            Anchor::Middle,
+        )
+    }
+
+    #[test]
+    fn applies_set_text_properties() {
+        let mut text = text();
+        let style = UiThemeStyle {
+            text_color: Some([0.5, 0., 0., 1.]),
+            font_size: Some(24.),
+            ..Default::default()
+        };
+
+        apply_text_style(&style, &mut text);
+        assert_eq!(text.color, [0.5, 0., 0., 1.]);
+        assert!((text.font_size - 24.).abs() < f32::EPSILON);
+        assert!(text.font.is_none());
+    }
+
+    #[test]
+    fn merges_button_actions() {
+        let mut world = World::default();
+        let target = world.push(());
+
+        let mut retrigger = UiButtonActionRetrigger::default();
+        retrigger.on_hover_start.push(UiButtonAction {
+            target,
+            event_type: SetTextColor([1., 0., 0., 1.]),
+        });
+        retrigger.on_hover_start.push(UiButtonAction {
+            target,
+            event_type: SetImage(UiImage::SolidColor([0., 0., 1., 1.])),
+        });
+        retrigger.on_click_start.push(UiButtonAction {
+            target,
+            event_type: SetTextColor([0., 1., 0., 1.]),
+        });
+
+        let style = UiThemeStyle {
+            hover_text_color: Some([1., 1., 0., 1.]),
+            ..Default::default()
+        };
+        assert!(has_button_actions(&style));
+        merge_button_retrigger(&style, target, &mut retrigger);
+        merge_button_retrigger(&style, target, &mut retrigger);
+
+        assert_eq!(retrigger.on_hover_start.len(), 2);
+        assert!(retrigger
+            .on_hover_start
+            .iter()
+            .any(|action| { matches!(action.event_type, SetImage(UiImage::SolidColor(_))) }));
+        assert!(retrigger.on_hover_start.iter().any(|action| {
+            matches!(action.event_type, SetTextColor(color) if color == [1., 1., 0., 1.])
+        }));
+        assert_eq!(retrigger.on_hover_stop.len(), 1);
+        assert_eq!(retrigger.on_click_start.len(), 1);
+    }
+
+    #[test]
+    fn converts_theme_images() {
+        let image = UiThemeImage::SolidColor([0.2, 0.4, 0.6, 1.]);
+        assert_eq!(
+            UiImage::from(&image),
+            UiImage::SolidColor([0.2, 0.4, 0.6, 1.])
+        );
+        assert!(!has_button_actions(&UiThemeStyle::default()));
+    }
+}
@@ -0,0 +1,298 @@
+//! Module for the `UiTooltip` component and `UiTooltipSystem`.
+
+use std::collections::HashMap;
+
+use amethyst_assets::Handle;
+use amethyst_core::{
+    ecs::{Entity, IntoQuery, ParallelRunnable, System, SystemBuilder},
+    shrev::{EventChannel, ReaderId},
+    Time,
+};
+use amethyst_input::InputHandler;
+use amethyst_window::ScreenDimensions;
+
+use crate::{
+    Anchor, FontAsset, LineMode, UiEvent, UiEventType, UiImage, UiScale, UiStyle, UiText, UiTheme,
+    UiTransform,
+};
+
+const TOOLTIP_Z: f32 = 1000.;
+const DEFAULT_DELAY: f32 = 0.5;
+const DEFAULT_WIDTH: f32 = 200.;
+const DEFAULT_HEIGHT: f32 = 32.;
+const DEFAULT_OFFSET: [f32; 2] = [12., 16.];
+const DEFAULT_TXT_COLOR: [f32; 4] = [1., 1., 1., 1.];
+const DEFAULT_BKGD_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 0.9];
+
+/// Shows a label near the cursor after the entity this is attached to was hovered for `delay`
+/// seconds. The label is deleted as soon as the hover stops, or when the entity is deleted.
+///
+/// The entity needs an `Interactable` component to receive hover events.
+#[derive(Debug, Clone)]
+pub struct UiTooltip {
+    /// The text of the label.
+    pub text: String,
+    /// Hover duration before the label is shown, in seconds.
+    pub delay: f32,
+    /// Width of the label.
+    pub width: f32,
+    /// Height of the label.
+    pub height: f32,
+    /// Offset of the top left corner of the label from the cursor, right and down.
+    pub offset: [f32; 2],
+    /// Font of the label, `None` uses the default font.
+    pub font: Option<Handle<FontAsset>>,
+    /// Font size of the label.
+    pub font_size: f32,
+    /// Text color of the label.
+    pub text_color: [f32; 4],
+    /// Background color of the label.
+    pub background_color: [f32; 4],
+    /// Style applied to the label, over the properties above.
+    pub style: Option<UiStyle>,
+}
+
+impl UiTooltip {
+    /// Creates a tooltip showing `text`.
+    pub fn new<S: ToString>(text: &S) -> Self {
+        Self {
+            text: text.to_string(),
+            delay: DEFAULT_DELAY,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            offset: DEFAULT_OFFSET,
+            font: None,
+            font_size: 16.,
+            text_color: DEFAULT_TXT_COLOR,
+            background_color: DEFAULT_BKGD_COLOR,
+            style: None,
+        }
+    }
+
+    /// Set the hover duration before the label is shown, in seconds.
+    #[must_use]
+    pub fn with_delay(mut self, delay: f32) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Set the size of the label.
+    #[must_use]
+    pub fn with_size(mut self, width: f32, height: f32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Style the label with the style `name` of `theme`.
+    #[must_use]
+    pub fn with_style<S: ToString>(mut self, theme: Handle<UiTheme>, name: &S) -> Self {
+        self.style = Some(UiStyle::new(theme, name));
+        self
+    }
+}
+
+/// Returns the center of a popup of `size` shown at `offset` from the cursor, kept inside the
+/// screen. The cursor is in physical pixels, the rest is in ui units of `factor` pixels.
+pub(crate) fn popup_position(
+    cursor: (f32, f32),
+    offset: [f32; 2],
+    size: (f32, f32),
+    screen: &ScreenDimensions,
+    factor: f32,
+) -> (f32, f32) {
+    let (width, height) = size;
+    let x = (cursor.0 / factor + offset[0] + width * 0.5)
+        .min(screen.width() / factor - width * 0.5)
+        .max(width * 0.5);
+    let y = (cursor.1 / factor - offset[1] - height * 0.5)
+        .min(screen.height() / factor - height * 0.5)
+        .max(height * 0.5);
+    (x, y)
+}
+
+/// Removes the labels of the owners which are not `alive` from `shown`, returning them.
+fn remove_orphans<F>(shown: &mut HashMap<Entity, Entity>, mut alive: F) -> Vec<Entity>
+where
+    F: FnMut(&Entity) -> bool,
+{
+    let orphans: Vec<Entity> = shown
+        .keys()
+        .filter(|owner| !alive(owner))
+        .copied()
+        .collect();
+    orphans
+        .iter()
+        .filter_map(|owner| shown.remove(owner))
+        .collect()
+}
+
+/// System showing and hiding the labels of `UiTooltip` components.
+///
+/// It's automatically registered with the `UiBundle`.
+#[derive(Debug)]
+pub struct UiTooltipSystem {
+    event_reader: ReaderId<UiEvent>,
+    /// Hovered entities with the time they have been hovered for.
+    hovered: HashMap<Entity, f32>,
+    /// Hovered entities with their visible label.
+    shown: HashMap<Entity, Entity>,
+}
+
+impl UiTooltipSystem {
+    /// Creates a new `UiTooltipSystem`.
+    pub fn new(event_reader: ReaderId<UiEvent>) -> Self {
+        Self {
+            event_reader,
+            hovered: HashMap::new(),
+            shown: HashMap::new(),
+        }
+    }
+}
+
+impl System for UiTooltipSystem {
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("UiTooltipSystem")
+                .read_resource::<EventChannel<UiEvent>>()
+                .read_resource::<Time>()
+                .read_resource::<InputHandler>()
+                .read_resource::<ScreenDimensions>()
+                .read_resource::<UiScale>()
+                .with_query(<&UiTooltip>::query())
+                .with_query(<&mut UiTransform>::query())
+                .build(
+                    move |commands,
+                          world,
+                          (events, time, input, screen_dimensions, ui_scale),
+                          (tooltips, transforms)| {
+                        for event in events.read(&mut self.event_reader) {
+                            match event.event_type {
+                                UiEventType::HoverStart => {
+                                    if tooltips.get(world, event.target).is_ok() {
+                                        self.hovered.insert(event.target, 0.);
+                                    }
+                                }
+                                UiEventType::HoverStop => {
+                                    self.hovered.remove(&event.target);
+                                    if let Some(label) = self.shown.remove(&event.target) {
+                                        commands.remove(label);
+                                    }
+                                }
+                                _ => {}
+                            }
+                        }
+
+                        // Forget the entities which were deleted or lost their tooltip, and
+                        // delete their label.
+                        self.hovered
+                            .retain(|owner, _| tooltips.get(world, *owner).is_ok());
+                        for label in remove_orphans(&mut self.shown, |owner| {
+                            tooltips.get(world, *owner).is_ok()
+                        }) {
+                            commands.remove(label);
+                        }
+
+                        let cursor = match input.mouse_position() {
+                            Some((x, y)) => (x, screen_dimensions.height() - y),
+                            None => return,
+                        };
+
+                        let delta = time.delta_real_time().as_secs_f32();
+                        for (owner, timer) in &mut self.hovered {
+                            *timer += delta;
+
+                            let tooltip = match tooltips.get(world, *owner) {
+                                Ok(tooltip) => tooltip.clone(),
+                                Err(_) => continue,
+                            };
+                            let (x, y) = popup_position(
+                                cursor,
+                                tooltip.offset,
+                                (tooltip.width, tooltip.height),
+                                screen_dimensions,
+                                ui_scale.factor(screen_dimensions),
+                            );
+
+                            if let Some(label) = self.shown.get(owner) {
+                                if let Ok(transform) = transforms.get_mut(world, *label) {
+                                    transform.local_x = x;
+                                    transform.local_y = y;
+                                }
+                            } else if *timer >= tooltip.delay {
+                                let transform = UiTransform::new(
+                                    "tooltip".to_string(),
+                                    Anchor::BottomLeft,
+                                    Anchor::Middle,
+                                    x,
+                                    y,
+                                    TOOLTIP_Z,
+                                    tooltip.width,
+                                    tooltip.height,
+                                )
+                                .into_transparent();
+                                let text = UiText::new(
+                                    tooltip.font,
+                                    tooltip.text,
+                                    tooltip.text_color,
+                                    tooltip.font_size,
+                                    LineMode::Wrap,
+                                    Anchor::Middle,
+                                );
+                                let image = UiImage::SolidColor(tooltip.background_color);
+                                let label = if let Some(style) = tooltip.style {
+                                    commands.push((transform, text, image, style))
+                                } else {
+                                    commands.push((transform, text, image))
+                                };
+                                self.shown.insert(*owner, label);
+                            }
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::World;
+
+    use super::*;
+
+    #[test]
+    fn keeps_popups_inside_the_screen() {
+        let screen = ScreenDimensions::new(800, 600);
+
+        let (x, y) = popup_position((100., 300.), [12., 16.], (200., 32.), &screen, 1.);
+        assert!((x - 212.).abs() < f32::EPSILON);
+        assert!((y - 268.).abs() < f32::EPSILON);
+
+        // Near the bottom right corner, the popup is moved up and left.
+        let (x, y) = popup_position((790., 10.), [12., 16.], (200., 32.), &screen, 1.);
+        assert!((x - 700.).abs() < f32::EPSILON);
+        assert!((y - 16.).abs() < f32::EPSILON);
+
+        // The cursor is in physical pixels, the popup in ui units.
+        let (x, y) = popup_position((100., 300.), [12., 16.], (200., 32.), &screen, 2.);
+        assert!((x - 162.).abs() < f32::EPSILON);
+        assert!((y - 118.).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn removes_labels_of_deleted_owners() {
+        let mut world = World::default();
+        let (kept, deleted) = (world.push(()), world.push(()));
+        let (kept_label, deleted_label) = (world.push(()), world.push(()));
+        world.remove(deleted);
+
+        let mut shown = HashMap::new();
+        shown.insert(kept, kept_label);
+        shown.insert(deleted, deleted_label);
+
+        let orphans = remove_orphans(&mut shown, |owner| world.contains(*owner));
+        assert_eq!(orphans, vec![deleted_label]);
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown.get(&kept), Some(&kept_label));
+    }
+}
@@ -0,0 +1,653 @@
+//! Module for the `Tween` component and `UiTweenSystem`.
+
+use std::f32::consts::PI;
+
+use amethyst_core::{
+    ecs::{Entity, IntoQuery, ParallelRunnable, System, SystemBuilder},
+    shrev::EventChannel,
+    Time,
+};
+use amethyst_rendy::{palette::Srgba, resources::Tint};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{UiEvent, UiEventType, UiImage, UiText, UiTransform};
+
+/// An easing function, mapping the linear progress of a `Tween` to the progress of its values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Easing {
+    /// Constant speed.
+    Linear,
+    /// Quadratic, accelerating from zero velocity.
+    QuadIn,
+    /// Quadratic, decelerating to zero velocity.
+    QuadOut,
+    /// Quadratic, accelerating then decelerating.
+    QuadInOut,
+    /// Cubic, accelerating from zero velocity.
+    CubicIn,
+    /// Cubic, decelerating to zero velocity.
+    CubicOut,
+    /// Cubic, accelerating then decelerating.
+    CubicInOut,
+    /// Sinusoidal, accelerating from zero velocity.
+    SineIn,
+    /// Sinusoidal, decelerating to zero velocity.
+    SineOut,
+    /// Sinusoidal, accelerating then decelerating.
+    SineInOut,
+    /// Exponential, accelerating from zero velocity.
+    ExpoIn,
+    /// Exponential, decelerating to zero velocity.
+    ExpoOut,
+    /// Backs up slightly before moving towards the end.
+    BackIn,
+    /// Overshoots the end slightly before settling.
+    BackOut,
+    /// Oscillates around the end before settling, as a spring.
+    ElasticOut,
+    /// Bounces against the end before settling.
+    BounceOut,
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Easing::Linear
+    }
+}
+
+impl Easing {
+    /// Returns the eased progress for the linear progress `t`, from `0.` to `1.`.
+    /// The result is `0.` at `0.` and `1.` at `1.`, but may leave that range in between.
+    #[must_use]
+    pub fn apply(self, t: f32) -> f32 {
+        const BACK: f32 = 1.701_58;
+
+        let t = t.max(0.).min(1.);
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => t * (2. - t),
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    2. * t * t
+                } else {
+                    1. - (-2. * t + 2.).powi(2) * 0.5
+                }
+            }
+            Easing::CubicIn => t.powi(3),
+            Easing::CubicOut => 1. - (1. - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4. * t.powi(3)
+                } else {
+                    1. - (-2. * t + 2.).powi(3) * 0.5
+                }
+            }
+            Easing::SineIn => 1. - (t * PI * 0.5).cos(),
+            Easing::SineOut => (t * PI * 0.5).sin(),
+            Easing::SineInOut => (1. - (t * PI).cos()) * 0.5,
+            Easing::ExpoIn if t <= 0. => 0.,
+            Easing::ExpoIn => 2_f32.powf(10. * t - 10.),
+            Easing::ExpoOut if t >= 1. => 1.,
+            Easing::ExpoOut => 1. - 2_f32.powf(-10. * t),
+            Easing::BackIn => t * t * ((BACK + 1.) * t - BACK),
+            Easing::BackOut => {
+                let t = t - 1.;
+                1. + t * t * ((BACK + 1.) * t + BACK)
+            }
+            Easing::ElasticOut if t <= 0. || t >= 1. => t,
+            Easing::ElasticOut => {
+                2_f32.powf(-10. * t) * ((t * 10. - 0.75) * (2. * PI / 3.)).sin() + 1.
+            }
+            Easing::BounceOut => bounce_out(t),
+        }
+    }
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+
+    if t < 1. / D {
+        N * t * t
+    } else if t < 2. / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984_375
+    }
+}
+
+/// A value animated by a `Tween`.
+///
+/// A `from` of `None` is replaced by the current value when the tween starts, after its delay.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum TweenProperty {
+    /// The `local_x` and `local_y` of the `UiTransform`.
+    Position {
+        /// Position at the start.
+        from: Option<[f32; 2]>,
+        /// Position at the end.
+        to: [f32; 2],
+    },
+    /// The `width` and `height` of the `UiTransform`.
+    Size {
+        /// Size at the start.
+        from: Option<[f32; 2]>,
+        /// Size at the end.
+        to: [f32; 2],
+    },
+    /// The alpha of the `UiText` color, and the alpha of the `UiImage` color.
+    ///
+    /// The alpha of images which are not a solid color is set through their `Tint`, which is
+    /// added if missing.
+    Opacity {
+        /// Opacity at the start.
+        from: Option<f32>,
+        /// Opacity at the end.
+        to: f32,
+    },
+    /// The color of the `UiText`.
+    TextColor {
+        /// Color at the start.
+        from: Option<[f32; 4]>,
+        /// Color at the end.
+        to: [f32; 4],
+    },
+    /// The color of a `UiImage::SolidColor`, or the `Tint` of other images, which is added if
+    /// missing. The `Tint` also applies to the text of the entity.
+    ImageColor {
+        /// Color at the start.
+        from: Option<[f32; 4]>,
+        /// Color at the end.
+        to: [f32; 4],
+    },
+}
+
+/// How many times a `Tween` is played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum TweenLoop {
+    /// Played once.
+    Once,
+    /// Played the given number of times. With `yoyo`, going back counts as a play.
+    Count(u32),
+    /// Played until the component is removed.
+    Forever,
+}
+
+/// # Tween Component
+/// Animates the `UiTransform`, `UiText` and `UiImage` of the entity this is attached to from
+/// their current values, without authoring an `Animation` asset.
+///
+/// ```rust
+/// use amethyst::ui::{Easing, Tween};
+///
+/// // Slides in while fading in, once the menu opened.
+/// let tween = Tween::new(0.3)
+///     .move_from_to([-200., 0.], [0., 0.])
+///     .fade_from_to(0., 1.)
+///     .with_easing(Easing::CubicOut)
+///     .with_delay(0.1);
+/// ```
+///
+/// A `UiEventType::TweenCompleted` event is emitted once the tween has played as many times as
+/// its `TweenLoop` requires. The component is kept, `restart` plays it again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tween {
+    /// The animated values.
+    pub properties: Vec<TweenProperty>,
+    /// Duration of a play, in seconds.
+    pub duration: f32,
+    /// Delay before the first play, in seconds.
+    pub delay: f32,
+    /// Easing of the progress.
+    pub easing: Easing,
+    /// How many times the tween is played.
+    pub looping: TweenLoop,
+    /// Whether every other play goes back from the end to the start.
+    pub yoyo: bool,
+    /// Whether to use the scaled or unscaled time.
+    pub absolute_time: bool,
+    elapsed: f32,
+    started: bool,
+    complete: bool,
+}
+
+impl Tween {
+    /// Creates a `Tween` played once over `duration` seconds, animating nothing yet.
+    #[must_use]
+    pub fn new(duration: f32) -> Self {
+        Self {
+            properties: Vec::new(),
+            duration,
+            delay: 0.,
+            easing: Easing::Linear,
+            looping: TweenLoop::Once,
+            yoyo: false,
+            absolute_time: false,
+            elapsed: 0.,
+            started: false,
+            complete: false,
+        }
+    }
+
+    /// Adds an animated value.
+    #[must_use]
+    pub fn with_property(mut self, property: TweenProperty) -> Self {
+        self.properties.push(property);
+        self
+    }
+
+    /// Moves the element from its current position to `to`.
+    #[must_use]
+    pub fn move_to(self, to: [f32; 2]) -> Self {
+        self.with_property(TweenProperty::Position { from: None, to })
+    }
+
+    /// Moves the element from `from` to `to`.
+    #[must_use]
+    pub fn move_from_to(self, from: [f32; 2], to: [f32; 2]) -> Self {
+        self.with_property(TweenProperty::Position {
+            from: Some(from),
+            to,
+        })
+    }
+
+    /// Resizes the element from its current size to `to`.
+    #[must_use]
+    pub fn resize_to(self, to: [f32; 2]) -> Self {
+        self.with_property(TweenProperty::Size { from: None, to })
+    }
+
+    /// Resizes the element from `from` to `to`.
+    #[must_use]
+    pub fn resize_from_to(self, from: [f32; 2], to: [f32; 2]) -> Self {
+        self.with_property(TweenProperty::Size {
+            from: Some(from),
+            to,
+        })
+    }
+
+    /// Fades the element from its current opacity to `to`.
+    #[must_use]
+    pub fn fade_to(self, to: f32) -> Self {
+        self.with_property(TweenProperty::Opacity { from: None, to })
+    }
+
+    /// Fades the element from `from` to `to`.
+    #[must_use]
+    pub fn fade_from_to(self, from: f32, to: f32) -> Self {
+        self.with_property(TweenProperty::Opacity {
+            from: Some(from),
+            to,
+        })
+    }
+
+    /// Changes the color of the `UiText` from its current color to `to`.
+    #[must_use]
+    pub fn text_color_to(self, to: [f32; 4]) -> Self {
+        self.with_property(TweenProperty::TextColor { from: None, to })
+    }
+
+    /// Changes the color of the `UiImage` from its current color to `to`.
+    #[must_use]
+    pub fn image_color_to(self, to: [f32; 4]) -> Self {
+        self.with_property(TweenProperty::ImageColor { from: None, to })
+    }
+
+    /// Sets the delay before the first play, in seconds.
+    #[must_use]
+    pub fn with_delay(mut self, delay: f32) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Sets the easing of the progress.
+    #[must_use]
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Sets how many times the tween is played.
+    #[must_use]
+    pub fn with_loop(mut self, looping: TweenLoop) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Makes every other play go back from the end to the start.
+    #[must_use]
+    pub fn with_yoyo(mut self) -> Self {
+        self.yoyo = true;
+        self
+    }
+
+    /// Uses the unscaled time, e.g. to keep animating a pause menu while the game is paused.
+    #[must_use]
+    pub fn with_absolute_time(mut self) -> Self {
+        self.absolute_time = true;
+        self
+    }
+
+    /// Plays the tween again, from the delay. Start values which were taken from the element
+    /// are kept.
+    pub fn restart(&mut self) {
+        self.elapsed = 0.;
+        self.complete = false;
+    }
+
+    /// Returns true once the tween has played as many times as its `TweenLoop` requires.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Advances the tween by `delta` seconds.
+    /// Returns the eased progress, or `None` while the tween is delayed or complete, and whether
+    /// the tween completed during this update.
+    #[allow(clippy::cast_precision_loss)]
+    fn advance(&mut self, delta: f32) -> (Option<f32>, bool) {
+        if self.complete {
+            return (None, false);
+        }
+
+        self.elapsed += delta;
+        let time = self.elapsed - self.delay;
+        if time < 0. {
+            return (None, false);
+        }
+
+        let duration = self.duration.max(f32::EPSILON);
+        let plays = match self.looping {
+            TweenLoop::Once => Some(1),
+            TweenLoop::Count(count) => Some(count.max(1)),
+            TweenLoop::Forever => None,
+        };
+        let (play, progress) = match plays {
+            Some(plays) if time >= duration * plays as f32 => {
+                self.complete = true;
+                (plays - 1, 1.)
+            }
+            _ => {
+                let play = (time / duration).floor();
+                if plays.is_none() {
+                    // Keeps the elapsed time small, an even number of plays keeps the direction.
+                    self.elapsed -= (play - play % 2.) * duration;
+                }
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let parity = play as u32 % 2;
+                (parity, (time / duration).fract())
+            }
+        };
+
+        let progress = if self.yoyo && play % 2 == 1 {
+            1. - progress
+        } else {
+            progress
+        };
+        (Some(self.easing.apply(progress)), self.complete)
+    }
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+fn lerp_color(from: [f32; 4], to: [f32; 4], t: f32) -> [f32; 4] {
+    [
+        lerp(from[0], to[0], t),
+        lerp(from[1], to[1], t),
+        lerp(from[2], to[2], t),
+        lerp(from[3], to[3], t),
+    ]
+}
+
+fn tint_color(tint: &Tint) -> [f32; 4] {
+    let (r, g, b, a) = tint.0.into_components();
+    [r, g, b, a]
+}
+
+/// The elements animated by a `Tween`. The `Tint` is added by the system if it's missing.
+struct TweenTargets<'a> {
+    transform: Option<&'a mut UiTransform>,
+    text: Option<&'a mut UiText>,
+    image: Option<&'a mut UiImage>,
+    tint: Option<[f32; 4]>,
+}
+
+impl TweenTargets<'_> {
+    fn image_color(&self) -> Option<[f32; 4]> {
+        match self.image.as_deref() {
+            Some(UiImage::SolidColor(color)) => Some(*color),
+            Some(_) => Some(self.tint.unwrap_or([1., 1., 1., 1.])),
+            None => None,
+        }
+    }
+
+    fn set_image_color(&mut self, color: [f32; 4]) {
+        match self.image.as_deref_mut() {
+            Some(UiImage::SolidColor(image_color)) => *image_color = color,
+            Some(_) => self.tint = Some(color),
+            None => {}
+        }
+    }
+
+    /// Replaces the missing start value of `property` by the current value.
+    fn capture(&self, property: &mut TweenProperty) {
+        match property {
+            TweenProperty::Position { from, .. } => {
+                *from = from.or_else(|| {
+                    let transform = self.transform.as_deref()?;
+                    Some([transform.local_x, transform.local_y])
+                });
+            }
+            TweenProperty::Size { from, .. } => {
+                *from = from.or_else(|| {
+                    let transform = self.transform.as_deref()?;
+                    Some([transform.width, transform.height])
+                });
+            }
+            TweenProperty::Opacity { from, .. } => {
+                *from = from.or_else(|| {
+                    self.text
+                        .as_deref()
+                        .map(|text| text.color[3])
+                        .or_else(|| self.image_color().map(|color| color[3]))
+                });
+            }
+            TweenProperty::TextColor { from, .. } => {
+                *from = from.or_else(|| self.text.as_deref().map(|text| text.color));
+            }
+            TweenProperty::ImageColor { from, .. } => {
+                *from = from.or_else(|| self.image_color());
+            }
+        }
+    }
+
+    /// Sets the value of `property` at the eased progress `t`.
+    fn apply(&mut self, property: &TweenProperty, t: f32) {
+        match *property {
+            TweenProperty::Position {
+                from: Some(from),
+                to,
+            } => {
+                if let Some(transform) = self.transform.as_deref_mut() {
+                    transform.local_x = lerp(from[0], to[0], t);
+                    transform.local_y = lerp(from[1], to[1], t);
+                }
+            }
+            TweenProperty::Size {
+                from: Some(from),
+                to,
+            } => {
+                if let Some(transform) = self.transform.as_deref_mut() {
+                    transform.width = lerp(from[0], to[0], t);
+                    transform.height = lerp(from[1], to[1], t);
+                }
+            }
+            TweenProperty::Opacity {
+                from: Some(from),
+                to,
+            } => {
+                let alpha = lerp(from, to, t);
+                if let Some(text) = self.text.as_deref_mut() {
+                    text.color[3] = alpha;
+                }
+                if let Some(mut color) = self.image_color() {
+                    color[3] = alpha;
+                    self.set_image_color(color);
+                }
+            }
+            TweenProperty::TextColor {
+                from: Some(from),
+                to,
+            } => {
+                if let Some(text) = self.text.as_deref_mut() {
+                    text.color = lerp_color(from, to, t);
+                }
+            }
+            TweenProperty::ImageColor {
+                from: Some(from),
+                to,
+            } => self.set_image_color(lerp_color(from, to, t)),
+            // The element had no such value when the tween started.
+            _ => {}
+        }
+    }
+}
+
+/// System updating the `Tween` components.
+///
+/// It's automatically registered with the `UiBundle`.
+#[derive(Debug, Default)]
+pub struct UiTweenSystem;
+
+impl System for UiTweenSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("UiTweenSystem")
+                .read_resource::<Time>()
+                .write_resource::<EventChannel<UiEvent>>()
+                .with_query(<(
+                    Entity,
+                    &mut Tween,
+                    Option<&mut UiTransform>,
+                    Option<&mut UiText>,
+                    Option<&mut UiImage>,
+                    Option<&mut Tint>,
+                )>::query())
+                .build(move |commands, world, (time, events), tweens| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("ui_tween_system");
+
+                    let abs_sec = time.delta_time().as_secs_f32();
+                    let abs_unscaled_sec = time.delta_real_time().as_secs_f32();
+
+                    let mut completed = Vec::new();
+                    tweens.for_each_mut(world, |(entity, tween, transform, text, image, tint)| {
+                        let delta = if tween.absolute_time {
+                            abs_unscaled_sec
+                        } else {
+                            abs_sec
+                        };
+                        let (progress, complete) = tween.advance(delta);
+                        let progress = match progress {
+                            Some(progress) => progress,
+                            None => return,
+                        };
+
+                        let tint_before = tint.as_deref().map(tint_color);
+                        let mut targets = TweenTargets {
+                            transform,
+                            text,
+                            image,
+                            tint: tint_before,
+                        };
+                        if !tween.started {
+                            tween.started = true;
+                            for property in &mut tween.properties {
+                                targets.capture(property);
+                            }
+                        }
+                        for property in &tween.properties {
+                            targets.apply(property, progress);
+                        }
+
+                        if targets.tint != tint_before {
+                            if let Some([r, g, b, a]) = targets.tint {
+                                let color = Srgba::new(r, g, b, a);
+                                match tint {
+                                    Some(tint) => tint.0 = color,
+                                    None => commands.add_component(*entity, Tint(color)),
+                                }
+                            }
+                        }
+                        if complete {
+                            completed.push(UiEvent::new(UiEventType::TweenCompleted, *entity));
+                        }
+                    });
+                    events.iter_write(completed);
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easings_start_and_end() {
+        for easing in &[
+            Easing::Linear,
+            Easing::QuadInOut,
+            Easing::CubicOut,
+            Easing::SineInOut,
+            Easing::ExpoIn,
+            Easing::BackOut,
+            Easing::ElasticOut,
+            Easing::BounceOut,
+        ] {
+            assert!(easing.apply(0.).abs() < 0.001, "{:?}", easing);
+            assert!((easing.apply(1.) - 1.).abs() < 0.001, "{:?}", easing);
+        }
+        assert!(Easing::QuadIn.apply(0.5) < 0.5);
+        assert!(Easing::QuadOut.apply(0.5) > 0.5);
+    }
+
+    #[test]
+    fn delay_and_completion() {
+        let mut tween = Tween::new(1.).with_delay(0.5);
+        assert_eq!(tween.advance(0.25), (None, false));
+        assert_eq!(tween.advance(0.75), (Some(0.5), false));
+        assert_eq!(tween.advance(1.), (Some(1.), true));
+        assert!(tween.is_complete());
+        assert_eq!(tween.advance(1.), (None, false));
+
+        tween.restart();
+        assert_eq!(tween.advance(0.5), (Some(0.), false));
+    }
+
+    #[test]
+    fn loops_and_yoyo() {
+        let mut tween = Tween::new(1.).with_loop(TweenLoop::Count(2)).with_yoyo();
+        assert_eq!(tween.advance(0.25), (Some(0.25), false));
+        assert_eq!(tween.advance(1.), (Some(0.75), false));
+        assert_eq!(tween.advance(1.), (Some(0.), true));
+
+        let mut tween = Tween::new(1.).with_loop(TweenLoop::Forever).with_yoyo();
+        for _ in 0..10 {
+            assert!(!tween.advance(1.).1);
+        }
+        assert_eq!(tween.advance(0.5), (Some(0.5), false));
+        assert_eq!(tween.advance(1.), (Some(0.5), false));
+        assert!(tween.elapsed < 4.);
+    }
+}
@@ -93,6 +93,11 @@ where
         self.items.insert(id, widget)
     }
 
+    /// Removes a widget by its ID, returning it if it existed.
+    pub fn remove(&mut self, id: &I) -> Option<T> {
+        self.items.remove(id)
+    }
+
     /// Retrieves a widget by its ID.
     pub fn get(&self, id: &I) -> Option<&T> {
         self.items.get(id)
@@ -0,0 +1,342 @@
+//! Module for the `UiWorldAnchor` component and `UiWorldAnchorSystem`.
+
+use std::collections::{HashMap, HashSet};
+
+use amethyst_core::{
+    ecs::{component, Entity, IntoQuery, ParallelRunnable, System, SystemBuilder},
+    math::{Point3, Vector2, Vector3},
+    transform::{Parent, Transform},
+    HiddenPropagate,
+};
+use amethyst_rendy::camera::{ActiveCamera, Camera};
+use amethyst_window::ScreenDimensions;
+
+use crate::{ScaleMode, UiScale, UiTransform};
+
+/// Scales an element anchored with a `UiWorldAnchor` with its distance to the camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DistanceScaling {
+    /// Distance at which the element has the size of its `UiTransform`.
+    /// The element is scaled by `reference_distance / distance`.
+    pub reference_distance: f32,
+    /// The smallest scale applied.
+    pub min_scale: f32,
+    /// The largest scale applied.
+    pub max_scale: f32,
+}
+
+/// Makes an ui element follow an entity of the world, such as a health bar following a
+/// character.
+///
+/// The position of the entity is projected through the active camera, or the first camera if
+/// there is no active camera. The element is hidden with `HiddenPropagate` while the entity is
+/// behind the camera or the element is off-screen, and shown again once it's visible, unless it
+/// was already hidden by something else.
+///
+/// The local position of the element is set relative to its parent, in the units of its
+/// `ScaleMode`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UiWorldAnchor {
+    /// The entity followed, it needs a `Transform`.
+    pub entity: Entity,
+    /// Offset from the position of the entity, in world space.
+    pub offset: Vector3<f32>,
+    /// Scales the element with its distance to the camera.
+    pub distance_scaling: Option<DistanceScaling>,
+    /// Size of the `UiTransform` before it was scaled.
+    base_size: Option<(f32, f32)>,
+}
+
+impl UiWorldAnchor {
+    /// Creates a `UiWorldAnchor` following `entity`.
+    #[must_use]
+    pub fn new(entity: Entity) -> Self {
+        Self {
+            entity,
+            offset: Vector3::zeros(),
+            distance_scaling: None,
+            base_size: None,
+        }
+    }
+
+    /// Set the offset from the position of the entity, in world space.
+    #[must_use]
+    pub fn with_offset(mut self, offset: Vector3<f32>) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Scales the element by `reference_distance / distance`, clamped between `min_scale` and
+    /// `max_scale`.
+    #[must_use]
+    pub fn with_distance_scaling(
+        mut self,
+        reference_distance: f32,
+        min_scale: f32,
+        max_scale: f32,
+    ) -> Self {
+        self.distance_scaling = Some(DistanceScaling {
+            reference_distance,
+            min_scale,
+            max_scale,
+        });
+        self
+    }
+}
+
+/// Projects `point` to ui coordinates, with the origin at the bottom left of the screen.
+/// Also returns the distance to the camera. Returns `None` if the point is behind the camera.
+pub(crate) fn project(
+    camera: &Camera,
+    camera_transform: &Transform,
+    point: &Point3<f32>,
+    screen: Vector2<f32>,
+) -> Option<(Vector2<f32>, f32)> {
+    let camera_matrix = camera_transform.global_matrix();
+    let camera_position = camera_matrix.transform_point(&Point3::origin());
+    let camera_backward = camera_matrix.column(2).xyz();
+    let from_camera = point - camera_position;
+    if from_camera.dot(&camera_backward) >= 0.0 {
+        return None;
+    }
+
+    let position = camera.world_to_screen(*point, screen, camera_transform);
+    Some((
+        Vector2::new(position.x, screen.y - position.y),
+        from_camera.norm(),
+    ))
+}
+
+/// Returns the local position moving `transform` to `position`, in a parent of `size` pixels
+/// centered on `center`.
+fn local_position(
+    position: Vector2<f32>,
+    transform: &UiTransform,
+    center: Vector2<f32>,
+    size: Vector2<f32>,
+    factor: f32,
+) -> Vector2<f32> {
+    let (anchor_x, anchor_y) = transform.anchor.norm_offset();
+    let anchor = center + size.component_mul(&Vector2::new(anchor_x, anchor_y));
+    let scale = match transform.scale_mode {
+        ScaleMode::Pixel => Vector2::new(factor, factor),
+        ScaleMode::Percent => size,
+    };
+    (position - anchor).component_div(&scale)
+}
+
+/// System moving the ui elements with a `UiWorldAnchor` to the position of their entity.
+///
+/// It's automatically registered with the `UiBundle`.
+#[derive(Debug, Default)]
+pub struct UiWorldAnchorSystem {
+    /// The elements this system hid.
+    hidden: HashSet<Entity>,
+}
+
+impl System for UiWorldAnchorSystem {
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("UiWorldAnchorSystem")
+                .read_resource::<ActiveCamera>()
+                .read_resource::<ScreenDimensions>()
+                .read_resource::<UiScale>()
+                .with_query(<(
+                    Entity,
+                    &mut UiWorldAnchor,
+                    &mut UiTransform,
+                    Option<&HiddenPropagate>,
+                )>::query())
+                .with_query(<(Entity, &Parent)>::query().filter(component::<UiWorldAnchor>()))
+                .with_query(<&UiTransform>::query())
+                .with_query(<(&Camera, &Transform)>::query())
+                .with_query(<(Entity, &Camera, &Transform)>::query())
+                .with_query(<&Transform>::query())
+                .build(
+                    move |commands,
+                          world,
+                          (active_camera, screen_dimensions, ui_scale),
+                          (
+                        anchors,
+                        anchored_children,
+                        ui_transforms,
+                        cameras,
+                        cameras_with_entity,
+                        transforms,
+                    )| {
+                        // The center and size of the parents of the anchored elements.
+                        let parents: HashMap<Entity, (Vector2<f32>, Vector2<f32>)> =
+                            anchored_children
+                                .iter(world)
+                                .filter_map(|(entity, parent)| {
+                                    ui_transforms.get(world, parent.0).ok().map(|parent| {
+                                        (
+                                            *entity,
+                                            (
+                                                Vector2::new(parent.pixel_x, parent.pixel_y),
+                                                Vector2::new(
+                                                    parent.pixel_width,
+                                                    parent.pixel_height,
+                                                ),
+                                            ),
+                                        )
+                                    })
+                                })
+                                .collect();
+
+                        let (mut anchor_world, world) = world.split_for_query(anchors);
+
+                        let camera = active_camera.entity.map_or_else(
+                            || cameras.iter(&world).next(),
+                            |e| {
+                                cameras_with_entity
+                                    .iter(&world)
+                                    .find(|(camera_entity, _, _)| **camera_entity == e)
+                                    .map(|(_, camera, camera_transform)| (camera, camera_transform))
+                            },
+                        );
+                        let (camera, camera_transform) = match camera {
+                            Some(camera) => camera,
+                            None => return,
+                        };
+                        let screen =
+                            Vector2::new(screen_dimensions.width(), screen_dimensions.height());
+                        let factor = ui_scale.factor(screen_dimensions);
+
+                        let mut hidden = HashSet::new();
+                        let hidden_before = &self.hidden;
+                        anchors.for_each_mut(
+                            &mut anchor_world,
+                            |(entity, anchor, transform, hidden_propagate)| {
+                                // Leave the elements hidden by something else alone.
+                                let hidden_by_us =
+                                    hidden_propagate.is_none() || hidden_before.contains(entity);
+                                let projected = transforms
+                                    .get(&world, anchor.entity)
+                                    .ok()
+                                    .and_then(|target| {
+                                        let point = target
+                                            .global_matrix()
+                                            .transform_point(&Point3::origin())
+                                            + anchor.offset;
+                                        project(camera, camera_transform, &point, screen)
+                                    });
+                                let (position, distance) = match projected {
+                                    Some(projected) => projected,
+                                    None => {
+                                        if hidden_by_us {
+                                            hidden.insert(*entity);
+                                        }
+                                        return;
+                                    }
+                                };
+
+                                if let Some(scaling) = anchor.distance_scaling {
+                                    let (width, height) = *anchor
+                                        .base_size
+                                        .get_or_insert((transform.width, transform.height));
+                                    let scale = (scaling.reference_distance / distance)
+                                        .max(scaling.min_scale)
+                                        .min(scaling.max_scale);
+                                    transform.width = width * scale;
+                                    transform.height = height * scale;
+                                }
+
+                                let (center, size) = parents
+                                    .get(entity)
+                                    .copied()
+                                    .unwrap_or((screen * 0.5, screen));
+                                let local =
+                                    local_position(position, transform, center, size, factor);
+                                transform.local_x = local.x;
+                                transform.local_y = local.y;
+
+                                let half_width = transform.pixel_width * 0.5;
+                                let half_height = transform.pixel_height * 0.5;
+                                let off_screen = position.x + half_width < 0.
+                                    || position.y + half_height < 0.
+                                    || position.x - half_width > screen.x
+                                    || position.y - half_height > screen.y;
+                                if off_screen && hidden_by_us {
+                                    hidden.insert(*entity);
+                                }
+                            },
+                        );
+
+                        for entity in hidden.difference(&self.hidden) {
+                            commands.add_component(*entity, HiddenPropagate::new());
+                        }
+                        for entity in self.hidden.difference(&hidden) {
+                            commands.remove_component::<HiddenPropagate>(*entity);
+                        }
+                        self.hidden = hidden;
+                    },
+                ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Anchor;
+
+    #[test]
+    fn projects_in_front_of_camera() {
+        let screen = Vector2::new(800., 600.);
+        let camera = Camera::standard_3d(screen.x, screen.y);
+        let mut camera_transform = Transform::default();
+        camera_transform.set_translation(Vector3::new(0., 0., 10.));
+        camera_transform.copy_local_to_global();
+
+        let (position, distance) =
+            project(&camera, &camera_transform, &Point3::new(0., 0., 0.), screen).unwrap();
+        assert!((position.x - 400.).abs() < 0.01);
+        assert!((position.y - 300.).abs() < 0.01);
+        assert!((distance - 10.).abs() < 0.01);
+
+        assert!(project(
+            &camera,
+            &camera_transform,
+            &Point3::new(0., 0., 20.),
+            screen
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn positions_in_the_parent() {
+        let mut transform = UiTransform::new(
+            "label".to_string(),
+            Anchor::BottomLeft,
+            Anchor::Middle,
+            0.,
+            0.,
+            0.,
+            20.,
+            10.,
+        );
+        let screen = Vector2::new(800., 600.);
+
+        let local = local_position(
+            Vector2::new(300., 200.),
+            &transform,
+            screen * 0.5,
+            screen,
+            2.,
+        );
+        assert!((local.x - 150.).abs() < 0.01);
+        assert!((local.y - 100.).abs() < 0.01);
+
+        // A parent of 200x100 pixels, centered on (500, 300).
+        let (center, size) = (Vector2::new(500., 300.), Vector2::new(200., 100.));
+        let local = local_position(Vector2::new(450., 275.), &transform, center, size, 2.);
+        assert!((local.x - 25.).abs() < 0.01);
+        assert!((local.y - 12.5).abs() < 0.01);
+
+        transform.scale_mode = ScaleMode::Percent;
+        let local = local_position(Vector2::new(450., 275.), &transform, center, size, 2.);
+        assert!((local.x - 0.25).abs() < 0.01);
+        assert!((local.y - 0.25).abs() < 0.01);
+    }
+}
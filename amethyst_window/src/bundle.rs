@@ -73,7 +73,9 @@ impl SystemBundle for WindowBundle {
 
         let (width, height) = window.inner_size().into();
 
-        resources.insert(ScreenDimensions::new(width, height));
+        let mut screen_dimensions = ScreenDimensions::new(width, height);
+        screen_dimensions.update_scale_factor(window.scale_factor());
+        resources.insert(screen_dimensions);
         resources.insert(window);
 
         builder
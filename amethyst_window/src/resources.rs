@@ -9,6 +9,8 @@ pub struct ScreenDimensions {
     pub(crate) h: f64,
     /// Width divided by height.
     aspect_ratio: f32,
+    /// Number of physical pixels per logical pixel.
+    pub(crate) scale_factor: f64,
     pub(crate) dirty: bool,
 }
 
@@ -20,23 +22,24 @@ impl ScreenDimensions {
             w: f64::from(w),
             h: f64::from(h),
             aspect_ratio: w as f32 / h as f32,
+            scale_factor: 1.0,
             dirty: false,
         }
     }
 
-    /// Returns the current logical size of window as diagonal vector.
+    /// Returns the current physical size of window as diagonal vector.
     #[must_use]
     pub fn diagonal(&self) -> Vector2<f32> {
         Vector2::new(self.width(), self.height())
     }
 
-    /// Returns the current logical width of the window.
+    /// Returns the current physical width of the window.
     #[must_use]
     pub fn width(&self) -> f32 {
         self.w as f32
     }
 
-    /// Returns the current logical height of the window.
+    /// Returns the current physical height of the window.
     #[must_use]
     pub fn height(&self) -> f32 {
         self.h as f32
@@ -48,6 +51,14 @@ impl ScreenDimensions {
         self.aspect_ratio
     }
 
+    /// Returns the number of physical pixels per logical pixel, as reported by the window.
+    ///
+    /// This is `1.0` on most displays and greater on high-DPI displays.
+    #[must_use]
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
     /// Updates the width and height of the screen and recomputes the aspect
     /// ratio.
     ///
@@ -60,4 +71,12 @@ impl ScreenDimensions {
         self.aspect_ratio = w as f32 / h as f32;
         self.dirty = true;
     }
+
+    /// Updates the scale factor of the screen.
+    ///
+    /// This resource is updated automatically by the engine when the window moves to a display
+    /// with a different scale factor, so you only need this when there is no window.
+    pub fn update_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+    }
 }
@@ -4,7 +4,7 @@ use amethyst_core::{
     EventChannel,
 };
 use winit::{
-    dpi::Size,
+    dpi::{PhysicalSize, Size},
     event::Event,
     event_loop::{ControlFlow, EventLoop},
     platform::run_return::EventLoopExtRunReturn,
@@ -30,7 +30,9 @@ impl System for WindowSystem {
 
                     // Send resource size changes to the window
                     if screen_dimensions.dirty {
-                        window.set_inner_size(Size::Logical((width, height).into()));
+                        window.set_inner_size(Size::Physical(
+                            PhysicalSize::<f64>::new(width, height).cast(),
+                        ));
                         screen_dimensions.dirty = false;
                     }
 
@@ -44,6 +46,8 @@ impl System for WindowSystem {
                         // so set dirty to false.
                         screen_dimensions.dirty = false;
                     }
+
+                    screen_dimensions.scale_factor = window.scale_factor();
                 }),
         )
     }